@@ -0,0 +1,30 @@
+const API_BASE =
+  window.location.pathname.split("/").slice(0, -1).join("/") + "/v1";
+
+document.addEventListener("DOMContentLoaded", () => {
+  document.querySelectorAll(".revoke-device").forEach((el) => {
+    const button = el as HTMLButtonElement;
+    button.addEventListener("click", async () => {
+      const deviceId = button.dataset.deviceId;
+      if (!deviceId) return;
+
+      const res = await fetch(
+        `${API_BASE}/devices/${encodeURIComponent(deviceId)}`,
+        {
+          method: "DELETE",
+          credentials: "same-origin",
+        },
+      );
+
+      if (!res.ok) {
+        const msg = await res.text();
+        console.error(
+          `Failed to revoke device "${deviceId}": ${res.status} – ${msg}`,
+        );
+        return;
+      }
+
+      button.closest("li")?.remove();
+    });
+  });
+});
@@ -0,0 +1,44 @@
+interface PairingCode {
+  code: string;
+  expires: number;
+  qr_payload: string;
+  qr_svg: string;
+}
+
+// Loaded next to the script of the page, so nothing is declared globally
+document.addEventListener("DOMContentLoaded", () => {
+  const apiBase =
+    window.location.pathname.split("/").slice(0, -1).join("/") + "/v1";
+  const pairBtn = document.getElementById("pair-device");
+  // Only admins pick the user, a code is for the caller's own devices otherwise
+  const userSelect = document.getElementById("pairing-user") as HTMLSelectElement | null;
+  const pairing = document.getElementById("pairing");
+  const pairingQr = document.getElementById("pairing-qr");
+  const pairingCode = document.getElementById("pairing-code");
+  const pairingExpires = document.getElementById("pairing-expires");
+
+  pairBtn?.addEventListener("click", async () => {
+    const query = userSelect
+      ? `?user_id=${encodeURIComponent(userSelect.value)}`
+      : "";
+    const res = await fetch(`${apiBase}/pairing_codes${query}`, {
+      method: "POST",
+      credentials: "same-origin",
+    });
+
+    if (!res.ok) {
+      const msg = await res.text();
+      console.error(`Failed to generate a pairing code: ${res.status} – ${msg}`);
+      return;
+    }
+
+    const pairingCodeData = (await res.json()) as PairingCode;
+    if (pairingQr) pairingQr.innerHTML = pairingCodeData.qr_svg;
+    if (pairingCode) pairingCode.textContent = pairingCodeData.code;
+    if (pairingExpires)
+      pairingExpires.textContent = `Expires at ${new Date(
+        pairingCodeData.expires * 1000,
+      ).toLocaleTimeString()}`;
+    pairing?.classList.remove("hidden");
+  });
+});
@@ -0,0 +1,74 @@
+const API_BASE =
+  window.location.pathname.split("/").slice(0, -1).join("/") + "/v1/tokens";
+
+interface ApiTokenCreated {
+  value: string;
+}
+
+document.addEventListener("DOMContentLoaded", () => {
+  const createBtn = document.getElementById("create-token");
+  const nameInput = document.getElementById("token-name") as HTMLInputElement;
+  const userSelect = document.getElementById("token-user") as HTMLSelectElement;
+  const expiresInput = document.getElementById(
+    "token-expires",
+  ) as HTMLInputElement;
+  const createdToken = document.getElementById("created-token");
+  const createdTokenValue = document.getElementById("created-token-value");
+
+  createBtn?.addEventListener("click", async () => {
+    const scopes = Array.from(
+      document.querySelectorAll(".token-scope"),
+    ) as HTMLInputElement[];
+
+    const payload = {
+      name: nameInput.value,
+      scopes: scopes.filter((scope) => scope.checked).map((scope) => scope.value),
+      expires: expiresInput.value
+        ? Math.floor(new Date(expiresInput.value).getTime() / 1000)
+        : null,
+    };
+    const headers: HeadersInit = {
+      "Content-Type": "application/json",
+    };
+
+    const res = await fetch(`${API_BASE}?user_id=${encodeURIComponent(userSelect.value)}`, {
+      method: "POST",
+      headers,
+      credentials: "same-origin",
+      body: JSON.stringify(payload),
+    });
+
+    if (!res.ok) {
+      const msg = await res.text();
+      console.error(`Failed to create the token: ${res.status} – ${msg}`);
+      return;
+    }
+
+    const created = (await res.json()) as ApiTokenCreated;
+    if (createdTokenValue) createdTokenValue.textContent = created.value;
+    createdToken?.classList.remove("hidden");
+  });
+
+  document.querySelectorAll(".revoke-token").forEach((el) => {
+    const button = el as HTMLButtonElement;
+    button.addEventListener("click", async () => {
+      const tokenId = button.dataset.tokenId;
+      if (!tokenId) return;
+
+      const res = await fetch(`${API_BASE}/${encodeURIComponent(tokenId)}`, {
+        method: "DELETE",
+        credentials: "same-origin",
+      });
+
+      if (!res.ok) {
+        const msg = await res.text();
+        console.error(
+          `Failed to revoke token "${tokenId}": ${res.status} – ${msg}`,
+        );
+        return;
+      }
+
+      button.closest("li")?.remove();
+    });
+  });
+});
@@ -0,0 +1,62 @@
+const API_BASE =
+  window.location.pathname.split("/").slice(0, -1).join("/") + "/v1/users";
+
+interface UserRegistration {
+  token: string;
+}
+
+document.addEventListener("DOMContentLoaded", () => {
+  const createBtn = document.getElementById("create-user");
+  const nameInput = document.getElementById("user-name") as HTMLInputElement;
+  const adminInput = document.getElementById("user-admin") as HTMLInputElement;
+  const createdUser = document.getElementById("created-user");
+  const createdUserToken = document.getElementById("created-user-token");
+
+  createBtn?.addEventListener("click", async () => {
+    const payload = {
+      name: nameInput.value,
+      admin: adminInput.checked,
+    };
+    const headers: HeadersInit = {
+      "Content-Type": "application/json",
+    };
+
+    const res = await fetch(API_BASE, {
+      method: "POST",
+      headers,
+      credentials: "same-origin",
+      body: JSON.stringify(payload),
+    });
+
+    if (!res.ok) {
+      const msg = await res.text();
+      console.error(`Failed to add the user: ${res.status} – ${msg}`);
+      return;
+    }
+
+    const registration = (await res.json()) as UserRegistration;
+    if (createdUserToken) createdUserToken.textContent = registration.token;
+    createdUser?.classList.remove("hidden");
+  });
+
+  document.querySelectorAll(".remove-user").forEach((el) => {
+    const button = el as HTMLButtonElement;
+    button.addEventListener("click", async () => {
+      const userId = button.dataset.userId;
+      if (!userId) return;
+
+      const res = await fetch(`${API_BASE}/${encodeURIComponent(userId)}`, {
+        method: "DELETE",
+        credentials: "same-origin",
+      });
+
+      if (!res.ok) {
+        const msg = await res.text();
+        console.error(`Failed to remove user "${userId}": ${res.status} – ${msg}`);
+        return;
+      }
+
+      button.closest("li")?.remove();
+    });
+  });
+});
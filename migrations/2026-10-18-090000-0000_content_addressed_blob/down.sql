@@ -0,0 +1,7 @@
+ALTER TABLE file_hash
+DROP COLUMN size;
+ALTER TABLE file_hash
+DROP COLUMN blob_hash;
+ALTER TABLE game_save
+DROP COLUMN legacy_archive;
+DROP TABLE IF EXISTS blob;
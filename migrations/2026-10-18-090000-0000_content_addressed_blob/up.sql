@@ -0,0 +1,12 @@
+CREATE TABLE blob (
+    hash TEXT NOT NULL PRIMARY KEY,
+    size BigInt NOT NULL,
+    ref_count INTEGER NOT NULL
+    );
+
+ALTER TABLE game_save
+ADD COLUMN legacy_archive BOOL NOT NULL DEFAULT TRUE;
+ALTER TABLE file_hash
+ADD COLUMN blob_hash TEXT;
+ALTER TABLE file_hash
+ADD COLUMN size BigInt;
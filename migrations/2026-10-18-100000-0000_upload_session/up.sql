@@ -0,0 +1,18 @@
+CREATE TABLE upload_session (
+    uuid TEXT NOT NULL PRIMARY KEY,
+    path_id INTEGER NOT NULL,
+    size BigInt NOT NULL,
+    file_hash TEXT NOT NULL,
+    last_activity TIMESTAMP NOT NULL,
+    finalizing BOOL NOT NULL DEFAULT FALSE,
+    FOREIGN KEY(path_id) REFERENCES game_path(id)
+    );
+
+CREATE TABLE upload_chunk (
+    chunk_number INTEGER NOT NULL,
+    upload_session_uuid TEXT NOT NULL,
+    start_offset BigInt NOT NULL,
+    size BigInt NOT NULL,
+    PRIMARY KEY (chunk_number, upload_session_uuid),
+    FOREIGN KEY(upload_session_uuid) REFERENCES upload_session(uuid)
+    );
@@ -0,0 +1,2 @@
+ALTER TABLE game_save
+DROP COLUMN parent_uuid;
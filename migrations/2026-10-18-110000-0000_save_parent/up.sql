@@ -0,0 +1,12 @@
+ALTER TABLE game_save
+ADD COLUMN parent_uuid TEXT;
+
+UPDATE game_save
+SET parent_uuid = (
+    SELECT previous.uuid
+    FROM game_save AS previous
+    WHERE previous.path_id = game_save.path_id
+    AND previous.time < game_save.time
+    ORDER BY previous.time DESC
+    LIMIT 1
+    );
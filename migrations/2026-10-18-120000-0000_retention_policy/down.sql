@@ -0,0 +1 @@
+DELETE FROM configurations WHERE id IN ('retention_daily_days', 'retention_weekly_weeks');
@@ -0,0 +1,2 @@
+INSERT INTO configurations VALUES ('retention_daily_days', '0');
+INSERT INTO configurations VALUES ('retention_weekly_weeks', '0');
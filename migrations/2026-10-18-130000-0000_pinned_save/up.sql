@@ -0,0 +1,2 @@
+ALTER TABLE game_save
+ADD COLUMN pinned BOOL NOT NULL DEFAULT FALSE;
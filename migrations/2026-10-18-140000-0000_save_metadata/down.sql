@@ -0,0 +1,15 @@
+ALTER TABLE upload_session
+DROP COLUMN metadata;
+
+ALTER TABLE game_save
+DROP COLUMN playtime;
+ALTER TABLE game_save
+DROP COLUMN game_version;
+ALTER TABLE game_save
+DROP COLUMN client_version;
+ALTER TABLE game_save
+DROP COLUMN hostname;
+ALTER TABLE game_save
+DROP COLUMN note;
+ALTER TABLE game_save
+DROP COLUMN label;
@@ -0,0 +1,15 @@
+ALTER TABLE game_save
+ADD COLUMN label TEXT;
+ALTER TABLE game_save
+ADD COLUMN note TEXT;
+ALTER TABLE game_save
+ADD COLUMN hostname TEXT;
+ALTER TABLE game_save
+ADD COLUMN client_version TEXT;
+ALTER TABLE game_save
+ADD COLUMN game_version TEXT;
+ALTER TABLE game_save
+ADD COLUMN playtime BigInt;
+
+ALTER TABLE upload_session
+ADD COLUMN metadata TEXT;
@@ -0,0 +1,6 @@
+ALTER TABLE upload_session
+DROP COLUMN hash_algorithm;
+ALTER TABLE game_save
+DROP COLUMN archive_digest;
+ALTER TABLE game_save
+DROP COLUMN hash_algorithm;
@@ -0,0 +1,6 @@
+ALTER TABLE game_save
+ADD COLUMN hash_algorithm TEXT;
+ALTER TABLE game_save
+ADD COLUMN archive_digest TEXT;
+ALTER TABLE upload_session
+ADD COLUMN hash_algorithm TEXT NOT NULL DEFAULT 'sha256';
@@ -0,0 +1,4 @@
+DROP TABLE scrub_report;
+
+ALTER TABLE game_save
+DROP COLUMN corrupt;
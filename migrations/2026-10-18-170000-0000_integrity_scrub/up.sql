@@ -0,0 +1,8 @@
+ALTER TABLE game_save
+ADD COLUMN corrupt BOOL NOT NULL DEFAULT FALSE;
+
+CREATE TABLE scrub_report (
+    id INTEGER NOT NULL PRIMARY KEY,
+    time TIMESTAMP NOT NULL,
+    report TEXT NOT NULL
+    );
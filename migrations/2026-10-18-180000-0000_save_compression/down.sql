@@ -0,0 +1,9 @@
+DELETE FROM configurations WHERE id = 'compression_level';
+
+ALTER TABLE game_save
+DROP COLUMN codec;
+
+ALTER TABLE blob
+DROP COLUMN stored_size;
+ALTER TABLE blob
+DROP COLUMN codec;
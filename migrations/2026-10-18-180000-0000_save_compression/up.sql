@@ -0,0 +1,10 @@
+ALTER TABLE blob
+ADD COLUMN codec TEXT NOT NULL DEFAULT 'identity';
+ALTER TABLE blob
+ADD COLUMN stored_size BigInt NOT NULL DEFAULT 0;
+UPDATE blob SET stored_size = size;
+
+ALTER TABLE game_save
+ADD COLUMN codec TEXT;
+
+INSERT INTO configurations VALUES ('compression_level', '0');
@@ -0,0 +1,4 @@
+ALTER TABLE blob DROP COLUMN nonce;
+ALTER TABLE blob DROP COLUMN data_key;
+ALTER TABLE game_save DROP COLUMN nonce;
+ALTER TABLE game_save DROP COLUMN data_key;
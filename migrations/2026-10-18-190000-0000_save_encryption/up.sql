@@ -0,0 +1,4 @@
+ALTER TABLE game_save ADD COLUMN data_key BLOB;
+ALTER TABLE game_save ADD COLUMN nonce BLOB;
+ALTER TABLE blob ADD COLUMN data_key BLOB;
+ALTER TABLE blob ADD COLUMN nonce BLOB;
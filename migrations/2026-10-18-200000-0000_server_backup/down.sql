@@ -0,0 +1,2 @@
+DELETE FROM configurations WHERE id = 'backup_retention_count';
+DELETE FROM configurations WHERE id = 'backup_interval_days';
@@ -0,0 +1,2 @@
+INSERT INTO configurations VALUES ('backup_interval_days', '0');
+INSERT INTO configurations VALUES ('backup_retention_count', '3');
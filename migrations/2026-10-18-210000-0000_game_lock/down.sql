@@ -0,0 +1,2 @@
+DELETE FROM configurations WHERE id = 'game_lock_lease_seconds';
+DROP TABLE game_lock;
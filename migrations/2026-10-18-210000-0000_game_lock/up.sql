@@ -0,0 +1,10 @@
+CREATE TABLE game_lock (
+    game_metadata_id INTEGER NOT NULL PRIMARY KEY,
+    holder TEXT NOT NULL,
+    hostname TEXT,
+    acquired TIMESTAMP NOT NULL,
+    expires TIMESTAMP NOT NULL,
+    FOREIGN KEY(game_metadata_id) REFERENCES game_metadata(id)
+    );
+
+INSERT INTO configurations VALUES ('game_lock_lease_seconds', '300');
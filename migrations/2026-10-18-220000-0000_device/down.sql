@@ -0,0 +1,4 @@
+DROP TABLE device_sync;
+ALTER TABLE game_save DROP COLUMN device_id;
+ALTER TABLE api_tokens DROP COLUMN device_id;
+DROP TABLE device;
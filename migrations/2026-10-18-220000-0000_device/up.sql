@@ -0,0 +1,23 @@
+CREATE TABLE device (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    operating_system TEXT NOT NULL,
+    client_version TEXT,
+    created TIMESTAMP NOT NULL,
+    last_seen TIMESTAMP NOT NULL
+    );
+
+ALTER TABLE api_tokens ADD COLUMN device_id INTEGER REFERENCES device(id);
+ALTER TABLE game_save ADD COLUMN device_id INTEGER REFERENCES device(id);
+
+-- The synced save may be pruned later, its time is kept to compare with the latest save
+CREATE TABLE device_sync (
+    device_id INTEGER NOT NULL,
+    path_id INTEGER NOT NULL,
+    save_uuid TEXT NOT NULL,
+    save_time TIMESTAMP NOT NULL,
+    synced TIMESTAMP NOT NULL,
+    PRIMARY KEY (device_id, path_id),
+    FOREIGN KEY(device_id) REFERENCES device(id),
+    FOREIGN KEY(path_id) REFERENCES game_path(id)
+    );
@@ -0,0 +1,5 @@
+CREATE TABLE pairing_code (
+    code TEXT NOT NULL PRIMARY KEY,
+    created TIMESTAMP NOT NULL,
+    expires TIMESTAMP NOT NULL
+    );
@@ -0,0 +1,5 @@
+DROP TABLE api_token_scope;
+ALTER TABLE api_tokens DROP COLUMN expires;
+ALTER TABLE api_tokens DROP COLUMN last_used;
+ALTER TABLE api_tokens DROP COLUMN created;
+ALTER TABLE api_tokens DROP COLUMN name;
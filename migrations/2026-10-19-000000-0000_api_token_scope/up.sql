@@ -0,0 +1,22 @@
+ALTER TABLE api_tokens ADD COLUMN name TEXT NOT NULL DEFAULT '';
+-- SQLite can't add a column defaulting to the current time, existing tokens are dated now
+ALTER TABLE api_tokens ADD COLUMN created TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE api_tokens ADD COLUMN last_used TIMESTAMP;
+ALTER TABLE api_tokens ADD COLUMN expires TIMESTAMP;
+
+UPDATE api_tokens SET created = datetime('now');
+UPDATE api_tokens SET name = (SELECT device.name FROM device WHERE device.id = api_tokens.device_id)
+    WHERE device_id IS NOT NULL;
+
+CREATE TABLE api_token_scope (
+    api_token_id INTEGER NOT NULL,
+    scope TEXT NOT NULL,
+    PRIMARY KEY (api_token_id, scope),
+    FOREIGN KEY(api_token_id) REFERENCES api_tokens(id)
+    );
+
+-- Tokens from before scopes keep what they could do: devices sync, the others administer
+INSERT INTO api_token_scope (api_token_id, scope)
+    SELECT id, 'read-only' FROM api_tokens WHERE device_id IS NOT NULL
+    UNION ALL SELECT id, 'upload' FROM api_tokens WHERE device_id IS NOT NULL
+    UNION ALL SELECT id, 'admin' FROM api_tokens WHERE device_id IS NULL;
@@ -0,0 +1,14 @@
+-- Hashed tokens can't be turned back into plaintext, every token is revoked and the server
+-- prints a new initial token at startup
+DROP TABLE legacy_api_token;
+DROP TABLE api_tokens;
+DELETE FROM api_token_scope;
+CREATE TABLE api_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    api_token TEXT NOT NULL UNIQUE,
+    device_id INTEGER REFERENCES device(id),
+    name TEXT NOT NULL DEFAULT '',
+    created TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+    last_used TIMESTAMP,
+    expires TIMESTAMP
+    );
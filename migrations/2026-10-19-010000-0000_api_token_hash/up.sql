@@ -0,0 +1,30 @@
+-- One way: reverting this migration revokes every token, as hashes can't be turned back into
+-- plaintext. The server then prints a new initial token at startup.
+
+-- SQLite has no SHA-256, plaintext tokens wait here until the server hashes them at startup
+CREATE TABLE legacy_api_token (
+    api_token_id INTEGER NOT NULL PRIMARY KEY,
+    api_token TEXT NOT NULL,
+    FOREIGN KEY(api_token_id) REFERENCES api_tokens(id)
+    );
+INSERT INTO legacy_api_token (api_token_id, api_token) SELECT id, api_token FROM api_tokens;
+
+-- An empty hash matches no token until then
+CREATE TABLE api_tokens_hashed (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    token_prefix TEXT NOT NULL,
+    salt TEXT NOT NULL,
+    token_hash TEXT NOT NULL,
+    device_id INTEGER REFERENCES device(id),
+    name TEXT NOT NULL DEFAULT '',
+    created TIMESTAMP NOT NULL,
+    last_used TIMESTAMP,
+    expires TIMESTAMP
+    );
+INSERT INTO api_tokens_hashed
+    (id, token_prefix, salt, token_hash, device_id, name, created, last_used, expires)
+    SELECT id, substr(api_token, 1, 8), '', '', device_id, name, created, last_used, expires
+    FROM api_tokens;
+DROP TABLE api_tokens;
+ALTER TABLE api_tokens_hashed RENAME TO api_tokens;
+CREATE INDEX api_tokens_token_prefix ON api_tokens(token_prefix);
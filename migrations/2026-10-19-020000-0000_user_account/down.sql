@@ -0,0 +1,19 @@
+DROP TABLE game_lock;
+CREATE TABLE game_lock (
+    game_metadata_id INTEGER NOT NULL PRIMARY KEY,
+    holder TEXT NOT NULL,
+    hostname TEXT,
+    acquired TIMESTAMP NOT NULL,
+    expires TIMESTAMP NOT NULL,
+    FOREIGN KEY(game_metadata_id) REFERENCES game_metadata(id)
+    );
+
+DROP INDEX game_save_path_id_user_id;
+
+ALTER TABLE upload_session DROP COLUMN user_id;
+ALTER TABLE pairing_code DROP COLUMN user_id;
+ALTER TABLE game_save DROP COLUMN user_id;
+ALTER TABLE device DROP COLUMN user_id;
+ALTER TABLE api_tokens DROP COLUMN user_id;
+
+DROP TABLE user_account;
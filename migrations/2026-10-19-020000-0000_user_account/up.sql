@@ -0,0 +1,31 @@
+CREATE TABLE user_account (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    admin BOOLEAN NOT NULL,
+    created TIMESTAMP NOT NULL
+    );
+
+-- Everything stored before accounts existed belongs to a first administrator
+INSERT INTO user_account (id, name, admin, created) VALUES (1, 'admin', 1, datetime('now'));
+
+ALTER TABLE api_tokens ADD COLUMN user_id INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE device ADD COLUMN user_id INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE game_save ADD COLUMN user_id INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE pairing_code ADD COLUMN user_id INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE upload_session ADD COLUMN user_id INTEGER NOT NULL DEFAULT 1;
+
+CREATE INDEX game_save_path_id_user_id ON game_save(path_id, user_id);
+
+-- Each user plays on their own, leases are short so dropping the current ones is harmless
+DROP TABLE game_lock;
+CREATE TABLE game_lock (
+    game_metadata_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    holder TEXT NOT NULL,
+    hostname TEXT,
+    acquired TIMESTAMP NOT NULL,
+    expires TIMESTAMP NOT NULL,
+    PRIMARY KEY (game_metadata_id, user_id),
+    FOREIGN KEY(game_metadata_id) REFERENCES game_metadata(id),
+    FOREIGN KEY(user_id) REFERENCES user_account(id)
+    );
@@ -0,0 +1 @@
+ALTER TABLE game_save ADD COLUMN archive_format TEXT;
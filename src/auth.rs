@@ -1,13 +1,62 @@
 use crate::{
     DATABASE,
     const_var::{COOKIE_AUTH_NAME, LOGIN_PATH},
+    database::interface::SaveOwner,
+    datatype_endpoint::{ApiToken, TokenScope},
 };
 use axum::{
     body::Body,
-    http::StatusCode,
+    http::{Method, StatusCode},
     response::{IntoResponse, Redirect},
 };
-use std::error::Error;
+
+/// Who sent an authenticated request, added to its extensions
+#[derive(Clone, Default, Debug)]
+pub struct Caller {
+    /// User the token acts as, whose saves are the only ones the request sees
+    pub user_id: i32,
+    /// Device of the token, `None` for a token of no device
+    pub device_id: Option<i32>,
+    pub scopes: Vec<TokenScope>,
+}
+
+impl Caller {
+    pub fn allows(&self, scope: TokenScope) -> bool {
+        self.scopes.contains(&TokenScope::Admin) || self.scopes.contains(&scope)
+    }
+
+    /// Owner of the saves the caller uploads
+    pub fn save_owner(&self) -> SaveOwner {
+        SaveOwner {
+            user_id: self.user_id,
+            device_id: self.device_id,
+        }
+    }
+}
+
+/// Guards a group of routes: `GET` and `HEAD` need `read`, the other methods need `write`. Goes
+/// inside the authentication layer, which adds the [`Caller`].
+#[allow(clippy::result_large_err)]
+pub fn require_scope(
+    read: TokenScope,
+    write: TokenScope,
+) -> impl Fn(&mut axum::http::Request<Body>) -> Result<(), axum::http::Response<Body>> + Clone {
+    move |request_body| {
+        let scope = if matches!(*request_body.method(), Method::GET | Method::HEAD) {
+            read
+        } else {
+            write
+        };
+        match request_body.extensions().get::<Caller>() {
+            Some(caller) if caller.allows(scope) => Ok(()),
+            _ => Err((
+                StatusCode::FORBIDDEN,
+                format!("the {} scope is required", scope.as_str()),
+            )
+                .into_response()),
+        }
+    }
+}
 
 #[allow(clippy::result_large_err)]
 pub fn bearer_cookie_auth_redirect(
@@ -28,10 +77,16 @@ fn bearer_cookie_auth(
     request_body: &mut axum::http::Request<Body>,
     redirect: bool,
 ) -> Result<(), axum::http::Response<Body>> {
-    let api_tokens = DATABASE.get_api_tokens();
-    if authorized_bearer_token(request_body, &api_tokens)
-        || authorized_cookie(request_body, &api_tokens)
-    {
+    let api_token = bearer_tokens(request_body)
+        .into_iter()
+        .chain(cookie_token(request_body))
+        .find_map(|candidate| authenticate(&candidate));
+    if let Some(api_token) = api_token {
+        request_body.extensions_mut().insert(Caller {
+            user_id: api_token.user_id,
+            device_id: api_token.device_id,
+            scopes: api_token.scopes,
+        });
         Ok(())
     } else if redirect {
         Err(Redirect::to(LOGIN_PATH).into_response())
@@ -40,10 +95,17 @@ fn bearer_cookie_auth(
     }
 }
 
-fn authorized_bearer_token(
-    request_body: &mut axum::http::Request<Body>,
-    api_tokens: &Result<Vec<uuid::Uuid>, Box<dyn Error + Send + Sync>>,
-) -> bool {
+/// Returns the token if it is valid and records its use
+pub fn authenticate(candidate: &str) -> Option<ApiToken> {
+    DATABASE
+        .authenticate_api_token(candidate)
+        .unwrap_or_else(|e| {
+            tracing::error!("Error authenticating a token: {}", e);
+            None
+        })
+}
+
+fn bearer_tokens(request_body: &axum::http::Request<Body>) -> Vec<String> {
     request_body
         .headers()
         .iter()
@@ -57,41 +119,26 @@ fn authorized_bearer_token(
                 None
             }
         })
-        .any(|auth_value| {
-            api_tokens
-                .iter()
-                .flatten()
-                .any(|api_token| api_token.to_string().trim() == auth_value)
-        })
+        .map(|value| value.to_string())
+        .collect()
 }
 
-fn authorized_cookie(
-    request_body: &mut axum::http::Request<Body>,
-    api_tokens: &Result<Vec<uuid::Uuid>, Box<dyn Error + Send + Sync>>,
-) -> bool {
+fn cookie_token(request_body: &axum::http::Request<Body>) -> Option<String> {
     request_body
         .headers()
         .get("cookie")
         .and_then(|value| value.to_str().ok())
-        .is_some_and(|cookie_header| {
-            let token_opt = cookie_header
+        .and_then(|cookie_header| {
+            cookie_header
                 .split(';')
                 .map(|string| string.trim())
                 .find_map(|pair| {
                     let (name, val) = pair.split_once('=')?;
                     if name.eq_ignore_ascii_case(COOKIE_AUTH_NAME) {
-                        Some(val.trim())
+                        Some(val.trim().to_string())
                     } else {
                         None
                     }
-                });
-            if let Some(token) = token_opt {
-                api_tokens
-                    .iter()
-                    .flatten()
-                    .any(|api_token| api_token.to_string().trim() == token)
-            } else {
-                false
-            }
+                })
         })
 }
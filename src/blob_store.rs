@@ -0,0 +1,411 @@
+use crate::DATABASE;
+use crate::const_var::{QUARANTINE_DIR, TMP_DIR};
+use crate::database::interface::{GameDatabase, SaveDigest, SaveFile, SaveOwner};
+use crate::datatype_endpoint::{ArchiveFormat, Codec, SaveMetadata, SaveParent};
+use crate::save_compression::{configured_codec, encoded_reader};
+use crate::save_encryption::{BlobKey, MASTER_KEY, MasterKey, decrypted_reader, encrypt};
+use crate::save_store::{LocalStore, SAVE_STORE, SaveStore, copy_object};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncWriteExt};
+use tokio::sync::{Mutex, MutexGuard};
+use uuid::Uuid;
+
+// Serializes blob placement/removal with the reference counting in the database so a blob
+// can't be deleted from disk while another save is being added with the same content.
+static BLOB_STORE_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Holds off blob placement and removal until the guard is dropped, so that the objects the
+/// database references stay in the store while a backup copies them
+pub async fn lock_store() -> MutexGuard<'static, ()> {
+    BLOB_STORE_LOCK.lock().await
+}
+
+pub struct UploadedFile {
+    pub relative_path: String,
+    pub tmp_path: String,
+    pub hash: String,
+    pub blob_hash: String,
+    pub size: i64,
+}
+
+/// Key of a blob in the save store
+pub fn blob_object(blob_hash: &str) -> String {
+    format!(
+        "blobs/{}/{}",
+        blob_hash.get(..2).unwrap_or_default(),
+        blob_hash
+    )
+}
+
+/// Key of a legacy archive in the save store
+pub fn legacy_archive_object(uuid: &str) -> String {
+    format!("{}.sav", uuid)
+}
+
+pub fn upload_session_path(uuid: &str) -> String {
+    format!("{}/{}.upload", TMP_DIR, uuid)
+}
+
+/// Reader over a blob as encoded by its codec, decrypted when stored with a `blob_key`
+pub async fn open_blob(
+    store: &dyn SaveStore,
+    object: &str,
+    blob_key: Option<BlobKey>,
+) -> std::io::Result<Box<dyn AsyncRead + Send + Unpin>> {
+    let blob = store.get(object).await?;
+    match blob_key {
+        Some(blob_key) => Ok(Box::new(decrypted_reader(blob.reader, blob_key, blob.size))),
+        None => Ok(blob.reader),
+    }
+}
+
+/// Writes `source` to `destination` encoded with `codec` then encrypted with `blob_key`,
+/// returns the encoded size
+async fn encode_file(
+    source: String,
+    destination: String,
+    codec: Codec,
+    level: i32,
+    blob_key: Option<BlobKey>,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    tokio::task::spawn_blocking(move || {
+        let mut reader = encoded_reader(std::fs::File::open(&source)?, codec, level)?;
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&destination)?);
+        match blob_key {
+            Some(blob_key) => encrypt(reader, writer, &blob_key),
+            None => {
+                let size = std::io::copy(&mut reader, &mut writer)?;
+                writer.flush()?;
+                Ok(size)
+            }
+        }
+    })
+    .await?
+}
+
+/// Stores the blobs of `files` not in the save store yet and adds the save referencing them.
+/// `archive_format` is the archive the client uploaded the whole save as, if any.
+#[allow(clippy::too_many_arguments)]
+pub async fn add_save(
+    uuid: Uuid,
+    path_id: i32,
+    parent: &SaveParent,
+    metadata: &SaveMetadata,
+    owner: SaveOwner,
+    digest: &SaveDigest,
+    archive_format: Option<ArchiveFormat>,
+    files: &[UploadedFile],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let _guard = BLOB_STORE_LOCK.lock().await;
+
+    let mut placed_blobs: Vec<String> = Vec::new();
+    let result = async {
+        let (codec, level) = configured_codec()?;
+        let encryption = match MASTER_KEY.as_ref() {
+            Some(master_key) => Some(master_key.generate_data_key()?),
+            None => None,
+        };
+        let mut save_files = Vec::with_capacity(files.len());
+        for file in files {
+            // A blob keeps the codec and the key it was first stored with
+            let (blob_codec, stored_size, blob_encryption) =
+                match DATABASE.get_blob(&file.blob_hash)? {
+                    Some(blob) => (blob.codec, blob.stored_size, blob.encryption),
+                    None => {
+                        let object = blob_object(&file.blob_hash);
+                        let blob_key = match (MASTER_KEY.as_ref(), &encryption) {
+                            (Some(master_key), Some(encryption)) => {
+                                Some(master_key.blob_key(encryption, &file.blob_hash)?)
+                            }
+                            _ => None,
+                        };
+                        let stored_size = if codec == Codec::Identity && blob_key.is_none() {
+                            SAVE_STORE.put(&object, Path::new(&file.tmp_path)).await?;
+                            file.size
+                        } else {
+                            let encoded_path = format!("{}/{}.blob", TMP_DIR, Uuid::new_v4());
+                            let stored_size = match encode_file(
+                                file.tmp_path.clone(),
+                                encoded_path.clone(),
+                                codec,
+                                level,
+                                blob_key,
+                            )
+                            .await
+                            {
+                                Ok(stored_size) => stored_size,
+                                Err(e) => {
+                                    let _ = fs::remove_file(&encoded_path).await;
+                                    return Err(e);
+                                }
+                            };
+                            SAVE_STORE.put(&object, Path::new(&encoded_path)).await?;
+                            i64::try_from(stored_size)?
+                        };
+                        placed_blobs.push(object);
+                        (codec, stored_size, encryption.clone())
+                    }
+                };
+            save_files.push(SaveFile {
+                relative_path: file.relative_path.clone(),
+                hash: file.hash.clone(),
+                blob_hash: file.blob_hash.clone(),
+                size: file.size,
+                codec: blob_codec,
+                stored_size,
+                encryption: blob_encryption,
+            });
+        }
+
+        DATABASE.add_reference_to_save(
+            uuid,
+            path_id,
+            parent,
+            metadata,
+            owner,
+            Some(digest),
+            codec,
+            encryption.as_ref(),
+            archive_format,
+            save_files,
+        )
+    }
+    .await;
+
+    if result.is_err() {
+        for object in placed_blobs {
+            let _ = SAVE_STORE.delete(&object).await;
+        }
+    }
+    result
+}
+
+pub async fn restore_save(
+    database: &GameDatabase,
+    store: &dyn SaveStore,
+    source_uuid: &str,
+    owner: SaveOwner,
+    lock_holder: Option<&str>,
+    uuid: Uuid,
+) -> Result<Option<i32>, Box<dyn Error + Send + Sync>> {
+    let _guard = BLOB_STORE_LOCK.lock().await;
+
+    let restored = match database.add_restored_save(source_uuid, owner, lock_holder, uuid)? {
+        Some(restored) => restored,
+        None => return Ok(None),
+    };
+    if restored.legacy_archive {
+        let archive = legacy_archive_object(&restored.uuid);
+        let source = legacy_archive_object(source_uuid);
+        let copy = copy_object(store, &source, store, &archive, Path::new(TMP_DIR));
+        if let Err(e) = copy.await {
+            database.remove_saves(std::slice::from_ref(&restored.uuid))?;
+            let _ = store.delete(&archive).await;
+            return Err(e.into());
+        }
+    }
+    Ok(Some(restored.path_id))
+}
+
+pub async fn remove_saves(uuids: &[String]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let _guard = BLOB_STORE_LOCK.lock().await;
+
+    for blob_hash in DATABASE.remove_saves(uuids)? {
+        let _ = SAVE_STORE.delete(&blob_object(&blob_hash)).await;
+    }
+    for uuid in uuids {
+        let _ = SAVE_STORE.delete(&legacy_archive_object(uuid)).await;
+    }
+    Ok(())
+}
+
+/// Encrypts the blobs of `database` stored in plaintext, before a master key was configured, each
+/// with a data key of its own. The key is recorded before the object is replaced and dropped if
+/// that fails, so no blob is left encrypted with a key the database doesn't hold. Returns the
+/// number of blobs encrypted.
+pub async fn encrypt_plaintext_blobs(
+    database: &GameDatabase,
+    store: &dyn SaveStore,
+    master_key: &MasterKey,
+    tmp_dir: &Path,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let _guard = BLOB_STORE_LOCK.lock().await;
+
+    let mut encrypted = 0;
+    for blob_hash in database.get_plaintext_blob_hashes()? {
+        let encryption = master_key.generate_data_key()?;
+        let blob_key = master_key.blob_key(&encryption, &blob_hash)?;
+        database.set_blob_encryption(&blob_hash, Some(&encryption))?;
+        if let Err(e) = encrypt_object(store, &blob_object(&blob_hash), blob_key, tmp_dir).await {
+            database.set_blob_encryption(&blob_hash, None)?;
+            return Err(format!("failed to encrypt blob {}: {}", blob_hash, e).into());
+        }
+        encrypted += 1;
+    }
+    Ok(encrypted)
+}
+
+/// Replaces the stored object by its content, as stored, encrypted with `blob_key`
+async fn encrypt_object(
+    store: &dyn SaveStore,
+    object: &str,
+    blob_key: BlobKey,
+    tmp_dir: &Path,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let plaintext_path = tmp_dir.join(format!("{}.blob", Uuid::new_v4()));
+    let encrypted_path = tmp_dir.join(format!("{}.blob", Uuid::new_v4()));
+    let result = async {
+        let mut stored = store.get(object).await?;
+        let mut plaintext = fs::File::create(&plaintext_path).await?;
+        tokio::io::copy(&mut stored.reader, &mut plaintext).await?;
+        plaintext.flush().await?;
+        encode_file(
+            plaintext_path.to_string_lossy().into_owned(),
+            encrypted_path.to_string_lossy().into_owned(),
+            Codec::Identity,
+            0,
+            Some(blob_key),
+        )
+        .await?;
+        store.put(object, &encrypted_path).await?;
+        Ok(())
+    }
+    .await;
+    let _ = fs::remove_file(&plaintext_path).await;
+    let _ = fs::remove_file(&encrypted_path).await;
+    result
+}
+
+/// Keys of the objects of the save store referenced by the saves of `database`
+pub fn referenced_objects(
+    database: &GameDatabase,
+) -> Result<BTreeSet<String>, Box<dyn Error + Send + Sync>> {
+    Ok(database
+        .get_blobs()?
+        .into_iter()
+        .map(|blob| blob_object(&blob.hash))
+        .chain(
+            database
+                .get_legacy_archive_uuids()?
+                .iter()
+                .map(|uuid| legacy_archive_object(uuid)),
+        )
+        .collect())
+}
+
+/// Moves the objects of the save store that no save references to the quarantine directory,
+/// they are left behind by crashes and by saves removed before the blob store existed.
+/// Returns the keys of the objects moved.
+pub async fn quarantine_orphans() -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+    let _guard = BLOB_STORE_LOCK.lock().await;
+
+    let referenced = referenced_objects(&DATABASE)?;
+
+    let quarantine = LocalStore::new(QUARANTINE_DIR);
+    let mut quarantined = Vec::new();
+    for object in SAVE_STORE.list().await? {
+        if referenced.contains(&object) {
+            continue;
+        }
+        let file_name = object.rsplit('/').next().unwrap_or(&object);
+        copy_object(
+            SAVE_STORE.as_ref(),
+            &object,
+            &quarantine,
+            file_name,
+            Path::new(TMP_DIR),
+        )
+        .await?;
+        SAVE_STORE.delete(&object).await?;
+        quarantined.push(object);
+    }
+    Ok(quarantined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::interface::{SaveFile, SaveOwner};
+    use crate::test_util::test_game_with_path;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_encrypt_plaintext_blobs() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let database =
+            GameDatabase::new(&format!("file:{}?mode=memory&cache=shared", Uuid::new_v4()));
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir_all(&dir).await?;
+        let store = LocalStore::new(dir.join("store"));
+        let master_key = MasterKey::parse(&hex::encode([7u8; 32]))?;
+
+        test_game_with_path(&database, "Plaintext")?;
+
+        let content = b"stored before the master key".to_vec();
+        let blob_hash = "ab".repeat(32);
+        let plaintext_path = dir.join("plaintext");
+        fs::write(&plaintext_path, &content).await?;
+        store.put(&blob_object(&blob_hash), &plaintext_path).await?;
+        database.add_reference_to_save(
+            Uuid::new_v4(),
+            1,
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            SaveOwner {
+                user_id: 1,
+                device_id: None,
+            },
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![SaveFile {
+                relative_path: "save.dat".to_string(),
+                hash: blob_hash.clone(),
+                blob_hash: blob_hash.clone(),
+                size: content.len() as i64,
+                codec: Codec::Identity,
+                stored_size: content.len() as i64,
+                encryption: None,
+            }],
+        )?;
+
+        assert_eq!(
+            encrypt_plaintext_blobs(&database, &store, &master_key, &dir).await?,
+            1
+        );
+        assert!(database.get_plaintext_blob_hashes()?.is_empty());
+        let encryption = database
+            .get_blob(&blob_hash)?
+            .and_then(|blob| blob.encryption)
+            .expect("encrypted blob");
+
+        let mut stored = Vec::new();
+        store
+            .get(&blob_object(&blob_hash))
+            .await?
+            .reader
+            .read_to_end(&mut stored)
+            .await?;
+        assert_ne!(stored, content);
+
+        let blob_key = master_key.blob_key(&encryption, &blob_hash)?;
+        let mut decrypted = Vec::new();
+        open_blob(&store, &blob_object(&blob_hash), Some(blob_key))
+            .await?
+            .read_to_end(&mut decrypted)
+            .await?;
+        assert_eq!(decrypted, content);
+
+        // Nothing left to encrypt
+        assert_eq!(
+            encrypt_plaintext_blobs(&database, &store, &master_key, &dir).await?,
+            0
+        );
+        fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+}
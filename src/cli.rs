@@ -0,0 +1,174 @@
+use crate::DATABASE;
+use crate::blob_store::encrypt_plaintext_blobs;
+use crate::const_var::TMP_DIR;
+use crate::datatype_endpoint::LudusaviImportReport;
+use crate::ludusavi_backup::import_backup_directory;
+use crate::save_encryption::{MASTER_KEY, MASTER_KEY_ENV, MASTER_KEY_FILE_ENV, rotate_keys};
+use crate::save_store::{SAVE_STORE, StoreLocation, migrate_store, open_store};
+use crate::server_backup::{create_backup, restore_backup};
+use std::error::Error;
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: GameSaveServer [command]
+
+Without a command, starts the server.
+
+Commands:
+  rotate-keys <new key file>  Rewraps the data key of every save with a new master key
+  encrypt-blobs               Encrypts the blobs stored before the master key was configured
+  migrate-store <from> <to>   Copies the stored saves between stores (local or s3://bucket/prefix)
+  backup                      Writes a backup archive of the database and the saves to data/backups
+  restore <archive>           Replaces the database and restores the saves of a backup archive
+  import-ludusavi <directory> [user]
+                              Imports the saves of a Ludusavi backup directory for the user, the
+                              first administrator by default";
+
+/// Runs the offline command given on the command line, the server must not be running except for
+/// `backup`
+pub async fn run(args: &[String]) -> ExitCode {
+    match args {
+        [command, new_key_path] if command == "rotate-keys" => match rotate_keys(new_key_path) {
+            Ok(rewrapped) => {
+                tracing::info!(
+                    "Rewrapped {} data key(s) with the new master key",
+                    rewrapped
+                );
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                tracing::error!("Error rotating the master key: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+        [command] if command == "encrypt-blobs" => match run_encrypt_blobs().await {
+            Ok(encrypted) => {
+                tracing::info!("Encrypted {} blob(s) with the master key", encrypted);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                tracing::error!("Error encrypting the blobs: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+        [command, from, to] if command == "migrate-store" => {
+            match run_migrate_store(from, to).await {
+                Ok((copied, skipped)) => {
+                    tracing::info!(
+                        "Copied {} object(s), skipped {} already present",
+                        copied,
+                        skipped
+                    );
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    tracing::error!("Error migrating the save store: {}", e);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        [command] if command == "backup" => match create_backup().await {
+            Ok(Some(backup)) => {
+                tracing::info!("Created backup {}", backup.file_name);
+                ExitCode::SUCCESS
+            }
+            Ok(None) => {
+                tracing::error!("A backup is already running");
+                ExitCode::FAILURE
+            }
+            Err(e) => {
+                tracing::error!("Error creating backup: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+        [command, archive] if command == "restore" => {
+            match restore_backup(Path::new(archive)).await {
+                Ok(header) => {
+                    tracing::info!(
+                        "Restored the backup of database {}",
+                        header.database_uuid.unwrap_or_default()
+                    );
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    tracing::error!("Error restoring backup: {}", e);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        [command, directory, user @ ..] if command == "import-ludusavi" && user.len() <= 1 => {
+            match run_import_ludusavi(directory, user.first()).await {
+                Ok(report) => {
+                    for skipped in &report.skipped_backups {
+                        tracing::warn!(
+                            "Skipped backup {} of {}: {}",
+                            skipped.backup_name,
+                            skipped.game_name,
+                            skipped.reason
+                        );
+                    }
+                    for game_name in &report.unmatched_games {
+                        tracing::warn!("No game matches {}", game_name);
+                    }
+                    tracing::info!(
+                        "Imported {} save(s), {} already imported",
+                        report.imported_saves.len(),
+                        report.already_imported
+                    );
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    tracing::error!("Error importing Ludusavi backups: {}", e);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+async fn run_encrypt_blobs() -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let master_key = MASTER_KEY.as_ref().ok_or(format!(
+        "no master key is configured, set {} or {}",
+        MASTER_KEY_ENV, MASTER_KEY_FILE_ENV
+    ))?;
+    encrypt_plaintext_blobs(
+        &DATABASE,
+        SAVE_STORE.as_ref(),
+        master_key,
+        Path::new(TMP_DIR),
+    )
+    .await
+}
+
+async fn run_migrate_store(
+    from: &str,
+    to: &str,
+) -> Result<(usize, usize), Box<dyn Error + Send + Sync>> {
+    let from = open_store(&from.parse::<StoreLocation>()?)?;
+    let to = open_store(&to.parse::<StoreLocation>()?)?;
+    migrate_store(from.as_ref(), to.as_ref(), Path::new(TMP_DIR)).await
+}
+
+async fn run_import_ludusavi(
+    directory: &str,
+    user_name: Option<&String>,
+) -> Result<LudusaviImportReport, Box<dyn Error + Send + Sync>> {
+    let user = DATABASE
+        .get_users()?
+        .into_iter()
+        .find(|user| match user_name {
+            Some(user_name) => user.name == *user_name,
+            None => user.admin,
+        });
+    let Some(user) = user else {
+        return Err(match user_name {
+            Some(user_name) => format!("no user named {user_name}").into(),
+            None => "no administrator to import the saves for".into(),
+        });
+    };
+    import_backup_directory(Path::new(directory), user.id).await
+}
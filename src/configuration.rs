@@ -13,9 +13,72 @@ pub const MAX_SAVE_PER_GAME_INFO: ConfigurationInfo = ConfigurationInfo {
     pattern: None,
 };
 
+pub const RETENTION_DAILY_DAYS_INFO: ConfigurationInfo = ConfigurationInfo {
+    id: "retention_daily_days",
+    name: "Number of days to keep one save per day",
+    max: Some(3650),
+    min: Some(0),
+    step: Some(1),
+    pattern: None,
+};
+
+pub const RETENTION_WEEKLY_WEEKS_INFO: ConfigurationInfo = ConfigurationInfo {
+    id: "retention_weekly_weeks",
+    name: "Number of weeks to keep one save per week",
+    max: Some(520),
+    min: Some(0),
+    step: Some(1),
+    pattern: None,
+};
+
+pub const COMPRESSION_LEVEL_INFO: ConfigurationInfo = ConfigurationInfo {
+    id: "compression_level",
+    name: "Zstd compression level of new saves, 0 to store them uncompressed",
+    max: Some(22),
+    min: Some(0),
+    step: Some(1),
+    pattern: None,
+};
+
+pub const BACKUP_INTERVAL_DAYS_INFO: ConfigurationInfo = ConfigurationInfo {
+    id: "backup_interval_days",
+    name: "Number of days between scheduled backups, 0 to disable them",
+    max: Some(365),
+    min: Some(0),
+    step: Some(1),
+    pattern: None,
+};
+
+pub const GAME_LOCK_LEASE_SECONDS_INFO: ConfigurationInfo = ConfigurationInfo {
+    id: "game_lock_lease_seconds",
+    name: "Number of seconds a game lock lasts without a heartbeat",
+    max: Some(86400),
+    min: Some(30),
+    step: Some(1),
+    pattern: None,
+};
+
+pub const BACKUP_RETENTION_COUNT_INFO: ConfigurationInfo = ConfigurationInfo {
+    id: "backup_retention_count",
+    name: "Number of backups to keep",
+    max: Some(999),
+    min: Some(1),
+    step: Some(1),
+    pattern: None,
+};
+
 pub static CONFIG_MAP: Lazy<HashMap<&'static str, ConfigurationInfo<'static>>> = Lazy::new(|| {
     let mut map = HashMap::new();
     map.insert(MAX_SAVE_PER_GAME_INFO.id, MAX_SAVE_PER_GAME_INFO);
+    map.insert(RETENTION_DAILY_DAYS_INFO.id, RETENTION_DAILY_DAYS_INFO);
+    map.insert(RETENTION_WEEKLY_WEEKS_INFO.id, RETENTION_WEEKLY_WEEKS_INFO);
+    map.insert(COMPRESSION_LEVEL_INFO.id, COMPRESSION_LEVEL_INFO);
+    map.insert(BACKUP_INTERVAL_DAYS_INFO.id, BACKUP_INTERVAL_DAYS_INFO);
+    map.insert(BACKUP_RETENTION_COUNT_INFO.id, BACKUP_RETENTION_COUNT_INFO);
+    map.insert(
+        GAME_LOCK_LEASE_SECONDS_INFO.id,
+        GAME_LOCK_LEASE_SECONDS_INFO,
+    );
     map
 });
 
@@ -72,6 +135,13 @@ impl<'a> ConfigurationInfo<'a> {
         }
     }
 
+    pub fn get_number_in_db(&self) -> Result<u32, Box<dyn Error + Send + Sync>> {
+        match self.get_value_in_db()? {
+            Some(configuration_form) => Ok(configuration_form.value.parse::<u32>()?),
+            None => Err(format!("No {} in the database", self.id).into()),
+        }
+    }
+
     pub fn update_value_in_db(&self, value: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
         match DATABASE.update_configuration_value(self.id, value) {
             Ok(()) => Ok(()),
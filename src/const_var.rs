@@ -4,10 +4,17 @@ pub const DATA_DIR: &str = "./data";
 pub const MAX_BODY_SIZE: usize = 25 * 1024 * 1024 * 1024;
 pub const ROOT_API_PATH: &str = "/v1";
 pub const SAVE_DIR: &str = concatcp!(DATA_DIR, "/saves");
+pub const BLOB_DIR: &str = concatcp!(SAVE_DIR, "/blobs");
 pub const TMP_DIR: &str = concatcp!(DATA_DIR, "/tmp");
+pub const DATABASE_PATH: &str = concatcp!(DATA_DIR, "/database.sqlite");
+pub const BACKUP_DIR: &str = concatcp!(DATA_DIR, "/backups");
+pub const QUARANTINE_DIR: &str = concatcp!(DATA_DIR, "/quarantine");
 pub const MANIFEST_URL: &str =
     "https://raw.githubusercontent.com/mtkennerly/ludusavi-manifest/master/data/manifest.yaml";
 pub const MANIFEST_PATH: &str = concatcp!(TMP_DIR, "/ludusavi_manifest.yaml");
 pub const COOKIE_AUTH_NAME: &str = "auth_token";
 pub const COOKIE_MAX_AGE: u32 = 2628000;
 pub const LOGIN_PATH: &str = "/login";
+pub const UPLOAD_SESSION_EXPIRATION_HOURS: i64 = 24;
+pub const PAIRING_CODE_LIFETIME_MINUTES: i64 = 10;
+pub const PAIRING_ATTEMPTS_PER_MINUTE: usize = 5;
@@ -0,0 +1,51 @@
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+/// Characters of a token stored as is, to find its row and tell tokens apart
+pub const TOKEN_PREFIX_LENGTH: usize = 8;
+
+pub fn api_token_prefix(api_token: &str) -> String {
+    api_token.chars().take(TOKEN_PREFIX_LENGTH).collect()
+}
+
+pub fn generate_salt() -> String {
+    let mut salt = [0u8; 16];
+    getrandom::fill(&mut salt).expect("OS random number generator");
+    hex::encode(salt)
+}
+
+pub fn hash_api_token(salt: &str, api_token: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(api_token.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// Compares in constant time so the response time doesn't tell how much of a guess was right
+pub fn verify_api_token(salt: &str, token_hash: &str, candidate: &str) -> bool {
+    hash_api_token(salt, candidate)
+        .as_bytes()
+        .ct_eq(token_hash.as_bytes())
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_api_token() {
+        let salt = generate_salt();
+        let token_hash = hash_api_token(&salt, "0b5e7c1a-token");
+        assert!(verify_api_token(&salt, &token_hash, "0b5e7c1a-token"));
+        assert!(!verify_api_token(&salt, &token_hash, "0b5e7c1a-other"));
+        assert!(!verify_api_token(
+            &generate_salt(),
+            &token_hash,
+            "0b5e7c1a-token"
+        ));
+        // Tokens waiting for the startup upgrade have no hash yet
+        assert!(!verify_api_token(&salt, "", "0b5e7c1a-token"));
+        assert_eq!(api_token_prefix("0b5e7c1a-token"), "0b5e7c1a");
+    }
+}
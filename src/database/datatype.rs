@@ -1,8 +1,10 @@
 use crate::database::schema::{
-    api_tokens, configurations, db_info, file_hash, game_alt_name, game_executable,
-    game_gog_extra_id, game_metadata, game_path, game_registry, game_save, game_steam_extra_id,
+    api_token_scope, api_tokens, blob, configurations, db_info, device, device_sync, file_hash,
+    game_alt_name, game_executable, game_gog_extra_id, game_lock, game_metadata, game_path,
+    game_registry, game_save, game_steam_extra_id, legacy_api_token, pairing_code, scrub_report,
+    upload_chunk, upload_session, user_account,
 };
-use crate::datatype_endpoint::OS;
+use crate::datatype_endpoint::{ArchiveFormat, Codec, HashAlgorithm, OS, TokenScope};
 use diesel::prelude::{AsChangeset, Associations, Identifiable};
 use diesel::{Insertable, Queryable, Selectable};
 
@@ -61,6 +63,25 @@ pub struct DbGameSave {
     pub uuid: String,
     pub path_id: i32,
     pub time: time::PrimitiveDateTime,
+    pub legacy_archive: bool,
+    pub parent_uuid: Option<String>,
+    pub pinned: bool,
+    pub label: Option<String>,
+    pub note: Option<String>,
+    pub hostname: Option<String>,
+    pub client_version: Option<String>,
+    pub game_version: Option<String>,
+    pub playtime: Option<i64>,
+    pub restored_from: Option<String>,
+    pub hash_algorithm: Option<HashAlgorithm>,
+    pub archive_digest: Option<String>,
+    pub corrupt: bool,
+    pub codec: Option<Codec>,
+    pub data_key: Option<Vec<u8>>,
+    pub nonce: Option<Vec<u8>>,
+    pub device_id: Option<i32>,
+    pub user_id: i32,
+    pub archive_format: Option<ArchiveFormat>,
 }
 
 #[derive(Identifiable, Insertable, Selectable, Queryable, PartialEq, Associations, Debug)]
@@ -71,6 +92,21 @@ pub struct DbFileHash {
     pub relative_path: String,
     pub hash: String,
     pub game_save_uuid: String,
+    pub blob_hash: Option<String>,
+    pub size: Option<i64>,
+}
+
+#[derive(Identifiable, Insertable, Selectable, Queryable, PartialEq, Debug)]
+#[diesel(primary_key(hash))]
+#[diesel(table_name = blob)]
+pub struct DbBlob {
+    pub hash: String,
+    pub size: i64,
+    pub ref_count: i32,
+    pub codec: Codec,
+    pub stored_size: i64,
+    pub data_key: Option<Vec<u8>>,
+    pub nonce: Option<Vec<u8>>,
 }
 
 #[derive(Insertable, Selectable, Queryable, PartialEq)]
@@ -86,7 +122,45 @@ pub struct DbDbInfo {
 #[diesel(table_name = api_tokens)]
 pub struct DbApiTokens {
     pub id: Option<i32>,
-    pub api_token: String,
+    pub token_prefix: String,
+    pub salt: String,
+    pub token_hash: String,
+    pub device_id: Option<i32>,
+    pub name: String,
+    pub created: time::PrimitiveDateTime,
+    pub last_used: Option<time::PrimitiveDateTime>,
+    pub expires: Option<time::PrimitiveDateTime>,
+    pub user_id: i32,
+}
+
+#[derive(Insertable, Selectable, Queryable, PartialEq, Debug)]
+#[diesel(table_name = api_token_scope)]
+pub struct DbApiTokenScope {
+    pub api_token_id: i32,
+    pub scope: TokenScope,
+}
+
+#[derive(Identifiable, Insertable, Selectable, Queryable, PartialEq, Debug)]
+#[diesel(table_name = device)]
+pub struct DbDevice {
+    pub id: Option<i32>,
+    pub name: String,
+    pub operating_system: OS,
+    pub client_version: Option<String>,
+    pub created: time::PrimitiveDateTime,
+    pub last_seen: time::PrimitiveDateTime,
+    pub user_id: i32,
+}
+
+#[derive(Identifiable, Insertable, Selectable, Queryable, PartialEq, Debug)]
+#[diesel(primary_key(device_id, path_id))]
+#[diesel(table_name = device_sync)]
+pub struct DbDeviceSync {
+    pub device_id: i32,
+    pub path_id: i32,
+    pub save_uuid: String,
+    pub save_time: time::PrimitiveDateTime,
+    pub synced: time::PrimitiveDateTime,
 }
 
 #[derive(Insertable, Selectable, Queryable, PartialEq)]
@@ -123,3 +197,75 @@ pub struct DbGameRegistry {
     pub path: String,
     pub game_metadata_id: i32,
 }
+
+#[derive(Identifiable, Insertable, Selectable, Queryable, PartialEq, Debug)]
+#[diesel(primary_key(uuid))]
+#[diesel(table_name = upload_session)]
+pub struct DbUploadSession {
+    pub uuid: String,
+    pub path_id: i32,
+    pub size: i64,
+    pub file_hash: String,
+    pub last_activity: time::PrimitiveDateTime,
+    pub finalizing: bool,
+    pub metadata: Option<String>,
+    pub hash_algorithm: HashAlgorithm,
+    pub user_id: i32,
+}
+
+#[derive(Identifiable, Insertable, Selectable, Queryable, PartialEq, Associations, Debug)]
+#[diesel(primary_key(chunk_number, upload_session_uuid))]
+#[diesel(belongs_to(DbUploadSession, foreign_key = upload_session_uuid))]
+#[diesel(table_name = upload_chunk)]
+pub struct DbUploadChunk {
+    pub chunk_number: i32,
+    pub upload_session_uuid: String,
+    pub start_offset: i64,
+    pub size: i64,
+}
+
+#[derive(Identifiable, Insertable, Selectable, Queryable, PartialEq, Debug)]
+#[diesel(primary_key(game_metadata_id, user_id))]
+#[diesel(table_name = game_lock)]
+pub struct DbGameLock {
+    pub game_metadata_id: i32,
+    pub user_id: i32,
+    pub holder: String,
+    pub hostname: Option<String>,
+    pub acquired: time::PrimitiveDateTime,
+    pub expires: time::PrimitiveDateTime,
+}
+
+#[derive(Insertable, Selectable, Queryable, PartialEq, Debug)]
+#[diesel(table_name = legacy_api_token)]
+pub struct DbLegacyApiToken {
+    pub api_token_id: i32,
+    pub api_token: String,
+}
+
+#[derive(Identifiable, Insertable, Selectable, Queryable, PartialEq, Debug)]
+#[diesel(primary_key(code))]
+#[diesel(table_name = pairing_code)]
+pub struct DbPairingCode {
+    pub code: String,
+    pub created: time::PrimitiveDateTime,
+    pub expires: time::PrimitiveDateTime,
+    pub user_id: i32,
+}
+
+#[derive(Identifiable, Insertable, Selectable, Queryable, PartialEq, Debug)]
+#[diesel(table_name = scrub_report)]
+pub struct DbScrubReport {
+    pub id: i32,
+    pub time: time::PrimitiveDateTime,
+    pub report: String,
+}
+
+#[derive(Identifiable, Insertable, Selectable, Queryable, PartialEq, Debug)]
+#[diesel(table_name = user_account)]
+pub struct DbUserAccount {
+    pub id: Option<i32>,
+    pub name: String,
+    pub admin: bool,
+    pub created: time::PrimitiveDateTime,
+}
@@ -1,23 +1,34 @@
 use std::collections::HashMap;
 use std::error::Error;
 
+use crate::database::api_token_hash::{
+    api_token_prefix, generate_salt, hash_api_token, verify_api_token,
+};
 use crate::database::datatype::{
-    DbApiTokens, DbConfiguration, DbDbInfo, DbFileHash, DbGameExecutable, DbGameGogExtraId,
-    DbGameMetadata, DbGameName, DbGamePath, DbGameRegistry, DbGameSave, DbGameSteamExtraId,
+    DbApiTokenScope, DbApiTokens, DbBlob, DbConfiguration, DbDbInfo, DbDevice, DbDeviceSync,
+    DbFileHash, DbGameExecutable, DbGameGogExtraId, DbGameLock, DbGameMetadata, DbGameName,
+    DbGamePath, DbGameRegistry, DbGameSave, DbGameSteamExtraId, DbLegacyApiToken, DbPairingCode,
+    DbScrubReport, DbUploadChunk, DbUploadSession, DbUserAccount,
 };
 use crate::database::schema::{
-    api_tokens, configurations, db_info, file_hash, game_alt_name, game_executable,
-    game_gog_extra_id, game_metadata, game_path, game_registry, game_save, game_steam_extra_id,
+    api_token_scope, api_tokens, blob, configurations, db_info, device, device_sync, file_hash,
+    game_alt_name, game_executable, game_gog_extra_id, game_lock, game_metadata, game_path,
+    game_registry, game_save, game_steam_extra_id, legacy_api_token, pairing_code, scrub_report,
+    upload_chunk, upload_session, user_account,
 };
 use crate::datatype_endpoint::{
-    Executable, ExecutableCreate, FileHash, GameDefaultName, GameMetadata, GameMetadataCreate,
-    GameMetadataWithPaths, GameRegistry, OS, SavePath, SavePathCreate, SaveReference,
+    ApiToken, ApiTokenCreate, ArchiveFormat, ByteRange, Codec, Device, DeviceCreate,
+    DeviceSyncState, Executable, ExecutableCreate, FileHash, GameDefaultName, GameLock,
+    GameMetadata, GameMetadataCreate, GameMetadataWithPaths, GameRegistry, HashAlgorithm, OS,
+    SaveConflict, SaveMetadata, SaveMetadataUpdate, SaveParent, SavePath, SavePathCreate,
+    SaveReference, ScrubReport, TokenScope, User, UserCreate,
 };
 use diesel::connection::SimpleConnection;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool};
 use diesel::sqlite::SqliteConnection;
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+use itertools::Itertools;
 use uuid::Uuid;
 
 pub type DbPool = Pool<ConnectionManager<SqliteConnection>>;
@@ -43,12 +54,535 @@ pub struct GameNameAndLudusavi {
     pub ludusavi_managed: Option<bool>,
 }
 
+pub struct SaveFile {
+    pub relative_path: String,
+    pub hash: String,
+    pub blob_hash: String,
+    pub size: i64,
+    pub codec: Codec,
+    pub stored_size: i64,
+    pub encryption: Option<EncryptionKey>,
+}
+
+pub struct BlobInfo {
+    pub hash: String,
+    pub size: i64,
+    pub codec: Codec,
+    pub stored_size: i64,
+    pub encryption: Option<EncryptionKey>,
+}
+
+/// Data key wrapped by the master key, and the nonce the blobs it encrypts are derived from
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncryptionKey {
+    pub wrapped_key: Vec<u8>,
+    pub nonce: Vec<u8>,
+}
+
+impl EncryptionKey {
+    fn from_columns(data_key: Option<Vec<u8>>, nonce: Option<Vec<u8>>) -> Option<Self> {
+        Some(Self {
+            wrapped_key: data_key?,
+            nonce: nonce?,
+        })
+    }
+}
+
+/// A file of a save as stored, legacy saves have no blob hash nor size
+pub struct SaveFileHash {
+    pub relative_path: String,
+    pub hash: String,
+    pub blob_hash: Option<String>,
+    pub size: Option<i64>,
+}
+
+pub struct SaveDigest {
+    pub hash_algorithm: HashAlgorithm,
+    pub archive_digest: String,
+}
+
+pub struct BundleSave {
+    pub game_name: String,
+    pub reference: SaveReference,
+}
+
+pub struct SaveManifest {
+    pub time: i64,
+    pub legacy_archive: bool,
+    pub archive_digest: Option<String>,
+    pub archive_format: Option<ArchiveFormat>,
+    pub files: Vec<SaveFile>,
+}
+
+pub struct UploadSessionRecord {
+    pub path_id: i32,
+    pub size: i64,
+    pub file_hash: Vec<FileHash>,
+    pub last_activity: i64,
+    pub finalizing: bool,
+    pub metadata: SaveMetadata,
+    pub hash_algorithm: HashAlgorithm,
+    pub chunks: Vec<ByteRange>,
+}
+
+/// User a save belongs to, and the device that uploaded it if any
+#[derive(Clone, Copy, Debug)]
+pub struct SaveOwner {
+    pub user_id: i32,
+    pub device_id: Option<i32>,
+}
+
 pub struct GameAdditionalMetadata {
     known_name: Option<Vec<String>>,
     gog_extra: Option<Vec<i64>>,
     steam_extra: Option<Vec<i64>>,
 }
 
+fn utc_now() -> time::PrimitiveDateTime {
+    let now = time::OffsetDateTime::now_utc();
+    time::PrimitiveDateTime::new(now.date(), now.time())
+}
+
+fn utc_from_unix_timestamp(
+    timestamp: i64,
+) -> Result<time::PrimitiveDateTime, time::error::ComponentRange> {
+    let time = time::OffsetDateTime::from_unix_timestamp(timestamp)?;
+    Ok(time::PrimitiveDateTime::new(time.date(), time.time()))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CatalogUpdate {
+    Done,
+    NotFound,
+    /// The change would duplicate an existing entry, remove a path or a user that still has saves
+    /// or leave no token with the admin scope
+    Conflict,
+}
+
+/// The save is marked as corrupt by the integrity scrub, restoring it would spread the damage
+#[derive(Debug)]
+pub struct CorruptSave {
+    pub uuid: String,
+}
+
+impl std::fmt::Display for CorruptSave {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "save {} is marked as corrupt", self.uuid)
+    }
+}
+
+impl Error for CorruptSave {}
+
+fn is_unique_violation(err: &diesel::result::Error) -> bool {
+    matches!(
+        err,
+        diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _)
+    )
+}
+
+fn game_exists(
+    connection: &mut SqliteConnection,
+    game_id: i32,
+) -> Result<bool, diesel::result::Error> {
+    diesel::select(diesel::dsl::exists(
+        game_metadata::table.filter(game_metadata::id.eq(game_id)),
+    ))
+    .get_result(connection)
+}
+
+// Ludusavi imports only update managed games, so edits must opt the game out to survive them
+fn unset_ludusavi_managed(
+    connection: &mut SqliteConnection,
+    game_id: i32,
+) -> Result<(), diesel::result::Error> {
+    diesel::update(game_metadata::table.filter(game_metadata::id.eq(game_id)))
+        .set(game_metadata::ludusavi_managed.eq(Some(false)))
+        .execute(connection)?;
+    Ok(())
+}
+
+// The archives left in the tmp dir are swept by the upload session job
+fn remove_upload_sessions_by_path_ids(
+    connection: &mut SqliteConnection,
+    path_ids: &[i32],
+) -> Result<(), diesel::result::Error> {
+    let session_uuids = upload_session::table
+        .filter(upload_session::path_id.eq_any(path_ids))
+        .select(upload_session::uuid);
+    diesel::delete(
+        upload_chunk::table.filter(upload_chunk::upload_session_uuid.eq_any(session_uuids)),
+    )
+    .execute(connection)?;
+    diesel::delete(upload_session::table.filter(upload_session::path_id.eq_any(path_ids)))
+        .execute(connection)?;
+    Ok(())
+}
+
+// Every user has a history of their own for a path
+fn get_head_save_uuid(
+    connection: &mut SqliteConnection,
+    path_id: i32,
+    user_id: i32,
+) -> Result<Option<String>, diesel::result::Error> {
+    game_save::table
+        .filter(game_save::path_id.eq(path_id))
+        .filter(game_save::user_id.eq(user_id))
+        .order(game_save::time.desc())
+        .select(game_save::uuid)
+        .first(connection)
+        .optional()
+}
+
+fn to_game_lock(game_lock: DbGameLock) -> GameLock {
+    GameLock {
+        game_id: game_lock.game_metadata_id,
+        holder: game_lock.holder,
+        hostname: game_lock.hostname,
+        acquired: game_lock.acquired.assume_utc().unix_timestamp(),
+        expires: game_lock.expires.assume_utc().unix_timestamp(),
+    }
+}
+
+// An expired lock is left in the table until it's replaced or released
+fn get_active_game_lock(
+    connection: &mut SqliteConnection,
+    game_id: i32,
+    user_id: i32,
+) -> Result<Option<DbGameLock>, diesel::result::Error> {
+    game_lock::table
+        .filter(game_lock::game_metadata_id.eq(game_id))
+        .filter(game_lock::user_id.eq(user_id))
+        .filter(game_lock::expires.gt(utc_now()))
+        .select(DbGameLock::as_select())
+        .first(connection)
+        .optional()
+}
+
+fn to_device(device: DbDevice) -> Device {
+    Device {
+        id: device.id.unwrap_or_default(),
+        name: device.name,
+        operating_system: device.operating_system,
+        client_version: device.client_version,
+        created: device.created.assume_utc().unix_timestamp(),
+        last_seen: device.last_seen.assume_utc().unix_timestamp(),
+        user_id: device.user_id,
+    }
+}
+
+fn insert_device(
+    connection: &mut SqliteConnection,
+    device: &DeviceCreate,
+    user_id: i32,
+    api_token: Uuid,
+) -> Result<Device, diesel::result::Error> {
+    let now = utc_now();
+    diesel::insert_into(device::table)
+        .values(DbDevice {
+            id: None,
+            name: device.name.clone(),
+            operating_system: device.operating_system,
+            client_version: device.client_version.clone(),
+            created: now,
+            last_seen: now,
+            user_id,
+        })
+        .execute(connection)?;
+    let db_device: DbDevice = device::table
+        .order(device::id.desc())
+        .select(DbDevice::as_select())
+        .first(connection)?;
+    insert_api_token(
+        connection,
+        api_token,
+        &ApiTokenCreate {
+            name: db_device.name.clone(),
+            scopes: vec![TokenScope::ReadOnly, TokenScope::Upload],
+            expires: None,
+        },
+        None,
+        user_id,
+        db_device.id,
+    )?;
+    Ok(to_device(db_device))
+}
+
+fn to_api_token(token: DbApiTokens, scopes: Vec<TokenScope>) -> ApiToken {
+    ApiToken {
+        id: token.id.unwrap_or_default(),
+        prefix: token.token_prefix,
+        name: token.name,
+        scopes,
+        device_id: token.device_id,
+        user_id: token.user_id,
+        created: token.created.assume_utc().unix_timestamp(),
+        last_used: token
+            .last_used
+            .map(|last_used| last_used.assume_utc().unix_timestamp()),
+        expires: token
+            .expires
+            .map(|expires| expires.assume_utc().unix_timestamp()),
+    }
+}
+
+// Only the prefix is looked up, the hash of every candidate is then checked
+fn find_api_token(
+    connection: &mut SqliteConnection,
+    api_token: &str,
+) -> Result<Option<DbApiTokens>, diesel::result::Error> {
+    let candidates: Vec<DbApiTokens> = api_tokens::table
+        .filter(api_tokens::token_prefix.eq(api_token_prefix(api_token)))
+        .select(DbApiTokens::as_select())
+        .load(connection)?;
+    Ok(candidates
+        .into_iter()
+        .find(|candidate| verify_api_token(&candidate.salt, &candidate.token_hash, api_token)))
+}
+
+fn load_api_token(
+    connection: &mut SqliteConnection,
+    token: DbApiTokens,
+) -> Result<ApiToken, diesel::result::Error> {
+    let scopes = api_token_scope::table
+        .filter(api_token_scope::api_token_id.eq(token.id.unwrap_or_default()))
+        .select(api_token_scope::scope)
+        .load::<TokenScope>(connection)?;
+    Ok(to_api_token(token, scopes))
+}
+
+fn insert_api_token(
+    connection: &mut SqliteConnection,
+    api_token: Uuid,
+    token: &ApiTokenCreate,
+    expires: Option<time::PrimitiveDateTime>,
+    user_id: i32,
+    device_id: Option<i32>,
+) -> Result<ApiToken, diesel::result::Error> {
+    let api_token = api_token.to_string();
+    let salt = generate_salt();
+    diesel::insert_into(api_tokens::table)
+        .values(DbApiTokens {
+            id: None,
+            token_prefix: api_token_prefix(&api_token),
+            token_hash: hash_api_token(&salt, &api_token),
+            salt,
+            device_id,
+            name: token.name.clone(),
+            created: utc_now(),
+            last_used: None,
+            expires,
+            user_id,
+        })
+        .execute(connection)?;
+    let db_token: DbApiTokens = api_tokens::table
+        .order(api_tokens::id.desc())
+        .select(DbApiTokens::as_select())
+        .first(connection)?;
+    set_api_token_scopes(connection, db_token.id.unwrap_or_default(), &token.scopes)?;
+    load_api_token(connection, db_token)
+}
+
+fn set_api_token_scopes(
+    connection: &mut SqliteConnection,
+    api_token_id: i32,
+    scopes: &[TokenScope],
+) -> Result<(), diesel::result::Error> {
+    diesel::delete(api_token_scope::table.filter(api_token_scope::api_token_id.eq(api_token_id)))
+        .execute(connection)?;
+    let db_scopes: Vec<DbApiTokenScope> = scopes
+        .iter()
+        .unique()
+        .map(|scope| DbApiTokenScope {
+            api_token_id,
+            scope: *scope,
+        })
+        .collect();
+    diesel::insert_into(api_token_scope::table)
+        .values(db_scopes)
+        .execute(connection)?;
+    Ok(())
+}
+
+fn api_token_exists(
+    connection: &mut SqliteConnection,
+    id: i32,
+) -> Result<bool, diesel::result::Error> {
+    diesel::select(diesel::dsl::exists(
+        api_tokens::table.filter(api_tokens::id.eq(id)),
+    ))
+    .get_result(connection)
+}
+
+fn to_user(user: DbUserAccount) -> User {
+    User {
+        id: user.id.unwrap_or_default(),
+        name: user.name,
+        admin: user.admin,
+        created: user.created.assume_utc().unix_timestamp(),
+    }
+}
+
+fn find_user(
+    connection: &mut SqliteConnection,
+    id: i32,
+) -> Result<Option<DbUserAccount>, diesel::result::Error> {
+    user_account::table
+        .filter(user_account::id.eq(id))
+        .select(DbUserAccount::as_select())
+        .first(connection)
+        .optional()
+}
+
+fn first_admin_user_id(connection: &mut SqliteConnection) -> Result<i32, diesel::result::Error> {
+    user_account::table
+        .filter(user_account::admin.eq(true))
+        .order(user_account::id)
+        .select(user_account::id.assume_not_null())
+        .first(connection)
+}
+
+fn save_belongs_to(
+    connection: &mut SqliteConnection,
+    uuid: &str,
+    user_id: i32,
+) -> Result<bool, diesel::result::Error> {
+    diesel::select(diesel::dsl::exists(
+        game_save::table
+            .filter(game_save::uuid.eq(uuid))
+            .filter(game_save::user_id.eq(user_id)),
+    ))
+    .get_result(connection)
+}
+
+// Unexpired tokens with the admin scope, apart from `excluded_id`
+fn other_admin_tokens(
+    connection: &mut SqliteConnection,
+    excluded_id: i32,
+) -> Result<i64, diesel::result::Error> {
+    api_tokens::table
+        .inner_join(api_token_scope::table)
+        .filter(api_token_scope::scope.eq(TokenScope::Admin))
+        .filter(api_tokens::id.ne(excluded_id))
+        .filter(
+            api_tokens::expires
+                .is_null()
+                .or(api_tokens::expires.gt(utc_now())),
+        )
+        .count()
+        .get_result(connection)
+}
+
+// Unexpired tokens with the admin scope of administrators other than `excluded_user_id`
+fn other_admin_users_tokens(
+    connection: &mut SqliteConnection,
+    excluded_user_id: i32,
+) -> Result<i64, diesel::result::Error> {
+    api_tokens::table
+        .inner_join(api_token_scope::table)
+        .inner_join(user_account::table)
+        .filter(user_account::admin.eq(true))
+        .filter(api_token_scope::scope.eq(TokenScope::Admin))
+        .filter(api_tokens::user_id.ne(excluded_user_id))
+        .filter(
+            api_tokens::expires
+                .is_null()
+                .or(api_tokens::expires.gt(utc_now())),
+        )
+        .count()
+        .get_result(connection)
+}
+
+fn remove_user_api_tokens(
+    connection: &mut SqliteConnection,
+    user_id: i32,
+) -> Result<(), diesel::result::Error> {
+    diesel::delete(
+        api_token_scope::table.filter(
+            api_token_scope::api_token_id.nullable().eq_any(
+                api_tokens::table
+                    .filter(api_tokens::user_id.eq(user_id))
+                    .select(api_tokens::id),
+            ),
+        ),
+    )
+    .execute(connection)?;
+    diesel::delete(api_tokens::table.filter(api_tokens::user_id.eq(user_id)))
+        .execute(connection)?;
+    Ok(())
+}
+
+fn touch_device(
+    connection: &mut SqliteConnection,
+    device_id: i32,
+) -> Result<(), diesel::result::Error> {
+    let now = utc_now();
+    diesel::update(
+        device::table
+            .filter(device::id.eq(device_id))
+            .filter(device::last_seen.lt(now - time::Duration::minutes(1))),
+    )
+    .set(device::last_seen.eq(now))
+    .execute(connection)?;
+    Ok(())
+}
+
+fn set_device_sync(
+    connection: &mut SqliteConnection,
+    device_id: i32,
+    path_id: i32,
+    save_uuid: &str,
+    save_time: time::PrimitiveDateTime,
+) -> Result<(), diesel::result::Error> {
+    diesel::replace_into(device_sync::table)
+        .values(DbDeviceSync {
+            device_id,
+            path_id,
+            save_uuid: save_uuid.to_string(),
+            save_time,
+            synced: utc_now(),
+        })
+        .execute(connection)?;
+    Ok(())
+}
+
+fn load_save_reference(
+    connection: &mut SqliteConnection,
+    game_save: DbGameSave,
+) -> Result<SaveReference, diesel::result::Error> {
+    let files_hash_db = DbFileHash::belonging_to(&game_save).load::<DbFileHash>(connection)?;
+
+    Ok(SaveReference {
+        uuid: game_save.uuid,
+        path_id: game_save.path_id,
+        time: game_save.time.assume_utc().unix_timestamp(),
+        parent_uuid: game_save.parent_uuid,
+        restored_from: game_save.restored_from,
+        pinned: game_save.pinned,
+        corrupt: game_save.corrupt,
+        metadata: SaveMetadata {
+            label: game_save.label,
+            note: game_save.note,
+            hostname: game_save.hostname,
+            client_version: game_save.client_version,
+            game_version: game_save.game_version,
+            playtime: game_save.playtime,
+        },
+        hash_algorithm: game_save.hash_algorithm,
+        archive_digest: game_save.archive_digest,
+        codec: game_save.codec,
+        encrypted: game_save.data_key.is_some(),
+        device_id: game_save.device_id,
+        files_hash: files_hash_db
+            .into_iter()
+            .map(|files_hash_db| FileHash {
+                relative_path: files_hash_db.relative_path,
+                hash: files_hash_db.hash,
+                size: files_hash_db.size,
+            })
+            .collect(),
+    })
+}
+
 fn add_game_metadata(
     connection: &mut SqliteConnection,
     game_metadata: &GameMetadataCreate,
@@ -247,14 +781,35 @@ impl GameDatabase {
             }
         }
 
-        let api_tokens = db
-            .get_api_tokens()
+        let upgraded = db
+            .upgrade_legacy_api_tokens()
+            .expect("unable to hash the plaintext api tokens");
+        if upgraded > 0 {
+            tracing::info!("Hashed {upgraded} API tokens stored in plaintext");
+        }
+
+        let has_api_tokens = db
+            .has_api_tokens()
             .expect("unable to get api_tokens the db at api_tokens initial validation");
 
-        if api_tokens.is_empty() {
+        if !has_api_tokens {
             let uuid = Uuid::new_v4();
-            db.add_api_tokens(vec![uuid])
-                .expect("unable to add initial api tokens");
+            let admin = db
+                .get_users()
+                .expect("unable to get users at api_tokens initial validation")
+                .into_iter()
+                .find(|user| user.admin)
+                .expect("no admin user to give the initial api token to");
+            db.add_api_token(
+                &ApiTokenCreate {
+                    name: "Initial token".to_string(),
+                    scopes: vec![TokenScope::Admin],
+                    expires: None,
+                },
+                admin.id,
+                uuid,
+            )
+            .expect("unable to add initial api tokens");
 
             tracing::info!("Initial API token : {uuid}");
         }
@@ -391,6 +946,76 @@ impl GameDatabase {
         Ok(())
     }
 
+    pub fn update_game_metadata_by_id(
+        &self,
+        game_id: i32,
+        game_metadata: &GameMetadataCreate,
+    ) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            if !game_exists(connection, game_id)? {
+                return Ok(CatalogUpdate::NotFound);
+            }
+            update_game_metadata(connection, game_id, game_metadata)?;
+            unset_ludusavi_managed(connection, game_id)?;
+            Ok(CatalogUpdate::Done)
+        })
+    }
+
+    pub fn remove_game(&self, game_id: i32) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            if !game_exists(connection, game_id)? {
+                return Ok(CatalogUpdate::NotFound);
+            }
+
+            let path_ids: Vec<Option<i32>> = game_path::table
+                .filter(game_path::game_metadata_id.eq(game_id))
+                .select(game_path::id)
+                .load(connection)?;
+            let path_ids: Vec<i32> = path_ids.into_iter().flatten().collect();
+            let has_saves: bool = diesel::select(diesel::dsl::exists(
+                game_save::table.filter(game_save::path_id.eq_any(&path_ids)),
+            ))
+            .get_result(connection)?;
+            if has_saves {
+                return Ok(CatalogUpdate::Conflict);
+            }
+
+            remove_upload_sessions_by_path_ids(connection, &path_ids)?;
+            diesel::delete(game_lock::table.filter(game_lock::game_metadata_id.eq(game_id)))
+                .execute(connection)?;
+            diesel::delete(game_path::table.filter(game_path::game_metadata_id.eq(game_id)))
+                .execute(connection)?;
+            diesel::delete(
+                game_executable::table.filter(game_executable::game_metadata_id.eq(game_id)),
+            )
+            .execute(connection)?;
+            diesel::delete(
+                game_registry::table.filter(game_registry::game_metadata_id.eq(game_id)),
+            )
+            .execute(connection)?;
+            diesel::delete(
+                game_alt_name::table.filter(game_alt_name::game_metadata_id.eq(game_id)),
+            )
+            .execute(connection)?;
+            diesel::delete(
+                game_gog_extra_id::table.filter(game_gog_extra_id::game_metadata_id.eq(game_id)),
+            )
+            .execute(connection)?;
+            diesel::delete(
+                game_steam_extra_id::table
+                    .filter(game_steam_extra_id::game_metadata_id.eq(game_id)),
+            )
+            .execute(connection)?;
+            diesel::delete(game_metadata::table.filter(game_metadata::id.eq(game_id)))
+                .execute(connection)?;
+            Ok(CatalogUpdate::Done)
+        })
+    }
+
     pub fn add_games_metadata(
         &self,
         games_metadata: Vec<&GameMetadataCreate>,
@@ -433,6 +1058,31 @@ impl GameDatabase {
             .collect())
     }
 
+    /// Id of the game named `name`, by default name first then by alternative name
+    pub fn get_game_id_by_name(
+        &self,
+        name: &str,
+    ) -> Result<Option<i32>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        let by_default_name: Option<Option<i32>> = game_metadata::table
+            .filter(game_metadata::default_name.eq(name))
+            .order(game_metadata::id.asc())
+            .select(game_metadata::id)
+            .first(connection)
+            .optional()?;
+        if let Some(game_id) = by_default_name.flatten() {
+            return Ok(Some(game_id));
+        }
+
+        Ok(game_alt_name::table
+            .filter(game_alt_name::name.eq(name))
+            .order(game_alt_name::game_metadata_id.asc())
+            .select(game_alt_name::game_metadata_id)
+            .first(connection)
+            .optional()?)
+    }
+
     pub fn get_games_metadata_by_name(
         &self,
         target_name: &str,
@@ -605,8 +1255,10 @@ impl GameDatabase {
         Ok(games)
     }
 
+    /// Games and paths the user `user_id` has saves for
     pub fn get_games_metadata_and_paths_if_saves_exist(
         &self,
+        user_id: i32,
     ) -> Result<Vec<GameMetadataWithPaths>, Box<dyn Error + Send + Sync>> {
         let connection = &mut self.pool.get()?;
         let db_games: Vec<(DbGameMetadata, DbGamePath)> = game_metadata::table
@@ -614,6 +1266,7 @@ impl GameDatabase {
                 game_path::table.on(game_path::game_metadata_id.nullable().eq(game_metadata::id)),
             )
             .inner_join(game_save::table.on(game_save::path_id.nullable().eq(game_path::id)))
+            .filter(game_save::user_id.eq(user_id))
             .select((DbGameMetadata::as_select(), DbGamePath::as_select()))
             .distinct()
             .load(connection)?;
@@ -687,6 +1340,68 @@ impl GameDatabase {
         Ok(())
     }
 
+    pub fn update_game_path(
+        &self,
+        game_id: i32,
+        path_id: i32,
+        path: &SavePathCreate,
+    ) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            let updated = diesel::update(
+                game_path::table
+                    .filter(game_path::id.eq(path_id))
+                    .filter(game_path::game_metadata_id.eq(game_id)),
+            )
+            .set((
+                game_path::path.eq(&path.path),
+                game_path::operating_system.eq(path.operating_system),
+            ))
+            .execute(connection);
+            match updated {
+                Ok(0) => Ok(CatalogUpdate::NotFound),
+                Ok(_) => {
+                    unset_ludusavi_managed(connection, game_id)?;
+                    Ok(CatalogUpdate::Done)
+                }
+                Err(err) if is_unique_violation(&err) => Ok(CatalogUpdate::Conflict),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+
+    pub fn remove_game_path(
+        &self,
+        game_id: i32,
+        path_id: i32,
+    ) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            let path = game_path::table
+                .filter(game_path::id.eq(path_id))
+                .filter(game_path::game_metadata_id.eq(game_id));
+            let exists: bool = diesel::select(diesel::dsl::exists(path)).get_result(connection)?;
+            if !exists {
+                return Ok(CatalogUpdate::NotFound);
+            }
+
+            let has_saves: bool = diesel::select(diesel::dsl::exists(
+                game_save::table.filter(game_save::path_id.eq(path_id)),
+            ))
+            .get_result(connection)?;
+            if has_saves {
+                return Ok(CatalogUpdate::Conflict);
+            }
+
+            diesel::delete(path).execute(connection)?;
+            remove_upload_sessions_by_path_ids(connection, &[path_id])?;
+            unset_ludusavi_managed(connection, game_id)?;
+            Ok(CatalogUpdate::Done)
+        })
+    }
+
     pub fn get_paths_by_game_id_and_os(
         &self,
         game_id: i32,
@@ -741,6 +1456,59 @@ impl GameDatabase {
             .execute(connection)?;
         Ok(())
     }
+    pub fn update_game_executable(
+        &self,
+        game_id: i32,
+        executable_id: i32,
+        executable: &ExecutableCreate,
+    ) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            let updated = diesel::update(
+                game_executable::table
+                    .filter(game_executable::id.eq(executable_id))
+                    .filter(game_executable::game_metadata_id.eq(game_id)),
+            )
+            .set((
+                game_executable::executable.eq(&executable.executable),
+                game_executable::operating_system.eq(executable.operating_system),
+            ))
+            .execute(connection);
+            match updated {
+                Ok(0) => Ok(CatalogUpdate::NotFound),
+                Ok(_) => {
+                    unset_ludusavi_managed(connection, game_id)?;
+                    Ok(CatalogUpdate::Done)
+                }
+                Err(err) if is_unique_violation(&err) => Ok(CatalogUpdate::Conflict),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+
+    pub fn remove_game_executable(
+        &self,
+        game_id: i32,
+        executable_id: i32,
+    ) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            let removed = diesel::delete(
+                game_executable::table
+                    .filter(game_executable::id.eq(executable_id))
+                    .filter(game_executable::game_metadata_id.eq(game_id)),
+            )
+            .execute(connection)?;
+            if removed == 0 {
+                return Ok(CatalogUpdate::NotFound);
+            }
+            unset_ludusavi_managed(connection, game_id)?;
+            Ok(CatalogUpdate::Done)
+        })
+    }
+
     pub fn get_executable_by_game_id_and_os(
         &self,
         game_id: i32,
@@ -783,261 +1551,2169 @@ impl GameDatabase {
         Ok(executables)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn add_reference_to_save(
         &self,
         uuid: Uuid,
         path_id: i32,
-        files_hash: Vec<FileHash>,
+        parent: &SaveParent,
+        metadata: &SaveMetadata,
+        owner: SaveOwner,
+        digest: Option<&SaveDigest>,
+        codec: Codec,
+        encryption: Option<&EncryptionKey>,
+        archive_format: Option<ArchiveFormat>,
+        files: Vec<SaveFile>,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let connection = &mut self.pool.get()?;
-        let now = time::OffsetDateTime::now_utc();
+
+        let mut blob_references: HashMap<String, DbBlob> = HashMap::new();
+        for file in &files {
+            blob_references
+                .entry(file.blob_hash.clone())
+                .or_insert(DbBlob {
+                    hash: file.blob_hash.clone(),
+                    size: file.size,
+                    ref_count: 0,
+                    codec: file.codec,
+                    stored_size: file.stored_size,
+                    data_key: file
+                        .encryption
+                        .as_ref()
+                        .map(|encryption| encryption.wrapped_key.clone()),
+                    nonce: file
+                        .encryption
+                        .as_ref()
+                        .map(|encryption| encryption.nonce.clone()),
+                })
+                .ref_count += 1;
+        }
 
         connection.immediate_transaction(|connection| {
+            let game_id: Option<i32> = game_path::table
+                .filter(game_path::id.eq(path_id))
+                .select(game_path::game_metadata_id)
+                .first(connection)
+                .optional()?;
+            if let Some(game_id) = game_id
+                && let Some(game_lock) = get_active_game_lock(connection, game_id, owner.user_id)?
+                && parent.lock_holder.as_deref() != Some(game_lock.holder.as_str())
+            {
+                return Err(to_game_lock(game_lock).into());
+            }
+
+            let head = get_head_save_uuid(connection, path_id, owner.user_id)?;
+            let parent_uuid = if parent.force {
+                let base_exists = match &parent.parent_uuid {
+                    Some(base) => diesel::select(diesel::dsl::exists(
+                        game_save::table
+                            .filter(game_save::uuid.eq(base))
+                            .filter(game_save::path_id.eq(path_id))
+                            .filter(game_save::user_id.eq(owner.user_id)),
+                    ))
+                    .get_result(connection)?,
+                    None => false,
+                };
+                if base_exists {
+                    parent.parent_uuid.clone()
+                } else {
+                    head
+                }
+            } else if parent.parent_uuid.is_none() || head == parent.parent_uuid {
+                if parent.parent_uuid.is_none() && head.is_some() {
+                    tracing::warn!(
+                        "Save {} of path {} declares no parent, storing it on top of the latest save",
+                        uuid,
+                        path_id
+                    );
+                }
+                head
+            } else {
+                return Err(SaveConflict {
+                    head,
+                    base: parent.parent_uuid.clone(),
+                }
+                .into());
+            };
+
+            let time = utc_now();
             diesel::insert_into(game_save::table)
                 .values(DbGameSave {
                     uuid: uuid.to_string(),
                     path_id,
-                    time: time::PrimitiveDateTime::new(now.date(), now.time()),
+                    time,
+                    legacy_archive: false,
+                    parent_uuid,
+                    pinned: false,
+                    label: metadata.label.clone(),
+                    note: metadata.note.clone(),
+                    hostname: metadata.hostname.clone(),
+                    client_version: metadata.client_version.clone(),
+                    game_version: metadata.game_version.clone(),
+                    playtime: metadata.playtime,
+                    restored_from: None,
+                    hash_algorithm: digest.map(|digest| digest.hash_algorithm),
+                    archive_digest: digest.map(|digest| digest.archive_digest.clone()),
+                    corrupt: false,
+                    codec: Some(codec),
+                    data_key: encryption.map(|encryption| encryption.wrapped_key.clone()),
+                    nonce: encryption.map(|encryption| encryption.nonce.clone()),
+                    device_id: owner.device_id,
+                    user_id: owner.user_id,
+                    archive_format,
                 })
                 .execute(connection)?;
 
-            for file_hash in files_hash {
-                diesel::insert_into(file_hash::table)
-                    .values(DbFileHash {
-                        relative_path: file_hash.relative_path,
-                        hash: file_hash.hash,
-                        game_save_uuid: uuid.to_string(),
-                    })
-                    .execute(connection)?;
+            // The device uploading a save has it on disk
+            if let Some(device_id) = owner.device_id {
+                set_device_sync(connection, device_id, path_id, &uuid.to_string(), time)?;
+                if let Some(client_version) = &metadata.client_version {
+                    diesel::update(device::table.filter(device::id.eq(device_id)))
+                        .set(device::client_version.eq(client_version))
+                        .execute(connection)?;
+                }
+            }
+
+            for db_blob in blob_references.into_values() {
+                let ref_count = db_blob.ref_count;
+                diesel::insert_into(blob::table)
+                    .values(db_blob)
+                    .on_conflict(blob::hash)
+                    .do_update()
+                    .set(blob::ref_count.eq(blob::ref_count + ref_count))
+                    .execute(connection)?;
+            }
+
+            for file in files {
+                diesel::insert_into(file_hash::table)
+                    .values(DbFileHash {
+                        relative_path: file.relative_path,
+                        hash: file.hash,
+                        game_save_uuid: uuid.to_string(),
+                        blob_hash: Some(file.blob_hash),
+                        size: Some(file.size),
+                    })
+                    .execute(connection)?;
             }
             Ok(())
         })
     }
 
-    pub fn get_reference_to_save_by_path_id(
+    /// Adds a new head for the path of `source_uuid` sharing its content, without any copy of the
+    /// blobs. Returns the new save, or `None` if `source_uuid` isn't a save of `user_id`.
+    /// Copies save `source_uuid` as the latest save of its path, uploaded by `owner`. Fails with
+    /// the lock of the game if it is held by another holder than `lock_holder`, or with
+    /// [`CorruptSave`].
+    pub fn add_restored_save(
         &self,
-        path_id: i32,
-    ) -> Result<Option<Vec<SaveReference>>, Box<dyn Error + Send + Sync>> {
+        source_uuid: &str,
+        owner: SaveOwner,
+        lock_holder: Option<&str>,
+        uuid: Uuid,
+    ) -> Result<Option<DbGameSave>, Box<dyn Error + Send + Sync>> {
         let connection = &mut self.pool.get()?;
+        let user_id = owner.user_id;
 
-        let save_rows = game_save::table
-            .filter(game_save::path_id.eq(path_id))
-            .select(DbGameSave::as_select())
-            .load(connection)
-            .optional()?
-            .unwrap_or_default();
-
-        if save_rows.is_empty() {
-            return Ok(None);
-        }
+        connection.immediate_transaction(|connection| {
+            let maybe_source: Option<DbGameSave> = game_save::table
+                .filter(game_save::uuid.eq(source_uuid))
+                .filter(game_save::user_id.eq(user_id))
+                .select(DbGameSave::as_select())
+                .first(connection)
+                .optional()?;
+            let source = match maybe_source {
+                Some(source) => source,
+                None => return Ok(None),
+            };
+            if source.corrupt {
+                return Err(CorruptSave { uuid: source.uuid }.into());
+            }
+            let game_id: i32 = game_path::table
+                .filter(game_path::id.eq(source.path_id))
+                .select(game_path::game_metadata_id)
+                .first(connection)?;
+            if let Some(game_lock) = get_active_game_lock(connection, game_id, user_id)?
+                && lock_holder != Some(game_lock.holder.as_str())
+            {
+                return Err(to_game_lock(game_lock).into());
+            }
+            let files_hash_db = DbFileHash::belonging_to(&source).load::<DbFileHash>(connection)?;
+
+            let restored = DbGameSave {
+                uuid: uuid.to_string(),
+                path_id: source.path_id,
+                time: utc_now(),
+                legacy_archive: source.legacy_archive,
+                parent_uuid: get_head_save_uuid(connection, source.path_id, user_id)?,
+                pinned: false,
+                label: source.label,
+                note: source.note,
+                hostname: source.hostname,
+                client_version: source.client_version,
+                game_version: source.game_version,
+                playtime: source.playtime,
+                restored_from: Some(source.uuid),
+                hash_algorithm: source.hash_algorithm,
+                archive_digest: source.archive_digest,
+                corrupt: false,
+                codec: source.codec,
+                data_key: source.data_key,
+                nonce: source.nonce,
+                device_id: owner.device_id,
+                user_id,
+                archive_format: source.archive_format,
+            };
+            diesel::insert_into(game_save::table)
+                .values(&restored)
+                .execute(connection)?;
 
-        let mut save_references: Vec<SaveReference> = Vec::with_capacity(save_rows.len());
-        for game_save in save_rows {
-            let files_hash_db =
-                DbFileHash::belonging_to(&game_save).load::<DbFileHash>(connection)?;
-
-            save_references.push(SaveReference {
-                uuid: game_save.uuid.to_string(),
-                path_id: game_save.path_id,
-                time: game_save.time.assume_utc().unix_timestamp(),
-                files_hash: files_hash_db
-                    .iter()
-                    .map(|files_hash_db| FileHash {
-                        relative_path: files_hash_db.relative_path.clone(),
-                        hash: files_hash_db.hash.clone(),
+            for file_hash_db in files_hash_db {
+                if let Some(blob_hash) = &file_hash_db.blob_hash {
+                    diesel::update(blob::table.filter(blob::hash.eq(blob_hash)))
+                        .set(blob::ref_count.eq(blob::ref_count + 1))
+                        .execute(connection)?;
+                }
+                diesel::insert_into(file_hash::table)
+                    .values(DbFileHash {
+                        game_save_uuid: uuid.to_string(),
+                        ..file_hash_db
                     })
-                    .collect(),
-            })
-        }
+                    .execute(connection)?;
+            }
 
-        Ok(Some(save_references))
+            Ok(Some(restored))
+        })
     }
 
-    pub fn get_database_uuid(&self) -> Result<Option<Uuid>, Box<dyn Error + Send + Sync>> {
+    pub fn get_save_file_hashes(
+        &self,
+        uuid: &str,
+        user_id: i32,
+    ) -> Result<Option<Vec<SaveFileHash>>, Box<dyn Error + Send + Sync>> {
         let connection = &mut self.pool.get()?;
-        let maybe_db_info: Option<DbDbInfo> = db_info::table
-            .select(DbDbInfo::as_select())
+
+        let maybe_game_save: Option<DbGameSave> = game_save::table
+            .filter(game_save::uuid.eq(uuid))
+            .filter(game_save::user_id.eq(user_id))
+            .select(DbGameSave::as_select())
             .first(connection)
             .optional()?;
 
-        match maybe_db_info {
-            Some(db_info) => Ok(Some(Uuid::parse_str(&db_info.db_uuid)?)),
+        match maybe_game_save {
+            Some(game_save) => Ok(Some(
+                DbFileHash::belonging_to(&game_save)
+                    .load::<DbFileHash>(connection)?
+                    .into_iter()
+                    .map(|file_hash_db| SaveFileHash {
+                        relative_path: file_hash_db.relative_path,
+                        hash: file_hash_db.hash,
+                        blob_hash: file_hash_db.blob_hash,
+                        size: file_hash_db.size,
+                    })
+                    .collect(),
+            )),
             None => Ok(None),
         }
     }
 
-    pub fn add_database_uuid(&self, uuid: Uuid) -> Result<(), Box<dyn Error + Send + Sync>> {
+    pub fn get_save_manifest(
+        &self,
+        uuid: &str,
+        user_id: i32,
+    ) -> Result<Option<SaveManifest>, Box<dyn Error + Send + Sync>> {
         let connection = &mut self.pool.get()?;
-        diesel::insert_into(db_info::table)
-            .values(DbDbInfo {
-                id: None,
-                db_uuid: uuid.to_string(),
-            })
-            .execute(connection)?;
 
-        Ok(())
-    }
+        let maybe_game_save: Option<DbGameSave> = game_save::table
+            .filter(game_save::uuid.eq(uuid))
+            .filter(game_save::user_id.eq(user_id))
+            .select(DbGameSave::as_select())
+            .first(connection)
+            .optional()?;
+
+        let game_save = match maybe_game_save {
+            Some(game_save) => game_save,
+            None => return Ok(None),
+        };
+
+        let files_hash_db: Vec<(DbFileHash, Option<DbBlob>)> = DbFileHash::belonging_to(&game_save)
+            .left_join(blob::table)
+            .select((DbFileHash::as_select(), Option::<DbBlob>::as_select()))
+            .load(connection)?;
 
-    pub fn get_api_tokens(&self) -> Result<Vec<Uuid>, Box<dyn Error + Send + Sync>> {
-        let mut api_tokens = Vec::<Uuid>::new();
+        Ok(Some(SaveManifest {
+            time: game_save.time.assume_utc().unix_timestamp(),
+            legacy_archive: game_save.legacy_archive,
+            archive_digest: game_save.archive_digest,
+            archive_format: game_save.archive_format,
+            files: files_hash_db
+                .into_iter()
+                .filter_map(|(file_hash_db, db_blob)| {
+                    let db_blob = db_blob?;
+                    Some(SaveFile {
+                        relative_path: file_hash_db.relative_path,
+                        hash: file_hash_db.hash,
+                        blob_hash: db_blob.hash,
+                        size: db_blob.size,
+                        codec: db_blob.codec,
+                        stored_size: db_blob.stored_size,
+                        encryption: EncryptionKey::from_columns(db_blob.data_key, db_blob.nonce),
+                    })
+                })
+                .collect(),
+        }))
+    }
 
+    pub fn remove_saves(
+        &self,
+        uuids: &[String],
+    ) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
         let connection = &mut self.pool.get()?;
-        let maybe_api_tokens = api_tokens::table
-            .select(DbApiTokens::as_select())
-            .load::<DbApiTokens>(connection)
-            .optional()?;
 
-        if let Some(db_api_tokens) = maybe_api_tokens {
-            db_api_tokens
-                .iter()
-                .filter_map(|db_api_token| Uuid::parse_str(&db_api_token.api_token).ok())
-                .for_each(|uuid| api_tokens.push(uuid));
-        }
+        connection.immediate_transaction(|connection| {
+            let blob_hashes: Vec<Option<String>> = file_hash::table
+                .filter(file_hash::game_save_uuid.eq_any(uuids))
+                .select(file_hash::blob_hash)
+                .load(connection)?;
+
+            let mut blob_references: HashMap<String, i32> = HashMap::new();
+            for blob_hash in blob_hashes.into_iter().flatten() {
+                *blob_references.entry(blob_hash).or_default() += 1;
+            }
+
+            //Keep the history connected by linking the children to the removed save's parent
+            for uuid in uuids {
+                let parent_uuid: Option<Option<String>> = game_save::table
+                    .filter(game_save::uuid.eq(uuid))
+                    .select(game_save::parent_uuid)
+                    .first(connection)
+                    .optional()?;
+                if let Some(parent_uuid) = parent_uuid {
+                    diesel::update(game_save::table.filter(game_save::parent_uuid.eq(uuid)))
+                        .set(game_save::parent_uuid.eq(parent_uuid))
+                        .execute(connection)?;
+                }
+            }
+
+            diesel::delete(file_hash::table.filter(file_hash::game_save_uuid.eq_any(uuids)))
+                .execute(connection)?;
+            diesel::delete(game_save::table.filter(game_save::uuid.eq_any(uuids)))
+                .execute(connection)?;
+
+            for (blob_hash, ref_count) in &blob_references {
+                diesel::update(blob::table.filter(blob::hash.eq(blob_hash)))
+                    .set(blob::ref_count.eq(blob::ref_count - ref_count))
+                    .execute(connection)?;
+            }
+
+            let orphan_blobs: Vec<String> = blob::table
+                .filter(blob::hash.eq_any(blob_references.keys()))
+                .filter(blob::ref_count.le(0))
+                .select(blob::hash)
+                .load(connection)?;
+            diesel::delete(blob::table.filter(blob::hash.eq_any(&orphan_blobs)))
+                .execute(connection)?;
+
+            Ok(orphan_blobs)
+        })
+    }
+
+    pub fn update_save_metadata(
+        &self,
+        uuid: &str,
+        user_id: i32,
+        update: &SaveMetadataUpdate,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let clear_if_empty =
+            |value: &String| Some(value.to_owned()).filter(|value| !value.is_empty());
 
-        Ok(api_tokens)
+        connection.immediate_transaction(|connection| {
+            if !save_belongs_to(connection, uuid, user_id)? {
+                return Ok(false);
+            }
+            if let Some(label) = &update.label {
+                diesel::update(game_save::table.filter(game_save::uuid.eq(uuid)))
+                    .set(game_save::label.eq(clear_if_empty(label)))
+                    .execute(connection)?;
+            }
+            if let Some(note) = &update.note {
+                diesel::update(game_save::table.filter(game_save::uuid.eq(uuid)))
+                    .set(game_save::note.eq(clear_if_empty(note)))
+                    .execute(connection)?;
+            }
+            Ok(true)
+        })
     }
 
-    pub fn add_api_tokens(&self, uuids: Vec<Uuid>) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let db_api_tokens: Vec<DbApiTokens> = uuids
-            .iter()
-            .map(|uuid| DbApiTokens {
-                id: None,
-                api_token: uuid.to_string(),
-            })
-            .collect();
+    pub fn set_save_pinned(
+        &self,
+        uuid: &str,
+        user_id: i32,
+        pinned: bool,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        let updated = diesel::update(
+            game_save::table
+                .filter(game_save::uuid.eq(uuid))
+                .filter(game_save::user_id.eq(user_id)),
+        )
+        .set(game_save::pinned.eq(pinned))
+        .execute(connection)?;
+        Ok(updated > 0)
+    }
 
+    /// Dates the save `uuid` at `time`, for saves imported from another tool
+    pub fn set_save_time(
+        &self,
+        uuid: &str,
+        time: i64,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
         let connection = &mut self.pool.get()?;
-        diesel::insert_into(api_tokens::table)
-            .values(db_api_tokens)
+
+        let time = time::OffsetDateTime::from_unix_timestamp(time)?;
+        let updated = diesel::update(game_save::table.filter(game_save::uuid.eq(uuid)))
+            .set(game_save::time.eq(time::PrimitiveDateTime::new(time.date(), time.time())))
             .execute(connection)?;
+        Ok(updated > 0)
+    }
 
-        Ok(())
+    /// Every path and user with a history of saves, as `(path_id, user_id)`
+    pub fn get_save_histories(&self) -> Result<Vec<(i32, i32)>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        Ok(game_save::table
+            .select((game_save::path_id, game_save::user_id))
+            .distinct()
+            .load(connection)?)
     }
 
-    pub fn remove_api_tokens(&self, uuids: Vec<Uuid>) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let db_api_tokens: Vec<String> = uuids.iter().map(|uuid| uuid.to_string()).collect();
+    pub fn get_blobs(&self) -> Result<Vec<BlobInfo>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
 
+        Ok(blob::table
+            .select(DbBlob::as_select())
+            .load(connection)?
+            .into_iter()
+            .map(|db_blob| BlobInfo {
+                hash: db_blob.hash,
+                size: db_blob.size,
+                codec: db_blob.codec,
+                stored_size: db_blob.stored_size,
+                encryption: EncryptionKey::from_columns(db_blob.data_key, db_blob.nonce),
+            })
+            .collect())
+    }
+
+    pub fn get_blob(&self, hash: &str) -> Result<Option<BlobInfo>, Box<dyn Error + Send + Sync>> {
         let connection = &mut self.pool.get()?;
-        diesel::delete(api_tokens::table.filter(api_tokens::api_token.eq_any(&db_api_tokens)))
-            .execute(connection)?;
 
-        Ok(())
+        Ok(blob::table
+            .filter(blob::hash.eq(hash))
+            .select(DbBlob::as_select())
+            .first(connection)
+            .optional()?
+            .map(|db_blob| BlobInfo {
+                hash: db_blob.hash,
+                size: db_blob.size,
+                codec: db_blob.codec,
+                stored_size: db_blob.stored_size,
+                encryption: EncryptionKey::from_columns(db_blob.data_key, db_blob.nonce),
+            }))
     }
 
-    pub fn get_configuration_value(
-        &self,
-        id: &str,
-    ) -> Result<Option<DbConfiguration>, Box<dyn Error + Send + Sync>> {
+    /// Hashes of the blobs stored in plaintext, before a master key was configured
+    pub fn get_plaintext_blob_hashes(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
         let connection = &mut self.pool.get()?;
-        let maybe_configurations = configurations::table
-            .filter(configurations::columns::id.eq(id))
-            .select(DbConfiguration::as_select())
-            .first::<DbConfiguration>(connection)
-            .optional()?;
 
-        Ok(maybe_configurations)
+        Ok(blob::table
+            .filter(blob::data_key.is_null())
+            .order(blob::hash)
+            .select(blob::hash)
+            .load(connection)?)
     }
 
-    pub fn update_configuration_value(
+    /// Records the key the blob `hash` is encrypted with, `None` for a blob in plaintext
+    pub fn set_blob_encryption(
         &self,
-        id: &str,
-        value: &str,
-    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        hash: &str,
+        encryption: Option<&EncryptionKey>,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
         let connection = &mut self.pool.get()?;
-        diesel::update(configurations::table.filter(configurations::columns::id.eq(id)))
-            .set(configurations::columns::value.eq(value))
+
+        let updated = diesel::update(blob::table.filter(blob::hash.eq(hash)))
+            .set((
+                blob::data_key.eq(encryption.map(|encryption| encryption.wrapped_key.clone())),
+                blob::nonce.eq(encryption.map(|encryption| encryption.nonce.clone())),
+            ))
             .execute(connection)?;
+        Ok(updated > 0)
+    }
 
-        Ok(())
+    /// Replaces every wrapped data key, of the saves and of the blobs, by `rewrap(wrapped_key)`
+    /// in a single transaction. Returns the number of keys replaced.
+    pub fn rewrap_data_keys(
+        &self,
+        rewrap: impl Fn(&[u8]) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>>,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            let mut rewrapped = 0;
+
+            let save_keys: Vec<(String, Option<Vec<u8>>)> = game_save::table
+                .filter(game_save::data_key.is_not_null())
+                .select((game_save::uuid, game_save::data_key))
+                .load(connection)?;
+            for (uuid, data_key) in save_keys {
+                if let Some(data_key) = data_key {
+                    diesel::update(game_save::table.filter(game_save::uuid.eq(uuid)))
+                        .set(game_save::data_key.eq(rewrap(&data_key)?))
+                        .execute(connection)?;
+                    rewrapped += 1;
+                }
+            }
+
+            let blob_keys: Vec<(String, Option<Vec<u8>>)> = blob::table
+                .filter(blob::data_key.is_not_null())
+                .select((blob::hash, blob::data_key))
+                .load(connection)?;
+            for (hash, data_key) in blob_keys {
+                if let Some(data_key) = data_key {
+                    diesel::update(blob::table.filter(blob::hash.eq(hash)))
+                        .set(blob::data_key.eq(rewrap(&data_key)?))
+                        .execute(connection)?;
+                    rewrapped += 1;
+                }
+            }
+
+            Ok(rewrapped)
+        })
     }
 
-    pub fn get_game_registry_by_game_id(
+    pub fn get_legacy_archive_uuids(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        Ok(game_save::table
+            .filter(game_save::legacy_archive.eq(true))
+            .select(game_save::uuid)
+            .load(connection)?)
+    }
+
+    /// Marks as corrupt the saves referencing one of `damaged_blobs` or listed in
+    /// `damaged_archives`, and clears the mark of every other save. Returns the corrupt saves.
+    pub fn mark_corrupt_saves(
         &self,
-        game_id: i32,
-    ) -> Result<Vec<GameRegistry>, Box<dyn Error + Send + Sync>> {
+        damaged_blobs: &[String],
+        damaged_archives: &[String],
+    ) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
         let connection = &mut self.pool.get()?;
-        let registries: Option<Vec<GameRegistry>> = game_registry::table
-            .filter(game_registry::game_metadata_id.eq(game_id))
-            .load::<DbGameRegistry>(connection)
-            .optional()?
-            .map(|vec_db_game_registry| {
-                vec_db_game_registry
-                    .iter()
-                    .map(|db_game_registry| GameRegistry {
-                        path: db_game_registry.path.clone(),
-                    })
-                    .collect()
-            });
 
-        Ok(registries.unwrap_or_default())
+        connection.immediate_transaction(|connection| {
+            let mut corrupt_saves: Vec<String> = file_hash::table
+                .filter(file_hash::blob_hash.eq_any(damaged_blobs))
+                .select(file_hash::game_save_uuid)
+                .distinct()
+                .load(connection)?;
+            corrupt_saves.extend(damaged_archives.iter().cloned());
+            corrupt_saves.sort();
+            corrupt_saves.dedup();
+
+            diesel::update(game_save::table)
+                .set(game_save::corrupt.eq(false))
+                .execute(connection)?;
+            diesel::update(game_save::table.filter(game_save::uuid.eq_any(&corrupt_saves)))
+                .set(game_save::corrupt.eq(true))
+                .execute(connection)?;
+
+            Ok(corrupt_saves)
+        })
     }
 
-    pub fn add_game_registry_path(
+    pub fn set_scrub_report(
         &self,
-        game_id: i32,
-        registry: &GameRegistry,
+        report: &ScrubReport,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let connection = &mut self.pool.get()?;
 
-        diesel::insert_into(game_registry::table)
-            .values(DbGameRegistry {
-                path: registry.path.clone(),
-                game_metadata_id: game_id,
-            })
+        let db_report = DbScrubReport {
+            id: 1,
+            time: utc_now(),
+            report: serde_json::to_string(report)?,
+        };
+        diesel::insert_into(scrub_report::table)
+            .values(&db_report)
+            .on_conflict(scrub_report::id)
+            .do_update()
+            .set((
+                scrub_report::time.eq(db_report.time),
+                scrub_report::report.eq(&db_report.report),
+            ))
             .execute(connection)?;
+
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn get_scrub_report(&self) -> Result<Option<ScrubReport>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
 
-    fn fresh_db() -> GameDatabase {
-        GameDatabase::new(&format!("file:{}?mode=memory&cache=shared", Uuid::new_v4()))
+        let maybe_report: Option<String> = scrub_report::table
+            .select(scrub_report::report)
+            .first(connection)
+            .optional()?;
+
+        Ok(maybe_report
+            .map(|report| serde_json::from_str(&report))
+            .transpose()?)
     }
 
-    #[test]
-    fn test_add_games_full() -> Result<(), Box<dyn Error + Send + Sync>> {
-        let db = fresh_db();
+    pub fn get_reference_to_save_by_path_id(
+        &self,
+        path_id: i32,
+        user_id: i32,
+    ) -> Result<Option<Vec<SaveReference>>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
 
-        let game_full = GameFull {
-            game_metadata: GameMetadataCreate {
-                known_name: None,
-                steam_appid: None,
-                default_name: "FullGame".to_string(),
-                install_dir: None,
-                gog: None,
-                flatpak_id: None,
-                lutris_id: None,
-                epic_cloud: None,
-                gog_cloud: None,
-                origin_cloud: None,
-                steam_cloud: None,
-                uplay_cloud: None,
-                ludusavi_managed: None,
-                gog_extra: None,
-                steam_extra: None,
-            },
-            executables: vec![],
-            paths: vec![],
-            registries: vec![],
-        };
+        let save_rows = game_save::table
+            .filter(game_save::path_id.eq(path_id))
+            .filter(game_save::user_id.eq(user_id))
+            .order(game_save::time.asc())
+            .select(DbGameSave::as_select())
+            .load(connection)
+            .optional()?
+            .unwrap_or_default();
 
-        db.add_games_full(vec![game_full])?;
-        let names = db.get_games_default_name()?;
-        assert!(names.iter().any(|n| n.default_name == "FullGame"));
+        if save_rows.is_empty() {
+            return Ok(None);
+        }
+
+        let mut save_references: Vec<SaveReference> = Vec::with_capacity(save_rows.len());
+        for game_save in save_rows {
+            save_references.push(load_save_reference(connection, game_save)?);
+        }
+
+        Ok(Some(save_references))
+    }
+
+    /// Saves `uuids` of `user_id` with the default name of their game, or their latest save of
+    /// every path when `uuids` is `None`. Unknown UUIDs are left out.
+    pub fn get_bundle_saves(
+        &self,
+        uuids: Option<&[String]>,
+        user_id: i32,
+    ) -> Result<Vec<BundleSave>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        let query = game_save::table
+            .inner_join(game_path::table.left_join(game_metadata::table))
+            .filter(game_save::user_id.eq(user_id))
+            .order((game_save::path_id.asc(), game_save::time.desc()))
+            .select((
+                DbGameSave::as_select(),
+                game_path::game_metadata_id,
+                game_metadata::default_name.nullable(),
+            ))
+            .into_boxed();
+        let save_rows: Vec<(DbGameSave, i32, Option<String>)> = match uuids {
+            Some(uuids) => query
+                .filter(game_save::uuid.eq_any(uuids))
+                .load(connection)?,
+            None => query
+                .load::<(DbGameSave, i32, Option<String>)>(connection)?
+                .into_iter()
+                .dedup_by(|(save1, _, _), (save2, _, _)| save1.path_id == save2.path_id)
+                .collect(),
+        };
+
+        let mut bundle_saves = Vec::with_capacity(save_rows.len());
+        for (game_save, game_id, game_name) in save_rows {
+            bundle_saves.push(BundleSave {
+                // Paths can outlive the game they were added to
+                game_name: game_name.unwrap_or_else(|| format!("game {}", game_id)),
+                reference: load_save_reference(connection, game_save)?,
+            });
+        }
+        Ok(bundle_saves)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_upload_session(
+        &self,
+        uuid: Uuid,
+        path_id: i32,
+        user_id: i32,
+        size: i64,
+        files_hash: &[FileHash],
+        metadata: Option<&SaveMetadata>,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let file_hash = serde_json::to_string(files_hash)?;
+        let metadata = metadata.map(serde_json::to_string).transpose()?;
+
+        connection.immediate_transaction(|connection| {
+            let path_exists: bool = diesel::select(diesel::dsl::exists(
+                game_path::table.filter(game_path::id.eq(path_id)),
+            ))
+            .get_result(connection)?;
+            if !path_exists {
+                return Ok(CatalogUpdate::NotFound);
+            }
+
+            diesel::insert_into(upload_session::table)
+                .values(DbUploadSession {
+                    uuid: uuid.to_string(),
+                    path_id,
+                    size,
+                    file_hash,
+                    last_activity: utc_now(),
+                    finalizing: false,
+                    metadata,
+                    hash_algorithm,
+                    user_id,
+                })
+                .execute(connection)?;
+            Ok(CatalogUpdate::Done)
+        })
+    }
+
+    /// Upload session `uuid` of `user_id`
+    pub fn get_upload_session(
+        &self,
+        uuid: &str,
+        user_id: i32,
+    ) -> Result<Option<UploadSessionRecord>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        let maybe_session: Option<DbUploadSession> = upload_session::table
+            .filter(upload_session::uuid.eq(uuid))
+            .filter(upload_session::user_id.eq(user_id))
+            .select(DbUploadSession::as_select())
+            .first(connection)
+            .optional()?;
+
+        let session = match maybe_session {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+
+        let chunks = DbUploadChunk::belonging_to(&session).load::<DbUploadChunk>(connection)?;
+
+        Ok(Some(UploadSessionRecord {
+            path_id: session.path_id,
+            size: session.size,
+            file_hash: serde_json::from_str(&session.file_hash)?,
+            last_activity: session.last_activity.assume_utc().unix_timestamp(),
+            finalizing: session.finalizing,
+            metadata: session
+                .metadata
+                .map(|metadata| serde_json::from_str(&metadata))
+                .transpose()?
+                .unwrap_or_default(),
+            hash_algorithm: session.hash_algorithm,
+            chunks: chunks
+                .iter()
+                .map(|chunk| ByteRange {
+                    start: chunk.start_offset,
+                    end: chunk.start_offset + chunk.size,
+                })
+                .collect(),
+        }))
+    }
+
+    /// Records a chunk of upload session `uuid`, refused with `Conflict` once it is being finalized
+    pub fn add_upload_chunk(
+        &self,
+        uuid: &str,
+        chunk_number: i32,
+        start_offset: i64,
+        size: i64,
+    ) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            let finalizing: Option<bool> = upload_session::table
+                .filter(upload_session::uuid.eq(uuid))
+                .select(upload_session::finalizing)
+                .first(connection)
+                .optional()?;
+            match finalizing {
+                None => return Ok(CatalogUpdate::NotFound),
+                Some(true) => return Ok(CatalogUpdate::Conflict),
+                Some(false) => (),
+            }
+
+            diesel::insert_into(upload_chunk::table)
+                .values(DbUploadChunk {
+                    chunk_number,
+                    upload_session_uuid: uuid.to_string(),
+                    start_offset,
+                    size,
+                })
+                .on_conflict((
+                    upload_chunk::chunk_number,
+                    upload_chunk::upload_session_uuid,
+                ))
+                .do_update()
+                .set((
+                    upload_chunk::start_offset.eq(start_offset),
+                    upload_chunk::size.eq(size),
+                ))
+                .execute(connection)?;
+
+            diesel::update(upload_session::table.filter(upload_session::uuid.eq(uuid)))
+                .set(upload_session::last_activity.eq(utc_now()))
+                .execute(connection)?;
+            Ok(CatalogUpdate::Done)
+        })
+    }
+
+    /// Starting to finalize also refreshes the last activity, so the session doesn't expire while
+    /// its archive is extracted but still does if the server stops before it is done
+    pub fn set_upload_session_finalizing(
+        &self,
+        uuid: &str,
+        finalizing: bool,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let updated = diesel::update(
+            upload_session::table
+                .filter(upload_session::uuid.eq(uuid))
+                .filter(upload_session::finalizing.eq(!finalizing)),
+        )
+        .set((
+            upload_session::finalizing.eq(finalizing),
+            upload_session::last_activity.eq(utc_now()),
+        ))
+        .execute(connection)?;
+
+        Ok(updated == 1)
+    }
+
+    pub fn remove_upload_session(&self, uuid: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            diesel::delete(upload_chunk::table.filter(upload_chunk::upload_session_uuid.eq(uuid)))
+                .execute(connection)?;
+            diesel::delete(upload_session::table.filter(upload_session::uuid.eq(uuid)))
+                .execute(connection)?;
+            Ok(())
+        })
+    }
+
+    pub fn remove_upload_sessions_inactive_since(
+        &self,
+        since: time::PrimitiveDateTime,
+    ) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            let uuids: Vec<String> = upload_session::table
+                .filter(upload_session::last_activity.lt(since))
+                .select(upload_session::uuid)
+                .load(connection)?;
+
+            diesel::delete(
+                upload_chunk::table.filter(upload_chunk::upload_session_uuid.eq_any(&uuids)),
+            )
+            .execute(connection)?;
+            diesel::delete(upload_session::table.filter(upload_session::uuid.eq_any(&uuids)))
+                .execute(connection)?;
+
+            Ok(uuids)
+        })
+    }
+
+    /// Locks the game of `user_id` for `holder` until `lease` from now, or renews its lock. Returns
+    /// `None` if the game doesn't exist, fails with the current lock if another holder has it.
+    pub fn acquire_game_lock(
+        &self,
+        game_id: i32,
+        user_id: i32,
+        holder: &str,
+        hostname: Option<&str>,
+        lease: time::Duration,
+    ) -> Result<Option<GameLock>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            if !game_exists(connection, game_id)? {
+                return Ok(None);
+            }
+            let now = utc_now();
+            let acquired = match get_active_game_lock(connection, game_id, user_id)? {
+                Some(game_lock) if game_lock.holder != holder => {
+                    return Err(to_game_lock(game_lock).into());
+                }
+                Some(game_lock) => game_lock.acquired,
+                None => now,
+            };
+            let game_lock = DbGameLock {
+                game_metadata_id: game_id,
+                user_id,
+                holder: holder.to_string(),
+                hostname: hostname.map(str::to_string),
+                acquired,
+                expires: now + lease,
+            };
+            diesel::replace_into(game_lock::table)
+                .values(&game_lock)
+                .execute(connection)?;
+            Ok(Some(to_game_lock(game_lock)))
+        })
+    }
+
+    pub fn get_game_lock(
+        &self,
+        game_id: i32,
+        user_id: i32,
+    ) -> Result<Option<GameLock>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        Ok(get_active_game_lock(connection, game_id, user_id)?.map(to_game_lock))
+    }
+
+    pub fn get_game_locks(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<GameLock>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let game_locks = game_lock::table
+            .filter(game_lock::user_id.eq(user_id))
+            .filter(game_lock::expires.gt(utc_now()))
+            .order(game_lock::game_metadata_id)
+            .select(DbGameLock::as_select())
+            .load(connection)?;
+        Ok(game_locks.into_iter().map(to_game_lock).collect())
+    }
+
+    /// Releases the lock of the game of `user_id` held by `holder`, or whoever holds it without a
+    /// holder. Returns whether a lock was active, fails with the current lock if another holder
+    /// has it.
+    pub fn release_game_lock(
+        &self,
+        game_id: i32,
+        user_id: i32,
+        holder: Option<&str>,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            let active = match get_active_game_lock(connection, game_id, user_id)? {
+                Some(game_lock) if holder.is_some_and(|holder| holder != game_lock.holder) => {
+                    return Err(to_game_lock(game_lock).into());
+                }
+                active => active.is_some(),
+            };
+            diesel::delete(
+                game_lock::table
+                    .filter(game_lock::game_metadata_id.eq(game_id))
+                    .filter(game_lock::user_id.eq(user_id)),
+            )
+            .execute(connection)?;
+            Ok(active)
+        })
+    }
+
+    pub fn get_database_uuid(&self) -> Result<Option<Uuid>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let maybe_db_info: Option<DbDbInfo> = db_info::table
+            .select(DbDbInfo::as_select())
+            .first(connection)
+            .optional()?;
+
+        match maybe_db_info {
+            Some(db_info) => Ok(Some(Uuid::parse_str(&db_info.db_uuid)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn add_database_uuid(&self, uuid: Uuid) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        diesel::insert_into(db_info::table)
+            .values(DbDbInfo {
+                id: None,
+                db_uuid: uuid.to_string(),
+            })
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    pub fn has_api_tokens(&self) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        Ok(diesel::select(diesel::dsl::exists(
+            api_tokens::table.select(api_tokens::id),
+        ))
+        .get_result(connection)?)
+    }
+
+    /// Hashes the tokens stored in plaintext before tokens were hashed. Returns how many were.
+    pub fn upgrade_legacy_api_tokens(&self) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        let upgraded = connection.immediate_transaction(|connection| {
+            let legacy_tokens: Vec<DbLegacyApiToken> = legacy_api_token::table
+                .select(DbLegacyApiToken::as_select())
+                .load(connection)?;
+            for legacy_token in &legacy_tokens {
+                let salt = generate_salt();
+                diesel::update(
+                    api_tokens::table.filter(api_tokens::id.eq(legacy_token.api_token_id)),
+                )
+                .set((
+                    api_tokens::token_prefix.eq(api_token_prefix(&legacy_token.api_token)),
+                    api_tokens::token_hash.eq(hash_api_token(&salt, &legacy_token.api_token)),
+                    api_tokens::salt.eq(salt),
+                ))
+                .execute(connection)?;
+            }
+            diesel::delete(legacy_api_token::table).execute(connection)?;
+            Ok(legacy_tokens.len())
+        });
+        // The plaintext stays in free pages and the WAL until both are rewritten
+        if upgraded.as_ref().is_ok_and(|upgraded| *upgraded > 0) {
+            connection.batch_execute("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")?;
+        }
+        upgraded
+    }
+
+    /// Adds unnamed tokens with the admin scope, for the first administrator
+    pub fn add_api_tokens(&self, uuids: Vec<Uuid>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let token = ApiTokenCreate {
+            name: String::new(),
+            scopes: vec![TokenScope::Admin],
+            expires: None,
+        };
+
+        let connection = &mut self.pool.get()?;
+        connection.immediate_transaction(|connection| {
+            let user_id = first_admin_user_id(connection)?;
+            for uuid in uuids {
+                insert_api_token(connection, uuid, &token, None, user_id, None)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn remove_api_tokens(&self, uuids: Vec<Uuid>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        connection.immediate_transaction(|connection| {
+            for uuid in uuids {
+                let Some(db_token) = find_api_token(connection, &uuid.to_string())? else {
+                    continue;
+                };
+                diesel::delete(
+                    api_token_scope::table
+                        .filter(api_token_scope::api_token_id.nullable().eq(db_token.id)),
+                )
+                .execute(connection)?;
+                diesel::delete(api_tokens::table.filter(api_tokens::id.eq(db_token.id)))
+                    .execute(connection)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn get_api_token_details(&self) -> Result<Vec<ApiToken>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let db_tokens: Vec<DbApiTokens> = api_tokens::table
+            .order(api_tokens::id)
+            .select(DbApiTokens::as_select())
+            .load(connection)?;
+        let mut scopes = api_token_scope::table
+            .select(DbApiTokenScope::as_select())
+            .load::<DbApiTokenScope>(connection)?
+            .into_iter()
+            .into_group_map_by(|scope| scope.api_token_id);
+        Ok(db_tokens
+            .into_iter()
+            .map(|db_token| {
+                let token_scopes = scopes
+                    .remove(&db_token.id.unwrap_or_default())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|scope| scope.scope)
+                    .collect();
+                to_api_token(db_token, token_scopes)
+            })
+            .collect())
+    }
+
+    pub fn get_api_token_detail(
+        &self,
+        id: i32,
+    ) -> Result<Option<ApiToken>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let db_token = api_tokens::table
+            .filter(api_tokens::id.eq(id))
+            .select(DbApiTokens::as_select())
+            .first(connection)
+            .optional()?;
+        Ok(db_token
+            .map(|db_token| load_api_token(connection, db_token))
+            .transpose()?)
+    }
+
+    pub fn add_api_token(
+        &self,
+        token: &ApiTokenCreate,
+        user_id: i32,
+        api_token: Uuid,
+    ) -> Result<ApiToken, Box<dyn Error + Send + Sync>> {
+        let expires = token.expires.map(utc_from_unix_timestamp).transpose()?;
+        let connection = &mut self.pool.get()?;
+        Ok(connection.immediate_transaction(|connection| {
+            insert_api_token(connection, api_token, token, expires, user_id, None)
+        })?)
+    }
+
+    /// Replaces the name, scopes and expiry of a token. The last token with the admin scope can't
+    /// lose it.
+    pub fn update_api_token(
+        &self,
+        id: i32,
+        token: &ApiTokenCreate,
+    ) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let expires = token.expires.map(utc_from_unix_timestamp).transpose()?;
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            if !api_token_exists(connection, id)? {
+                return Ok(CatalogUpdate::NotFound);
+            }
+            let stays_admin = token.scopes.contains(&TokenScope::Admin)
+                && expires.is_none_or(|expires| expires > utc_now());
+            if !stays_admin && other_admin_tokens(connection, id)? == 0 {
+                return Ok(CatalogUpdate::Conflict);
+            }
+            diesel::update(api_tokens::table.filter(api_tokens::id.eq(id)))
+                .set((
+                    api_tokens::name.eq(&token.name),
+                    api_tokens::expires.eq(expires),
+                ))
+                .execute(connection)?;
+            set_api_token_scopes(connection, id, &token.scopes)?;
+            Ok(CatalogUpdate::Done)
+        })
+    }
+
+    /// Revokes a token, unless it is the last one with the admin scope
+    pub fn remove_api_token(&self, id: i32) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            if !api_token_exists(connection, id)? {
+                return Ok(CatalogUpdate::NotFound);
+            }
+            if other_admin_tokens(connection, id)? == 0 {
+                return Ok(CatalogUpdate::Conflict);
+            }
+            diesel::delete(api_token_scope::table.filter(api_token_scope::api_token_id.eq(id)))
+                .execute(connection)?;
+            diesel::delete(api_tokens::table.filter(api_tokens::id.eq(id))).execute(connection)?;
+            Ok(CatalogUpdate::Done)
+        })
+    }
+
+    /// Returns the token if it exists and hasn't expired, and records that it and its device were
+    /// used. Like the device, the time is only written once a minute.
+    pub fn authenticate_api_token(
+        &self,
+        api_token: &str,
+    ) -> Result<Option<ApiToken>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let now = utc_now();
+        let db_token = find_api_token(connection, api_token)?
+            .filter(|db_token| db_token.expires.is_none_or(|expires| expires > now));
+        let Some(db_token) = db_token else {
+            return Ok(None);
+        };
+
+        if db_token
+            .last_used
+            .is_none_or(|last_used| last_used < now - time::Duration::minutes(1))
+        {
+            diesel::update(api_tokens::table.filter(api_tokens::id.eq(db_token.id)))
+                .set(api_tokens::last_used.eq(now))
+                .execute(connection)?;
+        }
+        if let Some(device_id) = db_token.device_id {
+            touch_device(connection, device_id)?;
+        }
+        Ok(Some(load_api_token(connection, db_token)?))
+    }
+
+    pub fn get_users(&self) -> Result<Vec<User>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let users = user_account::table
+            .order(user_account::id)
+            .select(DbUserAccount::as_select())
+            .load(connection)?;
+        Ok(users.into_iter().map(to_user).collect())
+    }
+
+    pub fn get_user(&self, id: i32) -> Result<Option<User>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        Ok(find_user(connection, id)?.map(to_user))
+    }
+
+    /// Adds a user with `api_token` as their first token, allowed to read, upload and edit the
+    /// catalog, and to administer if the user is an administrator. Returns `None` if the name is
+    /// taken.
+    pub fn add_user(
+        &self,
+        user: &UserCreate,
+        api_token: Uuid,
+    ) -> Result<Option<User>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            let name_taken: bool = diesel::select(diesel::dsl::exists(
+                user_account::table.filter(user_account::name.eq(&user.name)),
+            ))
+            .get_result(connection)?;
+            if name_taken {
+                return Ok(None);
+            }
+            diesel::insert_into(user_account::table)
+                .values(DbUserAccount {
+                    id: None,
+                    name: user.name.clone(),
+                    admin: user.admin,
+                    created: utc_now(),
+                })
+                .execute(connection)?;
+            let db_user: DbUserAccount = user_account::table
+                .order(user_account::id.desc())
+                .select(DbUserAccount::as_select())
+                .first(connection)?;
+
+            let mut scopes = vec![
+                TokenScope::ReadOnly,
+                TokenScope::Upload,
+                TokenScope::CatalogWrite,
+            ];
+            if user.admin {
+                scopes.push(TokenScope::Admin);
+            }
+            insert_api_token(
+                connection,
+                api_token,
+                &ApiTokenCreate {
+                    name: db_user.name.clone(),
+                    scopes,
+                    expires: None,
+                },
+                None,
+                db_user.id.unwrap_or_default(),
+                None,
+            )?;
+            Ok(Some(to_user(db_user)))
+        })
+    }
+
+    /// Renames the user and grants or revokes administration. A demoted user loses the admin
+    /// scope of their tokens, unless no other administrator could still administer.
+    pub fn update_user(
+        &self,
+        id: i32,
+        user: &UserCreate,
+    ) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            let Some(db_user) = find_user(connection, id)? else {
+                return Ok(CatalogUpdate::NotFound);
+            };
+            let name_taken: bool = diesel::select(diesel::dsl::exists(
+                user_account::table
+                    .filter(user_account::name.eq(&user.name))
+                    .filter(user_account::id.ne(id)),
+            ))
+            .get_result(connection)?;
+            if name_taken {
+                return Ok(CatalogUpdate::Conflict);
+            }
+            if db_user.admin && !user.admin {
+                if other_admin_users_tokens(connection, id)? == 0 {
+                    return Ok(CatalogUpdate::Conflict);
+                }
+                diesel::delete(
+                    api_token_scope::table
+                        .filter(api_token_scope::scope.eq(TokenScope::Admin))
+                        .filter(
+                            api_token_scope::api_token_id.nullable().eq_any(
+                                api_tokens::table
+                                    .filter(api_tokens::user_id.eq(id))
+                                    .select(api_tokens::id),
+                            ),
+                        ),
+                )
+                .execute(connection)?;
+            }
+            diesel::update(user_account::table.filter(user_account::id.eq(id)))
+                .set((
+                    user_account::name.eq(&user.name),
+                    user_account::admin.eq(user.admin),
+                ))
+                .execute(connection)?;
+            Ok(CatalogUpdate::Done)
+        })
+    }
+
+    /// Removes the user with their tokens and devices. A user with saves, or the last one able to
+    /// administer, can't be removed.
+    pub fn remove_user(&self, id: i32) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            if find_user(connection, id)?.is_none() {
+                return Ok(CatalogUpdate::NotFound);
+            }
+            let has_saves: bool = diesel::select(diesel::dsl::exists(
+                game_save::table.filter(game_save::user_id.eq(id)),
+            ))
+            .get_result(connection)?;
+            if has_saves || other_admin_users_tokens(connection, id)? == 0 {
+                return Ok(CatalogUpdate::Conflict);
+            }
+            remove_user_api_tokens(connection, id)?;
+            diesel::delete(
+                device_sync::table.filter(
+                    device_sync::device_id.nullable().eq_any(
+                        device::table
+                            .filter(device::user_id.eq(id))
+                            .select(device::id),
+                    ),
+                ),
+            )
+            .execute(connection)?;
+            diesel::delete(device::table.filter(device::user_id.eq(id))).execute(connection)?;
+            diesel::delete(pairing_code::table.filter(pairing_code::user_id.eq(id)))
+                .execute(connection)?;
+            diesel::delete(game_lock::table.filter(game_lock::user_id.eq(id)))
+                .execute(connection)?;
+            diesel::delete(user_account::table.filter(user_account::id.eq(id)))
+                .execute(connection)?;
+            Ok(CatalogUpdate::Done)
+        })
+    }
+
+    /// Registers a device of `user_id` with `api_token` as its own token
+    pub fn add_device(
+        &self,
+        device: &DeviceCreate,
+        user_id: i32,
+        api_token: Uuid,
+    ) -> Result<Device, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        Ok(connection.immediate_transaction(|connection| {
+            insert_device(connection, device, user_id, api_token)
+        })?)
+    }
+
+    /// Adds a pairing code valid for `lifetime` registering a device of `user_id`, expired codes
+    /// are removed on the way. Returns when it expires.
+    pub fn add_pairing_code(
+        &self,
+        code: &str,
+        user_id: i32,
+        lifetime: time::Duration,
+    ) -> Result<i64, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            let now = utc_now();
+            diesel::delete(pairing_code::table.filter(pairing_code::expires.le(now)))
+                .execute(connection)?;
+            let expires = now + lifetime;
+            diesel::insert_into(pairing_code::table)
+                .values(DbPairingCode {
+                    code: code.to_string(),
+                    created: now,
+                    expires,
+                    user_id,
+                })
+                .execute(connection)?;
+            Ok(expires.assume_utc().unix_timestamp())
+        })
+    }
+
+    /// Consumes the pairing code and registers the device, for the user the code was made for,
+    /// with `api_token` as its own token. Returns `None` if the code doesn't exist, was already
+    /// used or expired.
+    pub fn redeem_pairing_code(
+        &self,
+        code: &str,
+        device: &DeviceCreate,
+        api_token: Uuid,
+    ) -> Result<Option<Device>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        Ok(connection.immediate_transaction(|connection| {
+            let user_id: Option<i32> = pairing_code::table
+                .filter(pairing_code::code.eq(code))
+                .filter(pairing_code::expires.gt(utc_now()))
+                .select(pairing_code::user_id)
+                .first(connection)
+                .optional()?;
+            let Some(user_id) = user_id else {
+                return Ok(None);
+            };
+            diesel::delete(pairing_code::table.filter(pairing_code::code.eq(code)))
+                .execute(connection)?;
+            insert_device(connection, device, user_id, api_token).map(Some)
+        })?)
+    }
+
+    pub fn get_devices(&self) -> Result<Vec<Device>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let devices = device::table
+            .order(device::id)
+            .select(DbDevice::as_select())
+            .load(connection)?;
+        Ok(devices.into_iter().map(to_device).collect())
+    }
+
+    pub fn get_device(&self, id: i32) -> Result<Option<Device>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let device = device::table
+            .filter(device::id.eq(id))
+            .select(DbDevice::as_select())
+            .first(connection)
+            .optional()?;
+        Ok(device.map(to_device))
+    }
+
+    /// Removes the device and revokes its tokens, its saves are kept without a device
+    pub fn remove_device(&self, id: i32) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            diesel::delete(
+                api_token_scope::table.filter(
+                    api_token_scope::api_token_id.nullable().eq_any(
+                        api_tokens::table
+                            .filter(api_tokens::device_id.eq(id))
+                            .select(api_tokens::id),
+                    ),
+                ),
+            )
+            .execute(connection)?;
+            diesel::delete(api_tokens::table.filter(api_tokens::device_id.eq(id)))
+                .execute(connection)?;
+            diesel::delete(device_sync::table.filter(device_sync::device_id.eq(id)))
+                .execute(connection)?;
+            diesel::update(game_save::table.filter(game_save::device_id.eq(id)))
+                .set(game_save::device_id.eq(None::<i32>))
+                .execute(connection)?;
+            let removed =
+                diesel::delete(device::table.filter(device::id.eq(id))).execute(connection)?;
+            Ok(removed == 1)
+        })
+    }
+
+    /// Returns the device of `api_token`, if any, and records that it was seen. The time is only
+    /// written once a minute to spare a write on every request.
+    pub fn touch_device_by_api_token(
+        &self,
+        api_token: &str,
+    ) -> Result<Option<i32>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let device_id =
+            find_api_token(connection, api_token)?.and_then(|db_token| db_token.device_id);
+        let Some(device_id) = device_id else {
+            return Ok(None);
+        };
+        touch_device(connection, device_id)?;
+        Ok(Some(device_id))
+    }
+
+    /// Records that the device has `save_uuid` on disk. Returns `false` if the device or the save
+    /// doesn't exist, or the save isn't one of the path and user of the device.
+    pub fn set_device_synced_save(
+        &self,
+        device_id: i32,
+        path_id: i32,
+        save_uuid: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            let user_id: Option<i32> = device::table
+                .filter(device::id.eq(device_id))
+                .select(device::user_id)
+                .first(connection)
+                .optional()?;
+            let Some(user_id) = user_id else {
+                return Ok(false);
+            };
+            let save_time: Option<time::PrimitiveDateTime> = game_save::table
+                .filter(game_save::uuid.eq(save_uuid))
+                .filter(game_save::path_id.eq(path_id))
+                .filter(game_save::user_id.eq(user_id))
+                .select(game_save::time)
+                .first(connection)
+                .optional()?;
+            match save_time {
+                Some(save_time) => {
+                    set_device_sync(connection, device_id, path_id, save_uuid, save_time)?;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        })
+    }
+
+    /// Sync state of every path the device uploaded or synced a save of, by game name
+    pub fn get_device_sync_states(
+        &self,
+        device_id: i32,
+    ) -> Result<Vec<DeviceSyncState>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        let synced_paths: Vec<(DbDeviceSync, i32, String, i32)> = device_sync::table
+            .inner_join(game_path::table.inner_join(game_metadata::table))
+            .inner_join(device::table)
+            .filter(device_sync::device_id.eq(device_id))
+            .order((game_metadata::default_name, device_sync::path_id))
+            .select((
+                DbDeviceSync::as_select(),
+                game_path::game_metadata_id,
+                game_metadata::default_name,
+                device::user_id,
+            ))
+            .load(connection)?;
+
+        let mut states = Vec::with_capacity(synced_paths.len());
+        for (sync, game_id, game_name, user_id) in synced_paths {
+            let latest: Option<(String, time::PrimitiveDateTime)> = game_save::table
+                .filter(game_save::path_id.eq(sync.path_id))
+                .filter(game_save::user_id.eq(user_id))
+                .order(game_save::time.desc())
+                .select((game_save::uuid, game_save::time))
+                .first(connection)
+                .optional()?;
+            states.push(DeviceSyncState {
+                game_id,
+                game_name,
+                path_id: sync.path_id,
+                behind: latest
+                    .as_ref()
+                    .is_some_and(|(latest_uuid, _)| *latest_uuid != sync.save_uuid),
+                synced_save_uuid: sync.save_uuid,
+                synced_save_time: sync.save_time.assume_utc().unix_timestamp(),
+                synced: sync.synced.assume_utc().unix_timestamp(),
+                latest_save_time: latest
+                    .as_ref()
+                    .map(|(_, time)| time.assume_utc().unix_timestamp()),
+                latest_save_uuid: latest.map(|(uuid, _)| uuid),
+            });
+        }
+        Ok(states)
+    }
+
+    pub fn get_configuration_value(
+        &self,
+        id: &str,
+    ) -> Result<Option<DbConfiguration>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let maybe_configurations = configurations::table
+            .filter(configurations::columns::id.eq(id))
+            .select(DbConfiguration::as_select())
+            .first::<DbConfiguration>(connection)
+            .optional()?;
+
+        Ok(maybe_configurations)
+    }
+
+    pub fn update_configuration_value(
+        &self,
+        id: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        diesel::update(configurations::table.filter(configurations::columns::id.eq(id)))
+            .set(configurations::columns::value.eq(value))
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    pub fn get_game_registry_by_game_id(
+        &self,
+        game_id: i32,
+    ) -> Result<Vec<GameRegistry>, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+        let registries: Option<Vec<GameRegistry>> = game_registry::table
+            .filter(game_registry::game_metadata_id.eq(game_id))
+            .load::<DbGameRegistry>(connection)
+            .optional()?
+            .map(|vec_db_game_registry| {
+                vec_db_game_registry
+                    .iter()
+                    .map(|db_game_registry| GameRegistry {
+                        path: db_game_registry.path.clone(),
+                    })
+                    .collect()
+            });
+
+        Ok(registries.unwrap_or_default())
+    }
+
+    pub fn add_game_registry_path(
+        &self,
+        game_id: i32,
+        registry: &GameRegistry,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        diesel::insert_into(game_registry::table)
+            .values(DbGameRegistry {
+                path: registry.path.clone(),
+                game_metadata_id: game_id,
+            })
+            .execute(connection)?;
+        Ok(())
+    }
+
+    pub fn update_game_registry(
+        &self,
+        game_id: i32,
+        added: &[GameRegistry],
+        removed: &[GameRegistry],
+        replace: bool,
+    ) -> Result<CatalogUpdate, Box<dyn Error + Send + Sync>> {
+        let connection = &mut self.pool.get()?;
+
+        connection.immediate_transaction(|connection| {
+            if !game_exists(connection, game_id)? {
+                return Ok(CatalogUpdate::NotFound);
+            }
+
+            let registries =
+                game_registry::table.filter(game_registry::game_metadata_id.eq(game_id));
+            if replace {
+                diesel::delete(registries).execute(connection)?;
+            } else {
+                diesel::delete(registries.filter(
+                    game_registry::path.eq_any(removed.iter().map(|registry| &registry.path)),
+                ))
+                .execute(connection)?;
+            }
+            for registry in added {
+                diesel::insert_into(game_registry::table)
+                    .values(DbGameRegistry {
+                        path: registry.path.clone(),
+                        game_metadata_id: game_id,
+                    })
+                    .on_conflict_do_nothing()
+                    .execute(connection)?;
+            }
+            unset_ludusavi_managed(connection, game_id)?;
+            Ok(CatalogUpdate::Done)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_game_with_path;
+
+    const ADMIN: i32 = 1;
+    const ADMIN_SAVES: SaveOwner = SaveOwner {
+        user_id: ADMIN,
+        device_id: None,
+    };
+
+    fn fresh_db() -> GameDatabase {
+        GameDatabase::new(&format!("file:{}?mode=memory&cache=shared", Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_add_games_full() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        let game_full = GameFull {
+            game_metadata: GameMetadataCreate {
+                known_name: None,
+                steam_appid: None,
+                default_name: "FullGame".to_string(),
+                install_dir: None,
+                gog: None,
+                flatpak_id: None,
+                lutris_id: None,
+                epic_cloud: None,
+                gog_cloud: None,
+                origin_cloud: None,
+                steam_cloud: None,
+                uplay_cloud: None,
+                ludusavi_managed: None,
+                gog_extra: None,
+                steam_extra: None,
+            },
+            executables: vec![],
+            paths: vec![],
+            registries: vec![],
+        };
+
+        db.add_games_full(vec![game_full])?;
+        let names = db.get_games_default_name()?;
+        assert!(names.iter().any(|n| n.default_name == "FullGame"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_games_metadata() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: None,
+            steam_appid: None,
+            default_name: "MetaGame".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: None,
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        let names = db.get_games_default_name()?;
+        assert!(names.iter().any(|n| n.default_name == "MetaGame"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_games_by_name_contains() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: None,
+            steam_appid: None,
+            default_name: "Super Mario".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: None,
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        let results = db.search_games_by_name("Mario")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].default_name, "Super Mario");
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_games_by_name_equal() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: None,
+            steam_appid: None,
+            default_name: "Super Mario".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: None,
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        let results = db.search_games_by_name("Super Mario")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].default_name, "Super Mario");
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_games_by_known_name_contains() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: Some(vec!["Super Luigi".to_string()]),
+            steam_appid: None,
+            default_name: "Super Mario".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: None,
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        let results = db.search_games_by_name("Luigi")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].default_name, "Super Mario");
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_games_by_known_name_equal() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: Some(vec!["Super Luigi".to_string()]),
+            steam_appid: None,
+            default_name: "Super Mario".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: None,
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        let results = db.search_games_by_name("Super Luigi")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].default_name, "Super Mario");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_game_id_by_name() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: Some(vec!["Super Luigi".to_string()]),
+            steam_appid: None,
+            default_name: "Super Mario".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: None,
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        assert_eq!(db.get_game_id_by_name("Super Mario")?, Some(1));
+        assert_eq!(db.get_game_id_by_name("Super Luigi")?, Some(1));
+        assert_eq!(db.get_game_id_by_name("Super")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_games_by_default_name_case_insensitive()
+    -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: None,
+            steam_appid: None,
+            default_name: "Super Mario".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: None,
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        let results = db.search_games_by_name("super mario")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].default_name, "Super Mario");
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_games_no_results() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+        let results = db.search_games_by_name("Nonexistent Game")?;
+        assert!(results.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_game_metadata_by_name() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: None,
+            steam_appid: None,
+            default_name: "MetaName".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: None,
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        let meta = db.get_games_metadata_by_name("MetaName")?;
+        assert_eq!(meta.first().unwrap().metadata.default_name, "MetaName");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_game_metadata_by_id() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: None,
+            steam_appid: None,
+            default_name: "ById".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: None,
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        let meta = db.get_game_metadata_by_id(&1)?;
+        assert_eq!(meta.unwrap().metadata.default_name, "ById");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_games_default_name() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: None,
+            steam_appid: None,
+            default_name: "Default".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: None,
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        let names = db.get_games_default_name()?;
+        assert!(names.iter().any(|n| n.default_name == "Default"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_games_default_name_and_ludusavi_managed() -> Result<(), Box<dyn Error + Send + Sync>>
+    {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: None,
+            steam_appid: None,
+            default_name: "Managed".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: Some(true),
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        let result = db.get_games_default_name_and_ludusavi_managed()?;
+        assert!(
+            result.iter().any(
+                |game_name_ludusavi| game_name_ludusavi.default_name == "Managed"
+                    && game_name_ludusavi.ludusavi_managed.unwrap()
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_games_metadata() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: None,
+            steam_appid: None,
+            default_name: "MetaList".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: None,
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        let games_metadata = db.get_games_metadata()?;
+        assert!(
+            games_metadata
+                .iter()
+                .any(|m| m.metadata.default_name == "MetaList")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_games_metadata_and_paths_if_saves_exist() -> Result<(), Box<dyn Error + Send + Sync>>
+    {
+        let db = fresh_db();
+
+        db.add_games_metadata(vec![&GameMetadataCreate {
+            known_name: None,
+            steam_appid: None,
+            default_name: "PathsExist".to_string(),
+            install_dir: None,
+            gog: None,
+            flatpak_id: None,
+            lutris_id: None,
+            epic_cloud: None,
+            gog_cloud: None,
+            origin_cloud: None,
+            steam_cloud: None,
+            uplay_cloud: None,
+            ludusavi_managed: None,
+            gog_extra: None,
+            steam_extra: None,
+        }])?;
+
+        db.add_game_path(
+            1,
+            &SavePathCreate {
+                path: "p1".to_string(),
+                operating_system: OS::Undefined,
+            },
+        )?;
+
+        db.add_reference_to_save(
+            Uuid::new_v4(),
+            1,
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![SaveFile {
+                relative_path: "potato".to_string(),
+                hash: "potato".to_string(),
+                blob_hash: "potato".to_string(),
+                size: 6,
+                codec: Codec::Identity,
+                stored_size: 6,
+                encryption: None,
+            }],
+        )?;
+
+        let res = db.get_games_metadata_and_paths_if_saves_exist(ADMIN)?;
+        assert!(
+            res.iter()
+                .any(|m| m.game_metadata.metadata.default_name == "PathsExist")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_save_time() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        db.add_game_path(
+            1,
+            &SavePathCreate {
+                path: "p1".to_string(),
+                operating_system: OS::Undefined,
+            },
+        )?;
+        let uuid = Uuid::new_v4();
+        db.add_reference_to_save(
+            uuid,
+            1,
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![],
+        )?;
+
+        assert!(db.set_save_time(&uuid.to_string(), 1_700_000_000)?);
+        assert!(!db.set_save_time("missing", 1_700_000_000)?);
+        let saves = db.get_reference_to_save_by_path_id(1, ADMIN)?.unwrap();
+        assert_eq!(saves[0].time, 1_700_000_000);
         Ok(())
     }
 
     #[test]
-    fn test_add_games_metadata() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_add_game_path() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
         db.add_games_metadata(vec![&GameMetadataCreate {
             known_name: None,
             steam_appid: None,
-            default_name: "MetaGame".to_string(),
+            default_name: "AddPath".to_string(),
             install_dir: None,
             gog: None,
             flatpak_id: None,
@@ -1052,19 +3728,27 @@ mod tests {
             steam_extra: None,
         }])?;
 
-        let names = db.get_games_default_name()?;
-        assert!(names.iter().any(|n| n.default_name == "MetaGame"));
+        db.add_game_path(
+            1,
+            &SavePathCreate {
+                path: "/tmp".to_string(),
+                operating_system: OS::Undefined,
+            },
+        )?;
+
+        let paths = db.get_paths_by_game_id_and_os(1, OS::Undefined)?;
+        assert!(paths.iter().any(|p| p == "/tmp"));
         Ok(())
     }
 
     #[test]
-    fn test_search_games_by_name_contains() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_get_paths_by_game_id_and_os() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
         db.add_games_metadata(vec![&GameMetadataCreate {
             known_name: None,
             steam_appid: None,
-            default_name: "Super Mario".to_string(),
+            default_name: "PathOs".to_string(),
             install_dir: None,
             gog: None,
             flatpak_id: None,
@@ -1079,20 +3763,27 @@ mod tests {
             steam_extra: None,
         }])?;
 
-        let results = db.search_games_by_name("Mario")?;
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].default_name, "Super Mario");
+        db.add_game_path(
+            1,
+            &SavePathCreate {
+                path: "c:\\game".to_string(),
+                operating_system: OS::Windows,
+            },
+        )?;
+
+        let paths = db.get_paths_by_game_id_and_os(1, OS::Windows)?;
+        assert_eq!(paths.len(), 1);
         Ok(())
     }
 
     #[test]
-    fn test_search_games_by_name_equal() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_get_paths_by_game_id() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
         db.add_games_metadata(vec![&GameMetadataCreate {
             known_name: None,
             steam_appid: None,
-            default_name: "Super Mario".to_string(),
+            default_name: "AllPaths".to_string(),
             install_dir: None,
             gog: None,
             flatpak_id: None,
@@ -1107,20 +3798,27 @@ mod tests {
             steam_extra: None,
         }])?;
 
-        let results = db.search_games_by_name("Super Mario")?;
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].default_name, "Super Mario");
+        db.add_game_path(
+            1,
+            &SavePathCreate {
+                path: "x".to_string(),
+                operating_system: OS::Undefined,
+            },
+        )?;
+
+        let paths = db.get_paths_by_game_id(1)?;
+        assert_eq!(paths.len(), 1);
         Ok(())
     }
 
     #[test]
-    fn test_search_games_by_known_name_contains() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_add_game_executable() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
         db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: Some(vec!["Super Luigi".to_string()]),
+            known_name: None,
             steam_appid: None,
-            default_name: "Super Mario".to_string(),
+            default_name: "ExecGame".to_string(),
             install_dir: None,
             gog: None,
             flatpak_id: None,
@@ -1135,20 +3833,27 @@ mod tests {
             steam_extra: None,
         }])?;
 
-        let results = db.search_games_by_name("Luigi")?;
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].default_name, "Super Mario");
+        db.add_game_executable(
+            1,
+            &ExecutableCreate {
+                executable: "run.exe".to_string(),
+                operating_system: OS::Undefined,
+            },
+        )?;
+
+        let execs = db.get_executable_by_game_id_and_os(1, OS::Undefined)?;
+        assert!(execs.iter().any(|e| e == "run.exe"));
         Ok(())
     }
 
     #[test]
-    fn test_search_games_by_known_name_equal() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_get_executable_by_game_id_and_os() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
         db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: Some(vec!["Super Luigi".to_string()]),
+            known_name: None,
             steam_appid: None,
-            default_name: "Super Mario".to_string(),
+            default_name: "ExeOs".to_string(),
             install_dir: None,
             gog: None,
             flatpak_id: None,
@@ -1163,21 +3868,27 @@ mod tests {
             steam_extra: None,
         }])?;
 
-        let results = db.search_games_by_name("Super Luigi")?;
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].default_name, "Super Mario");
+        db.add_game_executable(
+            1,
+            &ExecutableCreate {
+                executable: "game.exe".to_string(),
+                operating_system: OS::Windows,
+            },
+        )?;
+
+        let execs = db.get_executable_by_game_id_and_os(1, OS::Windows)?;
+        assert_eq!(execs.len(), 1);
         Ok(())
     }
 
     #[test]
-    fn test_search_games_by_default_name_case_insensitive()
-    -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_get_executable_by_game_id() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
         db.add_games_metadata(vec![&GameMetadataCreate {
             known_name: None,
             steam_appid: None,
-            default_name: "Super Mario".to_string(),
+            default_name: "AllExe".to_string(),
             install_dir: None,
             gog: None,
             flatpak_id: None,
@@ -1192,28 +3903,27 @@ mod tests {
             steam_extra: None,
         }])?;
 
-        let results = db.search_games_by_name("super mario")?;
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].default_name, "Super Mario");
-        Ok(())
-    }
+        db.add_game_executable(
+            1,
+            &ExecutableCreate {
+                executable: "all.exe".to_string(),
+                operating_system: OS::Undefined,
+            },
+        )?;
 
-    #[test]
-    fn test_search_games_no_results() -> Result<(), Box<dyn Error + Send + Sync>> {
-        let db = fresh_db();
-        let results = db.search_games_by_name("Nonexistent Game")?;
-        assert!(results.is_empty());
+        let execs = db.get_executable_by_game_id(1)?;
+        assert!(execs.iter().any(|e| e.executable.executable == "all.exe"));
         Ok(())
     }
 
     #[test]
-    fn test_get_game_metadata_by_name() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_add_reference_to_save() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
         db.add_games_metadata(vec![&GameMetadataCreate {
             known_name: None,
             steam_appid: None,
-            default_name: "MetaName".to_string(),
+            default_name: "SaveRef".to_string(),
             install_dir: None,
             gog: None,
             flatpak_id: None,
@@ -1228,19 +3938,43 @@ mod tests {
             steam_extra: None,
         }])?;
 
-        let meta = db.get_games_metadata_by_name("MetaName")?;
-        assert_eq!(meta.first().unwrap().metadata.default_name, "MetaName");
+        db.add_game_path(
+            1,
+            &SavePathCreate {
+                path: "save_dir".to_string(),
+                operating_system: OS::Undefined,
+            },
+        )?;
+
+        let uuid = Uuid::new_v4();
+        db.add_reference_to_save(
+            uuid,
+            1,
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            Some(ArchiveFormat::Zip),
+            vec![],
+        )?;
+
+        let refs = db.get_reference_to_save_by_path_id(1, ADMIN)?;
+        assert_eq!(refs.unwrap().len(), 1);
+        let manifest = db.get_save_manifest(&uuid.to_string(), ADMIN)?.unwrap();
+        assert_eq!(manifest.archive_format, Some(ArchiveFormat::Zip));
         Ok(())
     }
 
     #[test]
-    fn test_get_game_metadata_by_id() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_get_reference_to_save_by_path_id() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
         db.add_games_metadata(vec![&GameMetadataCreate {
             known_name: None,
             steam_appid: None,
-            default_name: "ById".to_string(),
+            default_name: "RefPath".to_string(),
             install_dir: None,
             gog: None,
             flatpak_id: None,
@@ -1255,19 +3989,92 @@ mod tests {
             steam_extra: None,
         }])?;
 
-        let meta = db.get_game_metadata_by_id(&1)?;
-        assert_eq!(meta.unwrap().metadata.default_name, "ById");
+        db.add_game_path(
+            1,
+            &SavePathCreate {
+                path: "ref_dir".to_string(),
+                operating_system: OS::Undefined,
+            },
+        )?;
+
+        let uuid = Uuid::new_v4();
+        db.add_reference_to_save(
+            uuid,
+            1,
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![],
+        )?;
+
+        let refs = db.get_reference_to_save_by_path_id(1, ADMIN)?;
+        assert_eq!(refs.unwrap().len(), 1);
         Ok(())
     }
 
     #[test]
-    fn test_get_games_default_name() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_save_metadata() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
-        db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: None,
+        test_game_with_path(&db, "Metadata")?;
+
+        let uuid = Uuid::new_v4();
+        let metadata = SaveMetadata {
+            label: Some("Before the final boss".to_string()),
+            note: Some("Full potions".to_string()),
+            hostname: Some("steamdeck".to_string()),
+            client_version: Some("1.2.0".to_string()),
+            game_version: Some("1.0.3".to_string()),
+            playtime: Some(3600),
+        };
+        db.add_reference_to_save(
+            uuid,
+            1,
+            &SaveParent::default(),
+            &metadata,
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![],
+        )?;
+        let refs = db.get_reference_to_save_by_path_id(1, ADMIN)?.unwrap();
+        assert_eq!(refs[0].metadata, metadata);
+
+        assert!(db.update_save_metadata(
+            &uuid.to_string(),
+            ADMIN,
+            &SaveMetadataUpdate {
+                label: Some("Final boss".to_string()),
+                note: Some(String::new()),
+            }
+        )?);
+        let refs = db.get_reference_to_save_by_path_id(1, ADMIN)?.unwrap();
+        assert_eq!(refs[0].metadata.label.as_deref(), Some("Final boss"));
+        assert_eq!(refs[0].metadata.note, None);
+        assert_eq!(refs[0].metadata.hostname.as_deref(), Some("steamdeck"));
+
+        assert!(!db.update_save_metadata(
+            &Uuid::new_v4().to_string(),
+            ADMIN,
+            &SaveMetadataUpdate::default()
+        )?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_and_remove_game_catalog() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        let mut game = GameMetadataCreate {
+            known_name: Some(vec!["Alias".to_string()]),
             steam_appid: None,
-            default_name: "Default".to_string(),
+            default_name: "Catalog".to_string(),
             install_dir: None,
             gog: None,
             flatpak_id: None,
@@ -1277,57 +4084,305 @@ mod tests {
             origin_cloud: None,
             steam_cloud: None,
             uplay_cloud: None,
-            ludusavi_managed: None,
-            gog_extra: None,
+            ludusavi_managed: Some(true),
+            gog_extra: Some(vec![1]),
             steam_extra: None,
-        }])?;
+        };
+        db.add_games_metadata(vec![&game])?;
+        let linux_path = |path: &str| SavePathCreate {
+            path: path.to_string(),
+            operating_system: OS::Linux,
+        };
+        db.add_game_path(1, &linux_path("first"))?;
+        db.add_game_path(1, &linux_path("second"))?;
+        db.add_game_executable(
+            1,
+            &ExecutableCreate {
+                executable: "game.exe".to_string(),
+                operating_system: OS::Windows,
+            },
+        )?;
+        db.add_game_registry_path(
+            1,
+            &GameRegistry {
+                path: "HKEY_CURRENT_USER/Game".to_string(),
+            },
+        )?;
+
+        game.default_name = "Renamed".to_string();
+        assert_eq!(
+            db.update_game_metadata_by_id(1, &game)?,
+            CatalogUpdate::Done
+        );
+        let updated = db.get_game_metadata_by_id(&1)?.unwrap();
+        assert_eq!(updated.metadata.default_name, "Renamed");
+        assert_eq!(updated.metadata.ludusavi_managed, Some(false));
+        assert_eq!(
+            db.update_game_metadata_by_id(2, &game)?,
+            CatalogUpdate::NotFound
+        );
+
+        assert_eq!(
+            db.update_game_path(1, 2, &linux_path("first"))?,
+            CatalogUpdate::Conflict
+        );
+        assert_eq!(
+            db.update_game_path(1, 2, &linux_path("fixed"))?,
+            CatalogUpdate::Done
+        );
+        assert_eq!(
+            db.update_game_path(2, 2, &linux_path("other"))?,
+            CatalogUpdate::NotFound
+        );
+
+        db.add_reference_to_save(
+            Uuid::new_v4(),
+            1,
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![],
+        )?;
+        assert_eq!(db.remove_game_path(1, 1)?, CatalogUpdate::Conflict);
+        assert_eq!(db.remove_game_path(2, 1)?, CatalogUpdate::NotFound);
+        assert_eq!(db.remove_game(1)?, CatalogUpdate::Conflict);
+        assert_eq!(db.remove_game_path(1, 2)?, CatalogUpdate::Done);
+        assert_eq!(db.get_paths_by_game_id(1)?.len(), 1);
+
+        db.update_game_registry(
+            1,
+            &[GameRegistry {
+                path: "HKEY_CURRENT_USER/Other".to_string(),
+            }],
+            &[GameRegistry {
+                path: "HKEY_CURRENT_USER/Game".to_string(),
+            }],
+            false,
+        )?;
+        let registries = db.get_game_registry_by_game_id(1)?;
+        assert_eq!(registries.len(), 1);
+        assert_eq!(registries[0].path, "HKEY_CURRENT_USER/Other");
+
+        let save_uuids: Vec<String> = db
+            .get_reference_to_save_by_path_id(1, ADMIN)?
+            .unwrap()
+            .into_iter()
+            .map(|save_ref| save_ref.uuid)
+            .collect();
+        db.remove_saves(&save_uuids)?;
+        assert_eq!(db.remove_game(1)?, CatalogUpdate::Done);
+        assert!(db.get_game_metadata_by_id(&1)?.is_none());
+        assert!(db.get_paths_by_game_id(1)?.is_empty());
+        assert!(db.get_executable_by_game_id(1)?.is_empty());
+        assert!(db.get_game_registry_by_game_id(1)?.is_empty());
+        assert_eq!(db.remove_game(1)?, CatalogUpdate::NotFound);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_database_uuid() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+        let uuid_opt = db.get_database_uuid()?;
+        assert!(uuid_opt.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_api_tokens() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+        let uuid = Uuid::new_v4();
+        db.add_api_tokens(vec![uuid])?;
+        assert!(db.authenticate_api_token(&uuid.to_string())?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_api_tokens() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+        let token = Uuid::new_v4();
+        db.add_api_tokens(vec![token])?;
+        db.remove_api_tokens(vec![token])?;
+        assert!(db.authenticate_api_token(&token.to_string())?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_legacy_api_tokens() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+        let token = Uuid::new_v4().to_string();
+        {
+            // As left by the migration that hashed tokens
+            let connection = &mut db.pool.get()?;
+            diesel::insert_into(api_tokens::table)
+                .values(DbApiTokens {
+                    id: Some(100),
+                    token_prefix: api_token_prefix(&token),
+                    salt: String::new(),
+                    token_hash: String::new(),
+                    user_id: ADMIN,
+                    device_id: None,
+                    name: "legacy".to_string(),
+                    created: utc_now(),
+                    last_used: None,
+                    expires: None,
+                })
+                .execute(connection)?;
+            diesel::insert_into(legacy_api_token::table)
+                .values(DbLegacyApiToken {
+                    api_token_id: 100,
+                    api_token: token.clone(),
+                })
+                .execute(connection)?;
+        }
+        assert!(db.authenticate_api_token(&token)?.is_none());
+
+        assert_eq!(db.upgrade_legacy_api_tokens()?, 1);
+        assert_eq!(db.upgrade_legacy_api_tokens()?, 0);
+        let upgraded = db.authenticate_api_token(&token)?.expect("upgraded token");
+        assert_eq!(upgraded.id, 100);
+        assert_eq!(upgraded.prefix, token[..8]);
+
+        let connection = &mut db.pool.get()?;
+        let stored: DbApiTokens = api_tokens::table
+            .filter(api_tokens::id.eq(100))
+            .select(DbApiTokens::as_select())
+            .first(connection)?;
+        assert_ne!(stored.token_hash, token);
+        assert!(!stored.token_hash.contains(&token));
+        Ok(())
+    }
+
+    #[test]
+    fn test_api_token_scopes_and_expiry() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+        let now = time::OffsetDateTime::now_utc().unix_timestamp();
+        let admin_value = Uuid::new_v4();
+        let admin = db.add_api_token(
+            &ApiTokenCreate {
+                name: "admin".to_string(),
+                scopes: vec![TokenScope::Admin],
+                expires: None,
+            },
+            ADMIN,
+            admin_value,
+        )?;
+        let initial = db
+            .get_api_token_details()?
+            .into_iter()
+            .find(|token| token.name == "Initial token")
+            .expect("initial token");
+        assert_eq!(initial.scopes, vec![TokenScope::Admin]);
+        assert_eq!(db.remove_api_token(initial.id)?, CatalogUpdate::Done);
+        let reader_value = Uuid::new_v4();
+        let reader = db.add_api_token(
+            &ApiTokenCreate {
+                name: "reader".to_string(),
+                scopes: vec![TokenScope::ReadOnly, TokenScope::ReadOnly],
+                expires: Some(now + 3600),
+            },
+            ADMIN,
+            reader_value,
+        )?;
+        let expired_value = Uuid::new_v4();
+        db.add_api_token(
+            &ApiTokenCreate {
+                name: "expired".to_string(),
+                scopes: vec![TokenScope::Admin],
+                expires: Some(now - 1),
+            },
+            ADMIN,
+            expired_value,
+        )?;
+        assert_eq!(reader.scopes, vec![TokenScope::ReadOnly]);
+        assert_eq!(reader.last_used, None);
+
+        let authenticated = db
+            .authenticate_api_token(&reader_value.to_string())?
+            .expect("valid token");
+        assert_eq!(authenticated.id, reader.id);
+        assert_eq!(authenticated.scopes, vec![TokenScope::ReadOnly]);
+        assert!(
+            db.get_api_token_detail(reader.id)?
+                .and_then(|token| token.last_used)
+                .is_some()
+        );
+        assert!(
+            db.authenticate_api_token(&expired_value.to_string())?
+                .is_none()
+        );
 
-        let names = db.get_games_default_name()?;
-        assert!(names.iter().any(|n| n.default_name == "Default"));
+        // The expired token doesn't count as another admin
+        let downgrade = ApiTokenCreate {
+            name: "admin".to_string(),
+            scopes: vec![TokenScope::ReadOnly],
+            expires: None,
+        };
+        assert_eq!(
+            db.update_api_token(admin.id, &downgrade)?,
+            CatalogUpdate::Conflict
+        );
+        assert_eq!(db.remove_api_token(admin.id)?, CatalogUpdate::Conflict);
+        assert_eq!(db.remove_api_token(reader.id)?, CatalogUpdate::Done);
+        assert_eq!(db.remove_api_token(reader.id)?, CatalogUpdate::NotFound);
+        assert!(
+            db.authenticate_api_token(&reader_value.to_string())?
+                .is_none()
+        );
+
+        db.add_api_tokens(vec![Uuid::new_v4()])?;
+        assert_eq!(
+            db.update_api_token(admin.id, &downgrade)?,
+            CatalogUpdate::Done
+        );
+        assert_eq!(
+            db.authenticate_api_token(&admin_value.to_string())?
+                .map(|token| token.scopes),
+            Some(vec![TokenScope::ReadOnly])
+        );
+
+        let device_token = Uuid::new_v4();
+        let device = db.add_device(
+            &DeviceCreate {
+                name: "laptop".to_string(),
+                operating_system: OS::Linux,
+                client_version: None,
+            },
+            ADMIN,
+            device_token,
+        )?;
+        let device_api_token = db
+            .authenticate_api_token(&device_token.to_string())?
+            .expect("device token");
+        assert_eq!(device_api_token.device_id, Some(device.id));
+        assert_eq!(device_api_token.name, "laptop");
+        assert_eq!(
+            device_api_token.scopes,
+            vec![TokenScope::ReadOnly, TokenScope::Upload]
+        );
         Ok(())
     }
 
     #[test]
-    fn test_get_games_default_name_and_ludusavi_managed() -> Result<(), Box<dyn Error + Send + Sync>>
-    {
+    fn test_get_configuration_value() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
-
-        db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: None,
-            steam_appid: None,
-            default_name: "Managed".to_string(),
-            install_dir: None,
-            gog: None,
-            flatpak_id: None,
-            lutris_id: None,
-            epic_cloud: None,
-            gog_cloud: None,
-            origin_cloud: None,
-            steam_cloud: None,
-            uplay_cloud: None,
-            ludusavi_managed: Some(true),
-            gog_extra: None,
-            steam_extra: None,
-        }])?;
-
-        let result = db.get_games_default_name_and_ludusavi_managed()?;
-        assert!(
-            result.iter().any(
-                |game_name_ludusavi| game_name_ludusavi.default_name == "Managed"
-                    && game_name_ludusavi.ludusavi_managed.unwrap()
-            )
-        );
+        db.update_configuration_value("max_save_per_game", "10")?;
+        let val = db.get_configuration_value("max_save_per_game")?;
+        assert!(val.unwrap().value == "10");
         Ok(())
     }
 
     #[test]
-    fn test_get_games_metadata() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_get_game_registry_by_game_id() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
         db.add_games_metadata(vec![&GameMetadataCreate {
             known_name: None,
             steam_appid: None,
-            default_name: "MetaList".to_string(),
+            default_name: "RegGame".to_string(),
             install_dir: None,
             gog: None,
             flatpak_id: None,
@@ -1342,24 +4397,26 @@ mod tests {
             steam_extra: None,
         }])?;
 
-        let games_metadata = db.get_games_metadata()?;
-        assert!(
-            games_metadata
-                .iter()
-                .any(|m| m.metadata.default_name == "MetaList")
-        );
+        db.add_game_registry_path(
+            1,
+            &GameRegistry {
+                path: "reg1".to_string(),
+            },
+        )?;
+
+        let reg = db.get_game_registry_by_game_id(1)?;
+        assert!(reg.iter().any(|r| r.path == "reg1"));
         Ok(())
     }
 
     #[test]
-    fn test_get_games_metadata_and_paths_if_saves_exist() -> Result<(), Box<dyn Error + Send + Sync>>
-    {
+    fn test_add_game_registry_path() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
         db.add_games_metadata(vec![&GameMetadataCreate {
             known_name: None,
             steam_appid: None,
-            default_name: "PathsExist".to_string(),
+            default_name: "AddReg".to_string(),
             install_dir: None,
             gog: None,
             flatpak_id: None,
@@ -1374,419 +4431,1033 @@ mod tests {
             steam_extra: None,
         }])?;
 
-        db.add_game_path(
+        db.add_game_registry_path(
             1,
-            &SavePathCreate {
-                path: "p1".to_string(),
-                operating_system: OS::Undefined,
+            &GameRegistry {
+                path: "new_reg".to_string(),
             },
         )?;
 
+        let reg = db.get_game_registry_by_game_id(1)?;
+        assert!(reg.iter().any(|r| r.path == "new_reg"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_save_manifest() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+
+        test_game_with_path(&db, "Manifest")?;
+
+        let uuid = Uuid::new_v4();
         db.add_reference_to_save(
-            Uuid::new_v4(),
+            uuid,
             1,
-            vec![FileHash {
-                relative_path: "potato".to_string(),
-                hash: "potato".to_string(),
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            Some(&SaveDigest {
+                hash_algorithm: HashAlgorithm::Sha256,
+                archive_digest: "manifest_digest".to_string(),
+            }),
+            Codec::Zstd,
+            None,
+            None,
+            vec![SaveFile {
+                relative_path: "slot1.sav".to_string(),
+                hash: "client_hash".to_string(),
+                blob_hash: "blob_hash".to_string(),
+                size: 42,
+                codec: Codec::Zstd,
+                stored_size: 17,
+                encryption: None,
             }],
         )?;
 
-        let res = db.get_games_metadata_and_paths_if_saves_exist()?;
+        let manifest = db.get_save_manifest(&uuid.to_string(), ADMIN)?.unwrap();
+        assert!(!manifest.legacy_archive);
+        assert_eq!(manifest.archive_digest.as_deref(), Some("manifest_digest"));
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(manifest.files[0].blob_hash, "blob_hash");
+        assert_eq!(manifest.files[0].size, 42);
+        assert_eq!(manifest.files[0].codec, Codec::Zstd);
+        assert_eq!(manifest.files[0].stored_size, 17);
+        assert_eq!(
+            db.get_reference_to_save_by_path_id(1, ADMIN)?.unwrap()[0].codec,
+            Some(Codec::Zstd)
+        );
         assert!(
-            res.iter()
-                .any(|m| m.game_metadata.metadata.default_name == "PathsExist")
+            db.get_save_manifest(&Uuid::new_v4().to_string(), ADMIN)?
+                .is_none()
         );
         Ok(())
     }
 
     #[test]
-    fn test_add_game_path() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_rewrap_data_keys() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
-        db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: None,
-            steam_appid: None,
-            default_name: "AddPath".to_string(),
-            install_dir: None,
-            gog: None,
-            flatpak_id: None,
-            lutris_id: None,
-            epic_cloud: None,
-            gog_cloud: None,
-            origin_cloud: None,
-            steam_cloud: None,
-            uplay_cloud: None,
-            ludusavi_managed: None,
-            gog_extra: None,
-            steam_extra: None,
-        }])?;
+        test_game_with_path(&db, "Encrypted")?;
 
-        db.add_game_path(
+        let encryption = EncryptionKey {
+            wrapped_key: vec![1, 2, 3],
+            nonce: vec![4, 5],
+        };
+        let uuid = Uuid::new_v4();
+        db.add_reference_to_save(
+            uuid,
             1,
-            &SavePathCreate {
-                path: "/tmp".to_string(),
-                operating_system: OS::Undefined,
-            },
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            Some(&encryption),
+            None,
+            vec![SaveFile {
+                relative_path: "slot1.sav".to_string(),
+                hash: "client_hash".to_string(),
+                blob_hash: "encrypted_blob".to_string(),
+                size: 3,
+                codec: Codec::Identity,
+                stored_size: 3,
+                encryption: Some(encryption.clone()),
+            }],
         )?;
+        assert_eq!(
+            db.get_save_manifest(&uuid.to_string(), ADMIN)?
+                .unwrap()
+                .files[0]
+                .encryption,
+            Some(encryption.clone())
+        );
 
-        let paths = db.get_paths_by_game_id_and_os(1, OS::Undefined)?;
-        assert!(paths.iter().any(|p| p == "/tmp"));
+        let rewrapped =
+            db.rewrap_data_keys(|wrapped_key| Ok(wrapped_key.iter().rev().copied().collect()))?;
+        assert_eq!(rewrapped, 2);
+        let blob = db.get_blob("encrypted_blob")?.unwrap();
+        assert_eq!(
+            blob.encryption,
+            Some(EncryptionKey {
+                wrapped_key: vec![3, 2, 1],
+                nonce: vec![4, 5],
+            })
+        );
+
+        // A failing rewrap leaves every key untouched
+        assert!(
+            db.rewrap_data_keys(|_| Err("wrong master key".into()))
+                .is_err()
+        );
+        assert_eq!(
+            db.get_blob("encrypted_blob")?.unwrap().encryption,
+            blob.encryption
+        );
         Ok(())
     }
 
     #[test]
-    fn test_get_paths_by_game_id_and_os() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_remove_saves_shared_blob() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
-        db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: None,
-            steam_appid: None,
-            default_name: "PathOs".to_string(),
-            install_dir: None,
-            gog: None,
-            flatpak_id: None,
-            lutris_id: None,
-            epic_cloud: None,
-            gog_cloud: None,
-            origin_cloud: None,
-            steam_cloud: None,
-            uplay_cloud: None,
-            ludusavi_managed: None,
-            gog_extra: None,
-            steam_extra: None,
-        }])?;
+        test_game_with_path(&db, "SharedBlob")?;
 
-        db.add_game_path(
+        let shared_file = || SaveFile {
+            relative_path: "shared.cfg".to_string(),
+            hash: "shared".to_string(),
+            blob_hash: "shared".to_string(),
+            size: 1,
+            codec: Codec::Identity,
+            stored_size: 1,
+            encryption: None,
+        };
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        db.add_reference_to_save(
+            first,
             1,
-            &SavePathCreate {
-                path: "c:\\game".to_string(),
-                operating_system: OS::Windows,
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![
+                shared_file(),
+                SaveFile {
+                    relative_path: "slot.sav".to_string(),
+                    hash: "first".to_string(),
+                    blob_hash: "first".to_string(),
+                    size: 1,
+                    codec: Codec::Identity,
+                    stored_size: 1,
+                    encryption: None,
+                },
+            ],
+        )?;
+        db.add_reference_to_save(
+            second,
+            1,
+            &SaveParent {
+                parent_uuid: Some(first.to_string()),
+                force: false,
+                lock_holder: None,
             },
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![shared_file()],
         )?;
 
-        let paths = db.get_paths_by_game_id_and_os(1, OS::Windows)?;
-        assert_eq!(paths.len(), 1);
+        let orphans = db.remove_saves(&[first.to_string()])?;
+        assert_eq!(orphans, vec!["first".to_string()]);
+        assert_eq!(
+            db.get_reference_to_save_by_path_id(1, ADMIN)?
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let orphans = db.remove_saves(&[second.to_string()])?;
+        assert_eq!(orphans, vec!["shared".to_string()]);
+        assert!(db.get_reference_to_save_by_path_id(1, ADMIN)?.is_none());
         Ok(())
     }
 
     #[test]
-    fn test_get_paths_by_game_id() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_add_reference_to_save_conflict() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
-        db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: None,
-            steam_appid: None,
-            default_name: "AllPaths".to_string(),
-            install_dir: None,
-            gog: None,
-            flatpak_id: None,
-            lutris_id: None,
-            epic_cloud: None,
-            gog_cloud: None,
-            origin_cloud: None,
-            steam_cloud: None,
-            uplay_cloud: None,
-            ludusavi_managed: None,
-            gog_extra: None,
-            steam_extra: None,
-        }])?;
+        test_game_with_path(&db, "Conflict")?;
 
-        db.add_game_path(
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        let third = Uuid::new_v4();
+        let based_on = |uuid: Uuid, force: bool| SaveParent {
+            parent_uuid: Some(uuid.to_string()),
+            force,
+            lock_holder: None,
+        };
+        db.add_reference_to_save(
+            first,
             1,
-            &SavePathCreate {
-                path: "x".to_string(),
-                operating_system: OS::Undefined,
-            },
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![],
+        )?;
+        db.add_reference_to_save(
+            second,
+            1,
+            &based_on(first, false),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![],
         )?;
 
-        let paths = db.get_paths_by_game_id(1)?;
-        assert_eq!(paths.len(), 1);
+        let err = db
+            .add_reference_to_save(
+                third,
+                1,
+                &based_on(first, false),
+                &SaveMetadata::default(),
+                ADMIN_SAVES,
+                None,
+                Codec::Identity,
+                None,
+                None,
+                vec![],
+            )
+            .unwrap_err();
+        let conflict = err.downcast_ref::<SaveConflict>().unwrap();
+        assert_eq!(conflict.head, Some(second.to_string()));
+        assert_eq!(conflict.base, Some(first.to_string()));
+
+        db.add_reference_to_save(
+            third,
+            1,
+            &based_on(first, true),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![],
+        )?;
+        let parent_of = |refs: &[SaveReference], uuid: Uuid| {
+            refs.iter()
+                .find(|save_ref| save_ref.uuid == uuid.to_string())
+                .and_then(|save_ref| save_ref.parent_uuid.clone())
+        };
+        let refs = db.get_reference_to_save_by_path_id(1, ADMIN)?.unwrap();
+        assert_eq!(parent_of(&refs, second), Some(first.to_string()));
+        assert_eq!(parent_of(&refs, third), Some(first.to_string()));
+
+        db.remove_saves(&[first.to_string()])?;
+        let refs = db.get_reference_to_save_by_path_id(1, ADMIN)?.unwrap();
+        assert_eq!(parent_of(&refs, second), None);
+        assert_eq!(parent_of(&refs, third), None);
         Ok(())
     }
 
     #[test]
-    fn test_add_game_executable() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_add_reference_to_save_without_parent() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
-        db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: None,
-            steam_appid: None,
-            default_name: "ExecGame".to_string(),
-            install_dir: None,
-            gog: None,
-            flatpak_id: None,
-            lutris_id: None,
-            epic_cloud: None,
-            gog_cloud: None,
-            origin_cloud: None,
-            steam_cloud: None,
-            uplay_cloud: None,
-            ludusavi_managed: None,
-            gog_extra: None,
-            steam_extra: None,
-        }])?;
-
-        db.add_game_executable(
-            1,
-            &ExecutableCreate {
-                executable: "run.exe".to_string(),
-                operating_system: OS::Undefined,
-            },
-        )?;
+        test_game_with_path(&db, "NoParent")?;
+
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        for uuid in [first, second] {
+            db.add_reference_to_save(
+                uuid,
+                1,
+                &SaveParent::default(),
+                &SaveMetadata::default(),
+                ADMIN_SAVES,
+                None,
+                Codec::Identity,
+                None,
+                None,
+                vec![],
+            )?;
+        }
 
-        let execs = db.get_executable_by_game_id_and_os(1, OS::Undefined)?;
-        assert!(execs.iter().any(|e| e == "run.exe"));
+        let refs = db.get_reference_to_save_by_path_id(1, ADMIN)?.unwrap();
+        let second_ref = refs
+            .iter()
+            .find(|save_ref| save_ref.uuid == second.to_string())
+            .unwrap();
+        assert_eq!(second_ref.parent_uuid, Some(first.to_string()));
         Ok(())
     }
 
     #[test]
-    fn test_get_executable_by_game_id_and_os() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_add_restored_save() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
-        db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: None,
-            steam_appid: None,
-            default_name: "ExeOs".to_string(),
-            install_dir: None,
-            gog: None,
-            flatpak_id: None,
-            lutris_id: None,
-            epic_cloud: None,
-            gog_cloud: None,
-            origin_cloud: None,
-            steam_cloud: None,
-            uplay_cloud: None,
-            ludusavi_managed: None,
-            gog_extra: None,
-            steam_extra: None,
-        }])?;
+        test_game_with_path(&db, "Restore")?;
 
-        db.add_game_executable(
+        let old = Uuid::new_v4();
+        let head = Uuid::new_v4();
+        let restored = Uuid::new_v4();
+        db.add_reference_to_save(
+            old,
             1,
-            &ExecutableCreate {
-                executable: "game.exe".to_string(),
-                operating_system: OS::Windows,
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            Some(&SaveDigest {
+                hash_algorithm: HashAlgorithm::Sha512,
+                archive_digest: "digest".to_string(),
+            }),
+            Codec::Identity,
+            None,
+            None,
+            vec![SaveFile {
+                relative_path: "slot.sav".to_string(),
+                hash: "old".to_string(),
+                blob_hash: "old".to_string(),
+                size: 3,
+                codec: Codec::Identity,
+                stored_size: 3,
+                encryption: None,
+            }],
+        )?;
+        db.add_reference_to_save(
+            head,
+            1,
+            &SaveParent {
+                parent_uuid: Some(old.to_string()),
+                force: false,
+                lock_holder: None,
             },
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![],
         )?;
 
-        let execs = db.get_executable_by_game_id_and_os(1, OS::Windows)?;
-        assert_eq!(execs.len(), 1);
+        let laptop = db.add_device(
+            &DeviceCreate {
+                name: "laptop".to_string(),
+                operating_system: OS::Linux,
+                client_version: None,
+            },
+            ADMIN,
+            Uuid::new_v4(),
+        )?;
+        let restored_save = db
+            .add_restored_save(
+                &old.to_string(),
+                SaveOwner {
+                    user_id: ADMIN,
+                    device_id: Some(laptop.id),
+                },
+                None,
+                restored,
+            )?
+            .unwrap();
+        assert_eq!(restored_save.device_id, Some(laptop.id));
+        assert!(!restored_save.corrupt);
+        assert_eq!(restored_save.parent_uuid, Some(head.to_string()));
+        assert_eq!(restored_save.restored_from, Some(old.to_string()));
+        assert_eq!(restored_save.hash_algorithm, Some(HashAlgorithm::Sha512));
+        assert_eq!(restored_save.archive_digest.as_deref(), Some("digest"));
+        assert!(
+            db.add_restored_save(
+                &Uuid::new_v4().to_string(),
+                ADMIN_SAVES,
+                None,
+                Uuid::new_v4()
+            )?
+            .is_none()
+        );
+
+        let manifest = db.get_save_manifest(&restored.to_string(), ADMIN)?.unwrap();
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(manifest.files[0].blob_hash, "old");
+
+        db.acquire_game_lock(1, ADMIN, "desktop", None, time::Duration::minutes(5))?;
+        let locked = db
+            .add_restored_save(
+                &old.to_string(),
+                ADMIN_SAVES,
+                Some("laptop"),
+                Uuid::new_v4(),
+            )
+            .expect_err("game locked by another holder");
+        assert_eq!(locked.downcast::<GameLock>()?.holder, "desktop");
+        let holder_restored = Uuid::new_v4();
+        assert!(
+            db.add_restored_save(
+                &old.to_string(),
+                ADMIN_SAVES,
+                Some("desktop"),
+                holder_restored
+            )?
+            .is_some()
+        );
+
+        assert!(db.remove_saves(&[holder_restored.to_string()])?.is_empty());
+
+        db.mark_corrupt_saves(&["old".to_string()], &[])?;
+        let corrupt = db
+            .add_restored_save(
+                &old.to_string(),
+                ADMIN_SAVES,
+                Some("desktop"),
+                Uuid::new_v4(),
+            )
+            .expect_err("corrupt save restored");
+        assert_eq!(corrupt.downcast::<CorruptSave>()?.uuid, old.to_string());
+        assert!(db.remove_saves(&[old.to_string()])?.is_empty());
+        assert_eq!(
+            db.remove_saves(&[restored.to_string()])?,
+            vec!["old".to_string()]
+        );
         Ok(())
     }
 
     #[test]
-    fn test_get_executable_by_game_id() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_get_bundle_saves() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
-        db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: None,
-            steam_appid: None,
-            default_name: "AllExe".to_string(),
-            install_dir: None,
-            gog: None,
-            flatpak_id: None,
-            lutris_id: None,
-            epic_cloud: None,
-            gog_cloud: None,
-            origin_cloud: None,
-            steam_cloud: None,
-            uplay_cloud: None,
-            ludusavi_managed: None,
-            gog_extra: None,
-            steam_extra: None,
-        }])?;
-
-        db.add_game_executable(
+        test_game_with_path(&db, "Bundle")?;
+        db.add_game_path(
             1,
-            &ExecutableCreate {
-                executable: "all.exe".to_string(),
+            &SavePathCreate {
+                path: "bundle_dir2".to_string(),
                 operating_system: OS::Undefined,
             },
         )?;
 
-        let execs = db.get_executable_by_game_id(1)?;
-        assert!(execs.iter().any(|e| e.executable.executable == "all.exe"));
+        let old = Uuid::new_v4();
+        let latest = Uuid::new_v4();
+        let other_path = Uuid::new_v4();
+        for (uuid, path_id) in [(old, 1), (latest, 1), (other_path, 2)] {
+            db.add_reference_to_save(
+                uuid,
+                path_id,
+                &SaveParent {
+                    parent_uuid: None,
+                    force: true,
+                    lock_holder: None,
+                },
+                &SaveMetadata::default(),
+                ADMIN_SAVES,
+                None,
+                Codec::Identity,
+                None,
+                None,
+                vec![SaveFile {
+                    relative_path: "slot.sav".to_string(),
+                    hash: uuid.to_string(),
+                    blob_hash: uuid.to_string(),
+                    size: 3,
+                    codec: Codec::Identity,
+                    stored_size: 3,
+                    encryption: None,
+                }],
+            )?;
+        }
+
+        let latest_saves = db.get_bundle_saves(None, ADMIN)?;
+        assert_eq!(
+            latest_saves
+                .iter()
+                .map(|save| save.reference.uuid.clone())
+                .collect::<Vec<_>>(),
+            vec![latest.to_string(), other_path.to_string()]
+        );
+        assert!(latest_saves.iter().all(|save| save.game_name == "Bundle"));
+        assert_eq!(latest_saves[0].reference.files_hash.len(), 1);
+
+        let chosen_saves =
+            db.get_bundle_saves(Some(&[old.to_string(), Uuid::new_v4().to_string()]), ADMIN)?;
+        assert_eq!(chosen_saves.len(), 1);
+        assert_eq!(chosen_saves[0].reference.uuid, old.to_string());
         Ok(())
     }
 
     #[test]
-    fn test_add_reference_to_save() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_mark_corrupt_saves() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
 
-        db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: None,
-            steam_appid: None,
-            default_name: "SaveRef".to_string(),
-            install_dir: None,
-            gog: None,
-            flatpak_id: None,
-            lutris_id: None,
-            epic_cloud: None,
-            gog_cloud: None,
-            origin_cloud: None,
-            steam_cloud: None,
-            uplay_cloud: None,
-            ludusavi_managed: None,
-            gog_extra: None,
-            steam_extra: None,
-        }])?;
+        test_game_with_path(&db, "Scrub")?;
+
+        let damaged = Uuid::new_v4();
+        let healthy = Uuid::new_v4();
+        for (uuid, blob_hash) in [(damaged, "damaged"), (healthy, "healthy")] {
+            db.add_reference_to_save(
+                uuid,
+                1,
+                &SaveParent {
+                    parent_uuid: None,
+                    force: true,
+                    lock_holder: None,
+                },
+                &SaveMetadata::default(),
+                ADMIN_SAVES,
+                None,
+                Codec::Identity,
+                None,
+                None,
+                vec![SaveFile {
+                    relative_path: "slot.sav".to_string(),
+                    hash: blob_hash.to_string(),
+                    blob_hash: blob_hash.to_string(),
+                    size: 1,
+                    codec: Codec::Identity,
+                    stored_size: 1,
+                    encryption: None,
+                }],
+            )?;
+        }
+        assert_eq!(db.get_blobs()?.len(), 2);
 
-        db.add_game_path(
-            1,
-            &SavePathCreate {
-                path: "save_dir".to_string(),
-                operating_system: OS::Undefined,
-            },
-        )?;
+        assert_eq!(
+            db.mark_corrupt_saves(&["damaged".to_string()], &[])?,
+            vec![damaged.to_string()]
+        );
+        let corrupt: Vec<bool> = db
+            .get_reference_to_save_by_path_id(1, ADMIN)?
+            .unwrap()
+            .iter()
+            .map(|save_ref| save_ref.corrupt)
+            .collect();
+        assert_eq!(corrupt.iter().filter(|corrupt| **corrupt).count(), 1);
 
-        db.add_reference_to_save(Uuid::new_v4(), 1, vec![])?;
+        assert!(db.mark_corrupt_saves(&[], &[])?.is_empty());
+        assert!(
+            db.get_reference_to_save_by_path_id(1, ADMIN)?
+                .unwrap()
+                .iter()
+                .all(|save_ref| !save_ref.corrupt)
+        );
 
-        let refs = db.get_reference_to_save_by_path_id(1)?;
-        assert_eq!(refs.unwrap().len(), 1);
+        assert!(db.get_scrub_report()?.is_none());
+        let report = ScrubReport {
+            started: 1,
+            finished: 2,
+            checked_blobs: 2,
+            checked_archives: 0,
+            damaged_files: Vec::new(),
+            corrupt_saves: Vec::new(),
+            quarantined_files: vec!["orphan".to_string()],
+        };
+        db.set_scrub_report(&report)?;
+        db.set_scrub_report(&report)?;
+        assert_eq!(
+            db.get_scrub_report()?.unwrap().quarantined_files,
+            vec!["orphan".to_string()]
+        );
         Ok(())
     }
 
     #[test]
-    fn test_get_reference_to_save_by_path_id() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_device_sync_state() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
+        test_game_with_path(&db, "Synced")?;
 
-        db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: None,
-            steam_appid: None,
-            default_name: "RefPath".to_string(),
-            install_dir: None,
-            gog: None,
-            flatpak_id: None,
-            lutris_id: None,
-            epic_cloud: None,
-            gog_cloud: None,
-            origin_cloud: None,
-            steam_cloud: None,
-            uplay_cloud: None,
-            ludusavi_managed: None,
-            gog_extra: None,
-            steam_extra: None,
-        }])?;
-
-        db.add_game_path(
-            1,
-            &SavePathCreate {
-                path: "ref_dir".to_string(),
-                operating_system: OS::Undefined,
+        let token = Uuid::new_v4();
+        let laptop = db.add_device(
+            &DeviceCreate {
+                name: "laptop".to_string(),
+                operating_system: OS::Linux,
+                client_version: None,
             },
+            ADMIN,
+            token,
         )?;
+        let desktop = db.add_device(
+            &DeviceCreate {
+                name: "desktop".to_string(),
+                operating_system: OS::Windows,
+                client_version: Some("1.0".to_string()),
+            },
+            ADMIN,
+            Uuid::new_v4(),
+        )?;
+        assert_eq!(db.get_devices()?.len(), 2);
+        assert!(db.authenticate_api_token(&token.to_string())?.is_some());
+        assert_eq!(
+            db.touch_device_by_api_token(&token.to_string())?,
+            Some(laptop.id)
+        );
+        assert_eq!(db.touch_device_by_api_token("missing")?, None);
 
-        let uuid = Uuid::new_v4();
-        db.add_reference_to_save(uuid, 1, vec![])?;
-
-        let refs = db.get_reference_to_save_by_path_id(1)?;
-        assert_eq!(refs.unwrap().len(), 1);
+        let upload = |device_id: i32| -> Result<Uuid, Box<dyn Error + Send + Sync>> {
+            let uuid = Uuid::new_v4();
+            db.add_reference_to_save(
+                uuid,
+                1,
+                &SaveParent {
+                    parent_uuid: None,
+                    force: true,
+                    lock_holder: None,
+                },
+                &SaveMetadata {
+                    client_version: Some("2.0".to_string()),
+                    ..SaveMetadata::default()
+                },
+                SaveOwner {
+                    user_id: ADMIN,
+                    device_id: Some(device_id),
+                },
+                None,
+                Codec::Identity,
+                None,
+                None,
+                vec![],
+            )?;
+            Ok(uuid)
+        };
+        let first = upload(laptop.id)?;
+        let saves = db.get_reference_to_save_by_path_id(1, ADMIN)?.unwrap();
+        assert_eq!(saves[0].device_id, Some(laptop.id));
+        assert_eq!(
+            db.get_device(laptop.id)?.unwrap().client_version.as_deref(),
+            Some("2.0")
+        );
+        let states = db.get_device_sync_states(laptop.id)?;
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].game_name, "Synced");
+        assert_eq!(states[0].synced_save_uuid, first.to_string());
+        assert!(!states[0].behind);
+
+        // The laptop is behind once the desktop uploads, until it syncs the new save
+        let second = upload(desktop.id)?;
+        let states = db.get_device_sync_states(laptop.id)?;
+        assert!(states[0].behind);
+        assert_eq!(states[0].latest_save_uuid, Some(second.to_string()));
+        assert!(!db.set_device_synced_save(laptop.id, 2, &second.to_string())?);
+        assert!(db.set_device_synced_save(laptop.id, 1, &second.to_string())?);
+        assert!(!db.get_device_sync_states(laptop.id)?[0].behind);
+
+        assert!(db.remove_device(laptop.id)?);
+        assert!(!db.remove_device(laptop.id)?);
+        assert!(db.authenticate_api_token(&token.to_string())?.is_none());
+        assert!(db.get_device_sync_states(laptop.id)?.is_empty());
+        let saves = db.get_reference_to_save_by_path_id(1, ADMIN)?.unwrap();
+        assert!(saves.iter().all(|save| save.device_id != Some(laptop.id)));
         Ok(())
     }
 
     #[test]
-    fn test_get_database_uuid() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_pairing_code_single_use() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
-        let uuid_opt = db.get_database_uuid()?;
-        assert!(uuid_opt.is_some());
+        let device = DeviceCreate {
+            name: "handheld".to_string(),
+            operating_system: OS::Linux,
+            client_version: None,
+        };
+
+        db.add_pairing_code("ABCD2345", ADMIN, time::Duration::minutes(10))?;
+        assert!(
+            db.redeem_pairing_code("WRONG234", &device, Uuid::new_v4())?
+                .is_none()
+        );
+
+        let token = Uuid::new_v4();
+        let paired = db
+            .redeem_pairing_code("ABCD2345", &device, token)?
+            .expect("valid code");
+        assert_eq!(paired.name, "handheld");
+        assert_eq!(
+            db.touch_device_by_api_token(&token.to_string())?,
+            Some(paired.id)
+        );
+        assert!(
+            db.redeem_pairing_code("ABCD2345", &device, Uuid::new_v4())?
+                .is_none()
+        );
+
+        db.add_pairing_code("EXPIRED2", ADMIN, time::Duration::minutes(-1))?;
+        assert!(
+            db.redeem_pairing_code("EXPIRED2", &device, Uuid::new_v4())?
+                .is_none()
+        );
+        assert_eq!(db.get_devices()?.len(), 1);
         Ok(())
     }
 
     #[test]
-    fn test_add_api_tokens() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_game_lock_lifecycle() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
-        let uuid = Uuid::new_v4();
-        db.add_api_tokens(vec![uuid])?;
-        let tokens = db.get_api_tokens()?;
-        assert!(tokens.contains(&uuid));
+        let lease = time::Duration::minutes(5);
+
+        assert!(
+            db.acquire_game_lock(1, ADMIN, "desktop", None, lease)?
+                .is_none()
+        );
+        test_game_with_path(&db, "Locked")?;
+
+        let game_lock = db
+            .acquire_game_lock(1, ADMIN, "desktop", Some("desktop-pc"), lease)?
+            .unwrap();
+        assert_eq!(game_lock.holder, "desktop");
+        assert_eq!(db.get_game_lock(1, ADMIN)?, Some(game_lock.clone()));
+        assert_eq!(db.get_game_locks(ADMIN)?, vec![game_lock.clone()]);
+
+        // Another holder is turned away, the holder renews its lock
+        let err = db
+            .acquire_game_lock(1, ADMIN, "laptop", None, lease)
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<GameLock>(), Some(&game_lock));
+        let renewed = db
+            .acquire_game_lock(1, ADMIN, "desktop", Some("desktop-pc"), lease)?
+            .unwrap();
+        assert_eq!(renewed.acquired, game_lock.acquired);
+
+        // Only the holder uploads while the game is locked
+        let upload = |lock_holder: Option<&str>| {
+            db.add_reference_to_save(
+                Uuid::new_v4(),
+                1,
+                &SaveParent {
+                    parent_uuid: None,
+                    force: true,
+                    lock_holder: lock_holder.map(str::to_string),
+                },
+                &SaveMetadata::default(),
+                ADMIN_SAVES,
+                None,
+                Codec::Identity,
+                None,
+                None,
+                vec![],
+            )
+        };
+        assert!(
+            upload(None)
+                .unwrap_err()
+                .downcast_ref::<GameLock>()
+                .is_some()
+        );
+        assert!(upload(Some("laptop")).is_err());
+        upload(Some("desktop"))?;
+
+        assert!(db.release_game_lock(1, ADMIN, Some("laptop")).is_err());
+        assert!(db.release_game_lock(1, ADMIN, Some("desktop"))?);
+        assert!(!db.release_game_lock(1, ADMIN, Some("desktop"))?);
+        upload(None)?;
+
+        // An expired lock goes to the next holder, a forced release breaks any lock
+        db.acquire_game_lock(1, ADMIN, "desktop", None, time::Duration::seconds(-1))?;
+        assert_eq!(db.get_game_lock(1, ADMIN)?, None);
+        db.acquire_game_lock(1, ADMIN, "laptop", None, lease)?;
+        assert!(db.release_game_lock(1, ADMIN, None)?);
+        assert_eq!(db.get_game_locks(ADMIN)?, vec![]);
         Ok(())
     }
 
     #[test]
-    fn test_remove_api_tokens() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_upload_session_lifecycle() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
-        let token = Uuid::new_v4();
-        db.add_api_tokens(vec![token])?;
-        db.remove_api_tokens(vec![token])?;
-        let tokens = db.get_api_tokens()?;
-        assert!(!tokens.contains(&token));
+        let uuid = add_test_upload_session(&db)?;
+        assert_eq!(
+            db.add_upload_session(
+                Uuid::new_v4(),
+                2,
+                ADMIN,
+                2048,
+                &[],
+                None,
+                HashAlgorithm::Sha512
+            )?,
+            CatalogUpdate::NotFound
+        );
+
+        assert_eq!(
+            db.add_upload_chunk(&uuid.to_string(), 0, 0, 1024)?,
+            CatalogUpdate::Done
+        );
+        db.add_upload_chunk(&uuid.to_string(), 1, 1024, 512)?;
+        db.add_upload_chunk(&uuid.to_string(), 1, 1024, 1024)?;
+        assert_eq!(
+            db.add_upload_chunk(&Uuid::new_v4().to_string(), 0, 0, 1024)?,
+            CatalogUpdate::NotFound
+        );
+
+        let session = db.get_upload_session(&uuid.to_string(), ADMIN)?.unwrap();
+        assert_eq!(session.size, 2048);
+        assert_eq!(session.file_hash[0].relative_path, "slot1.sav");
+        assert_eq!(session.metadata.hostname.as_deref(), Some("steamdeck"));
+        assert_eq!(session.hash_algorithm, HashAlgorithm::Sha512);
+        assert_eq!(
+            session.chunks,
+            vec![
+                ByteRange {
+                    start: 0,
+                    end: 1024
+                },
+                ByteRange {
+                    start: 1024,
+                    end: 2048
+                }
+            ]
+        );
+
+        assert!(db.set_upload_session_finalizing(&uuid.to_string(), true)?);
+        assert!(!db.set_upload_session_finalizing(&uuid.to_string(), true)?);
+        assert!(db.set_upload_session_finalizing(&uuid.to_string(), false)?);
+
+        let expired = db.remove_upload_sessions_inactive_since(utc_now())?;
+        assert_eq!(expired, vec![uuid.to_string()]);
+        assert!(db.get_upload_session(&uuid.to_string(), ADMIN)?.is_none());
         Ok(())
     }
 
     #[test]
-    fn test_get_configuration_value() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_finalizing_upload_session_is_not_expired() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
-        db.update_configuration_value("max_save_per_game", "10")?;
-        let val = db.get_configuration_value("max_save_per_game")?;
-        assert!(val.unwrap().value == "10");
+        let uuid = add_test_upload_session(&db)?;
+        db.add_upload_chunk(&uuid.to_string(), 0, 0, 2048)?;
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let before_finalizing = utc_now();
+        assert!(db.set_upload_session_finalizing(&uuid.to_string(), true)?);
+        assert!(
+            db.remove_upload_sessions_inactive_since(before_finalizing)?
+                .is_empty()
+        );
+        assert!(db.get_upload_session(&uuid.to_string(), ADMIN)?.is_some());
         Ok(())
     }
 
     #[test]
-    fn test_get_game_registry_by_game_id() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_upload_chunk_after_finalize_is_rejected() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
+        let uuid = add_test_upload_session(&db)?;
+        db.add_upload_chunk(&uuid.to_string(), 0, 0, 1024)?;
 
-        db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: None,
-            steam_appid: None,
-            default_name: "RegGame".to_string(),
-            install_dir: None,
-            gog: None,
-            flatpak_id: None,
-            lutris_id: None,
-            epic_cloud: None,
-            gog_cloud: None,
-            origin_cloud: None,
-            steam_cloud: None,
-            uplay_cloud: None,
-            ludusavi_managed: None,
-            gog_extra: None,
-            steam_extra: None,
-        }])?;
+        assert!(db.set_upload_session_finalizing(&uuid.to_string(), true)?);
+        assert_eq!(
+            db.add_upload_chunk(&uuid.to_string(), 1, 1024, 1024)?,
+            CatalogUpdate::Conflict
+        );
+        let session = db.get_upload_session(&uuid.to_string(), ADMIN)?.unwrap();
+        assert_eq!(session.chunks.len(), 1);
+        Ok(())
+    }
 
-        db.add_game_registry_path(
+    fn add_test_upload_session(db: &GameDatabase) -> Result<Uuid, Box<dyn Error + Send + Sync>> {
+        db.add_game_path(
             1,
-            &GameRegistry {
-                path: "reg1".to_string(),
+            &SavePathCreate {
+                path: "/saves".to_string(),
+                operating_system: OS::Linux,
             },
         )?;
-
-        let reg = db.get_game_registry_by_game_id(1)?;
-        assert!(reg.iter().any(|r| r.path == "reg1"));
-        Ok(())
+        let uuid = Uuid::new_v4();
+        let added = db.add_upload_session(
+            uuid,
+            1,
+            ADMIN,
+            2048,
+            &[FileHash {
+                relative_path: "slot1.sav".to_string(),
+                hash: "hash".to_string(),
+                size: None,
+            }],
+            Some(&SaveMetadata {
+                hostname: Some("steamdeck".to_string()),
+                ..Default::default()
+            }),
+            HashAlgorithm::Sha512,
+        )?;
+        assert_eq!(added, CatalogUpdate::Done);
+        Ok(uuid)
     }
 
     #[test]
-    fn test_add_game_registry_path() -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn test_saves_are_isolated_per_user() -> Result<(), Box<dyn Error + Send + Sync>> {
         let db = fresh_db();
+        let player = db
+            .add_user(
+                &UserCreate {
+                    name: "player".to_string(),
+                    admin: false,
+                },
+                Uuid::new_v4(),
+            )?
+            .expect("new name");
 
-        db.add_games_metadata(vec![&GameMetadataCreate {
-            known_name: None,
-            steam_appid: None,
-            default_name: "AddReg".to_string(),
-            install_dir: None,
-            gog: None,
-            flatpak_id: None,
-            lutris_id: None,
-            epic_cloud: None,
-            gog_cloud: None,
-            origin_cloud: None,
-            steam_cloud: None,
-            uplay_cloud: None,
-            ludusavi_managed: None,
-            gog_extra: None,
-            steam_extra: None,
-        }])?;
+        test_game_with_path(&db, "Shared")?;
 
-        db.add_game_registry_path(
+        let admin_save = Uuid::new_v4();
+        db.add_reference_to_save(
+            admin_save,
             1,
-            &GameRegistry {
-                path: "new_reg".to_string(),
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            ADMIN_SAVES,
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![],
+        )?;
+        assert!(db.get_reference_to_save_by_path_id(1, player.id)?.is_none());
+        assert!(
+            db.get_games_metadata_and_paths_if_saves_exist(player.id)?
+                .is_empty()
+        );
+
+        // The player's first save doesn't conflict with the admin's head
+        let player_save = Uuid::new_v4();
+        db.add_reference_to_save(
+            player_save,
+            1,
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            SaveOwner {
+                user_id: player.id,
+                device_id: None,
             },
+            None,
+            Codec::Identity,
+            None,
+            None,
+            vec![],
         )?;
+        let player_saves = db.get_reference_to_save_by_path_id(1, player.id)?.unwrap();
+        assert_eq!(player_saves.len(), 1);
+        assert_eq!(player_saves[0].uuid, player_save.to_string());
+        assert_eq!(
+            db.get_reference_to_save_by_path_id(1, ADMIN)?.unwrap()[0].uuid,
+            admin_save.to_string()
+        );
+        assert_eq!(
+            db.get_games_metadata_and_paths_if_saves_exist(player.id)?
+                .len(),
+            1
+        );
 
-        let reg = db.get_game_registry_by_game_id(1)?;
-        assert!(reg.iter().any(|r| r.path == "new_reg"));
+        assert!(
+            db.get_save_manifest(&admin_save.to_string(), player.id)?
+                .is_none()
+        );
+        assert!(!db.update_save_metadata(
+            &admin_save.to_string(),
+            player.id,
+            &SaveMetadataUpdate::default()
+        )?);
+        assert!(
+            db.add_restored_save(
+                &admin_save.to_string(),
+                SaveOwner {
+                    user_id: player.id,
+                    device_id: None,
+                },
+                None,
+                Uuid::new_v4()
+            )?
+            .is_none()
+        );
+        assert_eq!(db.get_save_histories()?, vec![(1, ADMIN), (1, player.id)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_user_management() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db = fresh_db();
+        let player = UserCreate {
+            name: "player".to_string(),
+            admin: false,
+        };
+        let token = Uuid::new_v4();
+        let added = db.add_user(&player, token)?.expect("new name");
+        assert!(db.add_user(&player, Uuid::new_v4())?.is_none());
+        let caller = db
+            .authenticate_api_token(&token.to_string())?
+            .expect("first token");
+        assert_eq!(caller.user_id, added.id);
+        assert!(!caller.scopes.contains(&TokenScope::Admin));
+
+        let admin = db.get_users()?.remove(0);
+        assert_eq!(
+            db.update_user(
+                ADMIN,
+                &UserCreate {
+                    name: admin.name.clone(),
+                    admin: false,
+                }
+            )?,
+            CatalogUpdate::Conflict
+        );
+        assert_eq!(
+            db.update_user(
+                added.id,
+                &UserCreate {
+                    name: admin.name,
+                    admin: false,
+                }
+            )?,
+            CatalogUpdate::Conflict
+        );
+
+        assert_eq!(db.remove_user(ADMIN)?, CatalogUpdate::Conflict);
+        assert_eq!(db.remove_user(added.id)?, CatalogUpdate::Done);
+        assert!(db.authenticate_api_token(&token.to_string())?.is_none());
+        assert_eq!(db.remove_user(added.id)?, CatalogUpdate::NotFound);
         Ok(())
     }
 }
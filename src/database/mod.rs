@@ -1,3 +1,5 @@
+mod api_token_hash;
 mod datatype;
 pub mod interface;
 mod schema;
+pub mod sqlite_backup;
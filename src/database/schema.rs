@@ -1,9 +1,36 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_token_scope (api_token_id, scope) {
+        api_token_id -> Integer,
+        scope -> Text,
+    }
+}
+
 diesel::table! {
     api_tokens (id) {
         id -> Nullable<Integer>,
-        api_token -> Text,
+        token_prefix -> Text,
+        salt -> Text,
+        token_hash -> Text,
+        device_id -> Nullable<Integer>,
+        name -> Text,
+        created -> Timestamp,
+        last_used -> Nullable<Timestamp>,
+        expires -> Nullable<Timestamp>,
+        user_id -> Integer,
+    }
+}
+
+diesel::table! {
+    blob (hash) {
+        hash -> Text,
+        size -> BigInt,
+        ref_count -> Integer,
+        codec -> Text,
+        stored_size -> BigInt,
+        data_key -> Nullable<Binary>,
+        nonce -> Nullable<Binary>,
     }
 }
 
@@ -21,11 +48,35 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    device (id) {
+        id -> Nullable<Integer>,
+        name -> Text,
+        operating_system -> Text,
+        client_version -> Nullable<Text>,
+        created -> Timestamp,
+        last_seen -> Timestamp,
+        user_id -> Integer,
+    }
+}
+
+diesel::table! {
+    device_sync (device_id, path_id) {
+        device_id -> Integer,
+        path_id -> Integer,
+        save_uuid -> Text,
+        save_time -> Timestamp,
+        synced -> Timestamp,
+    }
+}
+
 diesel::table! {
     file_hash (relative_path, game_save_uuid) {
         relative_path -> Text,
         hash -> Text,
         game_save_uuid -> Text,
+        blob_hash -> Nullable<Text>,
+        size -> Nullable<BigInt>,
     }
 }
 
@@ -52,6 +103,17 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    game_lock (game_metadata_id, user_id) {
+        game_metadata_id -> Integer,
+        user_id -> Integer,
+        holder -> Text,
+        hostname -> Nullable<Text>,
+        acquired -> Timestamp,
+        expires -> Timestamp,
+    }
+}
+
 diesel::table! {
     game_metadata (id) {
         id -> Nullable<Integer>,
@@ -91,6 +153,25 @@ diesel::table! {
         uuid -> Text,
         path_id -> Integer,
         time -> Timestamp,
+        legacy_archive -> Bool,
+        parent_uuid -> Nullable<Text>,
+        pinned -> Bool,
+        label -> Nullable<Text>,
+        note -> Nullable<Text>,
+        hostname -> Nullable<Text>,
+        client_version -> Nullable<Text>,
+        game_version -> Nullable<Text>,
+        playtime -> Nullable<BigInt>,
+        restored_from -> Nullable<Text>,
+        hash_algorithm -> Nullable<Text>,
+        archive_digest -> Nullable<Text>,
+        corrupt -> Bool,
+        codec -> Nullable<Text>,
+        data_key -> Nullable<Binary>,
+        nonce -> Nullable<Binary>,
+        device_id -> Nullable<Integer>,
+        user_id -> Integer,
+        archive_format -> Nullable<Text>,
     }
 }
 
@@ -101,26 +182,109 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    legacy_api_token (api_token_id) {
+        api_token_id -> Integer,
+        api_token -> Text,
+    }
+}
+
+diesel::table! {
+    pairing_code (code) {
+        code -> Text,
+        created -> Timestamp,
+        expires -> Timestamp,
+        user_id -> Integer,
+    }
+}
+
+diesel::table! {
+    scrub_report (id) {
+        id -> Integer,
+        time -> Timestamp,
+        report -> Text,
+    }
+}
+
+diesel::table! {
+    upload_chunk (chunk_number, upload_session_uuid) {
+        chunk_number -> Integer,
+        upload_session_uuid -> Text,
+        start_offset -> BigInt,
+        size -> BigInt,
+    }
+}
+
+diesel::table! {
+    upload_session (uuid) {
+        uuid -> Text,
+        path_id -> Integer,
+        size -> BigInt,
+        file_hash -> Text,
+        last_activity -> Timestamp,
+        finalizing -> Bool,
+        metadata -> Nullable<Text>,
+        hash_algorithm -> Text,
+        user_id -> Integer,
+    }
+}
+
+diesel::table! {
+    user_account (id) {
+        id -> Nullable<Integer>,
+        name -> Text,
+        admin -> Bool,
+        created -> Timestamp,
+    }
+}
+
+diesel::joinable!(api_token_scope -> api_tokens (api_token_id));
+diesel::joinable!(api_tokens -> device (device_id));
+diesel::joinable!(api_tokens -> user_account (user_id));
+diesel::joinable!(legacy_api_token -> api_tokens (api_token_id));
+diesel::joinable!(device -> user_account (user_id));
+diesel::joinable!(device_sync -> device (device_id));
+diesel::joinable!(device_sync -> game_path (path_id));
+diesel::joinable!(file_hash -> blob (blob_hash));
 diesel::joinable!(file_hash -> game_save (game_save_uuid));
 diesel::joinable!(game_alt_name -> game_metadata (game_metadata_id));
 diesel::joinable!(game_executable -> game_metadata (game_metadata_id));
 diesel::joinable!(game_gog_extra_id -> game_metadata (game_metadata_id));
+diesel::joinable!(game_lock -> game_metadata (game_metadata_id));
+diesel::joinable!(game_lock -> user_account (user_id));
 diesel::joinable!(game_path -> game_metadata (game_metadata_id));
 diesel::joinable!(game_registry -> game_metadata (game_metadata_id));
+diesel::joinable!(game_save -> device (device_id));
 diesel::joinable!(game_save -> game_path (path_id));
+diesel::joinable!(game_save -> user_account (user_id));
 diesel::joinable!(game_steam_extra_id -> game_metadata (game_metadata_id));
+diesel::joinable!(pairing_code -> user_account (user_id));
+diesel::joinable!(upload_chunk -> upload_session (upload_session_uuid));
+diesel::joinable!(upload_session -> game_path (path_id));
+diesel::joinable!(upload_session -> user_account (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    api_token_scope,
     api_tokens,
+    blob,
     configurations,
     db_info,
+    device,
+    device_sync,
     file_hash,
     game_alt_name,
     game_executable,
     game_gog_extra_id,
+    game_lock,
     game_metadata,
     game_path,
     game_registry,
     game_save,
     game_steam_extra_id,
+    legacy_api_token,
+    pairing_code,
+    scrub_report,
+    upload_chunk,
+    upload_session,
+    user_account,
 );
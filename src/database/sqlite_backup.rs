@@ -0,0 +1,135 @@
+use libsqlite3_sys as ffi;
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_int;
+use std::path::Path;
+use std::ptr;
+use std::time::Duration;
+
+const BUSY_TIMEOUT_MS: c_int = 2000;
+const BUSY_RETRIES: u32 = 50;
+
+/// Connection opened outside of diesel, which doesn't expose the backup API
+struct RawConnection(*mut ffi::sqlite3);
+
+impl RawConnection {
+    fn open(path: &Path, flags: c_int) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let path = CString::new(path.to_string_lossy().as_bytes())?;
+        let mut handle = ptr::null_mut();
+        // SAFETY: `path` is a valid C string and `handle` is written by sqlite3_open_v2
+        let code = unsafe { ffi::sqlite3_open_v2(path.as_ptr(), &mut handle, flags, ptr::null()) };
+        let connection = Self(handle);
+        if code != ffi::SQLITE_OK {
+            return Err(connection.error(code).into());
+        }
+        // SAFETY: the connection was opened successfully
+        unsafe { ffi::sqlite3_busy_timeout(connection.0, BUSY_TIMEOUT_MS) };
+        Ok(connection)
+    }
+
+    fn error(&self, code: c_int) -> String {
+        if self.0.is_null() {
+            return format!("SQLite error {}", code);
+        }
+        // SAFETY: sqlite3_errmsg returns a C string owned by the connection
+        let message = unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.0)) };
+        format!("SQLite error {}: {}", code, message.to_string_lossy())
+    }
+}
+
+impl Drop for RawConnection {
+    fn drop(&mut self) {
+        // SAFETY: the handle comes from sqlite3_open_v2, closing a null handle is a no-op
+        unsafe { ffi::sqlite3_close(self.0) };
+    }
+}
+
+/// Copies the database at `source` to a new file `destination` with SQLite's online backup API:
+/// the copy is a consistent snapshot even while the server writes to the database.
+pub fn backup_database(
+    source: &Path,
+    destination: &Path,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let source = RawConnection::open(source, ffi::SQLITE_OPEN_READONLY)?;
+    let destination = RawConnection::open(
+        destination,
+        ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE,
+    )?;
+    let main = c"main";
+
+    // SAFETY: both connections are open for the lifetime of the backup
+    let backup =
+        unsafe { ffi::sqlite3_backup_init(destination.0, main.as_ptr(), source.0, main.as_ptr()) };
+    if backup.is_null() {
+        return Err(destination.error(ffi::SQLITE_ERROR).into());
+    }
+
+    // Copying every page in one step reads them all in a single transaction
+    let mut retries = 0;
+    let step_code = loop {
+        // SAFETY: `backup` is valid until sqlite3_backup_finish
+        match unsafe { ffi::sqlite3_backup_step(backup, -1) } {
+            ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED if retries < BUSY_RETRIES => {
+                retries += 1;
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            code => break code,
+        }
+    };
+    // SAFETY: `backup` is finished exactly once
+    let finish_code = unsafe { ffi::sqlite3_backup_finish(backup) };
+
+    if step_code != ffi::SQLITE_DONE {
+        return Err(destination.error(step_code).into());
+    }
+    if finish_code != ffi::SQLITE_OK {
+        return Err(destination.error(finish_code).into());
+    }
+    Ok(())
+}
+
+/// Writes the write-ahead log of the database at `path` back into the database file and
+/// truncates it, the file can then be moved on its own
+pub fn checkpoint_database(path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let connection = RawConnection::open(path, ffi::SQLITE_OPEN_READWRITE)?;
+    // SAFETY: the connection is open and the frame counts may be null
+    let code = unsafe {
+        ffi::sqlite3_wal_checkpoint_v2(
+            connection.0,
+            ptr::null(),
+            ffi::SQLITE_CHECKPOINT_TRUNCATE,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    };
+    if code != ffi::SQLITE_OK {
+        return Err(connection.error(code).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::interface::GameDatabase;
+
+    #[test]
+    fn test_backup_database() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let dir = std::env::temp_dir().join(format!("sqlite-backup-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let source = dir.join("source.sqlite");
+        let copy = dir.join("copy.sqlite");
+
+        let db = GameDatabase::new(source.to_str().unwrap());
+        backup_database(&source, &copy)?;
+        checkpoint_database(&copy)?;
+        let copied = GameDatabase::new(copy.to_str().unwrap());
+        assert_eq!(copied.get_database_uuid()?, db.get_database_uuid()?);
+
+        // A missing source isn't created
+        assert!(backup_database(&dir.join("missing.sqlite"), &dir.join("other.sqlite")).is_err());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -9,10 +9,14 @@ use utoipa::{IntoParams, ToSchema};
 #[derive(ToSchema)]
 #[allow(unused)]
 pub struct UploadedSave {
-    #[schema(value_type = String, format = Binary)]
-    pub file: Vec<u8>,
+    /// One part per file, the part file name being the relative path listed in `file_hash`, or a
+    /// single zip or tar archive of the files
+    #[schema(value_type = Vec<String>, format = Binary)]
+    pub file: Vec<Vec<u8>>,
     #[schema(value_type = String, example = json!([{"relative_path": "file.txt", "hash": "abc123"}]))]
     pub file_hash: Vec<FileHash>,
+    #[schema(value_type = Option<String>, example = json!({"label": "Before the final boss", "hostname": "steamdeck"}))]
+    pub metadata: Option<SaveMetadata>,
 }
 
 #[derive(ToSchema)]
@@ -22,6 +26,14 @@ pub struct UploadedFileYaml {
     pub file: Vec<u8>,
 }
 
+#[derive(ToSchema)]
+#[allow(unused)]
+pub struct UploadedLudusaviBackup {
+    /// Tar archive of a Ludusavi backup directory
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+}
+
 #[derive(
     Serialize,
     Deserialize,
@@ -73,6 +85,252 @@ where
     }
 }
 
+#[derive(
+    Serialize,
+    Deserialize,
+    ToSchema,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    AsExpression,
+    FromSqlRow,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    pub const SUPPORTED: [HashAlgorithm; 2] = [HashAlgorithm::Sha256, HashAlgorithm::Sha512];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+impl<DB> ToSql<Text, DB> for HashAlgorithm
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        <str as ToSql<Text, DB>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for HashAlgorithm
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: <DB as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, DB>>::from_sql(bytes)?;
+        HashAlgorithm::SUPPORTED
+            .into_iter()
+            .find(|algorithm| algorithm.as_str() == s)
+            .ok_or_else(|| format!("invalid hash algorithm in the database: {s}").into())
+    }
+}
+
+/// What a token may do, `admin` includes every other scope
+#[derive(
+    Serialize,
+    Deserialize,
+    ToSchema,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    AsExpression,
+    FromSqlRow,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenScope {
+    /// Every `GET` outside of administration
+    ReadOnly,
+    /// Saves, upload sessions, locks and device sync state
+    Upload,
+    /// Games and their paths, executables and registry keys
+    CatalogWrite,
+    /// Configuration, backups, scrub, devices and tokens
+    Admin,
+}
+
+impl TokenScope {
+    pub const ALL: [TokenScope; 4] = [
+        TokenScope::ReadOnly,
+        TokenScope::Upload,
+        TokenScope::CatalogWrite,
+        TokenScope::Admin,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::ReadOnly => "read-only",
+            TokenScope::Upload => "upload",
+            TokenScope::CatalogWrite => "catalog-write",
+            TokenScope::Admin => "admin",
+        }
+    }
+}
+
+impl<DB> ToSql<Text, DB> for TokenScope
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        <str as ToSql<Text, DB>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for TokenScope
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: <DB as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, DB>>::from_sql(bytes)?;
+        TokenScope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| format!("invalid token scope in the database: {s}").into())
+    }
+}
+
+/// How a blob is stored on disk
+#[derive(
+    Serialize,
+    Deserialize,
+    ToSchema,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    AsExpression,
+    FromSqlRow,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    #[default]
+    Identity,
+    Zstd,
+}
+
+impl Codec {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Codec::Identity => "identity",
+            Codec::Zstd => "zstd",
+        }
+    }
+}
+
+impl<DB> ToSql<Text, DB> for Codec
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        <str as ToSql<Text, DB>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for Codec
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: <DB as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, DB>>::from_sql(bytes)?;
+        match s.as_str() {
+            "identity" => Ok(Codec::Identity),
+            "zstd" => Ok(Codec::Zstd),
+            other => Err(format!("invalid codec in the database: {other}").into()),
+        }
+    }
+}
+
+/// Archive a save was uploaded as in a single part, sent back in the same format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum ArchiveFormat {
+    Tar,
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+impl<DB> ToSql<Text, DB> for ArchiveFormat
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        <str as ToSql<Text, DB>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl<DB> FromSql<Text, DB> for ArchiveFormat
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: <DB as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, DB>>::from_sql(bytes)?;
+        match s.as_str() {
+            "tar" => Ok(ArchiveFormat::Tar),
+            "zip" => Ok(ArchiveFormat::Zip),
+            other => Err(format!("invalid archive format in the database: {other}").into()),
+        }
+    }
+}
+
+/// Algorithm of the hashes listed in `file_hash`, see `GET /hash_algorithms`
+#[derive(Serialize, Deserialize, IntoParams, Clone, Copy, Default)]
+#[into_params(parameter_in = Query)]
+#[serde(default)]
+pub struct SaveHashAlgorithm {
+    pub hash_algorithm: HashAlgorithm,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct FileHashMismatch {
+    pub relative_path: String,
+    /// `None` if the file isn't listed in `file_hash`
+    pub declared_hash: Option<String>,
+    /// `None` if the file wasn't uploaded
+    pub computed_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct HashMismatch {
+    pub hash_algorithm: HashAlgorithm,
+    pub files: Vec<FileHashMismatch>,
+}
+
 #[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct SavePathCreate {
     pub path: String,
@@ -161,6 +419,9 @@ pub struct GameDefaultName {
 pub struct FileHash {
     pub relative_path: String,
     pub hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(required = false, nullable)]
+    pub size: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema, Clone)]
@@ -168,9 +429,287 @@ pub struct SaveReference {
     pub uuid: String,
     pub path_id: i32,
     pub time: i64,
+    /// Save this one was based on, `None` for the first save of a path
+    pub parent_uuid: Option<String>,
+    /// Save whose content was restored as this one, `None` for uploaded saves
+    pub restored_from: Option<String>,
+    /// Pinned saves are never pruned
+    pub pinned: bool,
+    /// Set by the integrity scrub when a file of the save is missing or damaged on disk
+    pub corrupt: bool,
+    pub metadata: SaveMetadata,
+    /// Algorithm of the file hashes, `None` for saves uploaded before hashes were verified
+    pub hash_algorithm: Option<HashAlgorithm>,
+    /// Digest of the sorted relative paths and file hashes
+    pub archive_digest: Option<String>,
+    /// Codec the files were stored with on upload, `None` for legacy archives
+    pub codec: Option<Codec>,
+    /// Whether new files of the save were encrypted with a data key of its own
+    pub encrypted: bool,
+    /// Device that uploaded the save, `None` when uploaded with a token of no device
+    pub device_id: Option<i32>,
     pub files_hash: Vec<FileHash>,
 }
 
+#[derive(Serialize, Deserialize, ToSchema, Clone, Default, Debug, PartialEq)]
+#[serde(default)]
+pub struct SaveMetadata {
+    pub label: Option<String>,
+    pub note: Option<String>,
+    /// Hostname of the client that uploaded the save
+    pub hostname: Option<String>,
+    pub client_version: Option<String>,
+    /// Build or version of the game that wrote the save
+    pub game_version: Option<String>,
+    /// Playtime in seconds
+    pub playtime: Option<i64>,
+}
+
+/// Fields left out are unchanged, an empty string clears the field
+#[derive(Serialize, Deserialize, ToSchema, Clone, Default)]
+#[serde(default)]
+pub struct SaveMetadataUpdate {
+    pub label: Option<String>,
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct FileChange {
+    pub relative_path: String,
+    /// `None` for an added file
+    pub old_hash: Option<String>,
+    /// `None` for a removed file
+    pub new_hash: Option<String>,
+    pub old_size: Option<i64>,
+    pub new_size: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct SaveDiff {
+    pub from: String,
+    pub to: String,
+    pub added: Vec<FileChange>,
+    pub removed: Vec<FileChange>,
+    pub modified: Vec<FileChange>,
+    pub unchanged: usize,
+}
+
+#[derive(Serialize, Deserialize, IntoParams, Clone, Default)]
+#[into_params(parameter_in = Query)]
+#[serde(default)]
+pub struct SaveParent {
+    /// UUID of the save the client last synced from, the latest save of the path if not set
+    pub parent_uuid: Option<String>,
+    /// Store the save even if `parent_uuid` isn't the latest save of the path
+    pub force: bool,
+    /// Holder of the lock of the game, needed to upload while the game is locked
+    pub lock_holder: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, IntoParams, Clone, Default)]
+#[into_params(parameter_in = Query)]
+#[serde(default)]
+pub struct SaveRestore {
+    /// Holder of the lock of the game, needed to restore while the game is locked
+    pub lock_holder: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct SaveConflict {
+    /// Latest save of the path on the server
+    pub head: Option<String>,
+    /// Save the upload was based on
+    pub base: Option<String>,
+}
+
+impl std::fmt::Display for SaveConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "save based on {:?} but the latest save is {:?}",
+            self.base, self.head
+        )
+    }
+}
+
+impl std::error::Error for SaveConflict {}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct DeviceCreate {
+    pub name: String,
+    pub operating_system: OS,
+    #[serde(default)]
+    pub client_version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct Device {
+    pub id: i32,
+    pub name: String,
+    pub operating_system: OS,
+    pub client_version: Option<String>,
+    pub created: i64,
+    /// Last request made with the token of the device
+    pub last_seen: i64,
+    /// User owning the device and the saves it uploads
+    pub user_id: i32,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct DeviceRegistration {
+    pub device: Device,
+    /// Bearer token of the device, only returned at registration
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct DeviceSyncUpdate {
+    /// Save the device now has on disk
+    pub save_uuid: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct DeviceSyncState {
+    pub game_id: i32,
+    pub game_name: String,
+    pub path_id: i32,
+    /// Last save the device uploaded or synced for the path
+    pub synced_save_uuid: String,
+    pub synced_save_time: i64,
+    /// When the device synced it
+    pub synced: i64,
+    /// Latest save of the path, `None` if every save was removed
+    pub latest_save_uuid: Option<String>,
+    pub latest_save_time: Option<i64>,
+    /// Whether the latest save of the path isn't the one the device has
+    pub behind: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct ApiTokenCreate {
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    /// Unix timestamp after which the token is refused, `None` for a token that doesn't expire
+    #[serde(default)]
+    pub expires: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct ApiToken {
+    pub id: i32,
+    /// First characters of the token, to recognize it without its value
+    pub prefix: String,
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    /// Device the token was issued to, if any
+    pub device_id: Option<i32>,
+    /// User the token acts as
+    pub user_id: i32,
+    pub created: i64,
+    /// Last authenticated request, precise to a minute
+    pub last_used: Option<i64>,
+    pub expires: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct ApiTokenCreated {
+    pub token: ApiToken,
+    /// Bearer token, only returned at creation
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, IntoParams, Clone, Copy, Default)]
+#[into_params(parameter_in = Query)]
+#[serde(default)]
+pub struct UserQuery {
+    /// User to issue it for, the user of the caller when omitted
+    pub user_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct UserCreate {
+    pub name: String,
+    /// Administrators manage users, tokens and devices of every user
+    #[serde(default)]
+    pub admin: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct User {
+    pub id: i32,
+    pub name: String,
+    pub admin: bool,
+    pub created: i64,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct UserRegistration {
+    pub user: User,
+    /// Bearer token of the user, only returned at creation
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct PairingCode {
+    /// Code to type on the new client, formatted as `XXXX-XXXX`
+    pub code: String,
+    pub expires: i64,
+    /// Content of the QR code, `gamesavesync://pair?server=...&code=...`
+    pub qr_payload: String,
+    /// QR code of `qr_payload` as an SVG image
+    pub qr_svg: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct PairingExchange {
+    /// Pairing code, case and dashes are ignored
+    pub code: String,
+    pub name: String,
+    pub operating_system: OS,
+    #[serde(default)]
+    pub client_version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct GameLockAcquire {
+    /// Identifier of the running game session, sent again to renew or release the lock
+    pub holder: String,
+    #[serde(default)]
+    pub hostname: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct GameLock {
+    pub game_id: i32,
+    pub holder: String,
+    pub hostname: Option<String>,
+    pub acquired: i64,
+    /// The lock is released at that time unless it's renewed before
+    pub expires: i64,
+}
+
+impl std::fmt::Display for GameLock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "game {} is locked by {} on {:?}",
+            self.game_id, self.holder, self.hostname
+        )
+    }
+}
+
+impl std::error::Error for GameLock {}
+
+#[derive(Serialize, Deserialize, IntoParams, Clone, Default)]
+#[into_params(parameter_in = Query)]
+#[serde(default)]
+pub struct GameLockRelease {
+    /// Holder of the lock
+    pub holder: Option<String>,
+    /// Break the lock whoever holds it, needs the admin scope
+    pub force: bool,
+}
+
 #[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct GameMetadataWithPaths {
     pub game_metadata: GameMetadata,
@@ -186,3 +725,134 @@ pub struct ConfigurationForm {
 pub struct GameRegistry {
     pub path: String,
 }
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Default)]
+#[serde(default)]
+pub struct GameRegistryUpdate {
+    pub added: Vec<GameRegistry>,
+    pub removed: Vec<GameRegistry>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
+pub struct UploadSessionCreate {
+    pub size: i64,
+    pub file_hash: Vec<FileHash>,
+    #[serde(default)]
+    pub metadata: Option<SaveMetadata>,
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+pub struct ByteRange {
+    pub start: i64,
+    pub end: i64,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
+pub struct UploadSession {
+    pub uuid: String,
+    pub path_id: i32,
+    pub size: i64,
+    pub last_activity: i64,
+    pub received: Vec<ByteRange>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrubProblem {
+    Missing,
+    SizeMismatch,
+    HashMismatch,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct DamagedFile {
+    pub path: String,
+    pub problem: ScrubProblem,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct ScrubReport {
+    pub started: i64,
+    pub finished: i64,
+    pub checked_blobs: usize,
+    pub checked_archives: usize,
+    pub damaged_files: Vec<DamagedFile>,
+    /// Saves with at least one damaged file, marked as corrupt
+    pub corrupt_saves: Vec<String>,
+    /// Files without any save referencing them, moved to the quarantine directory
+    pub quarantined_files: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingDownload {
+    /// No save has this UUID
+    Save,
+    /// The save has no file at this relative path
+    File,
+    /// The save exists but its content is missing from the save store
+    StoredContent,
+}
+
+/// Body of the 404 returned by the download endpoints
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct DownloadNotFound {
+    pub missing: MissingDownload,
+    pub uuid: String,
+    pub relative_path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum BundleFormat {
+    #[default]
+    Tar,
+    Zip,
+}
+
+/// Saves of a bundle: either `uuids` or `latest`
+#[derive(Serialize, Deserialize, IntoParams, Clone, Default)]
+#[into_params(parameter_in = Query)]
+#[serde(default)]
+pub struct SaveBundleQuery {
+    /// Comma separated UUIDs of the saves, at most one per path
+    pub uuids: Option<String>,
+    /// Bundle the latest save of every path
+    pub latest: bool,
+    pub format: BundleFormat,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct BackupInfo {
+    pub file_name: String,
+    pub size: u64,
+    pub created: i64,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct SkippedLudusaviBackup {
+    pub game_name: String,
+    pub backup_name: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Default, PartialEq, Debug)]
+pub struct LudusaviImportReport {
+    /// UUIDs of the saves created, one per full or differential backup, oldest first
+    pub imported_saves: Vec<String>,
+    /// Backups matching a save of the same date and content, left as is
+    pub already_imported: usize,
+    /// Names of the games of the backup matching no game, nor any alternative name
+    pub unmatched_games: Vec<String>,
+    pub skipped_backups: Vec<SkippedLudusaviBackup>,
+}
+
+#[derive(Serialize, Deserialize, IntoParams, Clone, Default)]
+#[into_params(parameter_in = Query)]
+#[serde(default)]
+pub struct LudusaviExportQuery {
+    /// Export only the latest save of every path instead of every save
+    pub latest: bool,
+}
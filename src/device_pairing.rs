@@ -0,0 +1,137 @@
+use crate::const_var::PAIRING_ATTEMPTS_PER_MINUTE;
+use once_cell::sync::Lazy;
+use qrcode::QrCode;
+use qrcode::render::svg;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// No 0/O, 1/I/L so a code read from a screen can be typed back without guessing
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 8;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+static PAIRING_ATTEMPTS: Lazy<Mutex<HashMap<IpAddr, Vec<Instant>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn generate_pairing_code() -> String {
+    // Largest multiple of the alphabet length that fits in a byte, so every character is as likely
+    let limit = (u8::MAX as usize / CODE_ALPHABET.len() * CODE_ALPHABET.len()) as u8;
+    let mut code = String::with_capacity(CODE_LENGTH);
+    let mut random = [0u8; 16];
+    while code.len() < CODE_LENGTH {
+        getrandom::fill(&mut random).expect("OS random number generator");
+        for byte in random.iter().filter(|byte| **byte < limit) {
+            if code.len() == CODE_LENGTH {
+                break;
+            }
+            code.push(CODE_ALPHABET[*byte as usize % CODE_ALPHABET.len()] as char);
+        }
+    }
+    code
+}
+
+/// `ABCD2345` is shown as `ABCD-2345`
+pub fn format_pairing_code(code: &str) -> String {
+    let (first, second) = code.split_at(code.len() / 2);
+    format!("{first}-{second}")
+}
+
+/// Turns what a user typed back into the stored code
+pub fn normalize_pairing_code(input: &str) -> String {
+    input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+pub fn pairing_qr_payload(server: &str, code: &str) -> String {
+    Url::parse_with_params("gamesavesync://pair", &[("server", server), ("code", code)])
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+pub fn pairing_qr_svg(payload: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+    Ok(QrCode::new(payload.as_bytes())?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build())
+}
+
+/// Records a pairing attempt from `ip`, returns `false` if it is over the limit
+pub fn allow_pairing_attempt(ip: IpAddr) -> bool {
+    allow_attempt(
+        &mut PAIRING_ATTEMPTS.lock().unwrap(),
+        ip,
+        Instant::now(),
+        PAIRING_ATTEMPTS_PER_MINUTE,
+    )
+}
+
+fn allow_attempt(
+    attempts: &mut HashMap<IpAddr, Vec<Instant>>,
+    ip: IpAddr,
+    now: Instant,
+    max_attempts: usize,
+) -> bool {
+    attempts.retain(|_, times| {
+        times.retain(|time| now.duration_since(*time) < RATE_LIMIT_WINDOW);
+        !times.is_empty()
+    });
+    let times = attempts.entry(ip).or_default();
+    if times.len() >= max_attempts {
+        return false;
+    }
+    times.push(now);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_generate_pairing_code() {
+        let code = generate_pairing_code();
+        assert_eq!(code.len(), CODE_LENGTH);
+        assert!(code.bytes().all(|c| CODE_ALPHABET.contains(&c)));
+        assert_ne!(code, generate_pairing_code());
+    }
+
+    #[test]
+    fn test_pairing_code_round_trip() {
+        let formatted = format_pairing_code("ABCD2345");
+        assert_eq!(formatted, "ABCD-2345");
+        assert_eq!(normalize_pairing_code(&formatted), "ABCD2345");
+        assert_eq!(normalize_pairing_code(" abcd 2345 "), "ABCD2345");
+    }
+
+    #[test]
+    fn test_pairing_qr() {
+        let payload = pairing_qr_payload("http://host:3000", "ABCD2345");
+        assert_eq!(
+            payload,
+            "gamesavesync://pair?server=http%3A%2F%2Fhost%3A3000&code=ABCD2345"
+        );
+        assert!(pairing_qr_svg(&payload).unwrap().contains("<svg"));
+    }
+
+    #[test]
+    fn test_allow_attempt() {
+        let mut attempts = HashMap::new();
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let other_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let now = Instant::now();
+
+        assert!(allow_attempt(&mut attempts, ip, now, 2));
+        assert!(allow_attempt(&mut attempts, ip, now, 2));
+        assert!(!allow_attempt(&mut attempts, ip, now, 2));
+        assert!(allow_attempt(&mut attempts, other_ip, now, 2));
+        assert!(allow_attempt(&mut attempts, ip, now + RATE_LIMIT_WINDOW, 2));
+    }
+}
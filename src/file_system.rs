@@ -1,4 +1,4 @@
-use crate::const_var::{DATA_DIR, TMP_DIR};
+use crate::const_var::{BACKUP_DIR, BLOB_DIR, DATA_DIR, QUARANTINE_DIR, TMP_DIR};
 use std::error::Error;
 use std::path::Path;
 use tokio::fs::{self, File};
@@ -39,5 +39,35 @@ pub async fn create_fs_structure() -> Result<(), Box<dyn Error>> {
     fs::create_dir_all(DATA_DIR).await?;
     fs::create_dir_all(TMP_DIR).await?;
     fs::create_dir_all(format!("{}/saves", DATA_DIR)).await?;
+    fs::create_dir_all(BLOB_DIR).await?;
+    fs::create_dir_all(QUARANTINE_DIR).await?;
+    fs::create_dir_all(BACKUP_DIR).await?;
     Ok(())
 }
+
+pub fn is_safe_relative_path(relative_path: &str) -> bool {
+    !relative_path.is_empty()
+        && !relative_path.starts_with(['/', '\\'])
+        && relative_path.get(1..2) != Some(":")
+        && relative_path
+            .split(['/', '\\'])
+            .all(|segment| segment != "..")
+}
+
+/// `name` without the characters file systems reject, to name the directory of a game
+pub fn portable_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    match name.trim() {
+        "" | "." | ".." => "_".to_string(),
+        name => name.to_string(),
+    }
+}
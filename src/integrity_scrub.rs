@@ -0,0 +1,173 @@
+use crate::DATABASE;
+use crate::blob_store::{self, blob_object, legacy_archive_object, open_blob};
+use crate::database::interface::BlobInfo;
+use crate::datatype_endpoint::{DamagedFile, ScrubProblem, ScrubReport};
+use crate::save_compression::decoded_reader;
+use crate::save_encryption::{blob_key, encrypted_size};
+use crate::save_store::SAVE_STORE;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::error::Error;
+use time::OffsetDateTime;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{Mutex, MutexGuard};
+use tokio_util::sync::CancellationToken;
+
+// The job and the API can both start a scrub, only one runs at a time
+static SCRUB_LOCK: Mutex<()> = Mutex::const_new(());
+
+async fn check_blob(blob: &BlobInfo) -> Result<Option<ScrubProblem>, Box<dyn Error + Send + Sync>> {
+    let object = blob_object(&blob.hash);
+    let file_size = match SAVE_STORE.stat(&object).await? {
+        Some(file_size) => file_size,
+        None => return Ok(Some(ScrubProblem::Missing)),
+    };
+    let stored_size = u64::try_from(blob.stored_size).unwrap_or_default();
+    let expected_size = match blob.encryption {
+        Some(_) => encrypted_size(stored_size),
+        None => stored_size,
+    };
+    if file_size != expected_size {
+        return Ok(Some(ScrubProblem::SizeMismatch));
+    }
+
+    // Without the master key an encrypted blob can't be verified, the scrub fails loudly
+    let blob_key = blob_key(blob.encryption.as_ref(), &blob.hash)?;
+    // A blob pruned since its size was checked is missing, the scrub drops it if its row is gone
+    let mut reader = match open_blob(SAVE_STORE.as_ref(), &object, blob_key).await {
+        Ok(reader) => decoded_reader(reader, blob.codec),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Some(ScrubProblem::Missing));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut hasher = Sha256::new();
+    let mut size = 0;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = match reader.read(&mut buffer).await {
+            Ok(read) => read,
+            // The compressed or encrypted stream itself is damaged
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                return Ok(Some(ScrubProblem::HashMismatch));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Some(ScrubProblem::Missing));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as i64;
+    }
+
+    if size != blob.size {
+        Ok(Some(ScrubProblem::SizeMismatch))
+    } else if hex::encode(hasher.finalize()) != blob.hash {
+        Ok(Some(ScrubProblem::HashMismatch))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Re-hashes every blob, checks that legacy archives still exist, marks the saves with a damaged
+/// file as corrupt and quarantines the files no save references. Returns `None` if a scrub is
+/// already running.
+pub async fn scrub(
+    cancellation_token: CancellationToken,
+) -> Result<Option<ScrubReport>, Box<dyn Error + Send + Sync>> {
+    match SCRUB_LOCK.try_lock() {
+        Ok(guard) => Ok(Some(run_scrub(guard, cancellation_token).await?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Starts a scrub in the background, its report is stored once it is done. Returns `false` if a
+/// scrub is already running.
+pub fn spawn_scrub() -> bool {
+    let Ok(guard) = SCRUB_LOCK.try_lock() else {
+        return false;
+    };
+    tokio::spawn(async move {
+        match run_scrub(guard, CancellationToken::new()).await {
+            Ok(report) => tracing::info!(
+                "Integrity scrub done, {} damaged files and {} corrupt saves",
+                report.damaged_files.len(),
+                report.corrupt_saves.len()
+            ),
+            Err(e) => tracing::error!("Error running integrity scrub: {}", e),
+        }
+    });
+    true
+}
+
+async fn run_scrub(
+    _guard: MutexGuard<'static, ()>,
+    cancellation_token: CancellationToken,
+) -> Result<ScrubReport, Box<dyn Error + Send + Sync>> {
+    let started = OffsetDateTime::now_utc().unix_timestamp();
+
+    let blobs = DATABASE.get_blobs()?;
+    let mut damaged_blobs = Vec::new();
+    for blob in &blobs {
+        if cancellation_token.is_cancelled() {
+            return Err("integrity scrub cancelled".into());
+        }
+        if let Some(problem) = check_blob(blob).await? {
+            damaged_blobs.push((blob.hash.clone(), problem));
+        }
+    }
+    // Blobs removed by a pruning running meanwhile aren't damaged
+    let remaining_blobs: HashSet<String> = DATABASE
+        .get_blobs()?
+        .into_iter()
+        .map(|blob| blob.hash)
+        .collect();
+    damaged_blobs.retain(|(blob_hash, _)| remaining_blobs.contains(blob_hash));
+
+    let legacy_archives = DATABASE.get_legacy_archive_uuids()?;
+    let mut missing_archives = Vec::new();
+    for uuid in &legacy_archives {
+        if SAVE_STORE
+            .stat(&legacy_archive_object(uuid))
+            .await?
+            .is_none()
+        {
+            missing_archives.push(uuid.clone());
+        }
+    }
+
+    let damaged_blob_hashes: Vec<String> = damaged_blobs
+        .iter()
+        .map(|(blob_hash, _)| blob_hash.clone())
+        .collect();
+    let corrupt_saves = DATABASE.mark_corrupt_saves(&damaged_blob_hashes, &missing_archives)?;
+    let quarantined_files = blob_store::quarantine_orphans().await?;
+
+    let mut damaged_files: Vec<DamagedFile> = damaged_blobs
+        .into_iter()
+        .map(|(blob_hash, problem)| DamagedFile {
+            path: blob_object(&blob_hash),
+            problem,
+        })
+        .collect();
+    damaged_files.extend(missing_archives.iter().map(|uuid| DamagedFile {
+        path: legacy_archive_object(uuid),
+        problem: ScrubProblem::Missing,
+    }));
+
+    let report = ScrubReport {
+        started,
+        finished: OffsetDateTime::now_utc().unix_timestamp(),
+        checked_blobs: blobs.len(),
+        checked_archives: legacy_archives.len(),
+        damaged_files,
+        corrupt_saves,
+        quarantined_files,
+    };
+    DATABASE.set_scrub_report(&report)?;
+
+    Ok(report)
+}
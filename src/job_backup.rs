@@ -0,0 +1,38 @@
+use crate::configuration::BACKUP_INTERVAL_DAYS_INFO;
+use crate::job_scheduler::Job;
+use crate::server_backup::{create_backup, list_backups};
+use async_trait::async_trait;
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Default)]
+pub struct BackupJob {}
+
+#[async_trait]
+impl Job for BackupJob {
+    fn name(&self) -> &'static str {
+        "Backup Job"
+    }
+
+    async fn execute(
+        &mut self,
+        _cancellation_token: CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let interval_days = BACKUP_INTERVAL_DAYS_INFO.get_number_in_db()?;
+        if interval_days == 0 {
+            return Ok(());
+        }
+        let due = OffsetDateTime::now_utc().unix_timestamp() - i64::from(interval_days) * 86400;
+        if let Some(latest) = list_backups().await?.first()
+            && latest.created > due
+        {
+            return Ok(());
+        }
+
+        match create_backup().await? {
+            Some(backup) => tracing::info!("Created backup {}", backup.file_name),
+            None => tracing::info!("Backup already running"),
+        }
+        Ok(())
+    }
+}
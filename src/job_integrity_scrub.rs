@@ -0,0 +1,32 @@
+use crate::integrity_scrub::scrub;
+use crate::job_scheduler::Job;
+use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Default)]
+pub struct IntegrityScrubJob {}
+
+#[async_trait]
+impl Job for IntegrityScrubJob {
+    fn name(&self) -> &'static str {
+        "Integrity Scrub Job"
+    }
+
+    async fn execute(
+        &mut self,
+        cancellation_token: CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match scrub(cancellation_token).await? {
+            Some(report) => tracing::info!(
+                "Checked {} blobs and {} archives, {} damaged files, {} corrupt saves, {} files quarantined",
+                report.checked_blobs,
+                report.checked_archives,
+                report.damaged_files.len(),
+                report.corrupt_saves.len(),
+                report.quarantined_files.len()
+            ),
+            None => tracing::info!("Integrity scrub already running"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+use crate::job_scheduler::Job;
+use crate::retention::prune_all_saves;
+use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Default)]
+pub struct RetentionJob {}
+
+#[async_trait]
+impl Job for RetentionJob {
+    fn name(&self) -> &'static str {
+        "Retention Job"
+    }
+
+    async fn execute(
+        &mut self,
+        _cancellation_token: CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let pruned = prune_all_saves().await?;
+        tracing::info!("Pruned {} game saves", pruned);
+        Ok(())
+    }
+}
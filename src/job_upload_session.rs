@@ -0,0 +1,52 @@
+use crate::DATABASE;
+use crate::blob_store::upload_session_path;
+use crate::const_var::{TMP_DIR, UPLOAD_SESSION_EXPIRATION_HOURS};
+use crate::job_scheduler::Job;
+use async_trait::async_trait;
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Default)]
+pub struct UploadSessionJob {}
+
+#[async_trait]
+impl Job for UploadSessionJob {
+    fn name(&self) -> &'static str {
+        "Upload Session Job"
+    }
+
+    async fn execute(
+        &mut self,
+        cancellation_token: CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let now = time::OffsetDateTime::now_utc();
+        let expiration = now - time::Duration::hours(UPLOAD_SESSION_EXPIRATION_HOURS);
+        let expired_sessions = DATABASE.remove_upload_sessions_inactive_since(
+            time::PrimitiveDateTime::new(expiration.date(), expiration.time()),
+        )?;
+        for uuid in &expired_sessions {
+            let _ = fs::remove_file(upload_session_path(uuid)).await;
+        }
+        tracing::info!("Expired {} upload sessions", expired_sessions.len());
+
+        // Files left behind by interrupted uploads or a crash
+        let max_age = Duration::from_secs(UPLOAD_SESSION_EXPIRATION_HOURS as u64 * 60 * 60);
+        let mut entries = fs::read_dir(TMP_DIR).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if cancellation_token.is_cancelled() {
+                break;
+            }
+            let metadata = entry.metadata().await?;
+            if metadata.is_file()
+                && SystemTime::now()
+                    .duration_since(metadata.modified()?)
+                    .is_ok_and(|age| age > max_age)
+            {
+                let _ = fs::remove_file(entry.path()).await;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,852 @@
+use crate::DATABASE;
+use crate::blob_store::{self, UploadedFile, blob_object, open_blob};
+use crate::const_var::TMP_DIR;
+use crate::database::interface::{SaveDigest, SaveFile, SaveOwner};
+use crate::datatype_endpoint::{
+    GameLock, HashAlgorithm, LudusaviImportReport, OS, SaveMetadata, SaveParent, SavePathCreate,
+    SkippedLudusaviBackup,
+};
+use crate::file_system::{is_safe_relative_path, portable_file_name};
+use crate::ludusavi_datatype::{BackupFile, BackupMapping, BackupRegistry, FullBackup};
+use crate::save_archive::{ArchiveEntry, EntryContent};
+use crate::save_compression::decoded_reader;
+use crate::save_encryption::blob_key;
+use crate::save_hash::{FileHasher, archive_digest};
+use crate::save_store::SAVE_STORE;
+use axum::body::Bytes;
+use chrono::{DateTime, NaiveDateTime, SecondsFormat};
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tar::Archive;
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+use uuid::Uuid;
+
+const MAPPING_FILE: &str = "mapping.yaml";
+const ROOT_DRIVE_FOLDER: &str = "drive-0";
+
+/// The files of a game at one point in time: a full backup, or a full backup with the changes of
+/// one of its differential backups
+struct BackupState {
+    name: String,
+    time: Option<i64>,
+    os: OS,
+    comment: Option<String>,
+    locked: bool,
+    /// Location in the backup directory and mapping entry of each file, by original path
+    files: BTreeMap<String, (PathBuf, BackupFile)>,
+}
+
+enum StateImport {
+    Imported(String),
+    AlreadyImported(String),
+    Skipped(String),
+}
+
+/// Date of a backup, from its mapping or else from its default name `backup-<date>`
+fn backup_time(name: &str, when: Option<&str>) -> Option<i64> {
+    if let Some(when) = when.and_then(|when| DateTime::parse_from_rfc3339(when).ok()) {
+        return Some(when.timestamp());
+    }
+    let stamp = name.strip_prefix("backup-")?;
+    let stamp = stamp.strip_suffix("-diff").unwrap_or(stamp);
+    NaiveDateTime::parse_from_str(stamp, "%Y%m%dT%H%M%SZ")
+        .ok()
+        .map(|time| time.and_utc().timestamp())
+}
+
+/// Splits an original path into its drive folder, drive and path on the drive, like Ludusavi
+/// lays out a backup: `C:/a/b` is stored as `drive-C/a/b` and `/a/b` as `drive-0/a/b`
+fn split_drive(original: &str) -> Option<(String, &str, &str)> {
+    let bytes = original.as_bytes();
+    if bytes.len() >= 2
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes.len() == 2 || bytes[2] == b'/')
+    {
+        let folder = format!("drive-{}", original[..1].to_ascii_uppercase());
+        return Some((
+            folder,
+            &original[..2],
+            original[2..].trim_start_matches('/'),
+        ));
+    }
+    original.strip_prefix('/').map(|rest| {
+        (
+            ROOT_DRIVE_FOLDER.to_string(),
+            "",
+            rest.trim_start_matches('/'),
+        )
+    })
+}
+
+/// Location of the file `original` in the backup `backup_name` of `game_dir`, using the drive
+/// folders of the mapping
+fn file_location(
+    game_dir: &Path,
+    backup_name: &str,
+    drives: &BTreeMap<String, String>,
+    original: &str,
+) -> Option<PathBuf> {
+    let (folder, rest) = match drives
+        .iter()
+        .filter(|(_, drive)| !drive.is_empty() && original.starts_with(drive.as_str()))
+        .max_by_key(|(_, drive)| drive.len())
+    {
+        Some((folder, drive)) => (folder.clone(), &original[drive.len()..]),
+        None => {
+            let (default_folder, _, rest) = split_drive(original)?;
+            let folder = drives
+                .iter()
+                .find(|(_, drive)| drive.is_empty())
+                .map(|(folder, _)| folder.clone())
+                .unwrap_or(default_folder);
+            (folder, rest)
+        }
+    };
+    let rest = rest.trim_start_matches('/');
+    if !is_safe_relative_path(backup_name)
+        || !is_safe_relative_path(&folder)
+        || !is_safe_relative_path(rest)
+    {
+        return None;
+    }
+    Some(game_dir.join(backup_name).join(folder).join(rest))
+}
+
+fn backup_os(os: Option<&str>, files: &BTreeMap<String, (PathBuf, BackupFile)>) -> OS {
+    match os {
+        Some("windows") => OS::Windows,
+        Some("linux") => OS::Linux,
+        Some(_) => OS::Undefined,
+        None => match files
+            .keys()
+            .next()
+            .and_then(|original| split_drive(original))
+        {
+            Some((folder, _, _)) if folder == ROOT_DRIVE_FOLDER => OS::Linux,
+            Some(_) => OS::Windows,
+            None => OS::Undefined,
+        },
+    }
+}
+
+/// Directory holding all the files of `originals`, it becomes the path of the saves
+fn common_root<'a>(originals: impl Iterator<Item = &'a str>) -> String {
+    let mut root: Option<Vec<&str>> = None;
+    for original in originals {
+        let mut directories: Vec<&str> = original.split('/').collect();
+        directories.pop();
+        root = Some(match root {
+            None => directories,
+            Some(root) => root
+                .into_iter()
+                .zip(directories)
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+    }
+    match root.unwrap_or_default().join("/") {
+        root if root.is_empty() => "/".to_string(),
+        root => root,
+    }
+}
+
+fn relative_path(root: &str, original: &str) -> String {
+    original
+        .strip_prefix(root)
+        .unwrap_or(original)
+        .trim_start_matches('/')
+        .to_string()
+}
+
+/// Every backup of a game as the full set of its files, in the order of the mapping
+fn backup_states(
+    game_dir: &Path,
+    mapping: &BackupMapping,
+    skipped: &mut Vec<(String, String)>,
+) -> Vec<BackupState> {
+    let mut states = Vec::new();
+    let locate = |backup_name: &str, original: &str| {
+        file_location(game_dir, backup_name, &mapping.drives, original)
+    };
+
+    for full in &mapping.backups {
+        if full.name.ends_with(".zip") {
+            skipped.push((
+                full.name.clone(),
+                "zip backups aren't supported".to_string(),
+            ));
+            for child in &full.children {
+                skipped.push((
+                    child.name.clone(),
+                    "zip backups aren't supported".to_string(),
+                ));
+            }
+            continue;
+        }
+
+        let mut files = BTreeMap::new();
+        for (original, file) in &full.files {
+            match locate(&full.name, original) {
+                Some(location) => {
+                    files.insert(original.clone(), (location, file.clone()));
+                }
+                None => {
+                    skipped.push((full.name.clone(), format!("invalid file path {}", original)))
+                }
+            }
+        }
+        states.push(BackupState {
+            name: full.name.clone(),
+            time: backup_time(&full.name, full.when.as_deref()),
+            os: backup_os(full.os.as_deref(), &files),
+            comment: full.comment.clone(),
+            locked: full.locked,
+            files: files.clone(),
+        });
+
+        for child in &full.children {
+            if child.name.ends_with(".zip") {
+                skipped.push((
+                    child.name.clone(),
+                    "zip backups aren't supported".to_string(),
+                ));
+                continue;
+            }
+            let mut child_files = files.clone();
+            for (original, file) in &child.files {
+                match (file, locate(&child.name, original)) {
+                    (Some(file), Some(location)) => {
+                        child_files.insert(original.clone(), (location, file.clone()));
+                    }
+                    (None, _) => {
+                        child_files.remove(original);
+                    }
+                    (Some(_), None) => skipped.push((
+                        child.name.clone(),
+                        format!("invalid file path {}", original),
+                    )),
+                }
+            }
+            states.push(BackupState {
+                name: child.name.clone(),
+                time: backup_time(&child.name, child.when.as_deref()),
+                os: backup_os(child.os.as_deref().or(full.os.as_deref()), &child_files),
+                comment: child.comment.clone(),
+                locked: child.locked,
+                files: child_files,
+            });
+        }
+    }
+    states
+}
+
+/// Folders holding a `mapping.yaml` under `root`, or `root` itself
+fn find_game_folders(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if root.join(MAPPING_FILE).is_file() {
+        return Ok(vec![root.to_path_buf()]);
+    }
+    let mut folders = Vec::new();
+    for entry in std::fs::read_dir(root)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            folders.extend(find_game_folders(&entry.path())?);
+        }
+    }
+    folders.sort();
+    Ok(folders)
+}
+
+/// Copies `source` to `destination`, returns its blob hash and SHA-1
+fn copy_and_hash(source: &Path, destination: &str) -> std::io::Result<(String, String, u64)> {
+    let mut reader = std::fs::File::open(source)?;
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(destination)?);
+    let mut hasher = FileHasher::new(HashAlgorithm::Sha256);
+    let mut sha1 = Sha1::new();
+    let mut size = 0;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        sha1.update(&buffer[..read]);
+        writer.write_all(&buffer[..read])?;
+        size += read as u64;
+    }
+    writer.flush()?;
+    let (blob_hash, _) = hasher.finalize();
+    Ok((blob_hash, hex::encode(sha1.finalize()), size))
+}
+
+/// Copies the files of `state` to the tmp directory, checking them against the mapping
+fn stage_files(
+    state: &BackupState,
+    root: &str,
+    uploaded_files: &mut Vec<UploadedFile>,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    for (original, (location, file)) in &state.files {
+        let tmp_path = format!("{}/{}", TMP_DIR, Uuid::new_v4());
+        let copy = copy_and_hash(location, &tmp_path);
+        let (blob_hash, sha1, size) = match copy {
+            Ok(copy) => copy,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let _ = std::fs::remove_file(&tmp_path);
+                return Ok(Some(format!("{} is missing from the backup", original)));
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&tmp_path);
+                return Err(e.into());
+            }
+        };
+        uploaded_files.push(UploadedFile {
+            relative_path: relative_path(root, original),
+            tmp_path,
+            hash: blob_hash.clone(),
+            blob_hash,
+            size: i64::try_from(size)?,
+        });
+        if size != file.size || !sha1.eq_ignore_ascii_case(&file.hash) {
+            return Ok(Some(format!("{} doesn't match the mapping", original)));
+        }
+    }
+    Ok(None)
+}
+
+async fn import_state(
+    path_id: i32,
+    user_id: i32,
+    parent_uuid: Option<String>,
+    state: BackupState,
+    root: String,
+) -> Result<StateImport, Box<dyn Error + Send + Sync>> {
+    let Some(time) = state.time else {
+        return Ok(StateImport::Skipped("the backup has no date".to_string()));
+    };
+
+    let (state, staged) = tokio::task::spawn_blocking(move || {
+        let mut uploaded_files = Vec::new();
+        let result = stage_files(&state, &root, &mut uploaded_files);
+        (state, result.map(|problem| (problem, uploaded_files)))
+    })
+    .await?;
+
+    let result = async {
+        let (problem, uploaded_files) = staged.as_ref().map_err(|e| e.to_string())?;
+        if let Some(problem) = problem {
+            return Ok(StateImport::Skipped(problem.clone()));
+        }
+
+        let digest = SaveDigest {
+            hash_algorithm: HashAlgorithm::Sha256,
+            archive_digest: archive_digest(HashAlgorithm::Sha256, uploaded_files),
+        };
+        let existing = DATABASE
+            .get_reference_to_save_by_path_id(path_id, user_id)?
+            .unwrap_or_default()
+            .into_iter()
+            .find(|save| {
+                save.time == time && save.archive_digest.as_ref() == Some(&digest.archive_digest)
+            });
+        if let Some(existing) = existing {
+            return Ok(StateImport::AlreadyImported(existing.uuid));
+        }
+
+        let uuid = Uuid::new_v4();
+        let metadata = SaveMetadata {
+            label: state.comment.clone(),
+            ..SaveMetadata::default()
+        };
+        let parent = SaveParent {
+            parent_uuid,
+            force: true,
+            lock_holder: None,
+        };
+        let added = blob_store::add_save(
+            uuid,
+            path_id,
+            &parent,
+            &metadata,
+            SaveOwner {
+                user_id,
+                device_id: None,
+            },
+            &digest,
+            None,
+            uploaded_files,
+        );
+        if let Err(e) = added.await {
+            return match e.downcast::<GameLock>() {
+                Ok(game_lock) => Ok(StateImport::Skipped(game_lock.to_string())),
+                Err(e) => Err(e),
+            };
+        }
+        let uuid = uuid.to_string();
+        DATABASE.set_save_time(&uuid, time)?;
+        if state.locked {
+            DATABASE.set_save_pinned(&uuid, user_id, true)?;
+        }
+        Ok::<_, Box<dyn Error + Send + Sync>>(StateImport::Imported(uuid))
+    }
+    .await;
+
+    // Blobs already in the store were moved out of the tmp dir
+    if let Ok((_, uploaded_files)) = &staged {
+        for uploaded_file in uploaded_files {
+            let _ = fs::remove_file(&uploaded_file.tmp_path).await;
+        }
+    }
+    result
+}
+
+/// Id of the path `root` of the game, added if it doesn't exist yet
+fn path_id(game_id: i32, root: &str, os: OS) -> Result<i32, Box<dyn Error + Send + Sync>> {
+    let find = || -> Result<Option<i32>, Box<dyn Error + Send + Sync>> {
+        Ok(DATABASE
+            .get_paths_by_game_id(game_id)?
+            .into_iter()
+            .find(|path| path.path.path == root && path.path.operating_system == os)
+            .and_then(|path| path.id))
+    };
+    if let Some(path_id) = find()? {
+        return Ok(path_id);
+    }
+    DATABASE.add_game_path(
+        game_id,
+        &SavePathCreate {
+            path: root.to_string(),
+            operating_system: os,
+        },
+    )?;
+    find()?.ok_or_else(|| "the path added can't be found".into())
+}
+
+/// Imports the Ludusavi backup directory `root`, or the folder of a single game, as saves of
+/// `user_id` for the games matching their name. Each full and differential backup becomes a save dated like the
+/// backup, in the path of the directory holding all the files of the game, which is added if
+/// needed. Backups already imported are recognized by their date and content.
+pub async fn import_backup_directory(
+    root: &Path,
+    user_id: i32,
+) -> Result<LudusaviImportReport, Box<dyn Error + Send + Sync>> {
+    let mut report = LudusaviImportReport::default();
+    let root = root.to_path_buf();
+    let game_dirs = tokio::task::spawn_blocking(move || find_game_folders(&root)).await??;
+
+    for game_dir in game_dirs {
+        let mapping: BackupMapping =
+            match serde_yaml::from_str(&fs::read_to_string(game_dir.join(MAPPING_FILE)).await?) {
+                Ok(mapping) => mapping,
+                Err(e) => {
+                    report.skipped_backups.push(SkippedLudusaviBackup {
+                        game_name: game_dir.to_string_lossy().to_string(),
+                        backup_name: MAPPING_FILE.to_string(),
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+        let Some(game_id) = DATABASE.get_game_id_by_name(&mapping.name)? else {
+            report.unmatched_games.push(mapping.name);
+            continue;
+        };
+
+        let mut skipped = Vec::new();
+        let states = backup_states(&game_dir, &mapping, &mut skipped);
+        let mut roots: HashMap<OS, String> = HashMap::new();
+        for os in states.iter().map(|state| state.os).collect::<HashSet<_>>() {
+            let originals = states
+                .iter()
+                .filter(|state| state.os == os)
+                .flat_map(|state| state.files.keys().map(String::as_str));
+            roots.insert(os, common_root(originals));
+        }
+
+        let mut parents: HashMap<i32, String> = HashMap::new();
+        for state in states {
+            if state.files.is_empty() {
+                skipped.push((state.name, "the backup has no file".to_string()));
+                continue;
+            }
+            let root = roots[&state.os].clone();
+            let path_id = path_id(game_id, &root, state.os)?;
+            let name = state.name.clone();
+            match import_state(
+                path_id,
+                user_id,
+                parents.get(&path_id).cloned(),
+                state,
+                root,
+            )
+            .await?
+            {
+                StateImport::Imported(uuid) => {
+                    report.imported_saves.push(uuid.clone());
+                    parents.insert(path_id, uuid);
+                }
+                StateImport::AlreadyImported(uuid) => {
+                    report.already_imported += 1;
+                    parents.insert(path_id, uuid);
+                }
+                StateImport::Skipped(reason) => skipped.push((name, reason)),
+            }
+        }
+        report
+            .skipped_backups
+            .extend(
+                skipped
+                    .into_iter()
+                    .map(|(backup_name, reason)| SkippedLudusaviBackup {
+                        game_name: mapping.name.clone(),
+                        backup_name,
+                        reason,
+                    }),
+            );
+    }
+    Ok(report)
+}
+
+/// Extracts the tar `archive` of a Ludusavi backup directory to `destination`, entries leaving
+/// it are ignored
+pub async fn unpack_backup_archive(
+    archive: PathBuf,
+    destination: PathBuf,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    tokio::task::spawn_blocking(move || {
+        std::fs::create_dir_all(&destination)?;
+        Archive::new(std::fs::File::open(archive)?).unpack(&destination)?;
+        Ok(())
+    })
+    .await?
+}
+
+/// SHA-1 of the content of a file, as listed in a Ludusavi mapping
+async fn content_sha1(file: &SaveFile) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let blob_key = blob_key(file.encryption.as_ref(), &file.blob_hash)?;
+    let mut reader = decoded_reader(
+        open_blob(SAVE_STORE.as_ref(), &blob_object(&file.blob_hash), blob_key).await?,
+        file.codec,
+    );
+    let mut hasher = Sha1::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn backup_name(time: i64, used_names: &mut HashSet<String>) -> String {
+    let stamp = DateTime::from_timestamp(time, 0)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ");
+    let mut name = format!("backup-{}", stamp);
+    let mut count = 1;
+    while used_names.contains(&name) {
+        count += 1;
+        name = format!("backup-{}-{}", stamp, count);
+    }
+    used_names.insert(name.clone());
+    name
+}
+
+/// Tar entries of a Ludusavi backup directory holding the saves of `user_id` of every path, or only
+/// the latest one when `latest`: one folder per game with its `mapping.yaml`, each save being a full
+/// backup. Paths that aren't absolute, like the ones of the manifest with placeholders, and
+/// legacy archives can't be restored by Ludusavi and are left out.
+pub async fn export_backup_entries(
+    latest: bool,
+    user_id: i32,
+) -> Result<Vec<ArchiveEntry>, Box<dyn Error + Send + Sync>> {
+    let mut games = DATABASE.get_games_metadata_and_paths_if_saves_exist(user_id)?;
+    games.sort_by_key(|game| game.game_metadata.id);
+
+    let mut entries = Vec::new();
+    let mut used_folders = HashSet::new();
+    let mut sha1_cache: HashMap<String, String> = HashMap::new();
+    for game in games {
+        let game_name = game.game_metadata.metadata.default_name;
+        let mut folder = portable_file_name(&game_name);
+        if !used_folders.insert(folder.clone()) {
+            folder = format!("{} ({})", folder, game.game_metadata.id.unwrap_or_default());
+            used_folders.insert(folder.clone());
+        }
+        let mut mapping = BackupMapping {
+            name: game_name,
+            ..BackupMapping::default()
+        };
+        let mut game_entries = Vec::new();
+        let mut used_names = HashSet::new();
+
+        let mut paths = game.paths;
+        paths.sort_by_key(|path| path.id);
+        for path in paths {
+            let root = path.path.path.replace('\\', "/");
+            let root = root.trim_end_matches('/');
+            if split_drive(&format!("{}/", root)).is_none() {
+                tracing::info!("Path {} isn't absolute, its saves aren't exported", root);
+                continue;
+            }
+            let mut saves = DATABASE
+                .get_reference_to_save_by_path_id(path.id.unwrap_or_default(), user_id)?
+                .unwrap_or_default();
+            if latest {
+                saves = saves.split_off(saves.len().saturating_sub(1));
+            }
+
+            for save in saves {
+                let Some(manifest) = DATABASE.get_save_manifest(&save.uuid, user_id)? else {
+                    continue;
+                };
+                if manifest.legacy_archive {
+                    continue;
+                }
+                let name = backup_name(save.time, &mut used_names);
+                let mtime = u64::try_from(save.time).unwrap_or_default();
+                let mut files = BTreeMap::new();
+                for file in manifest.files {
+                    let original = format!("{}/{}", root, file.relative_path);
+                    let Some((drive_folder, drive, rest)) = split_drive(&original) else {
+                        continue;
+                    };
+                    mapping
+                        .drives
+                        .insert(drive_folder.clone(), drive.to_string());
+                    let sha1 = match sha1_cache.get(&file.blob_hash) {
+                        Some(sha1) => sha1.clone(),
+                        None => {
+                            let sha1 = content_sha1(&file).await?;
+                            sha1_cache.insert(file.blob_hash.clone(), sha1.clone());
+                            sha1
+                        }
+                    };
+                    let size = u64::try_from(file.size).unwrap_or_default();
+                    game_entries.push(ArchiveEntry {
+                        path: format!("{}/{}/{}/{}", folder, name, drive_folder, rest),
+                        size,
+                        mtime,
+                        content: EntryContent::Blob {
+                            object: blob_object(&file.blob_hash),
+                            codec: file.codec,
+                            stored_size: u64::try_from(file.stored_size).unwrap_or_default(),
+                            blob_key: blob_key(file.encryption.as_ref(), &file.blob_hash)?,
+                        },
+                    });
+                    files.insert(original, BackupFile { hash: sha1, size });
+                }
+                mapping.backups.push(FullBackup {
+                    name,
+                    when: DateTime::from_timestamp(save.time, 0)
+                        .map(|when| when.to_rfc3339_opts(SecondsFormat::Secs, true)),
+                    os: match path.path.operating_system {
+                        OS::Windows => Some("windows".to_string()),
+                        OS::Linux => Some("linux".to_string()),
+                        OS::Undefined => None,
+                    },
+                    comment: save.metadata.label,
+                    locked: save.pinned,
+                    files,
+                    registry: BackupRegistry::default(),
+                    children: Vec::new(),
+                });
+            }
+        }
+
+        if mapping.backups.is_empty() {
+            continue;
+        }
+        let mapping_yaml = serde_yaml::to_string(&mapping)?;
+        let mtime = mapping
+            .backups
+            .iter()
+            .filter_map(|backup| backup_time(&backup.name, backup.when.as_deref()))
+            .max()
+            .unwrap_or_default();
+        entries.push(ArchiveEntry {
+            path: format!("{}/{}", folder, MAPPING_FILE),
+            size: mapping_yaml.len() as u64,
+            mtime: u64::try_from(mtime).unwrap_or_default(),
+            content: EntryContent::Bytes(Bytes::from(mapping_yaml)),
+        });
+        entries.extend(game_entries);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_time() {
+        assert_eq!(
+            backup_time("anything", Some("2024-01-02T03:04:05Z")),
+            Some(1704164645)
+        );
+        assert_eq!(
+            backup_time("anything", Some("2024-01-02T03:04:05.123456789Z")),
+            Some(1704164645)
+        );
+        assert_eq!(
+            backup_time("backup-20240102T030405Z", None),
+            Some(1704164645)
+        );
+        assert_eq!(
+            backup_time("backup-20240102T030405Z-diff", None),
+            Some(1704164645)
+        );
+        assert_eq!(backup_time("backup", None), None);
+    }
+
+    #[test]
+    fn test_split_drive() {
+        assert_eq!(
+            split_drive("C:/Users/me/save.dat"),
+            Some(("drive-C".to_string(), "C:", "Users/me/save.dat"))
+        );
+        assert_eq!(
+            split_drive("d:/save.dat"),
+            Some(("drive-D".to_string(), "d:", "save.dat"))
+        );
+        assert_eq!(
+            split_drive("/home/me/save.dat"),
+            Some(("drive-0".to_string(), "", "home/me/save.dat"))
+        );
+        assert_eq!(split_drive("<home>/save.dat"), None);
+        assert_eq!(split_drive("C:save.dat"), None);
+    }
+
+    #[test]
+    fn test_file_location() {
+        let drives = BTreeMap::from([
+            ("drive-0".to_string(), "".to_string()),
+            ("drive-C".to_string(), "C:".to_string()),
+            ("drive-1".to_string(), "//server/share".to_string()),
+        ]);
+        let game_dir = Path::new("/backups/Game");
+        assert_eq!(
+            file_location(game_dir, "backup-1", &drives, "C:/Users/me/save.dat"),
+            Some(PathBuf::from(
+                "/backups/Game/backup-1/drive-C/Users/me/save.dat"
+            ))
+        );
+        assert_eq!(
+            file_location(game_dir, "backup-1", &drives, "/home/me/save.dat"),
+            Some(PathBuf::from(
+                "/backups/Game/backup-1/drive-0/home/me/save.dat"
+            ))
+        );
+        assert_eq!(
+            file_location(game_dir, "backup-1", &drives, "//server/share/save.dat"),
+            Some(PathBuf::from("/backups/Game/backup-1/drive-1/save.dat"))
+        );
+        assert_eq!(
+            file_location(game_dir, "backup-1", &drives, "/home/../../etc/passwd"),
+            None
+        );
+        assert_eq!(
+            file_location(game_dir, "../backup-1", &drives, "/home/me/save.dat"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_common_root() {
+        assert_eq!(
+            common_root(["C:/Users/me/Game/a.sav", "C:/Users/me/Game/slots/b.sav"].into_iter()),
+            "C:/Users/me/Game"
+        );
+        assert_eq!(common_root(["/home/me/a.sav"].into_iter()), "/home/me");
+        assert_eq!(common_root(["/a.sav", "/home/b.sav"].into_iter()), "/");
+        assert_eq!(relative_path("/", "/home/b.sav"), "home/b.sav");
+        assert_eq!(
+            relative_path("C:/Users/me/Game", "C:/Users/me/Game/slots/b.sav"),
+            "slots/b.sav"
+        );
+    }
+
+    #[test]
+    fn test_backup_states() {
+        let mapping: BackupMapping = serde_yaml::from_str(
+            r#"
+name: Game
+drives:
+  drive-C: "C:"
+backups:
+  - name: backup-20240101T000000Z
+    when: "2024-01-01T00:00:00Z"
+    os: windows
+    comment: first
+    locked: true
+    files:
+      "C:/Game/a.sav":
+        hash: aaa
+        size: 1
+      "C:/Game/b.sav":
+        hash: bbb
+        size: 2
+    registry:
+      hash: ~
+    children:
+      - name: backup-20240102T000000Z-diff
+        when: "2024-01-02T00:00:00Z"
+        os: windows
+        comment: ~
+        locked: false
+        files:
+          "C:/Game/a.sav":
+            hash: aaa2
+            size: 3
+          "C:/Game/b.sav": ~
+          "C:/Game/c.sav":
+            hash: ccc
+            size: 4
+        registry: ~
+  - name: backup-20240103T000000Z.zip
+    when: "2024-01-03T00:00:00Z"
+    files: {}
+"#,
+        )
+        .unwrap();
+
+        let mut skipped = Vec::new();
+        let states = backup_states(Path::new("/b/Game"), &mapping, &mut skipped);
+        assert_eq!(states.len(), 2);
+        assert_eq!(skipped.len(), 1);
+
+        let full = &states[0];
+        assert_eq!(full.time, Some(1704067200));
+        assert_eq!(full.os, OS::Windows);
+        assert!(full.locked);
+        assert_eq!(full.comment.as_deref(), Some("first"));
+        assert_eq!(
+            full.files.keys().collect::<Vec<_>>(),
+            vec!["C:/Game/a.sav", "C:/Game/b.sav"]
+        );
+
+        // The differential backup changes a.sav, removes b.sav and adds c.sav
+        let diff = &states[1];
+        assert_eq!(diff.time, Some(1704153600));
+        assert_eq!(
+            diff.files.keys().collect::<Vec<_>>(),
+            vec!["C:/Game/a.sav", "C:/Game/c.sav"]
+        );
+        assert_eq!(
+            diff.files["C:/Game/a.sav"].0,
+            PathBuf::from("/b/Game/backup-20240102T000000Z-diff/drive-C/Game/a.sav")
+        );
+        assert_eq!(
+            diff.files["C:/Game/c.sav"].0,
+            PathBuf::from("/b/Game/backup-20240102T000000Z-diff/drive-C/Game/c.sav")
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 // Top-level schema: an object with additional Properties being a "Game" entry.
 pub type GameIndex = HashMap<String, Game>;
@@ -125,3 +125,58 @@ pub enum Tag {
     Config,
     Save,
 }
+
+/// `mapping.yaml` of the folder of a game in a Ludusavi backup directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupMapping {
+    pub name: String,
+    /// Folder of each drive in a backup, like `drive-C: "C:"`, `drive-0` being the root
+    pub drives: BTreeMap<String, String>,
+    pub backups: Vec<FullBackup>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FullBackup {
+    /// Folder of the backup, or zip file when it ends with `.zip`
+    pub name: String,
+    /// RFC 3339 date
+    pub when: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    pub comment: Option<String>,
+    pub locked: bool,
+    /// Files by original path, like `C:/Users/me/save.dat`
+    pub files: BTreeMap<String, BackupFile>,
+    pub registry: BackupRegistry,
+    pub children: Vec<DifferentialBackup>,
+}
+
+/// Changes since the full backup it belongs to
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DifferentialBackup {
+    pub name: String,
+    pub when: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    pub comment: Option<String>,
+    pub locked: bool,
+    /// Files added or modified, `None` for the files removed
+    pub files: BTreeMap<String, Option<BackupFile>>,
+    pub registry: Option<BackupRegistry>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BackupFile {
+    /// SHA-1 of the content
+    pub hash: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupRegistry {
+    pub hash: Option<String>,
+}
@@ -1,70 +1,139 @@
 mod auth;
+mod blob_store;
+mod cli;
 mod configuration;
 mod const_var;
 mod database;
 mod datatype_endpoint;
+mod device_pairing;
 mod file_system;
+mod integrity_scrub;
+mod job_backup;
+mod job_integrity_scrub;
 mod job_ludusavi;
+mod job_retention;
 mod job_scheduler;
+mod job_upload_session;
 mod ludusavi;
+mod ludusavi_backup;
 mod ludusavi_datatype;
 mod openapi;
+mod retention;
+mod route_backups;
 mod route_configuration;
+mod route_devices;
 mod route_executables;
+mod route_game_locks;
 mod route_games;
+mod route_hash_algorithms;
 mod route_health;
+mod route_ludusavi_backup;
+mod route_pairing;
 mod route_paths;
 mod route_registry_paths;
 mod route_saves;
+mod route_scrub;
+mod route_tokens;
+mod route_upload_sessions;
+mod route_users;
 mod route_uuid;
 mod route_web_configuration;
 mod route_web_dashboard;
+mod route_web_devices;
 mod route_web_login;
+mod route_web_save_diff;
+mod route_web_tokens;
+mod route_web_users;
 mod route_yaml_import;
+mod save_archive;
+mod save_compression;
+mod save_diff;
+mod save_download;
+mod save_encryption;
+mod save_hash;
+mod save_store;
+mod save_zip;
+mod server_backup;
+#[cfg(test)]
+mod test_util;
 
-use crate::auth::{bearer_cookie_auth_no_redirect, bearer_cookie_auth_redirect};
-use crate::const_var::{DATA_DIR, LOGIN_PATH, MAX_BODY_SIZE, ROOT_API_PATH};
+use crate::auth::{bearer_cookie_auth_no_redirect, bearer_cookie_auth_redirect, require_scope};
+use crate::const_var::{DATABASE_PATH, LOGIN_PATH, MAX_BODY_SIZE, ROOT_API_PATH};
 use crate::database::interface::GameDatabase;
+use crate::datatype_endpoint::TokenScope;
 use crate::file_system::create_fs_structure;
+use crate::job_backup::BackupJob;
+use crate::job_integrity_scrub::IntegrityScrubJob;
 use crate::job_ludusavi::LudusaviJob;
+use crate::job_retention::RetentionJob;
 use crate::job_scheduler::JobScheduler;
+use crate::job_upload_session::UploadSessionJob;
 use crate::openapi::ApiDoc;
+use crate::route_backups::{get_backup, get_backups, post_backup};
 use crate::route_configuration::{get_configuration, put_configuration};
+use crate::route_devices::{
+    delete_device, get_device, get_device_sync, get_devices, post_device, put_device_sync,
+};
 use crate::route_executables::{
-    get_game_executables, get_game_executables_by_os, post_game_executable,
+    delete_game_executable, get_game_executables, get_game_executables_by_os,
+    patch_game_executable, post_game_executable, put_game_executable,
 };
+use crate::route_game_locks::{delete_game_lock, get_game_lock, get_game_locks, put_game_lock};
 use crate::route_games::{
-    get_game_metadata, get_games_default_name, get_games_metadata,
-    get_games_metadata_with_paths_if_saves_exists, get_games_search, post_game_metadata,
+    delete_game_metadata, get_game_metadata, get_games_default_name, get_games_metadata,
+    get_games_metadata_with_paths_if_saves_exists, get_games_search, patch_game_metadata,
+    post_game_metadata, put_game_metadata,
 };
+use crate::route_hash_algorithms::get_hash_algorithms;
 use crate::route_health::get_health;
-use crate::route_paths::{get_game_paths, get_game_paths_by_os, post_game_path};
-use crate::route_registry_paths::{get_game_registries, post_game_registry};
+use crate::route_ludusavi_backup::{get_ludusavi_backup, post_ludusavi_backup};
+use crate::route_pairing::{post_pairing, post_pairing_code};
+use crate::route_paths::{
+    delete_game_path, get_game_paths, get_game_paths_by_os, patch_game_path, post_game_path,
+    put_game_path,
+};
+use crate::route_registry_paths::{
+    delete_game_registries, get_game_registries, patch_game_registries, post_game_registry,
+    put_game_registries,
+};
 use crate::route_saves::{
-    get_game_save_by_uuid, get_game_saves_reference_by_path_id, post_game_save_by_path_id,
+    delete_game_save_pin, get_game_save_by_uuid, get_game_save_diff, get_game_save_file,
+    get_game_save_files, get_game_saves_reference_by_path_id, get_save_bundle,
+    patch_game_save_by_uuid, post_game_save_by_path_id, post_game_save_restore, put_game_save_pin,
+};
+use crate::route_scrub::{get_scrub_report, post_scrub};
+use crate::route_tokens::{delete_token, get_token, get_tokens, post_token, put_token};
+use crate::route_upload_sessions::{
+    delete_upload_session, get_upload_session, post_upload_session, post_upload_session_finalize,
+    put_upload_chunk,
 };
+use crate::route_users::{delete_user, get_user, get_users, post_user, put_user};
 use crate::route_uuid::get_db_uuid;
 use crate::route_web_configuration::configuration_handler;
 use crate::route_web_dashboard::dashboard_handler;
+use crate::route_web_devices::devices_handler;
 use crate::route_web_login::{get_login, post_login};
+use crate::route_web_save_diff::save_diff_handler;
+use crate::route_web_tokens::tokens_handler;
+use crate::route_web_users::users_handler;
 use crate::route_yaml_import::post_ludusavi_yaml;
+use crate::save_encryption::MASTER_KEY;
+use crate::save_store::SAVE_STORE;
 use axum::extract::DefaultBodyLimit;
-use axum::{Router, routing::get, routing::post};
-use const_format::concatcp;
+use axum::{Router, routing::get, routing::post, routing::put};
 use once_cell::sync::Lazy;
+use std::net::SocketAddr;
+use std::process::ExitCode;
 use tower_http::{
     services::ServeDir, trace::TraceLayer, validate_request::ValidateRequestHeaderLayer,
 };
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-pub static DATABASE: Lazy<GameDatabase> = Lazy::new(|| {
-    let db_path = concatcp!(DATA_DIR, "/database.sqlite");
-    GameDatabase::new(db_path)
-});
+pub static DATABASE: Lazy<GameDatabase> = Lazy::new(|| GameDatabase::new(DATABASE_PATH));
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::try_from_default_env()
@@ -87,19 +156,35 @@ async fn main() {
     }));
 
     create_fs_structure().await.unwrap();
+    Lazy::force(&MASTER_KEY);
+    Lazy::force(&SAVE_STORE);
+
+    // Commands open the database when they need it, restoring a backup replaces it
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&args).await;
+    }
     Lazy::force(&DATABASE);
 
     let mut job_scheduler = JobScheduler::new();
     job_scheduler
         .add_job(LudusaviJob::default(), chrono::Duration::hours(1))
         .await;
+    job_scheduler
+        .add_job(UploadSessionJob::default(), chrono::Duration::hours(1))
+        .await;
+    job_scheduler
+        .add_job(RetentionJob::default(), chrono::Duration::hours(1))
+        .await;
+    job_scheduler
+        .add_job(IntegrityScrubJob::default(), chrono::Duration::days(1))
+        .await;
+    job_scheduler
+        .add_job(BackupJob::default(), chrono::Duration::hours(1))
+        .await;
     job_scheduler.start_scheduler();
 
-    let api_router = Router::new()
-        .route(
-            "/configuration/{configuration}",
-            get(get_configuration).put(put_configuration),
-        )
+    let catalog_router = Router::new()
         .route("/games", get(get_games_metadata).post(post_game_metadata))
         .route(
             "/games/paths/saves",
@@ -107,7 +192,13 @@ async fn main() {
         )
         .route("/games/default_name", get(get_games_default_name))
         .route("/games/search", get(get_games_search))
-        .route("/games/{Id}", get(get_game_metadata))
+        .route(
+            "/games/{Id}",
+            get(get_game_metadata)
+                .put(put_game_metadata)
+                .patch(patch_game_metadata)
+                .delete(delete_game_metadata),
+        )
         .route(
             "/games/{Id}/executables",
             get(get_game_executables).post(post_game_executable),
@@ -116,16 +207,58 @@ async fn main() {
             "/games/{Id}/executables/{OS}",
             get(get_game_executables_by_os),
         )
+        .route(
+            "/games/{Id}/executables/id/{ExecutableId}",
+            put(put_game_executable)
+                .patch(patch_game_executable)
+                .delete(delete_game_executable),
+        )
         .route(
             "/games/{Id}/paths",
             get(get_game_paths).post(post_game_path),
         )
         .route("/games/{Id}/paths/{OS}", get(get_game_paths_by_os))
+        .route(
+            "/games/{Id}/paths/id/{PathId}",
+            put(put_game_path)
+                .patch(patch_game_path)
+                .delete(delete_game_path),
+        )
         .route(
             "/games/{Id}/registry",
-            get(get_game_registries).post(post_game_registry),
+            get(get_game_registries)
+                .post(post_game_registry)
+                .put(put_game_registries)
+                .patch(patch_game_registries)
+                .delete(delete_game_registries),
         )
+        .route("/hash_algorithms", get(get_hash_algorithms))
         .route("/health", get(get_health))
+        .route("/uuid", get(get_db_uuid))
+        .route(
+            "/yaml/ludusavi",
+            post(post_ludusavi_yaml).route_layer(DefaultBodyLimit::max(MAX_BODY_SIZE)),
+        )
+        .layer(ValidateRequestHeaderLayer::custom(require_scope(
+            TokenScope::ReadOnly,
+            TokenScope::CatalogWrite,
+        )));
+
+    let save_router = Router::new()
+        .route("/devices/{Id}/sync", get(get_device_sync))
+        .route("/devices/{Id}/sync/{PathId}", put(put_device_sync))
+        .route(
+            "/games/{Id}/lock",
+            get(get_game_lock)
+                .put(put_game_lock)
+                .delete(delete_game_lock),
+        )
+        .route("/locks", get(get_game_locks))
+        .route("/pairing_codes", post(post_pairing_code))
+        .route(
+            "/ludusavi/backups",
+            get(get_ludusavi_backup).post(post_ludusavi_backup),
+        )
         .route(
             "/paths/{Id}/saves",
             get(get_game_saves_reference_by_path_id),
@@ -134,22 +267,92 @@ async fn main() {
             "/paths/{Id}/saves/upload",
             post(post_game_save_by_path_id).route_layer(DefaultBodyLimit::max(MAX_BODY_SIZE)),
         )
-        .route("/saves/{Uuid}", get(get_game_save_by_uuid))
-        .route("/uuid", get(get_db_uuid))
+        .route("/paths/{Id}/saves/uploads", post(post_upload_session))
+        .route("/saves/bundle", get(get_save_bundle))
         .route(
-            "/yaml/ludusavi",
-            post(post_ludusavi_yaml).route_layer(DefaultBodyLimit::max(MAX_BODY_SIZE)),
+            "/saves/{Uuid}",
+            get(get_game_save_by_uuid).patch(patch_game_save_by_uuid),
+        )
+        .route("/saves/{Uuid}/diff/{Other}", get(get_game_save_diff))
+        .route("/saves/{Uuid}/files", get(get_game_save_files))
+        .route(
+            "/saves/{Uuid}/files/{*RelativePath}",
+            get(get_game_save_file),
+        )
+        .route("/saves/{Uuid}/restore", post(post_game_save_restore))
+        .route(
+            "/saves/{Uuid}/pin",
+            put(put_game_save_pin).delete(delete_game_save_pin),
+        )
+        .route(
+            "/uploads/{Uuid}",
+            get(get_upload_session).delete(delete_upload_session),
+        )
+        .route(
+            "/uploads/{Uuid}/chunks/{Number}",
+            put(put_upload_chunk).route_layer(DefaultBodyLimit::max(MAX_BODY_SIZE)),
         )
+        .route(
+            "/uploads/{Uuid}/finalize",
+            post(post_upload_session_finalize),
+        )
+        .layer(ValidateRequestHeaderLayer::custom(require_scope(
+            TokenScope::ReadOnly,
+            TokenScope::Upload,
+        )));
+
+    let admin_router = Router::new()
+        .route("/backups", get(get_backups).post(post_backup))
+        .route("/backups/{Name}", get(get_backup))
+        .route(
+            "/configuration/{configuration}",
+            get(get_configuration).put(put_configuration),
+        )
+        .route("/devices", get(get_devices).post(post_device))
+        .route("/devices/{Id}", get(get_device).delete(delete_device))
+        .route("/scrub", get(get_scrub_report).post(post_scrub))
+        .route("/tokens", get(get_tokens).post(post_token))
+        .route(
+            "/tokens/{Id}",
+            get(get_token).put(put_token).delete(delete_token),
+        )
+        .route("/users", get(get_users).post(post_user))
+        .route(
+            "/users/{Id}",
+            get(get_user).put(put_user).delete(delete_user),
+        )
+        .layer(ValidateRequestHeaderLayer::custom(require_scope(
+            TokenScope::Admin,
+            TokenScope::Admin,
+        )));
+
+    // Scopes are checked per group, after the token itself
+    let api_router = Router::new()
+        .merge(catalog_router)
+        .merge(save_router)
+        .merge(admin_router)
         .layer(ValidateRequestHeaderLayer::custom(
             bearer_cookie_auth_no_redirect,
-        ));
+        ))
+        // A new client has no token yet, the single-use code is its credential
+        .route("/pairing", post(post_pairing));
 
     let swagger_router =
         SwaggerUi::new("/swagger-ui").url("/api-doc/openapi.json", ApiDoc::openapi());
 
+    let admin_pages = Router::new()
+        .route("/configuration", get(configuration_handler))
+        .route("/devices", get(devices_handler))
+        .route("/tokens", get(tokens_handler))
+        .route("/users", get(users_handler))
+        .layer(ValidateRequestHeaderLayer::custom(require_scope(
+            TokenScope::Admin,
+            TokenScope::Admin,
+        )));
     let protected_router = Router::new()
         .route("/", get(dashboard_handler))
-        .route("/configuration", get(configuration_handler));
+        .route("/saves/{Uuid}/diff/{Other}", get(save_diff_handler))
+        .merge(admin_pages);
     let login_router = Router::new().route(LOGIN_PATH, get(get_login).post(post_login));
     let web_router = Router::new()
         .merge(login_router)
@@ -173,5 +376,11 @@ async fn main() {
 
     tracing::info!("Server Starting");
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
+    ExitCode::SUCCESS
 }
@@ -1,24 +1,62 @@
 use crate::datatype_endpoint::{
-    Executable, ExecutableCreate, FileHash, GameMetadata, GameMetadataCreate, OS, SavePath,
-    SavePathCreate, SaveReference, UploadedFileYaml, UploadedSave,
+    ApiToken, ApiTokenCreate, ApiTokenCreated, BackupInfo, BundleFormat, ByteRange, DamagedFile,
+    Device, DeviceCreate, DeviceRegistration, DeviceSyncState, DeviceSyncUpdate, DownloadNotFound,
+    Executable, ExecutableCreate, FileChange, FileHash, FileHashMismatch, GameLock,
+    GameLockAcquire, GameMetadata, GameMetadataCreate, GameRegistryUpdate, HashAlgorithm,
+    HashMismatch, LudusaviImportReport, MissingDownload, OS, PairingCode, PairingExchange,
+    SaveConflict, SaveDiff, SaveMetadata, SaveMetadataUpdate, SavePath, SavePathCreate,
+    SaveReference, ScrubProblem, ScrubReport, SkippedLudusaviBackup, TokenScope, UploadSession,
+    UploadSessionCreate, UploadedFileYaml, UploadedLudusaviBackup, UploadedSave, User, UserCreate,
+    UserRegistration,
 };
+use crate::route_backups::{__path_get_backup, __path_get_backups, __path_post_backup};
 use crate::route_configuration::{__path_get_configuration, __path_put_configuration};
+use crate::route_devices::{
+    __path_delete_device, __path_get_device, __path_get_device_sync, __path_get_devices,
+    __path_post_device, __path_put_device_sync,
+};
 use crate::route_executables::{
-    __path_get_game_executables, __path_get_game_executables_by_os, __path_post_game_executable,
+    __path_delete_game_executable, __path_get_game_executables, __path_get_game_executables_by_os,
+    __path_patch_game_executable, __path_post_game_executable, __path_put_game_executable,
+};
+use crate::route_game_locks::{
+    __path_delete_game_lock, __path_get_game_lock, __path_get_game_locks, __path_put_game_lock,
 };
 use crate::route_games::{
-    __path_get_game_metadata, __path_get_games_default_name, __path_get_games_metadata,
-    __path_get_games_metadata_with_paths_if_saves_exists, __path_get_games_search,
-    __path_post_game_metadata,
+    __path_delete_game_metadata, __path_get_game_metadata, __path_get_games_default_name,
+    __path_get_games_metadata, __path_get_games_metadata_with_paths_if_saves_exists,
+    __path_get_games_search, __path_patch_game_metadata, __path_post_game_metadata,
+    __path_put_game_metadata,
 };
+use crate::route_hash_algorithms::__path_get_hash_algorithms;
 use crate::route_health::__path_get_health;
+use crate::route_ludusavi_backup::{__path_get_ludusavi_backup, __path_post_ludusavi_backup};
+use crate::route_pairing::{__path_post_pairing, __path_post_pairing_code};
 use crate::route_paths::{
-    __path_get_game_paths, __path_get_game_paths_by_os, __path_post_game_path,
+    __path_delete_game_path, __path_get_game_paths, __path_get_game_paths_by_os,
+    __path_patch_game_path, __path_post_game_path, __path_put_game_path,
+};
+use crate::route_registry_paths::{
+    __path_delete_game_registries, __path_get_game_registries, __path_patch_game_registries,
+    __path_post_game_registry, __path_put_game_registries,
 };
-use crate::route_registry_paths::{__path_get_game_registries, __path_post_game_registry};
 use crate::route_saves::{
-    __path_get_game_save_by_uuid, __path_get_game_saves_reference_by_path_id,
-    __path_post_game_save_by_path_id,
+    __path_delete_game_save_pin, __path_get_game_save_by_uuid, __path_get_game_save_diff,
+    __path_get_game_save_file, __path_get_game_save_files,
+    __path_get_game_saves_reference_by_path_id, __path_get_save_bundle,
+    __path_patch_game_save_by_uuid, __path_post_game_save_by_path_id,
+    __path_post_game_save_restore, __path_put_game_save_pin,
+};
+use crate::route_scrub::{__path_get_scrub_report, __path_post_scrub};
+use crate::route_tokens::{
+    __path_delete_token, __path_get_token, __path_get_tokens, __path_post_token, __path_put_token,
+};
+use crate::route_upload_sessions::{
+    __path_delete_upload_session, __path_get_upload_session, __path_post_upload_session,
+    __path_post_upload_session_finalize, __path_put_upload_chunk,
+};
+use crate::route_users::{
+    __path_delete_user, __path_get_user, __path_get_users, __path_post_user, __path_put_user,
 };
 use crate::route_uuid::__path_get_db_uuid;
 use crate::route_yaml_import::__path_post_ludusavi_yaml;
@@ -30,28 +68,83 @@ use utoipa::{
 #[derive(OpenApi)]
 #[openapi(
     paths(
+        delete_device,
+        delete_game_executable,
+        delete_game_lock,
+        delete_game_metadata,
+        delete_game_path,
+        delete_game_registries,
+        delete_game_save_pin,
+        delete_token,
+        delete_upload_session,
+        delete_user,
+        get_backup,
+        get_backups,
         get_configuration,
         get_db_uuid,
+        get_device,
+        get_device_sync,
+        get_devices,
         get_game_executables,
         get_game_executables_by_os,
+        get_game_lock,
+        get_game_locks,
         get_game_metadata,
         get_game_paths,
         get_game_paths_by_os,
         get_game_registries,
         get_game_save_by_uuid,
+        get_save_bundle,
+        get_game_save_diff,
+        get_game_save_file,
+        get_game_save_files,
         get_game_saves_reference_by_path_id,
         get_games_default_name,
         get_games_metadata,
         get_games_metadata_with_paths_if_saves_exists,
         get_games_search,
+        get_hash_algorithms,
         get_health,
+        get_ludusavi_backup,
+        get_scrub_report,
+        get_token,
+        get_tokens,
+        get_upload_session,
+        get_user,
+        get_users,
+        patch_game_executable,
+        patch_game_metadata,
+        patch_game_path,
+        patch_game_registries,
+        patch_game_save_by_uuid,
+        post_backup,
+        post_device,
         post_game_executable,
         post_game_metadata,
         post_game_path,
         post_game_registry,
         post_game_save_by_path_id,
+        post_game_save_restore,
+        post_ludusavi_backup,
         post_ludusavi_yaml,
+        post_pairing,
+        post_pairing_code,
+        post_scrub,
+        post_token,
+        post_upload_session,
+        post_upload_session_finalize,
+        post_user,
         put_configuration,
+        put_device_sync,
+        put_game_executable,
+        put_game_lock,
+        put_game_metadata,
+        put_game_path,
+        put_game_registries,
+        put_game_save_pin,
+        put_token,
+        put_upload_chunk,
+        put_user,
     ),
     components(schemas(
         FileHash,
@@ -64,7 +157,45 @@ use utoipa::{
         GameMetadataCreate,
         GameMetadata,
         SaveReference,
+        SaveConflict,
+        GameLock,
+        GameLockAcquire,
+        Device,
+        DeviceCreate,
+        DeviceRegistration,
+        DeviceSyncState,
+        DeviceSyncUpdate,
+        PairingCode,
+        PairingExchange,
+        ApiToken,
+        ApiTokenCreate,
+        ApiTokenCreated,
+        TokenScope,
+        User,
+        UserCreate,
+        UserRegistration,
+        SaveDiff,
+        FileChange,
+        SaveMetadata,
+        SaveMetadataUpdate,
+        GameRegistryUpdate,
         OS,
+        HashAlgorithm,
+        HashMismatch,
+        FileHashMismatch,
+        UploadSessionCreate,
+        UploadSession,
+        ByteRange,
+        ScrubReport,
+        ScrubProblem,
+        DamagedFile,
+        DownloadNotFound,
+        MissingDownload,
+        BundleFormat,
+        BackupInfo,
+        UploadedLudusaviBackup,
+        LudusaviImportReport,
+        SkippedLudusaviBackup,
     ),),
     security(
         ("bearer_auth" = [])
@@ -0,0 +1,213 @@
+use crate::DATABASE;
+use crate::blob_store;
+use crate::configuration::{
+    MAX_SAVE_PER_GAME_INFO, RETENTION_DAILY_DAYS_INFO, RETENTION_WEEKLY_WEEKS_INFO,
+};
+use crate::datatype_endpoint::SaveReference;
+use std::collections::HashSet;
+use std::error::Error;
+use time::{Date, OffsetDateTime};
+
+/// Grandfather-father-son retention: a save is kept if it is one of the `keep_last` most recent
+/// saves, the latest save of one of the last `daily_days` days or the latest save of one of the
+/// last `weekly_weeks` weeks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetentionPolicy {
+    pub keep_last: usize,
+    pub daily_days: u32,
+    pub weekly_weeks: u32,
+}
+
+impl RetentionPolicy {
+    pub fn from_configuration() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self {
+            keep_last: MAX_SAVE_PER_GAME_INFO.get_number_in_db()? as usize,
+            daily_days: RETENTION_DAILY_DAYS_INFO.get_number_in_db()?,
+            weekly_weeks: RETENTION_WEEKLY_WEEKS_INFO.get_number_in_db()?,
+        })
+    }
+
+    /// Pinned saves are left out entirely, they are neither pruned nor counted.
+    pub fn saves_to_prune(&self, saves: &[SaveReference], now: i64) -> Vec<String> {
+        let mut saves: Vec<&SaveReference> =
+            saves.iter().filter(|save_ref| !save_ref.pinned).collect();
+        // Saves come oldest first, reversing after the stable sort keeps the newest first on ties
+        saves.sort_by_key(|save_ref| save_ref.time);
+        saves.reverse();
+
+        let today = date_of(now);
+        let mut kept_days: HashSet<Date> = HashSet::new();
+        let mut kept_weeks: HashSet<(i32, u8)> = HashSet::new();
+        let mut to_prune = Vec::new();
+
+        for (index, save_ref) in saves.into_iter().enumerate() {
+            let date = date_of(save_ref.time);
+            let age_in_days = (today - date).whole_days();
+
+            let mut keep = index < self.keep_last;
+            if age_in_days < i64::from(self.daily_days) && kept_days.insert(date) {
+                keep = true;
+            }
+            if age_in_days < i64::from(self.weekly_weeks) * 7
+                && kept_weeks.insert(iso_week_of(date))
+            {
+                keep = true;
+            }
+
+            if !keep {
+                to_prune.push(save_ref.uuid.clone());
+            }
+        }
+
+        to_prune
+    }
+}
+
+fn iso_week_of(date: Date) -> (i32, u8) {
+    let (year, week, _) = date.to_iso_week_date();
+    (year, week)
+}
+
+fn date_of(unix_timestamp: i64) -> Date {
+    OffsetDateTime::from_unix_timestamp(unix_timestamp)
+        .map(|date_time| date_time.date())
+        .unwrap_or(Date::MIN)
+}
+
+/// Prunes the history of saves of `user_id` for the path
+pub async fn prune_saves(
+    path_id: i32,
+    user_id: i32,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let policy = RetentionPolicy::from_configuration()?;
+    prune_saves_with_policy(path_id, user_id, &policy).await
+}
+
+pub async fn prune_all_saves() -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let policy = RetentionPolicy::from_configuration()?;
+    let mut pruned = 0;
+    for (path_id, user_id) in DATABASE.get_save_histories()? {
+        pruned += prune_saves_with_policy(path_id, user_id, &policy).await?;
+    }
+    Ok(pruned)
+}
+
+async fn prune_saves_with_policy(
+    path_id: i32,
+    user_id: i32,
+    policy: &RetentionPolicy,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let saves_ref = DATABASE
+        .get_reference_to_save_by_path_id(path_id, user_id)?
+        .unwrap_or_default();
+    let old_saves = policy.saves_to_prune(&saves_ref, OffsetDateTime::now_utc().unix_timestamp());
+    if !old_saves.is_empty() {
+        blob_store::remove_saves(&old_saves).await?;
+    }
+    Ok(old_saves.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatype_endpoint::SaveMetadata;
+
+    const NOW: i64 = 1_760_000_000;
+    const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+    fn save(uuid: &str, age_in_seconds: i64) -> SaveReference {
+        SaveReference {
+            uuid: uuid.to_string(),
+            path_id: 1,
+            time: NOW - age_in_seconds,
+            parent_uuid: None,
+            restored_from: None,
+            pinned: false,
+            corrupt: false,
+            codec: None,
+            encrypted: false,
+            device_id: None,
+            metadata: SaveMetadata::default(),
+            hash_algorithm: None,
+            archive_digest: None,
+            files_hash: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_keep_last() {
+        let policy = RetentionPolicy {
+            keep_last: 2,
+            daily_days: 0,
+            weekly_weeks: 0,
+        };
+        let saves = vec![save("a", 30), save("b", 10), save("c", 20)];
+        assert_eq!(policy.saves_to_prune(&saves, NOW), vec!["a".to_string()]);
+
+        let same_second = vec![save("older", 0), save("newer", 0)];
+        let policy = RetentionPolicy {
+            keep_last: 1,
+            ..policy
+        };
+        assert_eq!(
+            policy.saves_to_prune(&same_second, NOW),
+            vec!["older".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_pinned_saves_are_not_counted() {
+        let policy = RetentionPolicy {
+            keep_last: 1,
+            daily_days: 0,
+            weekly_weeks: 0,
+        };
+        let pinned = SaveReference {
+            pinned: true,
+            ..save("pinned", 0)
+        };
+        let saves = vec![save("old", 20), pinned, save("b", 10)];
+
+        assert_eq!(policy.saves_to_prune(&saves, NOW), vec!["old".to_string()]);
+    }
+
+    #[test]
+    fn test_grandfather_father_son() {
+        let policy = RetentionPolicy {
+            keep_last: 1,
+            daily_days: 3,
+            weekly_weeks: 2,
+        };
+        let day = SECONDS_PER_DAY;
+        let saves = vec![
+            save("latest", 0),
+            save("same_day", 1),
+            save("yesterday", day),
+            save("yesterday_older", day + 1),
+            save("two_days", 2 * day),
+            save("last_week", 8 * day),
+            save("last_week_older", 9 * day),
+            save("too_old", 30 * day),
+        ];
+
+        let to_prune = policy.saves_to_prune(&saves, NOW);
+        let kept: Vec<&str> = saves
+            .iter()
+            .map(|save_ref| save_ref.uuid.as_str())
+            .filter(|uuid| !to_prune.iter().any(|pruned| pruned == uuid))
+            .collect();
+
+        assert!(kept.contains(&"latest"));
+        assert!(kept.contains(&"yesterday"));
+        assert!(kept.contains(&"two_days"));
+        assert!(to_prune.contains(&"same_day".to_string()));
+        assert!(to_prune.contains(&"yesterday_older".to_string()));
+        assert!(to_prune.contains(&"too_old".to_string()));
+        assert_eq!(
+            kept.iter()
+                .filter(|uuid| uuid.starts_with("last_week"))
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::const_var::ROOT_API_PATH;
+use crate::datatype_endpoint::{BackupInfo, Codec};
+use crate::save_download::{Validators, download_response, range_body};
+use crate::server_backup::{backup_path, create_backup, is_backup_file_name, list_backups};
+use axum::extract::Path;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, http::header};
+use const_format::concatcp;
+use std::time::UNIX_EPOCH;
+use tokio::fs::{self, File};
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/backups"),
+    responses(
+        (status = StatusCode::OK, description = "backup archives, newest first", body = Vec<BackupInfo>)
+    )
+)]
+pub async fn get_backups() -> Result<Json<Vec<BackupInfo>>, StatusCode> {
+    match list_backups().await {
+        Ok(backups) => Ok(Json(backups)),
+        Err(e) => {
+            tracing::error!("Error listing backups: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = concatcp!(ROOT_API_PATH, "/backups"),
+    responses(
+        (status = StatusCode::CREATED, description = "backup archive created", body = BackupInfo),
+        (status = StatusCode::CONFLICT, description = "a backup is already running")
+    )
+)]
+pub async fn post_backup() -> Result<(StatusCode, Json<BackupInfo>), StatusCode> {
+    match create_backup().await {
+        Ok(Some(backup)) => Ok((StatusCode::CREATED, Json(backup))),
+        Ok(None) => Err(StatusCode::CONFLICT),
+        Err(e) => {
+            tracing::error!("Error creating backup: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/backups/{file_name}"),
+    params(
+        ("file_name" = String, Path, description = "file name of the backup archive")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "backup archive", content_type = "application/x-tar"),
+        (status = StatusCode::PARTIAL_CONTENT, description = "requested range of the backup archive"),
+        (status = StatusCode::NOT_FOUND, description = "backup not found")
+    )
+)]
+pub async fn get_backup(Path((file_name,)): Path<(String,)>, headers: HeaderMap) -> Response {
+    if !is_backup_file_name(&file_name) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let path = backup_path(&file_name);
+    let metadata = match fs::metadata(&path).await {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        Err(e) => {
+            tracing::error!("Error getting backup {}: {}", file_name, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // A backup archive is never rewritten, its date validates it
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_secs() as i64)
+        .unwrap_or_default();
+    let validators = Validators::new(None, Codec::Identity, modified);
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, "application/x-tar")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        );
+    download_response(builder, &headers, &validators, metadata.len(), |range| {
+        range_body(File::open(path), range)
+    })
+}
@@ -0,0 +1,277 @@
+use crate::DATABASE;
+use crate::auth::Caller;
+use crate::const_var::ROOT_API_PATH;
+use crate::database::interface::GameDatabase;
+use crate::datatype_endpoint::{
+    Device, DeviceCreate, DeviceRegistration, DeviceSyncState, DeviceSyncUpdate, TokenScope,
+    UserQuery,
+};
+use crate::route_users::target_user;
+use axum::{
+    Json,
+    extract::{Extension, Path, Query},
+    http::StatusCode,
+};
+use const_format::concatcp;
+use uuid::Uuid;
+
+// Sync states only need the upload scope, only administrators see those of other users
+fn sees_device(caller: &Caller, device: &Device) -> bool {
+    device.user_id == caller.user_id || caller.allows(TokenScope::Admin)
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/devices"),
+    responses(
+        (status = StatusCode::OK, description = "registered devices", body = [Device]),
+    )
+)]
+pub async fn get_devices() -> Result<Json<Vec<Device>>, StatusCode> {
+    match DATABASE.get_devices() {
+        Ok(devices) => Ok(Json(devices)),
+        Err(e) => {
+            tracing::error!("Error getting devices: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = concatcp!(ROOT_API_PATH, "/devices"),
+    params(UserQuery),
+    request_body = DeviceCreate,
+    responses(
+        (status = StatusCode::CREATED, description = "device registered with a token of its own", body = DeviceRegistration),
+        (status = StatusCode::BAD_REQUEST, description = "empty name or unknown user"),
+    )
+)]
+pub async fn post_device(
+    Extension(caller): Extension<Caller>,
+    Query(query): Query<UserQuery>,
+    Json(payload): Json<DeviceCreate>,
+) -> Result<(StatusCode, Json<DeviceRegistration>), StatusCode> {
+    if payload.name.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let user = target_user(&caller, query)?;
+    let token = Uuid::new_v4();
+    match DATABASE.add_device(&payload, user.id, token) {
+        Ok(device) => Ok((
+            StatusCode::CREATED,
+            Json(DeviceRegistration {
+                device,
+                token: token.to_string(),
+            }),
+        )),
+        Err(e) => {
+            tracing::error!("Error registering device: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/devices/{Id}"),
+    params(
+        ("Id" = String, Path, description = "Id of the device")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "device returned", body = Device),
+        (status = StatusCode::NOT_FOUND, description = "device not found")
+    )
+)]
+pub async fn get_device(Path(id): Path<i32>) -> Result<Json<Device>, StatusCode> {
+    match DATABASE.get_device(id) {
+        Ok(Some(device)) => Ok(Json(device)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Error getting device: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = concatcp!(ROOT_API_PATH, "/devices/{Id}"),
+    params(
+        ("Id" = String, Path, description = "Id of the device")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "device removed and its tokens revoked"),
+        (status = StatusCode::NOT_FOUND, description = "device not found")
+    )
+)]
+pub async fn delete_device(Path(id): Path<i32>) -> StatusCode {
+    match DATABASE.remove_device(id) {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!("Error removing device: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/devices/{Id}/sync"),
+    params(
+        ("Id" = String, Path, description = "Id of the device")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "sync state of every path the device has a save of", body = [DeviceSyncState]),
+        (status = StatusCode::NOT_FOUND, description = "device not found, or a device of another user")
+    )
+)]
+pub async fn get_device_sync(
+    Path(id): Path<i32>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<Vec<DeviceSyncState>>, StatusCode> {
+    device_sync_states(&DATABASE, &caller, id)
+}
+
+fn device_sync_states(
+    database: &GameDatabase,
+    caller: &Caller,
+    id: i32,
+) -> Result<Json<Vec<DeviceSyncState>>, StatusCode> {
+    let result = database.get_device(id).and_then(|device| match device {
+        Some(device) if sees_device(caller, &device) => {
+            database.get_device_sync_states(id).map(Some)
+        }
+        _ => Ok(None),
+    });
+    match result {
+        Ok(Some(states)) => Ok(Json(states)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Error getting device sync state: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = concatcp!(ROOT_API_PATH, "/devices/{Id}/sync/{PathId}"),
+    params(
+        ("Id" = String, Path, description = "Id of the device"),
+        ("PathId" = String, Path, description = "Id of the path")
+    ),
+    request_body = DeviceSyncUpdate,
+    responses(
+        (status = StatusCode::OK, description = "synced save recorded"),
+        (status = StatusCode::NOT_FOUND, description = "device of the caller's user not found, or save of its user not found in the path")
+    )
+)]
+pub async fn put_device_sync(
+    Path((id, path_id)): Path<(i32, i32)>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<DeviceSyncUpdate>,
+) -> StatusCode {
+    set_device_synced_save(&DATABASE, &caller, id, path_id, &payload.save_uuid)
+}
+
+fn set_device_synced_save(
+    database: &GameDatabase,
+    caller: &Caller,
+    id: i32,
+    path_id: i32,
+    save_uuid: &str,
+) -> StatusCode {
+    match database.get_device(id) {
+        Ok(Some(device)) if sees_device(caller, &device) => {}
+        Ok(_) => return StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!("Error getting device: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    match database.set_device_synced_save(id, path_id, save_uuid) {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!("Error recording device sync state: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::interface::SaveOwner;
+    use crate::datatype_endpoint::OS;
+    use crate::test_util::{test_game_with_path, test_player, test_save};
+    use std::error::Error;
+
+    #[test]
+    fn test_sync_of_another_user_is_not_found() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let database =
+            GameDatabase::new(&format!("file:{}?mode=memory&cache=shared", Uuid::new_v4()));
+        test_game_with_path(&database, "Synced")?;
+        let player = test_player(&database)?;
+        let device = |name: &str, user_id| {
+            database.add_device(
+                &DeviceCreate {
+                    name: name.to_string(),
+                    operating_system: OS::Linux,
+                    client_version: None,
+                },
+                user_id,
+                Uuid::new_v4(),
+            )
+        };
+        let laptop = device("laptop", 1)?;
+        let deck = device("deck", player.id)?;
+        let uuid = test_save(
+            &database,
+            1,
+            SaveOwner {
+                user_id: 1,
+                device_id: Some(laptop.id),
+            },
+        )?;
+        let admin = Caller {
+            user_id: 1,
+            device_id: Some(laptop.id),
+            scopes: vec![TokenScope::Admin],
+        };
+        let player = Caller {
+            user_id: player.id,
+            device_id: Some(deck.id),
+            scopes: vec![TokenScope::ReadOnly, TokenScope::Upload],
+        };
+
+        assert_eq!(
+            device_sync_states(&database, &admin, laptop.id)
+                .map_err(|status| status.to_string())?
+                .0
+                .len(),
+            1
+        );
+        assert_eq!(
+            device_sync_states(&database, &player, laptop.id).err(),
+            Some(StatusCode::NOT_FOUND)
+        );
+        assert_eq!(
+            set_device_synced_save(&database, &player, laptop.id, 1, &uuid),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            set_device_synced_save(&database, &player, deck.id, 1, &uuid),
+            StatusCode::NOT_FOUND
+        );
+        assert!(
+            device_sync_states(&database, &player, deck.id)
+                .map_err(|status| status.to_string())?
+                .0
+                .is_empty()
+        );
+        Ok(())
+    }
+}
@@ -1,8 +1,10 @@
 use crate::DATABASE;
 use crate::const_var::ROOT_API_PATH;
 use crate::datatype_endpoint::{Executable, ExecutableCreate, OS};
+use crate::route_games::{catalog_update_status, merge_patch};
 use axum::{Json, extract::Path, http::StatusCode};
 use const_format::concatcp;
+use serde_json::Value;
 
 #[utoipa::path(
     get,
@@ -72,3 +74,88 @@ pub async fn post_game_executable(
         }
     }
 }
+
+#[utoipa::path(
+    put,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}/executables/id/{ExecutableId}"),
+    params(
+        ("Id" = String, Path, description = "Id of the game"),
+        ("ExecutableId" = String, Path, description = "Id of the executable"),
+    ),
+    request_body = ExecutableCreate,
+    responses(
+        (status = StatusCode::OK, description = "game executable replaced, the game is no longer managed by ludusavi"),
+        (status = StatusCode::NOT_FOUND, description = "game executable not found"),
+        (status = StatusCode::CONFLICT, description = "game already has this executable")
+    )
+)]
+pub async fn put_game_executable(
+    Path((id, executable_id)): Path<(i32, i32)>,
+    Json(payload): Json<ExecutableCreate>,
+) -> StatusCode {
+    catalog_update_status(DATABASE.update_game_executable(id, executable_id, &payload))
+}
+
+#[utoipa::path(
+    patch,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}/executables/id/{ExecutableId}"),
+    params(
+        ("Id" = String, Path, description = "Id of the game"),
+        ("ExecutableId" = String, Path, description = "Id of the executable"),
+    ),
+    request_body(
+        content = ExecutableCreate,
+        content_type = "application/merge-patch+json",
+        description = "fields to change"
+    ),
+    responses(
+        (status = StatusCode::OK, description = "game executable updated, the game is no longer managed by ludusavi"),
+        (status = StatusCode::BAD_REQUEST, description = "patched game executable is invalid"),
+        (status = StatusCode::NOT_FOUND, description = "game executable not found"),
+        (status = StatusCode::CONFLICT, description = "game already has this executable")
+    )
+)]
+pub async fn patch_game_executable(
+    Path((id, executable_id)): Path<(i32, i32)>,
+    Json(patch): Json<Value>,
+) -> StatusCode {
+    let executable = match DATABASE.get_executable_by_game_id(id) {
+        Ok(executables) => match executables
+            .into_iter()
+            .find(|executable| executable.id == Some(executable_id))
+        {
+            Some(executable) => executable.executable,
+            None => return StatusCode::NOT_FOUND,
+        },
+        Err(e) => {
+            tracing::error!("Error getting game executables: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+
+    match merge_patch(&executable, patch) {
+        Ok(payload) => {
+            catalog_update_status(DATABASE.update_game_executable(id, executable_id, &payload))
+        }
+        Err(e) => {
+            tracing::warn!("Rejected game executable patch: {}", e);
+            StatusCode::BAD_REQUEST
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}/executables/id/{ExecutableId}"),
+    params(
+        ("Id" = String, Path, description = "Id of the game"),
+        ("ExecutableId" = String, Path, description = "Id of the executable"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "game executable deleted, the game is no longer managed by ludusavi"),
+        (status = StatusCode::NOT_FOUND, description = "game executable not found")
+    )
+)]
+pub async fn delete_game_executable(Path((id, executable_id)): Path<(i32, i32)>) -> StatusCode {
+    catalog_update_status(DATABASE.remove_game_executable(id, executable_id))
+}
@@ -0,0 +1,169 @@
+use crate::DATABASE;
+use crate::auth::Caller;
+use crate::configuration::GAME_LOCK_LEASE_SECONDS_INFO;
+use crate::const_var::ROOT_API_PATH;
+use crate::datatype_endpoint::{GameLock, GameLockAcquire, GameLockRelease, TokenScope};
+use axum::extract::{Extension, Query};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, extract::Path, http::StatusCode};
+use const_format::concatcp;
+
+fn game_lock_error_response(e: Box<dyn std::error::Error + Send + Sync>) -> Response {
+    match e.downcast::<GameLock>() {
+        Ok(game_lock) => (StatusCode::CONFLICT, Json(*game_lock)).into_response(),
+        Err(e) => {
+            tracing::error!("Error updating game lock: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/locks"),
+    responses(
+        (status = StatusCode::OK, description = "active game locks of the caller", body = [GameLock]),
+    )
+)]
+pub async fn get_game_locks(
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<Vec<GameLock>>, StatusCode> {
+    match DATABASE.get_game_locks(caller.user_id) {
+        Ok(game_locks) => Ok(Json(game_locks)),
+        Err(e) => {
+            tracing::error!("Error getting game locks: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}/lock"),
+    params(
+        ("Id" = String, Path, description = "Id of the game")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "lock of the game", body = GameLock),
+        (status = StatusCode::NOT_FOUND, description = "the game isn't locked")
+    )
+)]
+pub async fn get_game_lock(
+    Path(id): Path<i32>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<GameLock>, StatusCode> {
+    match DATABASE.get_game_lock(id, caller.user_id) {
+        Ok(Some(game_lock)) => Ok(Json(game_lock)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Error getting game lock: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}/lock"),
+    params(
+        ("Id" = String, Path, description = "Id of the game")
+    ),
+    request_body = GameLockAcquire,
+    responses(
+        (status = StatusCode::OK, description = "lock acquired or renewed", body = GameLock),
+        (status = StatusCode::BAD_REQUEST, description = "empty holder"),
+        (status = StatusCode::NOT_FOUND, description = "game not found"),
+        (status = StatusCode::CONFLICT, description = "the game is locked by another holder", body = GameLock)
+    )
+)]
+pub async fn put_game_lock(
+    Path(id): Path<i32>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<GameLockAcquire>,
+) -> Result<Json<GameLock>, Response> {
+    if payload.holder.is_empty() {
+        return Err(StatusCode::BAD_REQUEST.into_response());
+    }
+    let lease = match GAME_LOCK_LEASE_SECONDS_INFO.get_number_in_db() {
+        Ok(lease) => time::Duration::seconds(i64::from(lease)),
+        Err(e) => {
+            tracing::error!("Error getting game lock lease: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+    match DATABASE.acquire_game_lock(
+        id,
+        caller.user_id,
+        &payload.holder,
+        payload.hostname.as_deref(),
+        lease,
+    ) {
+        Ok(Some(game_lock)) => Ok(Json(game_lock)),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(game_lock_error_response(e)),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}/lock"),
+    params(
+        ("Id" = String, Path, description = "Id of the game"),
+        GameLockRelease
+    ),
+    responses(
+        (status = StatusCode::OK, description = "lock released"),
+        (status = StatusCode::BAD_REQUEST, description = "neither holder nor force given"),
+        (status = StatusCode::FORBIDDEN, description = "force given without the admin scope"),
+        (status = StatusCode::NOT_FOUND, description = "the game isn't locked"),
+        (status = StatusCode::CONFLICT, description = "the game is locked by another holder", body = GameLock)
+    )
+)]
+pub async fn delete_game_lock(
+    Path(id): Path<i32>,
+    Extension(caller): Extension<Caller>,
+    Query(release): Query<GameLockRelease>,
+) -> Response {
+    if release.force && !caller.allows(TokenScope::Admin) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let holder = match (release.force, release.holder.as_deref()) {
+        (true, _) => None,
+        (false, Some(holder)) => Some(holder),
+        (false, None) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    match DATABASE.release_game_lock(id, caller.user_id, holder) {
+        Ok(true) => {
+            if release.force {
+                tracing::info!("Broke the lock of game {}", id);
+            }
+            StatusCode::OK.into_response()
+        }
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => game_lock_error_response(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_force_release_needs_admin() {
+        let caller = Caller {
+            user_id: 1,
+            device_id: None,
+            scopes: vec![TokenScope::ReadOnly, TokenScope::Upload],
+        };
+        let response = delete_game_lock(
+            Path(1),
+            Extension(caller),
+            Query(GameLockRelease {
+                holder: None,
+                force: true,
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -1,21 +1,69 @@
 use crate::DATABASE;
+use crate::auth::Caller;
 use crate::const_var::ROOT_API_PATH;
+use crate::database::interface::CatalogUpdate;
 use crate::datatype_endpoint::{
     GameDefaultName, GameMetadata, GameMetadataCreate, GameMetadataWithPaths,
 };
 use axum::{
     Json,
-    extract::{Path, Query},
+    extract::{Extension, Path, Query},
     http::StatusCode,
 };
 use const_format::concatcp;
-use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[derive(Debug, Deserialize)]
 pub struct SearchParams {
     name: String,
 }
 
+/// Applies a JSON merge patch (RFC 7386): fields set to null are cleared, missing fields are kept
+pub fn merge_patch<T: Serialize + DeserializeOwned>(
+    current: &T,
+    patch: Value,
+) -> Result<T, serde_json::Error> {
+    let mut value = serde_json::to_value(current)?;
+    merge_json(&mut value, patch);
+    serde_json::from_value(value)
+}
+
+fn merge_json(target: &mut Value, patch: Value) {
+    match patch {
+        Value::Object(patch) => {
+            if !target.is_object() {
+                *target = Value::Object(serde_json::Map::new());
+            }
+            if let Value::Object(target) = target {
+                for (key, value) in patch {
+                    if value.is_null() {
+                        target.remove(&key);
+                    } else {
+                        merge_json(target.entry(key).or_insert(Value::Null), value);
+                    }
+                }
+            }
+        }
+        patch => *target = patch,
+    }
+}
+
+pub fn catalog_update_status(
+    result: Result<CatalogUpdate, Box<dyn std::error::Error + Send + Sync>>,
+) -> StatusCode {
+    match result {
+        Ok(CatalogUpdate::Done) => StatusCode::OK,
+        Ok(CatalogUpdate::NotFound) => StatusCode::NOT_FOUND,
+        Ok(CatalogUpdate::Conflict) => StatusCode::CONFLICT,
+        Err(e) => {
+            tracing::error!("Error updating game catalog: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
 #[utoipa::path(
     post,
     path = concatcp!(ROOT_API_PATH, "/games"),
@@ -101,12 +149,13 @@ pub async fn get_games_default_name() -> Result<Json<Vec<GameDefaultName>>, Stat
     path = concatcp!(ROOT_API_PATH, "/games/paths/saves"),
     params(),
     responses(
-        (status = StatusCode::OK, description = "get all games metadata that has paths with saves", body = [GameMetadataWithPaths])
+        (status = StatusCode::OK, description = "get all games metadata that has paths with saves of the caller", body = [GameMetadataWithPaths])
     )
 )]
-pub async fn get_games_metadata_with_paths_if_saves_exists()
--> Result<Json<Vec<GameMetadataWithPaths>>, StatusCode> {
-    match DATABASE.get_games_metadata_and_paths_if_saves_exist() {
+pub async fn get_games_metadata_with_paths_if_saves_exists(
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<Vec<GameMetadataWithPaths>>, StatusCode> {
+    match DATABASE.get_games_metadata_and_paths_if_saves_exist(caller.user_id) {
         Ok(data) => Ok(Json(data)),
         Err(e) => {
             tracing::error!("Error retrieving game metadata: {}", e);
@@ -136,3 +185,74 @@ pub async fn get_game_metadata(Path(id): Path<i32>) -> Result<Json<GameMetadata>
         }
     }
 }
+
+#[utoipa::path(
+    put,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}"),
+    params(
+        ("Id" = String, Path, description = "Id of the game")
+    ),
+    request_body = GameMetadataCreate,
+    responses(
+        (status = StatusCode::OK, description = "game metadata replaced, the game is no longer managed by ludusavi"),
+        (status = StatusCode::NOT_FOUND, description = "game not found")
+    )
+)]
+pub async fn put_game_metadata(
+    Path(id): Path<i32>,
+    Json(payload): Json<GameMetadataCreate>,
+) -> StatusCode {
+    catalog_update_status(DATABASE.update_game_metadata_by_id(id, &payload))
+}
+
+#[utoipa::path(
+    patch,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}"),
+    params(
+        ("Id" = String, Path, description = "Id of the game")
+    ),
+    request_body(
+        content = GameMetadataCreate,
+        content_type = "application/merge-patch+json",
+        description = "fields to change, null clears a field"
+    ),
+    responses(
+        (status = StatusCode::OK, description = "game metadata updated, the game is no longer managed by ludusavi"),
+        (status = StatusCode::BAD_REQUEST, description = "patched game metadata is invalid"),
+        (status = StatusCode::NOT_FOUND, description = "game not found")
+    )
+)]
+pub async fn patch_game_metadata(Path(id): Path<i32>, Json(patch): Json<Value>) -> StatusCode {
+    let game_metadata = match DATABASE.get_game_metadata_by_id(&id) {
+        Ok(Some(game_metadata)) => game_metadata,
+        Ok(None) => return StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!("Error getting game metadata: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+
+    match merge_patch(&game_metadata.metadata, patch) {
+        Ok(payload) => catalog_update_status(DATABASE.update_game_metadata_by_id(id, &payload)),
+        Err(e) => {
+            tracing::warn!("Rejected game metadata patch: {}", e);
+            StatusCode::BAD_REQUEST
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}"),
+    params(
+        ("Id" = String, Path, description = "Id of the game")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "game deleted with its paths, executables and registry paths"),
+        (status = StatusCode::NOT_FOUND, description = "game not found"),
+        (status = StatusCode::CONFLICT, description = "saves exist for one of the game paths")
+    )
+)]
+pub async fn delete_game_metadata(Path(id): Path<i32>) -> StatusCode {
+    catalog_update_status(DATABASE.remove_game(id))
+}
@@ -0,0 +1,15 @@
+use crate::const_var::ROOT_API_PATH;
+use crate::datatype_endpoint::HashAlgorithm;
+use axum::Json;
+use const_format::concatcp;
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/hash_algorithms"),
+    responses(
+        (status = StatusCode::OK, description = "hash algorithms accepted for file_hash", body = [HashAlgorithm]),
+    )
+)]
+pub async fn get_hash_algorithms() -> Json<Vec<HashAlgorithm>> {
+    Json(HashAlgorithm::SUPPORTED.to_vec())
+}
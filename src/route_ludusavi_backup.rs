@@ -0,0 +1,115 @@
+use crate::auth::Caller;
+use crate::const_var::{ROOT_API_PATH, TMP_DIR};
+use crate::datatype_endpoint::{
+    Codec, LudusaviExportQuery, LudusaviImportReport, UploadedLudusaviBackup,
+};
+use crate::file_system::{append_file, create_tmp_file};
+use crate::ludusavi_backup::{
+    export_backup_entries, import_backup_directory, unpack_backup_archive,
+};
+use crate::save_archive::TarArchive;
+use crate::save_store::SAVE_STORE;
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{Extension, Multipart, Query};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use const_format::concatcp;
+use std::error::Error;
+use std::path::PathBuf;
+use tokio::fs;
+use uuid::Uuid;
+
+async fn receive_archive(
+    multipart: &mut Multipart,
+    tmp_path: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut file = create_tmp_file(tmp_path).await?;
+    while let Some(mut field) = multipart.next_field().await? {
+        while let Some(chunk) = field.chunk().await? {
+            append_file(&mut file, &chunk).await?;
+        }
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = concatcp!(ROOT_API_PATH, "/ludusavi/backups"),
+    request_body(
+        content = UploadedLudusaviBackup,
+        content_type = "multipart/form-data",
+        description = "tar archive of a Ludusavi backup directory"
+    ),
+    responses(
+        (status = StatusCode::OK, description = "backups imported", body = LudusaviImportReport),
+        (status = StatusCode::BAD_REQUEST, description = "invalid archive")
+    )
+)]
+pub async fn post_ludusavi_backup(
+    Extension(caller): Extension<Caller>,
+    mut multipart: Multipart,
+) -> Result<Json<LudusaviImportReport>, StatusCode> {
+    let id = Uuid::new_v4();
+    let tmp_path = format!("{}/{}", TMP_DIR, id);
+    let directory = PathBuf::from(format!("{}/ludusavi-{}", TMP_DIR, id));
+
+    let result = async {
+        if let Err(e) = receive_archive(&mut multipart, &tmp_path).await {
+            tracing::error!("Error receiving Ludusavi backup: {}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        if let Err(e) = unpack_backup_archive(PathBuf::from(&tmp_path), directory.clone()).await {
+            tracing::error!("Error unpacking Ludusavi backup: {}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        import_backup_directory(&directory, caller.user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error importing Ludusavi backup: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })
+    }
+    .await;
+
+    // Whatever happened, clean up
+    let _ = fs::remove_file(&tmp_path).await;
+    let _ = fs::remove_dir_all(&directory).await;
+    result.map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/ludusavi/backups"),
+    params(LudusaviExportQuery),
+    responses(
+        (status = StatusCode::OK, description = "tar archive of a Ludusavi backup directory", content_type = "application/x-tar")
+    )
+)]
+pub async fn get_ludusavi_backup(
+    Query(query): Query<LudusaviExportQuery>,
+    Extension(caller): Extension<Caller>,
+) -> Response {
+    let entries = match export_backup_entries(query.latest, caller.user_id).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::error!("Error exporting Ludusavi backup: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    match TarArchive::new(entries, Codec::Identity, SAVE_STORE.as_ref()) {
+        Ok(archive) => Response::builder()
+            .header("Content-Type", "application/x-tar")
+            .header("Content-Length", archive.size)
+            .header(
+                "Content-Disposition",
+                "attachment; filename=\"ludusavi-backup.tar\"",
+            )
+            .body(Body::from_stream(archive.into_stream()))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        Err(e) => {
+            tracing::error!("Error building the Ludusavi backup: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::DATABASE;
+use crate::auth::Caller;
+use crate::const_var::{PAIRING_CODE_LIFETIME_MINUTES, ROOT_API_PATH};
+use crate::datatype_endpoint::{
+    DeviceCreate, DeviceRegistration, PairingCode, PairingExchange, UserQuery,
+};
+use crate::device_pairing::{
+    allow_pairing_attempt, format_pairing_code, generate_pairing_code, normalize_pairing_code,
+    pairing_qr_payload, pairing_qr_svg,
+};
+use crate::route_users::target_user;
+use axum::{
+    Json,
+    extract::{ConnectInfo, Extension, Query},
+    http::{HeaderMap, StatusCode, header},
+};
+use const_format::concatcp;
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+// Address the new client should use, as seen by the dashboard that asked for the code
+fn server_url(headers: &HeaderMap) -> String {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or("localhost:3000");
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|scheme| scheme.to_str().ok())
+        .unwrap_or("http");
+    format!("{scheme}://{host}")
+}
+
+#[utoipa::path(
+    post,
+    path = concatcp!(ROOT_API_PATH, "/pairing_codes"),
+    params(UserQuery),
+    responses(
+        (status = StatusCode::CREATED, description = "single-use pairing code for a new device of the user", body = PairingCode),
+        (status = StatusCode::BAD_REQUEST, description = "unknown user"),
+        (status = StatusCode::FORBIDDEN, description = "user_id of another user given without the admin scope"),
+    )
+)]
+pub async fn post_pairing_code(
+    Extension(caller): Extension<Caller>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<PairingCode>), StatusCode> {
+    let user = target_user(&caller, query)?;
+    let code = generate_pairing_code();
+    let expires = match DATABASE.add_pairing_code(
+        &code,
+        user.id,
+        time::Duration::minutes(PAIRING_CODE_LIFETIME_MINUTES),
+    ) {
+        Ok(expires) => expires,
+        Err(e) => {
+            tracing::error!("Error adding pairing code: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let qr_payload = pairing_qr_payload(&server_url(&headers), &code);
+    let qr_svg = match pairing_qr_svg(&qr_payload) {
+        Ok(qr_svg) => qr_svg,
+        Err(e) => {
+            tracing::error!("Error rendering pairing QR code: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    Ok((
+        StatusCode::CREATED,
+        Json(PairingCode {
+            code: format_pairing_code(&code),
+            expires,
+            qr_payload,
+            qr_svg,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = concatcp!(ROOT_API_PATH, "/pairing"),
+    request_body = PairingExchange,
+    security(()),
+    responses(
+        (status = StatusCode::CREATED, description = "device registered with a token of its own", body = DeviceRegistration),
+        (status = StatusCode::BAD_REQUEST, description = "empty name"),
+        (status = StatusCode::UNAUTHORIZED, description = "unknown, used or expired pairing code"),
+        (status = StatusCode::TOO_MANY_REQUESTS, description = "too many pairing attempts from this address"),
+    )
+)]
+pub async fn post_pairing(
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    Json(payload): Json<PairingExchange>,
+) -> Result<(StatusCode, Json<DeviceRegistration>), StatusCode> {
+    if !allow_pairing_attempt(address.ip()) {
+        tracing::warn!("Too many pairing attempts from {}", address.ip());
+        return Err(StatusCode::TOO_MANY_REQUESTS);
+    }
+    if payload.name.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let device = DeviceCreate {
+        name: payload.name,
+        operating_system: payload.operating_system,
+        client_version: payload.client_version,
+    };
+    let token = Uuid::new_v4();
+    match DATABASE.redeem_pairing_code(&normalize_pairing_code(&payload.code), &device, token) {
+        Ok(Some(device)) => Ok((
+            StatusCode::CREATED,
+            Json(DeviceRegistration {
+                device,
+                token: token.to_string(),
+            }),
+        )),
+        Ok(None) => Err(StatusCode::UNAUTHORIZED),
+        Err(e) => {
+            tracing::error!("Error redeeming pairing code: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
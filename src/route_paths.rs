@@ -1,8 +1,10 @@
 use crate::DATABASE;
 use crate::const_var::ROOT_API_PATH;
 use crate::datatype_endpoint::{OS, SavePath, SavePathCreate};
+use crate::route_games::{catalog_update_status, merge_patch};
 use axum::{Json, extract::Path, http::StatusCode};
 use const_format::concatcp;
+use serde_json::Value;
 
 #[utoipa::path(
     get,
@@ -70,3 +72,84 @@ pub async fn post_game_path(
         }
     }
 }
+
+#[utoipa::path(
+    put,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}/paths/id/{PathId}"),
+    params(
+        ("Id" = String, Path, description = "Id of the game"),
+        ("PathId" = String, Path, description = "Id of the path"),
+    ),
+    request_body = SavePathCreate,
+    responses(
+        (status = StatusCode::OK, description = "game path replaced, the game is no longer managed by ludusavi"),
+        (status = StatusCode::NOT_FOUND, description = "game path not found"),
+        (status = StatusCode::CONFLICT, description = "game already has this path")
+    )
+)]
+pub async fn put_game_path(
+    Path((id, path_id)): Path<(i32, i32)>,
+    Json(payload): Json<SavePathCreate>,
+) -> StatusCode {
+    catalog_update_status(DATABASE.update_game_path(id, path_id, &payload))
+}
+
+#[utoipa::path(
+    patch,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}/paths/id/{PathId}"),
+    params(
+        ("Id" = String, Path, description = "Id of the game"),
+        ("PathId" = String, Path, description = "Id of the path"),
+    ),
+    request_body(
+        content = SavePathCreate,
+        content_type = "application/merge-patch+json",
+        description = "fields to change"
+    ),
+    responses(
+        (status = StatusCode::OK, description = "game path updated, the game is no longer managed by ludusavi"),
+        (status = StatusCode::BAD_REQUEST, description = "patched game path is invalid"),
+        (status = StatusCode::NOT_FOUND, description = "game path not found"),
+        (status = StatusCode::CONFLICT, description = "game already has this path")
+    )
+)]
+pub async fn patch_game_path(
+    Path((id, path_id)): Path<(i32, i32)>,
+    Json(patch): Json<Value>,
+) -> StatusCode {
+    let path = match DATABASE.get_paths_by_game_id(id) {
+        Ok(paths) => match paths.into_iter().find(|path| path.id == Some(path_id)) {
+            Some(path) => path.path,
+            None => return StatusCode::NOT_FOUND,
+        },
+        Err(e) => {
+            tracing::error!("Error getting game paths: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+
+    match merge_patch(&path, patch) {
+        Ok(payload) => catalog_update_status(DATABASE.update_game_path(id, path_id, &payload)),
+        Err(e) => {
+            tracing::warn!("Rejected game path patch: {}", e);
+            StatusCode::BAD_REQUEST
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}/paths/id/{PathId}"),
+    params(
+        ("Id" = String, Path, description = "Id of the game"),
+        ("PathId" = String, Path, description = "Id of the path"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "game path deleted, the game is no longer managed by ludusavi"),
+        (status = StatusCode::NOT_FOUND, description = "game path not found"),
+        (status = StatusCode::CONFLICT, description = "saves exist for this path")
+    )
+)]
+pub async fn delete_game_path(Path((id, path_id)): Path<(i32, i32)>) -> StatusCode {
+    catalog_update_status(DATABASE.remove_game_path(id, path_id))
+}
@@ -1,6 +1,7 @@
 use crate::DATABASE;
 use crate::const_var::ROOT_API_PATH;
-use crate::datatype_endpoint::GameRegistry;
+use crate::datatype_endpoint::{GameRegistry, GameRegistryUpdate};
+use crate::route_games::catalog_update_status;
 use axum::{Json, extract::Path, http::StatusCode};
 use const_format::concatcp;
 
@@ -49,3 +50,61 @@ pub async fn post_game_registry(
         }
     }
 }
+
+#[utoipa::path(
+    put,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}/registry"),
+    params(
+        ("Id" = String, Path, description = "Id of the game"),
+    ),
+    request_body = [GameRegistry],
+    responses(
+        (status = StatusCode::OK, description = "game registry paths replaced, the game is no longer managed by ludusavi"),
+        (status = StatusCode::NOT_FOUND, description = "game not found")
+    )
+)]
+pub async fn put_game_registries(
+    Path(id): Path<i32>,
+    Json(payload): Json<Vec<GameRegistry>>,
+) -> StatusCode {
+    catalog_update_status(DATABASE.update_game_registry(id, &payload, &[], true))
+}
+
+#[utoipa::path(
+    patch,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}/registry"),
+    params(
+        ("Id" = String, Path, description = "Id of the game"),
+    ),
+    request_body = GameRegistryUpdate,
+    responses(
+        (status = StatusCode::OK, description = "game registry paths added and removed, the game is no longer managed by ludusavi"),
+        (status = StatusCode::NOT_FOUND, description = "game not found")
+    )
+)]
+pub async fn patch_game_registries(
+    Path(id): Path<i32>,
+    Json(payload): Json<GameRegistryUpdate>,
+) -> StatusCode {
+    catalog_update_status(DATABASE.update_game_registry(
+        id,
+        &payload.added,
+        &payload.removed,
+        false,
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = concatcp!(ROOT_API_PATH, "/games/{Id}/registry"),
+    params(
+        ("Id" = String, Path, description = "Id of the game"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "game registry paths deleted, the game is no longer managed by ludusavi"),
+        (status = StatusCode::NOT_FOUND, description = "game not found")
+    )
+)]
+pub async fn delete_game_registries(Path(id): Path<i32>) -> StatusCode {
+    catalog_update_status(DATABASE.update_game_registry(id, &[], &[], true))
+}
@@ -1,17 +1,39 @@
 use crate::DATABASE;
-use crate::configuration::MAX_SAVE_PER_GAME_INFO;
-use crate::const_var::{ROOT_API_PATH, SAVE_DIR, TMP_DIR};
-use crate::datatype_endpoint::{SaveReference, UploadedSave};
-use crate::file_system::{append_file, create_tmp_file, move_file};
+use crate::auth::Caller;
+use crate::blob_store::{self, UploadedFile, blob_object, legacy_archive_object, open_blob};
+use crate::const_var::{ROOT_API_PATH, TMP_DIR};
+use crate::database::interface::{CorruptSave, GameDatabase, SaveDigest, SaveFile, SaveOwner};
+use crate::datatype_endpoint::{
+    ArchiveFormat, BundleFormat, Codec, DownloadNotFound, FileHash, FileHashMismatch, GameLock,
+    HashAlgorithm, HashMismatch, MissingDownload, SaveBundleQuery, SaveConflict, SaveDiff,
+    SaveHashAlgorithm, SaveMetadata, SaveMetadataUpdate, SaveParent, SaveReference, SaveRestore,
+    UploadedSave,
+};
+use crate::file_system::{append_file, create_tmp_file, is_safe_relative_path, portable_file_name};
+use crate::retention::prune_saves;
+use crate::save_archive::{
+    ArchiveEntry, EntryContent, TarArchive, archive_format, extract_archive,
+};
+use crate::save_compression::{accepts_zstd, decoded_reader};
+use crate::save_diff::get_save_diff;
+use crate::save_download::{Validators, attachment_disposition, download_response, range_body};
+use crate::save_encryption::blob_key;
+use crate::save_hash::{FileHasher, archive_digest};
+use crate::save_store::{SAVE_STORE, SaveStore};
+use crate::save_zip::zip_stream;
 use axum::body::Body;
-use axum::extract::Multipart;
+use axum::body::Bytes;
+use axum::extract::{Extension, Multipart, Query};
+use axum::http::response::Builder;
+use axum::http::{HeaderMap, header};
 use axum::response::{IntoResponse, Response};
 use axum::{Json, extract::Path, http::StatusCode};
 use const_format::concatcp;
+use itertools::Itertools;
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
-use tokio::fs::File;
-use tokio_util::io::ReaderStream;
+use tokio::io::AsyncReadExt;
 use uuid::Uuid;
 
 #[utoipa::path(
@@ -26,8 +48,9 @@ use uuid::Uuid;
 )]
 pub async fn get_game_saves_reference_by_path_id(
     Path((path_id,)): Path<(i32,)>,
+    Extension(caller): Extension<Caller>,
 ) -> Result<Json<Vec<SaveReference>>, StatusCode> {
-    match DATABASE.get_reference_to_save_by_path_id(path_id) {
+    match DATABASE.get_reference_to_save_by_path_id(path_id, caller.user_id) {
         Ok(Some(data)) => Ok(Json(data)),
         Ok(None) => Ok(Json(Vec::new())),
         Err(e) => {
@@ -41,7 +64,9 @@ pub async fn get_game_saves_reference_by_path_id(
     post,
     path = concatcp!(ROOT_API_PATH, "/paths/{Id}/saves/upload"),
     params(
-        ("Id" = String, Path, description = "Id of the path")
+        ("Id" = String, Path, description = "Id of the path"),
+        SaveParent,
+        SaveHashAlgorithm
     ),
     request_body(
         content = UploadedSave,
@@ -50,132 +75,1176 @@ pub async fn get_game_saves_reference_by_path_id(
     ),
     responses(
         (status = StatusCode::CREATED, description = "game save created", body = String),
+        (status = StatusCode::BAD_REQUEST, description = "invalid file_hash, metadata or archive"),
+        (status = StatusCode::CONFLICT, description = "parent_uuid isn't the latest save of the path", body = SaveConflict),
+        (status = StatusCode::LOCKED, description = "the game is locked by another lock_holder", body = GameLock),
+        (status = StatusCode::UNPROCESSABLE_ENTITY, description = "uploaded files don't match file_hash", body = HashMismatch),
     )
 )]
 pub async fn post_game_save_by_path_id(
     Path((path_id,)): Path<(i32,)>,
+    Query(parent): Query<SaveParent>,
+    Query(SaveHashAlgorithm { hash_algorithm }): Query<SaveHashAlgorithm>,
+    Extension(caller): Extension<Caller>,
     mut multipart: Multipart,
-) -> Result<(StatusCode, String), StatusCode> {
+) -> Result<(StatusCode, String), Response> {
     let uuid = Uuid::new_v4();
-    let tmp_path = format!("{}/{}.sav", TMP_DIR, uuid);
-    let save_path = format!("{}/{}.sav", SAVE_DIR, uuid);
-
-    let result: Result<(), Box<dyn std::error::Error + Send + Sync>> = async {
-        let mut file_hash: Vec<crate::datatype_endpoint::FileHash> = Vec::new();
-        let mut file = create_tmp_file(&tmp_path).await?;
-
-        while let Some(mut field) = multipart.next_field().await? {
-            match field.name() {
-                Some("file_hash") => {
-                    let bytes = field.bytes().await?;
-                    let json_str = String::from_utf8(bytes.to_vec())?;
-                    file_hash = serde_json::from_str(&json_str)?;
-                }
-                _ => {
-                    while let Some(chunk) = field.chunk().await? {
-                        append_file(&mut file, &chunk).await?;
+    let mut uploaded_files: Vec<UploadedFile> = Vec::new();
+
+    let result = match receive_save_files(&mut multipart, hash_algorithm, &mut uploaded_files).await
+    {
+        Ok((file_hash, metadata)) => {
+            match unpack_single_archive(&mut uploaded_files, &file_hash, hash_algorithm).await {
+                Ok(archive_format) => {
+                    match attach_file_hash(&uploaded_files, file_hash, hash_algorithm) {
+                        Ok(digest) => blob_store::add_save(
+                            uuid,
+                            path_id,
+                            &parent,
+                            &metadata,
+                            caller.save_owner(),
+                            &digest,
+                            archive_format,
+                            &uploaded_files,
+                        )
+                        .await
+                        .map_err(add_save_error_response),
+                        Err(e) => Err(file_hash_error_response(e)),
                     }
                 }
+                Err(e) => {
+                    tracing::warn!("Invalid archive in game save upload: {}", e);
+                    Err((StatusCode::BAD_REQUEST, "invalid archive").into_response())
+                }
             }
         }
+        Err(e) => Err(receive_error_response(e)),
+    };
 
-        move_file(&tmp_path, &save_path).await?;
+    //Try to clean up, blobs already in the store were moved out of the tmp dir
+    for uploaded_file in &uploaded_files {
+        let _ = fs::remove_file(&uploaded_file.tmp_path);
+    }
+    result?;
 
-        DATABASE.add_reference_to_save(uuid, path_id, file_hash)?;
+    if let Err(e) = prune_saves(path_id, caller.user_id).await {
+        tracing::error!("Error pruning game saves: {}", e);
+    }
 
-        if let Some(mut saves_ref) = DATABASE.get_reference_to_save_by_path_id(path_id)? {
-            saves_ref.sort_by_key(|save_ref| save_ref.time);
-            let maybe_max_saves = match MAX_SAVE_PER_GAME_INFO.get_value_in_db() {
-                Ok(Some(cfg)) => match cfg.value.parse::<usize>() {
-                    Ok(value) => Some(value),
-                    Err(err) => {
-                        tracing::error!(
-                            "Failed to read {} config: {}",
-                            MAX_SAVE_PER_GAME_INFO.id,
-                            err
-                        );
-                        None
-                    }
-                },
-                Ok(None) => {
-                    tracing::error!("No {} in the database", MAX_SAVE_PER_GAME_INFO.id);
-                    None
-                }
-                Err(err) => {
-                    tracing::error!(
-                        "Failed to read {} config: {}",
-                        MAX_SAVE_PER_GAME_INFO.id,
-                        err
-                    );
-                    None
+    Ok((StatusCode::CREATED, uuid.to_string()))
+}
+
+fn receive_error_response(e: Box<dyn std::error::Error + Send + Sync>) -> Response {
+    match e.downcast::<serde_json::Error>() {
+        Ok(e) => {
+            tracing::warn!("Invalid part in game save upload: {}", e);
+            (StatusCode::BAD_REQUEST, "invalid file_hash or metadata").into_response()
+        }
+        Err(e) => {
+            tracing::error!("Error uploading game save: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub fn add_save_error_response(e: Box<dyn std::error::Error + Send + Sync>) -> Response {
+    let e = match e.downcast::<SaveConflict>() {
+        Ok(conflict) => {
+            tracing::info!("Rejected game save upload: {}", conflict);
+            return (StatusCode::CONFLICT, Json(*conflict)).into_response();
+        }
+        Err(e) => e,
+    };
+    match e.downcast::<GameLock>() {
+        Ok(game_lock) => {
+            tracing::info!("Rejected game save upload: {}", game_lock);
+            (StatusCode::LOCKED, Json(*game_lock)).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Error storing game save: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn receive_save_files(
+    multipart: &mut Multipart,
+    hash_algorithm: HashAlgorithm,
+    uploaded_files: &mut Vec<UploadedFile>,
+) -> Result<(Vec<FileHash>, SaveMetadata), Box<dyn std::error::Error + Send + Sync>> {
+    let mut file_hash: Vec<FileHash> = Vec::new();
+    let mut metadata = SaveMetadata::default();
+
+    while let Some(mut field) = multipart.next_field().await? {
+        match field.name() {
+            Some("file_hash") => {
+                let bytes = field.bytes().await?;
+                file_hash = serde_json::from_slice(&bytes)?;
+            }
+            Some("metadata") => {
+                let bytes = field.bytes().await?;
+                metadata = serde_json::from_slice(&bytes)?;
+            }
+            _ => {
+                let relative_path = field.file_name().unwrap_or_default().to_string();
+                let tmp_path = format!("{}/{}", TMP_DIR, Uuid::new_v4());
+                let mut file = create_tmp_file(&tmp_path).await?;
+                uploaded_files.push(UploadedFile {
+                    relative_path,
+                    tmp_path,
+                    hash: String::new(),
+                    blob_hash: String::new(),
+                    size: 0,
+                });
+
+                let mut hasher = FileHasher::new(hash_algorithm);
+                let mut size = 0;
+                while let Some(chunk) = field.chunk().await? {
+                    hasher.update(&chunk);
+                    size += chunk.len() as i64;
+                    append_file(&mut file, &chunk).await?;
                 }
-            };
-            if let Some(max_saves) = maybe_max_saves {
-                while saves_ref.len() > max_saves {
-                    let old = saves_ref.remove(0);
-                    let old_path = format!("{}/{}.sav", SAVE_DIR, old.uuid);
-                    let _ = std::fs::remove_file(&old_path);
+
+                if let Some(uploaded_file) = uploaded_files.last_mut() {
+                    (uploaded_file.blob_hash, uploaded_file.hash) = hasher.finalize();
+                    uploaded_file.size = size;
                 }
             }
         }
+    }
 
-        Ok(())
+    Ok((file_hash, metadata))
+}
+
+/// Clients sending the whole save as one archive part, not named after any file of `file_hash`,
+/// get it unpacked into its files. Returns the format of the archive unpacked.
+async fn unpack_single_archive(
+    uploaded_files: &mut Vec<UploadedFile>,
+    file_hash: &[FileHash],
+    hash_algorithm: HashAlgorithm,
+) -> Result<Option<ArchiveFormat>, Box<dyn std::error::Error + Send + Sync>> {
+    let [archive] = uploaded_files.as_slice() else {
+        return Ok(None);
+    };
+    if file_hash
+        .iter()
+        .any(|declared| declared.relative_path == archive.relative_path)
+    {
+        return Ok(None);
     }
-    .await;
+    let archive_path = PathBuf::from(&archive.tmp_path);
+    let Some((archive_format, files)) = unpack_archive(&archive_path, hash_algorithm).await? else {
+        return Ok(None);
+    };
 
-    if let Err(e) = result {
-        tracing::error!("Error uploading game save: {}", e);
-        //Try to clean up
-        let _ = fs::remove_file(&tmp_path);
-        let _ = fs::remove_file(&save_path);
-        Err(StatusCode::INTERNAL_SERVER_ERROR)
-    } else {
-        Ok((StatusCode::CREATED, uuid.to_string()))
+    *uploaded_files = files;
+    let _ = fs::remove_file(&archive_path);
+    Ok(Some(archive_format))
+}
+
+/// Files of the zip or tar archive at `archive_path` with its format, `None` if it's neither
+pub async fn unpack_archive(
+    archive_path: &std::path::Path,
+    hash_algorithm: HashAlgorithm,
+) -> Result<Option<(ArchiveFormat, Vec<UploadedFile>)>, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(archive_format) = archive_format(archive_path).await? else {
+        return Ok(None);
+    };
+
+    let files = extract_archive(archive_path.to_path_buf(), archive_format, hash_algorithm).await?;
+    Ok(Some((archive_format, files)))
+}
+
+pub fn validate_file_hash(file_hash: Vec<FileHash>) -> Result<HashMap<String, String>, String> {
+    let mut declared_hashes: HashMap<String, String> = HashMap::new();
+    for declared in file_hash {
+        if !is_safe_relative_path(&declared.relative_path) {
+            return Err(format!("invalid relative path {}", declared.relative_path));
+        }
+        if declared_hashes
+            .insert(declared.relative_path.clone(), declared.hash)
+            .is_some()
+        {
+            return Err(format!(
+                "duplicate relative path {}",
+                declared.relative_path
+            ));
+        }
+    }
+    Ok(declared_hashes)
+}
+
+pub enum FileHashError {
+    Invalid(String),
+    Mismatch(HashMismatch),
+}
+
+/// Checks the hashes computed while receiving the files against the declared `file_hash` and
+/// returns the digest of the whole save.
+pub fn attach_file_hash(
+    uploaded_files: &[UploadedFile],
+    file_hash: Vec<FileHash>,
+    hash_algorithm: HashAlgorithm,
+) -> Result<SaveDigest, FileHashError> {
+    let mut declared_hashes = validate_file_hash(file_hash).map_err(FileHashError::Invalid)?;
+
+    let mut mismatches = Vec::new();
+    for uploaded_file in uploaded_files.iter() {
+        match declared_hashes.remove(&uploaded_file.relative_path) {
+            Some(hash) if hash.eq_ignore_ascii_case(&uploaded_file.hash) => (),
+            declared_hash => mismatches.push(FileHashMismatch {
+                relative_path: uploaded_file.relative_path.clone(),
+                declared_hash,
+                computed_hash: Some(uploaded_file.hash.clone()),
+            }),
+        }
+    }
+    mismatches.extend(
+        declared_hashes
+            .into_iter()
+            .map(|(relative_path, hash)| FileHashMismatch {
+                relative_path,
+                declared_hash: Some(hash),
+                computed_hash: None,
+            }),
+    );
+
+    if !mismatches.is_empty() {
+        mismatches
+            .sort_by(|mismatch1, mismatch2| mismatch1.relative_path.cmp(&mismatch2.relative_path));
+        return Err(FileHashError::Mismatch(HashMismatch {
+            hash_algorithm,
+            files: mismatches,
+        }));
+    }
+
+    Ok(SaveDigest {
+        hash_algorithm,
+        archive_digest: archive_digest(hash_algorithm, uploaded_files),
+    })
+}
+
+pub fn file_hash_error_response(e: FileHashError) -> Response {
+    match e {
+        FileHashError::Invalid(e) => {
+            tracing::warn!("Rejected game save upload: {}", e);
+            StatusCode::BAD_REQUEST.into_response()
+        }
+        FileHashError::Mismatch(mismatch) => {
+            tracing::warn!(
+                "Rejected game save upload: {} file(s) don't match file_hash",
+                mismatch.files.len()
+            );
+            (StatusCode::UNPROCESSABLE_ENTITY, Json(mismatch)).into_response()
+        }
     }
 }
 
 #[utoipa::path(
-    get,
+    method(get, head),
     path = concatcp!(ROOT_API_PATH, "/saves/{uuid}"),
     params(
         ("uuid" = String, Path, description = "UUID of the game save")
     ),
     responses(
-        (status = StatusCode::OK, description = "game save file returned", content_type = "application/octet-stream"),
-        (status = StatusCode::NOT_FOUND, description = "save not found")
+        (status = StatusCode::OK, description = "game save files returned as a tar archive named `<uuid>.tar`, or as `<uuid>.sav` in the format of the archive the save was uploaded as (a zip is sent whole, without ranges or validators). The tar is zstd encoded when the client accepts it and the files are stored as zstd. Supports `Range`, `If-None-Match` and `If-Modified-Since`, the `ETag` is derived from the save digest", content_type = "application/x-tar"),
+        (status = StatusCode::PARTIAL_CONTENT, description = "requested byte range of the archive returned"),
+        (status = StatusCode::NOT_MODIFIED, description = "the client already holds this save"),
+        (status = StatusCode::RANGE_NOT_SATISFIABLE, description = "requested range outside the archive"),
+        (status = StatusCode::NOT_FOUND, description = "save not found, or legacy archive missing from the save store", body = DownloadNotFound)
     )
 )]
-pub async fn get_game_save_by_uuid(Path((uuid,)): Path<(String,)>) -> impl IntoResponse {
-    let file_path = format!("{}/{}.sav", SAVE_DIR, uuid);
-    let path_buf = PathBuf::from(&file_path);
-
-    match File::open(&path_buf).await {
-        Ok(file) => {
-            // Stream the file contents
-            let stream = ReaderStream::new(file);
-            let body = Body::from_stream(stream);
-
-            // Detect the MIME type (defaults to application/octet-stream)
-            let mime = mime_guess::from_path(&path_buf).first_or_octet_stream();
-
-            // Return the file as an attachment
-            Response::builder()
-                .header("Content-Type", mime.to_string())
+pub async fn get_game_save_by_uuid(
+    Path((uuid,)): Path<(String,)>,
+    Extension(caller): Extension<Caller>,
+    headers: HeaderMap,
+) -> Response {
+    save_response(
+        &DATABASE,
+        SAVE_STORE.as_ref(),
+        &uuid,
+        caller.user_id,
+        &headers,
+    )
+    .await
+}
+
+async fn save_response(
+    database: &GameDatabase,
+    store: &'static dyn SaveStore,
+    uuid: &str,
+    user_id: i32,
+    headers: &HeaderMap,
+) -> Response {
+    let manifest = match database.get_save_manifest(uuid, user_id) {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => return download_not_found(MissingDownload::Save, uuid, None),
+        Err(e) => {
+            tracing::error!("Error getting game save manifest: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    if manifest.legacy_archive {
+        return get_legacy_archive(store, uuid, manifest.time, headers).await;
+    }
+
+    // Saves uploaded as a zip archive go back to their client as one, the whole archive at once
+    if manifest.archive_format == Some(ArchiveFormat::Zip) {
+        let entries = match archive_entries(manifest.files, "", manifest.time) {
+            Ok(entries) => entries,
+            Err(status) => return status.into_response(),
+        };
+        return Response::builder()
+            .header("Content-Type", "application/zip")
+            .header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}.sav\"", uuid),
+            )
+            .body(Body::from_stream(zip_stream(entries, store)))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    // Stored zstd frames are passed through as is when every file is compressed
+    let encoding = if accepts_zstd(headers)
+        && !manifest.files.is_empty()
+        && manifest.files.iter().all(|file| file.codec == Codec::Zstd)
+    {
+        Codec::Zstd
+    } else {
+        Codec::Identity
+    };
+    let entries = match archive_entries(manifest.files, "", manifest.time) {
+        Ok(entries) => entries,
+        Err(status) => return status.into_response(),
+    };
+
+    // Saves uploaded as a tar archive keep the name their client gave them
+    let extension = match manifest.archive_format {
+        Some(_) => "sav",
+        None => "tar",
+    };
+    match TarArchive::new(entries, encoding, store) {
+        Ok(archive) => {
+            let validators = Validators::new(
+                manifest.archive_digest.as_deref(),
+                archive.encoding,
+                manifest.time,
+            );
+            let builder = with_content_encoding(Response::builder(), archive.encoding)
+                .header("Content-Type", "application/x-tar")
                 .header(
                     "Content-Disposition",
-                    format!("attachment; filename=\"{}.sav\"", uuid),
-                )
-                .body(body)
-                .unwrap_or_else(|_| {
-                    Response::builder()
-                        .status(StatusCode::INTERNAL_SERVER_ERROR)
-                        .body(Body::empty())
-                        .unwrap()
+                    format!("attachment; filename=\"{}.{}\"", uuid, extension),
+                );
+            let size = archive.size;
+            download_response(builder, headers, &validators, size, |range| {
+                Body::from_stream(archive.into_range_stream(range))
+            })
+        }
+        Err(e) => {
+            tracing::error!("Error building game save archive: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Archive entries of the files of a save, their paths prefixed by `directory`
+fn archive_entries(
+    files: Vec<SaveFile>,
+    directory: &str,
+    time: i64,
+) -> Result<Vec<ArchiveEntry>, StatusCode> {
+    let mut entries = Vec::with_capacity(files.len());
+    for file in files {
+        let blob_key = blob_key(file.encryption.as_ref(), &file.blob_hash).map_err(|e| {
+            tracing::error!("Error getting the key of blob {}: {}", file.blob_hash, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        entries.push(ArchiveEntry {
+            path: format!("{}{}", directory, file.relative_path),
+            size: u64::try_from(file.size).unwrap_or_default(),
+            mtime: u64::try_from(time).unwrap_or_default(),
+            content: EntryContent::Blob {
+                object: blob_object(&file.blob_hash),
+                codec: file.codec,
+                stored_size: u64::try_from(file.stored_size).unwrap_or_default(),
+                blob_key,
+            },
+        });
+    }
+    Ok(entries)
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/saves/bundle"),
+    params(SaveBundleQuery),
+    responses(
+        (status = StatusCode::OK, description = "saves returned as a single tar or zip archive laid out as `<game default_name>/<path id>/<relative path>`, with a `manifest.json` listing their `SaveReference`. Legacy archives are included as `<uuid>.sav`", content_type = "application/x-tar"),
+        (status = StatusCode::BAD_REQUEST, description = "neither or both of `uuids` and `latest` given, or several saves of the same path"),
+        (status = StatusCode::NOT_FOUND, description = "a save isn't found, or a legacy archive is missing from the save store", body = DownloadNotFound)
+    )
+)]
+pub async fn get_save_bundle(
+    Query(query): Query<SaveBundleQuery>,
+    Extension(caller): Extension<Caller>,
+) -> Response {
+    save_bundle_response(&DATABASE, SAVE_STORE.as_ref(), query, caller.user_id).await
+}
+
+async fn save_bundle_response(
+    database: &GameDatabase,
+    store: &'static dyn SaveStore,
+    query: SaveBundleQuery,
+    user_id: i32,
+) -> Response {
+    let uuids: Option<Vec<String>> = query.uuids.map(|uuids| {
+        uuids
+            .split(',')
+            .map(|uuid| uuid.trim().to_string())
+            .filter(|uuid| !uuid.is_empty())
+            .collect()
+    });
+    if uuids.is_some() == query.latest {
+        return (
+            StatusCode::BAD_REQUEST,
+            "either uuids or latest=true is required",
+        )
+            .into_response();
+    }
+
+    let saves = match database.get_bundle_saves(uuids.as_deref(), user_id) {
+        Ok(saves) => saves,
+        Err(e) => {
+            tracing::error!("Error getting the saves of the bundle: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    if let Some(missing) = uuids
+        .iter()
+        .flatten()
+        .find(|uuid| !saves.iter().any(|save| &&save.reference.uuid == uuid))
+    {
+        return download_not_found(MissingDownload::Save, missing, None);
+    }
+    if saves
+        .iter()
+        .map(|save| save.reference.path_id)
+        .duplicates()
+        .next()
+        .is_some()
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            "a bundle holds at most one save per path",
+        )
+            .into_response();
+    }
+
+    let mut entries = Vec::new();
+    for save in &saves {
+        let uuid = &save.reference.uuid;
+        let directory = format!(
+            "{}/{}/",
+            portable_file_name(&save.game_name),
+            save.reference.path_id
+        );
+        let manifest = match database.get_save_manifest(uuid, user_id) {
+            Ok(Some(manifest)) => manifest,
+            Ok(None) => return download_not_found(MissingDownload::Save, uuid, None),
+            Err(e) => {
+                tracing::error!("Error getting game save manifest: {}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+        if manifest.legacy_archive {
+            let object = legacy_archive_object(uuid);
+            let size = match store.stat(&object).await {
+                Ok(Some(size)) => size,
+                Ok(None) => return download_not_found(MissingDownload::StoredContent, uuid, None),
+                Err(e) => {
+                    tracing::error!("Error getting legacy archive {}: {}", uuid, e);
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            };
+            entries.push(ArchiveEntry {
+                path: format!("{}{}", directory, object),
+                size,
+                mtime: u64::try_from(manifest.time).unwrap_or_default(),
+                content: EntryContent::Blob {
+                    object,
+                    codec: Codec::Identity,
+                    stored_size: size,
+                    blob_key: None,
+                },
+            });
+            continue;
+        }
+        match archive_entries(manifest.files, &directory, manifest.time) {
+            Ok(save_entries) => entries.extend(save_entries),
+            Err(status) => return status.into_response(),
+        }
+    }
+
+    let time = saves
+        .iter()
+        .map(|save| save.reference.time)
+        .max()
+        .unwrap_or_default();
+    let references: Vec<SaveReference> = saves.into_iter().map(|save| save.reference).collect();
+    let manifest = match serde_json::to_vec_pretty(&references) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            tracing::error!("Error serializing the bundle manifest: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    entries.push(ArchiveEntry {
+        path: "manifest.json".to_string(),
+        size: manifest.len() as u64,
+        mtime: u64::try_from(time).unwrap_or_default(),
+        content: EntryContent::Bytes(Bytes::from(manifest)),
+    });
+
+    let response = match query.format {
+        BundleFormat::Tar => match TarArchive::new(entries, Codec::Identity, store) {
+            Ok(archive) => Response::builder()
+                .header("Content-Type", "application/x-tar")
+                .header("Content-Length", archive.size)
+                .header("Content-Disposition", "attachment; filename=\"saves.tar\"")
+                .body(Body::from_stream(archive.into_stream())),
+            Err(e) => {
+                tracing::error!("Error building the save bundle: {}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        },
+        BundleFormat::Zip => Response::builder()
+            .header("Content-Type", "application/zip")
+            .header("Content-Disposition", "attachment; filename=\"saves.zip\"")
+            .body(Body::from_stream(zip_stream(entries, store))),
+    };
+    response.unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/saves/{uuid}/files"),
+    params(
+        ("uuid" = String, Path, description = "UUID of the game save")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "files of the game save returned", body = [FileHash]),
+        (status = StatusCode::NOT_FOUND, description = "save not found")
+    )
+)]
+pub async fn get_game_save_files(
+    Path((uuid,)): Path<(String,)>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<Vec<FileHash>>, StatusCode> {
+    save_files(&DATABASE, &uuid, caller.user_id)
+}
+
+fn save_files(
+    database: &GameDatabase,
+    uuid: &str,
+    user_id: i32,
+) -> Result<Json<Vec<FileHash>>, StatusCode> {
+    match database.get_save_file_hashes(uuid, user_id) {
+        Ok(Some(files)) => Ok(Json(
+            files
+                .into_iter()
+                .sorted_by(|file1, file2| file1.relative_path.cmp(&file2.relative_path))
+                .map(|file| FileHash {
+                    relative_path: file.relative_path,
+                    hash: file.hash,
+                    size: file.size,
+                })
+                .collect(),
+        )),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Error getting game save files: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    method(get, head),
+    path = concatcp!(ROOT_API_PATH, "/saves/{uuid}/files/{relative_path}"),
+    params(
+        ("uuid" = String, Path, description = "UUID of the game save"),
+        ("relative_path" = String, Path, description = "relative path of the file inside the save")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "file content returned, zstd encoded when the client accepts it and the file is stored as zstd. Supports `Range`, `If-None-Match` and `If-Modified-Since`, the `ETag` is derived from the file hash", content_type = "application/octet-stream"),
+        (status = StatusCode::PARTIAL_CONTENT, description = "requested byte range of the file returned"),
+        (status = StatusCode::NOT_MODIFIED, description = "the client already holds this file"),
+        (status = StatusCode::RANGE_NOT_SATISFIABLE, description = "requested range outside the file"),
+        (status = StatusCode::NOT_FOUND, description = "save or file not found, or file content missing from the save store. Files of legacy archives can't be downloaded one by one", body = DownloadNotFound)
+    )
+)]
+pub async fn get_game_save_file(
+    Path((uuid, relative_path)): Path<(String, String)>,
+    Extension(caller): Extension<Caller>,
+    headers: HeaderMap,
+) -> Response {
+    save_file_response(
+        &DATABASE,
+        SAVE_STORE.as_ref(),
+        &uuid,
+        caller.user_id,
+        &relative_path,
+        &headers,
+    )
+    .await
+}
+
+/// Only the paths listed in the manifest of the save are served, whatever `relative_path` holds
+async fn save_file_response(
+    database: &GameDatabase,
+    store: &'static dyn SaveStore,
+    uuid: &str,
+    user_id: i32,
+    relative_path: &str,
+    headers: &HeaderMap,
+) -> Response {
+    let manifest = match database.get_save_manifest(uuid, user_id) {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => return download_not_found(MissingDownload::Save, uuid, None),
+        Err(e) => {
+            tracing::error!("Error getting game save manifest: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let Some(file) = manifest_file(manifest.files, relative_path) else {
+        return download_not_found(MissingDownload::File, uuid, Some(relative_path));
+    };
+    let blob_key = match blob_key(file.encryption.as_ref(), &file.blob_hash) {
+        Ok(blob_key) => blob_key,
+        Err(e) => {
+            tracing::error!("Error getting the key of blob {}: {}", file.blob_hash, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let object = blob_object(&file.blob_hash);
+    match store.stat(&object).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return download_not_found(MissingDownload::StoredContent, uuid, Some(relative_path));
+        }
+        Err(e) => {
+            tracing::error!("Error getting blob {}: {}", file.blob_hash, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let (encoding, content_length) = if file.codec == Codec::Zstd && accepts_zstd(headers) {
+        (Codec::Zstd, file.stored_size)
+    } else {
+        (Codec::Identity, file.size)
+    };
+    let validators = Validators::new(Some(&file.hash), encoding, manifest.time);
+
+    let file_name = relative_path
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(relative_path);
+    let builder = with_content_encoding(Response::builder(), encoding)
+        .header(
+            "Content-Type",
+            mime_guess::from_path(relative_path)
+                .first_or_octet_stream()
+                .to_string(),
+        )
+        .header("Content-Disposition", attachment_disposition(file_name));
+    let size = u64::try_from(file.size).unwrap_or_default();
+    download_response(
+        builder,
+        headers,
+        &validators,
+        u64::try_from(content_length).unwrap_or_default(),
+        |range| {
+            let open = async move {
+                let blob = open_blob(store, &object, blob_key).await?;
+                Ok(match encoding {
+                    Codec::Zstd => blob,
+                    Codec::Identity => Box::new(decoded_reader(blob, file.codec).take(size)),
                 })
+            };
+            range_body(open, range)
+        },
+    )
+}
+
+/// Adds the `Content-Encoding` of a download, the response depends on `Accept-Encoding` either way
+fn with_content_encoding(builder: Builder, encoding: Codec) -> Builder {
+    let builder = builder.header(header::VARY, "Accept-Encoding");
+    match encoding {
+        Codec::Identity => builder,
+        Codec::Zstd => builder.header(header::CONTENT_ENCODING, "zstd"),
+    }
+}
+
+fn download_not_found(
+    missing: MissingDownload,
+    uuid: &str,
+    relative_path: Option<&str>,
+) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(DownloadNotFound {
+            missing,
+            uuid: uuid.to_string(),
+            relative_path: relative_path.map(str::to_string),
+        }),
+    )
+        .into_response()
+}
+
+/// The file of a save manifest at `relative_path`. Only the paths listed in the manifest are
+/// served, so paths with `..` components or absolute paths never reach the stored blobs.
+fn manifest_file(files: Vec<SaveFile>, relative_path: &str) -> Option<SaveFile> {
+    files
+        .into_iter()
+        .find(|file| file.relative_path == relative_path)
+}
+
+async fn get_legacy_archive(
+    store: &'static dyn SaveStore,
+    uuid: &str,
+    time: i64,
+    headers: &HeaderMap,
+) -> Response {
+    let object = legacy_archive_object(uuid);
+    let size = match store.stat(&object).await {
+        Ok(Some(size)) => size,
+        Ok(None) => return download_not_found(MissingDownload::StoredContent, uuid, None),
+        Err(e) => {
+            tracing::error!("Error getting legacy archive {}: {}", uuid, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // Legacy archives have no digest, only their date validates them
+    let validators = Validators::new(None, Codec::Identity, time);
+    // Detect the MIME type (defaults to application/octet-stream)
+    let mime = mime_guess::from_path(&object).first_or_octet_stream();
+    let builder = Response::builder()
+        .header("Content-Type", mime.to_string())
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{}.sav\"", uuid),
+        );
+    download_response(builder, headers, &validators, size, |range| {
+        let open = async move { store.get(&object).await.map(|archive| archive.reader) };
+        range_body(open, range)
+    })
+}
+
+#[utoipa::path(
+    patch,
+    path = concatcp!(ROOT_API_PATH, "/saves/{uuid}"),
+    params(
+        ("uuid" = String, Path, description = "UUID of the game save")
+    ),
+    request_body = SaveMetadataUpdate,
+    responses(
+        (status = StatusCode::OK, description = "label and note of the game save updated"),
+        (status = StatusCode::NOT_FOUND, description = "save not found")
+    )
+)]
+pub async fn patch_game_save_by_uuid(
+    Path((uuid,)): Path<(String,)>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<SaveMetadataUpdate>,
+) -> StatusCode {
+    match DATABASE.update_save_metadata(&uuid, caller.user_id, &payload) {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!("Error updating game save metadata: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = concatcp!(ROOT_API_PATH, "/saves/{uuid}/restore"),
+    params(
+        ("uuid" = String, Path, description = "UUID of the game save to restore"),
+        SaveRestore
+    ),
+    responses(
+        (status = StatusCode::CREATED, description = "game save restored as the latest save of its path, the UUID of the new save is returned", body = String),
+        (status = StatusCode::NOT_FOUND, description = "save not found"),
+        (status = StatusCode::CONFLICT, description = "save marked as corrupt by the integrity scrub"),
+        (status = StatusCode::LOCKED, description = "the game is locked by another lock_holder", body = GameLock)
+    )
+)]
+pub async fn post_game_save_restore(
+    Path((uuid,)): Path<(String,)>,
+    Query(restore): Query<SaveRestore>,
+    Extension(caller): Extension<Caller>,
+) -> Result<(StatusCode, String), Response> {
+    let restored_uuid = Uuid::new_v4();
+    let path_id = restore_save_response(
+        &DATABASE,
+        SAVE_STORE.as_ref(),
+        &uuid,
+        caller.save_owner(),
+        restore.lock_holder.as_deref(),
+        restored_uuid,
+    )
+    .await?;
+
+    if let Err(e) = prune_saves(path_id, caller.user_id).await {
+        tracing::error!("Error pruning game saves: {}", e);
+    }
+
+    Ok((StatusCode::CREATED, restored_uuid.to_string()))
+}
+
+/// Path of the restored save
+async fn restore_save_response(
+    database: &GameDatabase,
+    store: &'static dyn SaveStore,
+    uuid: &str,
+    owner: SaveOwner,
+    lock_holder: Option<&str>,
+    restored_uuid: Uuid,
+) -> Result<i32, Response> {
+    let restoring =
+        blob_store::restore_save(database, store, uuid, owner, lock_holder, restored_uuid);
+    match restoring.await {
+        Ok(Some(path_id)) => Ok(path_id),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => match e.downcast::<CorruptSave>() {
+            Ok(corrupt) => {
+                tracing::info!("Rejected game save restore: {}", corrupt);
+                Err((StatusCode::CONFLICT, "save is corrupt").into_response())
+            }
+            Err(e) => Err(add_save_error_response(e)),
+        },
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/saves/{uuid}/diff/{other}"),
+    params(
+        ("uuid" = String, Path, description = "UUID of the older game save"),
+        ("other" = String, Path, description = "UUID of the newer game save")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "files added, removed and modified between the two saves", body = SaveDiff),
+        (status = StatusCode::NOT_FOUND, description = "one of the saves was not found")
+    )
+)]
+pub async fn get_game_save_diff(
+    Path((uuid, other)): Path<(String, String)>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<SaveDiff>, StatusCode> {
+    match get_save_diff(&uuid, &other, caller.user_id) {
+        Ok(Some(diff)) => Ok(Json(diff)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Error comparing game saves: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn set_game_save_pinned(uuid: &str, user_id: i32, pinned: bool) -> StatusCode {
+    match DATABASE.set_save_pinned(uuid, user_id, pinned) {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!("Error updating game save pin: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = concatcp!(ROOT_API_PATH, "/saves/{uuid}/pin"),
+    params(
+        ("uuid" = String, Path, description = "UUID of the game save")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "game save pinned, it won't be pruned"),
+        (status = StatusCode::NOT_FOUND, description = "save not found")
+    )
+)]
+pub async fn put_game_save_pin(
+    Path((uuid,)): Path<(String,)>,
+    Extension(caller): Extension<Caller>,
+) -> StatusCode {
+    set_game_save_pinned(&uuid, caller.user_id, true)
+}
+
+#[utoipa::path(
+    delete,
+    path = concatcp!(ROOT_API_PATH, "/saves/{uuid}/pin"),
+    params(
+        ("uuid" = String, Path, description = "UUID of the game save")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "game save unpinned"),
+        (status = StatusCode::NOT_FOUND, description = "save not found")
+    )
+)]
+pub async fn delete_game_save_pin(
+    Path((uuid,)): Path<(String,)>,
+    Extension(caller): Extension<Caller>,
+) -> StatusCode {
+    set_game_save_pinned(&uuid, caller.user_id, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::interface::SaveOwner;
+    use crate::save_store::LocalStore;
+    use crate::test_util::{test_game_with_path, test_player, test_save};
+    use axum::extract::FromRequest;
+    use axum::http::Request;
+    use std::error::Error;
+
+    async fn multipart(parts: &[(&str, &str)]) -> Result<Multipart, Box<dyn Error + Send + Sync>> {
+        let mut body = String::new();
+        for (name, content) in parts {
+            body.push_str(&format!(
+                "--boundary\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{content}\r\n"
+            ));
+        }
+        body.push_str("--boundary--\r\n");
+        let request = Request::builder()
+            .header(
+                header::CONTENT_TYPE,
+                "multipart/form-data; boundary=boundary",
+            )
+            .body(Body::from(body))?;
+        Ok(Multipart::from_request(request, &())
+            .await
+            .map_err(|e| e.body_text())?)
+    }
+
+    #[tokio::test]
+    async fn test_receive_save_files_invalid_parts() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut uploaded_files = Vec::new();
+        let mut valid = multipart(&[
+            (
+                "file_hash",
+                r#"[{"relative_path":"slot1.sav","hash":"abc"}]"#,
+            ),
+            ("metadata", r#"{"hostname":"steamdeck"}"#),
+        ])
+        .await?;
+        let (file_hash, metadata) =
+            receive_save_files(&mut valid, HashAlgorithm::Sha256, &mut uploaded_files).await?;
+        assert_eq!(file_hash[0].relative_path, "slot1.sav");
+        assert_eq!(metadata.hostname.as_deref(), Some("steamdeck"));
+
+        for (name, content) in [("file_hash", "[{"), ("metadata", "not json")] {
+            let mut invalid = multipart(&[(name, content)]).await?;
+            let Err(e) =
+                receive_save_files(&mut invalid, HashAlgorithm::Sha256, &mut uploaded_files).await
+            else {
+                panic!("malformed {name} accepted");
+            };
+            assert_eq!(receive_error_response(e).status(), StatusCode::BAD_REQUEST);
+        }
+        assert!(uploaded_files.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_save_files() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let database =
+            GameDatabase::new(&format!("file:{}?mode=memory&cache=shared", Uuid::new_v4()));
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(&dir).await?;
+        let store: &'static LocalStore = Box::leak(Box::new(LocalStore::new(dir.join("store"))));
+
+        test_game_with_path(&database, "Files")?;
+
+        let mut files = Vec::new();
+        for (relative_path, content) in [
+            ("slots/sauvegarde é.sav", b"first slot".as_slice()),
+            ("config.ini", b"[config]".as_slice()),
+        ] {
+            let (blob_hash, hash) = {
+                let mut hasher = FileHasher::new(HashAlgorithm::Sha256);
+                hasher.update(content);
+                hasher.finalize()
+            };
+            let content_path = dir.join(&blob_hash);
+            tokio::fs::write(&content_path, content).await?;
+            store.put(&blob_object(&blob_hash), &content_path).await?;
+            files.push(SaveFile {
+                relative_path: relative_path.to_string(),
+                hash,
+                blob_hash,
+                size: content.len() as i64,
+                codec: Codec::Identity,
+                stored_size: content.len() as i64,
+                encryption: None,
+            });
+        }
+        let uuid = Uuid::new_v4().to_string();
+        database.add_reference_to_save(
+            Uuid::parse_str(&uuid)?,
+            1,
+            &SaveParent::default(),
+            &SaveMetadata::default(),
+            SaveOwner {
+                user_id: 1,
+                device_id: None,
+            },
+            None,
+            Codec::Identity,
+            None,
+            None,
+            files,
+        )?;
+
+        let Json(listed) = save_files(&database, &uuid, 1).map_err(|status| status.to_string())?;
+        assert_eq!(
+            listed
+                .iter()
+                .map(|file| file.relative_path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["config.ini", "slots/sauvegarde é.sav"]
+        );
+        assert_eq!(listed[1].size, Some(10));
+        assert_eq!(
+            save_files(&database, &Uuid::new_v4().to_string(), 1).err(),
+            Some(StatusCode::NOT_FOUND)
+        );
+
+        let headers = HeaderMap::new();
+        let response = save_file_response(
+            &database,
+            store,
+            &uuid,
+            1,
+            "slots/sauvegarde é.sav",
+            &headers,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"sauvegarde _.sav\"; filename*=UTF-8''sauvegarde%20%C3%A9.sav"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        assert_eq!(body.as_ref(), b"first slot");
+
+        for relative_path in [
+            "unknown.sav",
+            "../slots/sauvegarde é.sav",
+            "slots/../config.ini",
+            "/config.ini",
+        ] {
+            let response =
+                save_file_response(&database, store, &uuid, 1, relative_path, &headers).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{relative_path}");
+        }
+        let response = save_file_response(
+            &database,
+            store,
+            &Uuid::new_v4().to_string(),
+            1,
+            "config.ini",
+            &headers,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_saves_of_another_user_are_not_found() -> Result<(), Box<dyn Error + Send + Sync>>
+    {
+        let database =
+            GameDatabase::new(&format!("file:{}?mode=memory&cache=shared", Uuid::new_v4()));
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let store: &'static LocalStore = Box::leak(Box::new(LocalStore::new(dir.join("store"))));
+        test_game_with_path(&database, "Isolated")?;
+        let player = test_player(&database)?;
+        let admin = SaveOwner {
+            user_id: 1,
+            device_id: None,
+        };
+        let uuid = test_save(&database, 1, admin)?;
+        let bundle = || SaveBundleQuery {
+            uuids: Some(uuid.clone()),
+            ..Default::default()
+        };
+
+        let headers = HeaderMap::new();
+        for (user_id, status) in [(1, StatusCode::OK), (player.id, StatusCode::NOT_FOUND)] {
+            let response = save_response(&database, store, &uuid, user_id, &headers).await;
+            assert_eq!(response.status(), status);
+            assert_eq!(
+                save_files(&database, &uuid, user_id).err(),
+                (status == StatusCode::NOT_FOUND).then_some(status)
+            );
+            let response = save_bundle_response(&database, store, bundle(), user_id).await;
+            assert_eq!(response.status(), status);
+        }
+
+        let player_owner = SaveOwner {
+            user_id: player.id,
+            device_id: None,
+        };
+        let Err(response) =
+            restore_save_response(&database, store, &uuid, player_owner, None, Uuid::new_v4())
+                .await
+        else {
+            panic!("save of another user restored");
+        };
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(
+            database
+                .get_reference_to_save_by_path_id(1, player.id)?
+                .is_none()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_file_only_serves_listed_paths() {
+        let files = || {
+            vec![SaveFile {
+                relative_path: "slots/slot1.sav".to_string(),
+                hash: "hash".to_string(),
+                blob_hash: "blob".to_string(),
+                size: 4,
+                codec: Codec::Identity,
+                stored_size: 4,
+                encryption: None,
+            }]
+        };
+        assert!(manifest_file(files(), "slots/slot1.sav").is_some());
+        for relative_path in [
+            "../slots/slot1.sav",
+            "slots/../slots/slot1.sav",
+            "/slots/slot1.sav",
+            "slots\\slot1.sav",
+        ] {
+            assert!(
+                manifest_file(files(), relative_path).is_none(),
+                "{relative_path}"
+            );
         }
-        Err(_) => Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(Body::empty())
-            .unwrap(),
     }
 }
@@ -0,0 +1,41 @@
+use crate::DATABASE;
+use crate::const_var::ROOT_API_PATH;
+use crate::datatype_endpoint::ScrubReport;
+use crate::integrity_scrub::spawn_scrub;
+use axum::{Json, http::StatusCode};
+use const_format::concatcp;
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/scrub"),
+    responses(
+        (status = StatusCode::OK, description = "report of the last integrity scrub", body = ScrubReport),
+        (status = StatusCode::NOT_FOUND, description = "no integrity scrub ran yet")
+    )
+)]
+pub async fn get_scrub_report() -> Result<Json<ScrubReport>, StatusCode> {
+    match DATABASE.get_scrub_report() {
+        Ok(Some(report)) => Ok(Json(report)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Error getting integrity scrub report: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = concatcp!(ROOT_API_PATH, "/scrub"),
+    responses(
+        (status = StatusCode::ACCEPTED, description = "integrity scrub started, its report is returned by `GET /scrub` once done"),
+        (status = StatusCode::CONFLICT, description = "an integrity scrub is already running")
+    )
+)]
+pub async fn post_scrub() -> StatusCode {
+    if spawn_scrub() {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::CONFLICT
+    }
+}
@@ -0,0 +1,165 @@
+use crate::DATABASE;
+use crate::auth::Caller;
+use crate::const_var::ROOT_API_PATH;
+use crate::database::interface::CatalogUpdate;
+use crate::datatype_endpoint::{
+    ApiToken, ApiTokenCreate, ApiTokenCreated, TokenScope, User, UserQuery,
+};
+use crate::route_users::target_user;
+use axum::{
+    Json,
+    extract::{Extension, Path, Query},
+    http::StatusCode,
+};
+use const_format::concatcp;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+// Only administrators can hold a token with the admin scope
+fn is_valid(token: &ApiTokenCreate, user: &User) -> bool {
+    !token.name.trim().is_empty()
+        && !token.scopes.is_empty()
+        && (user.admin || !token.scopes.contains(&TokenScope::Admin))
+        && token
+            .expires
+            .is_none_or(|expires| expires > OffsetDateTime::now_utc().unix_timestamp())
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/tokens"),
+    responses(
+        (status = StatusCode::OK, description = "every token, without its value", body = [ApiToken]),
+    )
+)]
+pub async fn get_tokens() -> Result<Json<Vec<ApiToken>>, StatusCode> {
+    match DATABASE.get_api_token_details() {
+        Ok(tokens) => Ok(Json(tokens)),
+        Err(e) => {
+            tracing::error!("Error getting tokens: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = concatcp!(ROOT_API_PATH, "/tokens"),
+    params(UserQuery),
+    request_body = ApiTokenCreate,
+    responses(
+        (status = StatusCode::CREATED, description = "token created, its value is only returned now", body = ApiTokenCreated),
+        (status = StatusCode::BAD_REQUEST, description = "empty name or scopes, expiry in the past, unknown user, or admin scope for a user who isn't an administrator"),
+    )
+)]
+pub async fn post_token(
+    Extension(caller): Extension<Caller>,
+    Query(query): Query<UserQuery>,
+    Json(payload): Json<ApiTokenCreate>,
+) -> Result<(StatusCode, Json<ApiTokenCreated>), StatusCode> {
+    let user = target_user(&caller, query)?;
+    if !is_valid(&payload, &user) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let value = Uuid::new_v4();
+    match DATABASE.add_api_token(&payload, user.id, value) {
+        Ok(token) => Ok((
+            StatusCode::CREATED,
+            Json(ApiTokenCreated {
+                token,
+                value: value.to_string(),
+            }),
+        )),
+        Err(e) => {
+            tracing::error!("Error adding token: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/tokens/{Id}"),
+    params(
+        ("Id" = String, Path, description = "Id of the token")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "token returned, without its value", body = ApiToken),
+        (status = StatusCode::NOT_FOUND, description = "token not found")
+    )
+)]
+pub async fn get_token(Path(id): Path<i32>) -> Result<Json<ApiToken>, StatusCode> {
+    match DATABASE.get_api_token_detail(id) {
+        Ok(Some(token)) => Ok(Json(token)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Error getting token: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = concatcp!(ROOT_API_PATH, "/tokens/{Id}"),
+    params(
+        ("Id" = String, Path, description = "Id of the token")
+    ),
+    request_body = ApiTokenCreate,
+    responses(
+        (status = StatusCode::OK, description = "name, scopes and expiry replaced"),
+        (status = StatusCode::BAD_REQUEST, description = "empty name or scopes, expiry in the past, or admin scope for a user who isn't an administrator"),
+        (status = StatusCode::NOT_FOUND, description = "token not found"),
+        (status = StatusCode::CONFLICT, description = "no token with the admin scope would be left")
+    )
+)]
+pub async fn put_token(Path(id): Path<i32>, Json(payload): Json<ApiTokenCreate>) -> StatusCode {
+    let user = DATABASE
+        .get_api_token_detail(id)
+        .and_then(|token| match token {
+            Some(token) => DATABASE.get_user(token.user_id),
+            None => Ok(None),
+        });
+    let user = match user {
+        Ok(Some(user)) => user,
+        Ok(None) => return StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!("Error getting the user of the token: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+    if !is_valid(&payload, &user) {
+        return StatusCode::BAD_REQUEST;
+    }
+    token_update_status(DATABASE.update_api_token(id, &payload))
+}
+
+#[utoipa::path(
+    delete,
+    path = concatcp!(ROOT_API_PATH, "/tokens/{Id}"),
+    params(
+        ("Id" = String, Path, description = "Id of the token")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "token revoked"),
+        (status = StatusCode::NOT_FOUND, description = "token not found"),
+        (status = StatusCode::CONFLICT, description = "no token with the admin scope would be left")
+    )
+)]
+pub async fn delete_token(Path(id): Path<i32>) -> StatusCode {
+    token_update_status(DATABASE.remove_api_token(id))
+}
+
+fn token_update_status(
+    result: Result<CatalogUpdate, Box<dyn std::error::Error + Send + Sync>>,
+) -> StatusCode {
+    match result {
+        Ok(CatalogUpdate::Done) => StatusCode::OK,
+        Ok(CatalogUpdate::NotFound) => StatusCode::NOT_FOUND,
+        Ok(CatalogUpdate::Conflict) => StatusCode::CONFLICT,
+        Err(e) => {
+            tracing::error!("Error updating token: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
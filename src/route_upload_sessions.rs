@@ -0,0 +1,376 @@
+use crate::DATABASE;
+use crate::auth::Caller;
+use crate::blob_store::{self, upload_session_path};
+use crate::const_var::{MAX_BODY_SIZE, ROOT_API_PATH};
+use crate::database::interface::{CatalogUpdate, UploadSessionRecord};
+use crate::datatype_endpoint::{
+    ByteRange, GameLock, HashMismatch, SaveConflict, SaveParent, UploadSession, UploadSessionCreate,
+};
+use crate::file_system::{append_file, create_tmp_file};
+use crate::retention::prune_saves;
+use crate::route_saves::{
+    add_save_error_response, attach_file_hash, file_hash_error_response, unpack_archive,
+    validate_file_hash,
+};
+use axum::body::Body;
+use axum::extract::{Extension, Query};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, extract::Path, http::StatusCode};
+use const_format::concatcp;
+use futures_util::TryStreamExt;
+use serde::Deserialize;
+use std::io::SeekFrom;
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncSeekExt;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct ChunkParams {
+    offset: i64,
+}
+
+fn merge_ranges(mut ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<ByteRange> = Vec::new();
+    for range in ranges.into_iter().filter(|range| range.end > range.start) {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+fn is_upload_complete(session: &UploadSessionRecord) -> bool {
+    match merge_ranges(session.chunks.clone()).as_slice() {
+        [] => session.size == 0,
+        [range] => range.start == 0 && range.end == session.size,
+        _ => false,
+    }
+}
+
+fn get_upload_session_by_uuid(uuid: &str, user_id: i32) -> Result<UploadSession, StatusCode> {
+    match DATABASE.get_upload_session(uuid, user_id) {
+        Ok(Some(session)) => Ok(UploadSession {
+            uuid: uuid.to_string(),
+            path_id: session.path_id,
+            size: session.size,
+            last_activity: session.last_activity,
+            received: merge_ranges(session.chunks),
+        }),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Error getting upload session: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn write_chunk(
+    tmp_path: &str,
+    offset: i64,
+    max_size: i64,
+    body: Body,
+) -> Result<Option<i64>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut file = OpenOptions::new().write(true).open(tmp_path).await?;
+    file.seek(SeekFrom::Start(offset as u64)).await?;
+
+    let mut written = 0;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.try_next().await? {
+        written += chunk.len() as i64;
+        if offset + written > max_size {
+            return Ok(None);
+        }
+        append_file(&mut file, &chunk).await?;
+    }
+
+    Ok(Some(written))
+}
+
+#[utoipa::path(
+    post,
+    path = concatcp!(ROOT_API_PATH, "/paths/{Id}/saves/uploads"),
+    params(
+        ("Id" = String, Path, description = "Id of the path")
+    ),
+    request_body = UploadSessionCreate,
+    responses(
+        (status = StatusCode::CREATED, description = "upload session created", body = UploadSession),
+        (status = StatusCode::BAD_REQUEST, description = "invalid size or file_hash"),
+        (status = StatusCode::NOT_FOUND, description = "path not found"),
+    )
+)]
+pub async fn post_upload_session(
+    Path(path_id): Path<i32>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<UploadSessionCreate>,
+) -> Result<(StatusCode, Json<UploadSession>), StatusCode> {
+    if payload.size < 0 || payload.size as u64 > MAX_BODY_SIZE as u64 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if let Err(e) = validate_file_hash(payload.file_hash.clone()) {
+        tracing::warn!("Rejected upload session: {}", e);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let uuid = Uuid::new_v4();
+    let tmp_path = upload_session_path(&uuid.to_string());
+    let result = match create_tmp_file(&tmp_path).await {
+        Ok(_) => DATABASE.add_upload_session(
+            uuid,
+            path_id,
+            caller.user_id,
+            payload.size,
+            &payload.file_hash,
+            payload.metadata.as_ref(),
+            payload.hash_algorithm,
+        ),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(CatalogUpdate::Done) => (),
+        Ok(_) => {
+            let _ = fs::remove_file(&tmp_path).await;
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Err(e) => {
+            tracing::error!("Error creating upload session: {}", e);
+            let _ = fs::remove_file(&tmp_path).await;
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(get_upload_session_by_uuid(
+            &uuid.to_string(),
+            caller.user_id,
+        )?),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/uploads/{Uuid}"),
+    params(
+        ("Uuid" = String, Path, description = "UUID of the upload session")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "upload session returned", body = UploadSession),
+        (status = StatusCode::NOT_FOUND, description = "upload session not found")
+    )
+)]
+pub async fn get_upload_session(
+    Path(uuid): Path<String>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<UploadSession>, StatusCode> {
+    Ok(Json(get_upload_session_by_uuid(&uuid, caller.user_id)?))
+}
+
+#[utoipa::path(
+    put,
+    path = concatcp!(ROOT_API_PATH, "/uploads/{Uuid}/chunks/{Number}"),
+    params(
+        ("Uuid" = String, Path, description = "UUID of the upload session"),
+        ("Number" = i32, Path, description = "Number of the chunk, sending it again replaces it"),
+        ("offset" = i64, Query, description = "Offset of the chunk in the uploaded archive")
+    ),
+    request_body(
+        content = String,
+        content_type = "application/octet-stream",
+        description = "chunk of the zip or tar archive of the save"
+    ),
+    responses(
+        (status = StatusCode::OK, description = "chunk received", body = UploadSession),
+        (status = StatusCode::BAD_REQUEST, description = "chunk outside of the upload size"),
+        (status = StatusCode::NOT_FOUND, description = "upload session not found"),
+        (status = StatusCode::CONFLICT, description = "upload session is being finalized")
+    )
+)]
+pub async fn put_upload_chunk(
+    Path((uuid, chunk_number)): Path<(String, i32)>,
+    Query(params): Query<ChunkParams>,
+    Extension(caller): Extension<Caller>,
+    body: Body,
+) -> Result<Json<UploadSession>, StatusCode> {
+    let session = match DATABASE.get_upload_session(&uuid, caller.user_id) {
+        Ok(Some(session)) => session,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Error getting upload session: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if session.finalizing {
+        return Err(StatusCode::CONFLICT);
+    }
+    if params.offset < 0 || params.offset > session.size {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let result = match write_chunk(
+        &upload_session_path(&uuid),
+        params.offset,
+        session.size,
+        body,
+    )
+    .await
+    {
+        Ok(Some(written)) => DATABASE.add_upload_chunk(&uuid, chunk_number, params.offset, written),
+        Ok(None) => return Err(StatusCode::BAD_REQUEST),
+        Err(e) => Err(e),
+    };
+
+    // finalizing may have started while the chunk was written, its hashes catch a torn archive
+    match result {
+        Ok(CatalogUpdate::Done) => (),
+        Ok(CatalogUpdate::NotFound) => return Err(StatusCode::NOT_FOUND),
+        Ok(CatalogUpdate::Conflict) => return Err(StatusCode::CONFLICT),
+        Err(e) => {
+            tracing::error!("Error writing upload chunk: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    Ok(Json(get_upload_session_by_uuid(&uuid, caller.user_id)?))
+}
+
+#[utoipa::path(
+    post,
+    path = concatcp!(ROOT_API_PATH, "/uploads/{Uuid}/finalize"),
+    params(
+        ("Uuid" = String, Path, description = "UUID of the upload session"),
+        SaveParent
+    ),
+    responses(
+        (status = StatusCode::CREATED, description = "game save created", body = String),
+        (status = StatusCode::BAD_REQUEST, description = "invalid archive"),
+        (status = StatusCode::NOT_FOUND, description = "upload session not found"),
+        (status = StatusCode::CONFLICT, description = "upload incomplete, already being finalized or parent_uuid isn't the latest save of the path", body = SaveConflict),
+        (status = StatusCode::LOCKED, description = "the game is locked by another lock_holder", body = GameLock),
+        (status = StatusCode::UNPROCESSABLE_ENTITY, description = "archive doesn't match file_hash", body = HashMismatch)
+    )
+)]
+pub async fn post_upload_session_finalize(
+    Path(uuid): Path<String>,
+    Query(parent): Query<SaveParent>,
+    Extension(caller): Extension<Caller>,
+) -> Result<(StatusCode, String), Response> {
+    let session = match DATABASE.get_upload_session(&uuid, caller.user_id) {
+        Ok(Some(session)) => session,
+        Ok(None) => return Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => {
+            tracing::error!("Error getting upload session: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+
+    if !is_upload_complete(&session) {
+        return Err(StatusCode::CONFLICT.into_response());
+    }
+    match DATABASE.set_upload_session_finalizing(&uuid, true) {
+        Ok(true) => (),
+        Ok(false) => return Err(StatusCode::CONFLICT.into_response()),
+        Err(e) => {
+            tracing::error!("Error finalizing upload session: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    }
+
+    let save_uuid = Uuid::new_v4();
+    let tmp_path = upload_session_path(&uuid);
+    let result = match unpack_archive(tmp_path.as_ref(), session.hash_algorithm).await {
+        Ok(Some((archive_format, uploaded_files))) => {
+            let result = match attach_file_hash(
+                &uploaded_files,
+                session.file_hash,
+                session.hash_algorithm,
+            ) {
+                Ok(digest) => blob_store::add_save(
+                    save_uuid,
+                    session.path_id,
+                    &parent,
+                    &session.metadata,
+                    caller.save_owner(),
+                    &digest,
+                    Some(archive_format),
+                    &uploaded_files,
+                )
+                .await
+                .map_err(add_save_error_response),
+                Err(e) => Err(file_hash_error_response(e)),
+            };
+            for uploaded_file in &uploaded_files {
+                let _ = fs::remove_file(&uploaded_file.tmp_path).await;
+            }
+            result
+        }
+        Ok(None) => {
+            tracing::warn!("Upload session {} isn't a zip or a tar archive", uuid);
+            Err(StatusCode::BAD_REQUEST.into_response())
+        }
+        Err(e) => {
+            tracing::warn!("Invalid archive in upload session {}: {}", uuid, e);
+            Err(StatusCode::BAD_REQUEST.into_response())
+        }
+    };
+
+    if let Err(status) = result {
+        if let Err(e) = DATABASE.set_upload_session_finalizing(&uuid, false) {
+            tracing::error!("Error resetting upload session: {}", e);
+        }
+        return Err(status);
+    }
+
+    if let Err(e) = DATABASE.remove_upload_session(&uuid) {
+        tracing::error!("Error removing upload session: {}", e);
+    }
+    let _ = fs::remove_file(&tmp_path).await;
+
+    if let Err(e) = prune_saves(session.path_id, caller.user_id).await {
+        tracing::error!("Error pruning game saves: {}", e);
+    }
+
+    Ok((StatusCode::CREATED, save_uuid.to_string()))
+}
+
+#[utoipa::path(
+    delete,
+    path = concatcp!(ROOT_API_PATH, "/uploads/{Uuid}"),
+    params(
+        ("Uuid" = String, Path, description = "UUID of the upload session")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "upload session aborted"),
+        (status = StatusCode::NOT_FOUND, description = "upload session not found"),
+        (status = StatusCode::CONFLICT, description = "upload session is being finalized")
+    )
+)]
+pub async fn delete_upload_session(
+    Path(uuid): Path<String>,
+    Extension(caller): Extension<Caller>,
+) -> StatusCode {
+    match DATABASE.get_upload_session(&uuid, caller.user_id) {
+        Ok(Some(session)) if session.finalizing => return StatusCode::CONFLICT,
+        Ok(Some(_)) => (),
+        Ok(None) => return StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!("Error getting upload session: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+
+    match DATABASE.remove_upload_session(&uuid) {
+        Ok(()) => {
+            let _ = fs::remove_file(upload_session_path(&uuid)).await;
+            StatusCode::OK
+        }
+        Err(e) => {
+            tracing::error!("Error removing upload session: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::DATABASE;
+use crate::auth::Caller;
+use crate::const_var::ROOT_API_PATH;
+use crate::database::interface::CatalogUpdate;
+use crate::datatype_endpoint::{TokenScope, User, UserCreate, UserQuery, UserRegistration};
+use axum::{Json, extract::Path, http::StatusCode};
+use const_format::concatcp;
+use uuid::Uuid;
+
+/// User a token, device or pairing code is issued for: the one of the query, or the caller's.
+/// Only an admin can name another user.
+pub fn target_user(caller: &Caller, query: UserQuery) -> Result<User, StatusCode> {
+    let user_id = query.user_id.unwrap_or(caller.user_id);
+    if user_id != caller.user_id && !caller.allows(TokenScope::Admin) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    match DATABASE.get_user(user_id) {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(StatusCode::BAD_REQUEST),
+        Err(e) => {
+            tracing::error!("Error getting user: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/users"),
+    responses(
+        (status = StatusCode::OK, description = "every user", body = [User]),
+    )
+)]
+pub async fn get_users() -> Result<Json<Vec<User>>, StatusCode> {
+    match DATABASE.get_users() {
+        Ok(users) => Ok(Json(users)),
+        Err(e) => {
+            tracing::error!("Error getting users: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = concatcp!(ROOT_API_PATH, "/users"),
+    request_body = UserCreate,
+    responses(
+        (status = StatusCode::CREATED, description = "user created with a first token, its value is only returned now", body = UserRegistration),
+        (status = StatusCode::BAD_REQUEST, description = "empty name"),
+        (status = StatusCode::CONFLICT, description = "name already taken"),
+    )
+)]
+pub async fn post_user(
+    Json(payload): Json<UserCreate>,
+) -> Result<(StatusCode, Json<UserRegistration>), StatusCode> {
+    if payload.name.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let token = Uuid::new_v4();
+    match DATABASE.add_user(&payload, token) {
+        Ok(Some(user)) => Ok((
+            StatusCode::CREATED,
+            Json(UserRegistration {
+                user,
+                token: token.to_string(),
+            }),
+        )),
+        Ok(None) => Err(StatusCode::CONFLICT),
+        Err(e) => {
+            tracing::error!("Error adding user: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = concatcp!(ROOT_API_PATH, "/users/{Id}"),
+    params(
+        ("Id" = String, Path, description = "Id of the user")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "user returned", body = User),
+        (status = StatusCode::NOT_FOUND, description = "user not found")
+    )
+)]
+pub async fn get_user(Path(id): Path<i32>) -> Result<Json<User>, StatusCode> {
+    match DATABASE.get_user(id) {
+        Ok(Some(user)) => Ok(Json(user)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Error getting user: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = concatcp!(ROOT_API_PATH, "/users/{Id}"),
+    params(
+        ("Id" = String, Path, description = "Id of the user")
+    ),
+    request_body = UserCreate,
+    responses(
+        (status = StatusCode::OK, description = "user renamed, and made or no longer an administrator. A demoted user's tokens lose the admin scope"),
+        (status = StatusCode::BAD_REQUEST, description = "empty name"),
+        (status = StatusCode::NOT_FOUND, description = "user not found"),
+        (status = StatusCode::CONFLICT, description = "name already taken, or no administrator with a token of the admin scope would be left")
+    )
+)]
+pub async fn put_user(Path(id): Path<i32>, Json(payload): Json<UserCreate>) -> StatusCode {
+    if payload.name.trim().is_empty() {
+        return StatusCode::BAD_REQUEST;
+    }
+    user_update_status(DATABASE.update_user(id, &payload))
+}
+
+#[utoipa::path(
+    delete,
+    path = concatcp!(ROOT_API_PATH, "/users/{Id}"),
+    params(
+        ("Id" = String, Path, description = "Id of the user")
+    ),
+    responses(
+        (status = StatusCode::OK, description = "user removed, with their devices and tokens"),
+        (status = StatusCode::NOT_FOUND, description = "user not found"),
+        (status = StatusCode::CONFLICT, description = "the user still has saves, or no administrator with a token of the admin scope would be left")
+    )
+)]
+pub async fn delete_user(Path(id): Path<i32>) -> StatusCode {
+    user_update_status(DATABASE.remove_user(id))
+}
+
+fn user_update_status(
+    result: Result<CatalogUpdate, Box<dyn std::error::Error + Send + Sync>>,
+) -> StatusCode {
+    match result {
+        Ok(CatalogUpdate::Done) => StatusCode::OK,
+        Ok(CatalogUpdate::NotFound) => StatusCode::NOT_FOUND,
+        Ok(CatalogUpdate::Conflict) => StatusCode::CONFLICT,
+        Err(e) => {
+            tracing::error!("Error updating user: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::require_scope;
+    use axum::body::Body;
+    use axum::http::{Method, Request};
+
+    #[test]
+    fn test_target_user_of_another_user_needs_admin() {
+        let caller = Caller {
+            user_id: 2,
+            device_id: None,
+            scopes: vec![TokenScope::ReadOnly, TokenScope::Upload],
+        };
+        assert_eq!(
+            target_user(&caller, UserQuery { user_id: Some(1) }).err(),
+            Some(StatusCode::FORBIDDEN)
+        );
+    }
+
+    #[test]
+    fn test_non_admin_cannot_create_users() -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    {
+        // The user routes are guarded as the rest of the admin API
+        let guard = require_scope(TokenScope::Admin, TokenScope::Admin);
+        for (scopes, allowed) in [
+            (vec![TokenScope::ReadOnly, TokenScope::Upload], false),
+            (vec![TokenScope::Admin], true),
+        ] {
+            let mut request = Request::builder()
+                .method(Method::POST)
+                .uri(concatcp!(ROOT_API_PATH, "/users"))
+                .body(Body::empty())?;
+            request.extensions_mut().insert(Caller {
+                user_id: 2,
+                device_id: None,
+                scopes,
+            });
+            match guard(&mut request) {
+                Ok(()) => assert!(allowed),
+                Err(response) => {
+                    assert!(!allowed);
+                    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,15 @@
 use askama::Template;
+use axum::Extension;
 use axum::response::{Html, IntoResponse};
 use reqwest::StatusCode;
 
-use crate::configuration::MAX_SAVE_PER_GAME_INFO;
+use crate::auth::Caller;
+use crate::configuration::{
+    BACKUP_INTERVAL_DAYS_INFO, BACKUP_RETENTION_COUNT_INFO, COMPRESSION_LEVEL_INFO,
+    ConfigurationInfo, GAME_LOCK_LEASE_SECONDS_INFO, MAX_SAVE_PER_GAME_INFO,
+    RETENTION_DAILY_DAYS_INFO, RETENTION_WEEKLY_WEEKS_INFO,
+};
+use crate::datatype_endpoint::TokenScope;
 
 struct Setting {
     id: String,
@@ -27,40 +34,75 @@ struct Category {
 #[template(path = "configuration.html")]
 struct ConfigurationTemplate {
     title: String,
+    is_admin: bool,
     categories: Vec<Category>,
 }
 
-pub async fn configuration_handler() -> Result<impl IntoResponse, (StatusCode, String)> {
-    let max_save_per_game_setting = Setting {
-        id: MAX_SAVE_PER_GAME_INFO.id.to_string(),
-        name: MAX_SAVE_PER_GAME_INFO.name.to_string(),
+fn number_setting(
+    configuration_info: &ConfigurationInfo,
+    placeholder: &str,
+) -> Result<Setting, (StatusCode, String)> {
+    Ok(Setting {
+        id: configuration_info.id.to_string(),
+        name: configuration_info.name.to_string(),
         input_type: "number".to_string(),
         required: false,
-        value: match MAX_SAVE_PER_GAME_INFO.get_value_in_db() {
+        value: match configuration_info.get_value_in_db() {
             Ok(maybe_configuration_form) => match maybe_configuration_form {
                 Some(configuration_form) => configuration_form.value,
                 None => return Err((StatusCode::NOT_FOUND, "not found".to_string())),
             },
             Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
         },
-        max: MAX_SAVE_PER_GAME_INFO.max.map(|max| max.to_string()),
-        min: MAX_SAVE_PER_GAME_INFO.min.map(|min| min.to_string()),
-        step: MAX_SAVE_PER_GAME_INFO.step.map(|step| step.to_string()),
-        pattern: MAX_SAVE_PER_GAME_INFO
-            .pattern
-            .map(|patern| patern.to_string()),
-        placeholder: Some("Number of save".to_string()),
-        label: MAX_SAVE_PER_GAME_INFO.name.to_string(),
-    };
+        max: configuration_info.max.map(|max| max.to_string()),
+        min: configuration_info.min.map(|min| min.to_string()),
+        step: configuration_info.step.map(|step| step.to_string()),
+        pattern: configuration_info.pattern.map(|patern| patern.to_string()),
+        placeholder: Some(placeholder.to_string()),
+        label: configuration_info.name.to_string(),
+    })
+}
 
+pub async fn configuration_handler(
+    Extension(caller): Extension<Caller>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
     let category = Category {
         title: "Saves".to_string(),
-        settings: vec![max_save_per_game_setting],
+        settings: vec![
+            number_setting(&MAX_SAVE_PER_GAME_INFO, "Number of save")?,
+            number_setting(&RETENTION_DAILY_DAYS_INFO, "Number of days")?,
+            number_setting(&RETENTION_WEEKLY_WEEKS_INFO, "Number of weeks")?,
+        ],
+    };
+
+    let storage_category = Category {
+        title: "Storage".to_string(),
+        settings: vec![number_setting(
+            &COMPRESSION_LEVEL_INFO,
+            "Compression level",
+        )?],
+    };
+
+    let backup_category = Category {
+        title: "Backups".to_string(),
+        settings: vec![
+            number_setting(&BACKUP_INTERVAL_DAYS_INFO, "Number of days")?,
+            number_setting(&BACKUP_RETENTION_COUNT_INFO, "Number of backups")?,
+        ],
+    };
+
+    let lock_category = Category {
+        title: "Game locks".to_string(),
+        settings: vec![number_setting(
+            &GAME_LOCK_LEASE_SECONDS_INFO,
+            "Number of seconds",
+        )?],
     };
 
     let template = ConfigurationTemplate {
         title: "Configuration".to_string(),
-        categories: vec![category],
+        is_admin: caller.allows(TokenScope::Admin),
+        categories: vec![category, storage_category, backup_category, lock_category],
     };
 
     match template.render() {
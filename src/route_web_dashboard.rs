@@ -1,4 +1,5 @@
 use askama::Template;
+use axum::extract::Extension;
 use axum::response::{Html, IntoResponse};
 use itertools::Itertools;
 use reqwest::StatusCode;
@@ -6,7 +7,8 @@ use time::OffsetDateTime;
 
 use crate::{
     DATABASE,
-    datatype_endpoint::{GameMetadata, SavePath},
+    auth::Caller,
+    datatype_endpoint::{GameMetadata, SavePath, TokenScope},
 };
 
 struct GameSaveCardDashTemplate {
@@ -14,20 +16,41 @@ struct GameSaveCardDashTemplate {
     date: String,
     base_path: String,
     paths: Vec<String>,
+    pinned_saves: Vec<PinnedSaveDashTemplate>,
+    diff_url: Option<String>,
+    corrupt: bool,
+}
+
+struct ScrubDashTemplate {
+    date: String,
+    checked_files: usize,
+    damaged_files: Vec<String>,
+    corrupt_saves: usize,
+    quarantined_files: usize,
+}
+
+struct PinnedSaveDashTemplate {
+    uuid: String,
+    date: String,
 }
 
 #[derive(Template)]
 #[template(path = "dashboard.html")]
 struct DashboardTemplate<'a> {
     title: &'a str,
+    is_admin: bool,
+    scrub: Option<ScrubDashTemplate>,
     saves: Vec<GameSaveCardDashTemplate>,
 }
 
-pub async fn dashboard_handler() -> Result<impl IntoResponse, (StatusCode, String)> {
-    let games_metadata_with_paths = match DATABASE.get_games_metadata_and_paths_if_saves_exist() {
-        Ok(data) => data,
-        Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
-    };
+pub async fn dashboard_handler(
+    Extension(caller): Extension<Caller>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let games_metadata_with_paths =
+        match DATABASE.get_games_metadata_and_paths_if_saves_exist(caller.user_id) {
+            Ok(data) => data,
+            Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+        };
 
     let mut saves = Vec::new();
     for (game_metadata, path) in games_metadata_with_paths
@@ -41,10 +64,11 @@ pub async fn dashboard_handler() -> Result<impl IntoResponse, (StatusCode, Strin
         })
         .collect::<Vec<(GameMetadata, SavePath)>>()
     {
-        let saves_for_path = match DATABASE.get_reference_to_save_by_path_id(path.id.unwrap()) {
-            Ok(save_ref) => save_ref,
-            Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
-        };
+        let saves_for_path =
+            match DATABASE.get_reference_to_save_by_path_id(path.id.unwrap(), caller.user_id) {
+                Ok(save_ref) => save_ref,
+                Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+            };
 
         let save_ref = saves_for_path
             .iter()
@@ -53,6 +77,19 @@ pub async fn dashboard_handler() -> Result<impl IntoResponse, (StatusCode, Strin
             .last()
             .map(|save_ref| save_ref.to_owned());
 
+        let pinned_saves = saves_for_path
+            .iter()
+            .flatten()
+            .filter(|save_ref| save_ref.pinned)
+            .sorted_by(|item1, item2| item2.time.cmp(&item1.time))
+            .map(|save_ref| PinnedSaveDashTemplate {
+                uuid: save_ref.uuid.clone(),
+                date: OffsetDateTime::from_unix_timestamp(save_ref.time)
+                    .unwrap()
+                    .to_string(),
+            })
+            .collect();
+
         if let Some(save_ref) = save_ref {
             saves.push(GameSaveCardDashTemplate {
                 game_title: game_metadata.metadata.default_name,
@@ -65,12 +102,43 @@ pub async fn dashboard_handler() -> Result<impl IntoResponse, (StatusCode, Strin
                     .iter()
                     .map(|file_hash| file_hash.relative_path.clone())
                     .collect(),
+                pinned_saves,
+                diff_url: save_ref
+                    .parent_uuid
+                    .as_ref()
+                    .map(|parent_uuid| format!("/saves/{}/diff/{}", parent_uuid, save_ref.uuid)),
+                corrupt: save_ref.corrupt,
             });
         }
     }
 
+    // The scrub covers the saves of every user, only administrators see it
+    let scrub_report = if caller.allows(TokenScope::Admin) {
+        DATABASE.get_scrub_report()
+    } else {
+        Ok(None)
+    };
+    let scrub = match scrub_report {
+        Ok(report) => report.map(|report| ScrubDashTemplate {
+            date: OffsetDateTime::from_unix_timestamp(report.finished)
+                .unwrap()
+                .to_string(),
+            checked_files: report.checked_blobs + report.checked_archives,
+            damaged_files: report
+                .damaged_files
+                .iter()
+                .map(|damaged_file| damaged_file.path.clone())
+                .collect(),
+            corrupt_saves: report.corrupt_saves.len(),
+            quarantined_files: report.quarantined_files.len(),
+        }),
+        Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    };
+
     match (DashboardTemplate {
         title: "Dashboard",
+        is_admin: caller.allows(TokenScope::Admin),
+        scrub,
         saves,
     }
     .render())
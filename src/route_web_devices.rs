@@ -0,0 +1,70 @@
+use askama::Template;
+use axum::Extension;
+use axum::response::{Html, IntoResponse};
+use reqwest::StatusCode;
+use time::OffsetDateTime;
+
+use crate::DATABASE;
+use crate::auth::Caller;
+use crate::datatype_endpoint::TokenScope;
+use crate::route_web_users::{UserOptionTemplate, user_names, user_options};
+
+struct DeviceTemplate {
+    id: i32,
+    name: String,
+    user: String,
+    operating_system: String,
+    client_version: String,
+    last_seen: String,
+}
+
+#[derive(Template)]
+#[template(path = "devices.html")]
+struct DevicesTemplate<'a> {
+    title: &'a str,
+    is_admin: bool,
+    users: Vec<UserOptionTemplate>,
+    devices: Vec<DeviceTemplate>,
+}
+
+pub async fn devices_handler(
+    Extension(caller): Extension<Caller>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let users = match DATABASE.get_users() {
+        Ok(users) => users,
+        Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    };
+    let options = user_options(&caller, &users);
+    let names = user_names(users);
+
+    let devices = match DATABASE.get_devices() {
+        Ok(devices) => devices,
+        Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    };
+
+    let devices = devices
+        .into_iter()
+        .map(|device| DeviceTemplate {
+            id: device.id,
+            name: device.name,
+            user: names.get(&device.user_id).cloned().unwrap_or_default(),
+            operating_system: format!("{:?}", device.operating_system),
+            client_version: device.client_version.unwrap_or_default(),
+            last_seen: OffsetDateTime::from_unix_timestamp(device.last_seen)
+                .map(|date| date.to_string())
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    match (DevicesTemplate {
+        title: "Devices",
+        is_admin: caller.allows(TokenScope::Admin),
+        users: options,
+        devices,
+    }
+    .render())
+    {
+        Ok(html) => Ok(Html(html)),
+        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    }
+}
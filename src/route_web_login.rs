@@ -1,5 +1,5 @@
 use crate::{
-    DATABASE,
+    auth::authenticate,
     const_var::{COOKIE_AUTH_NAME, COOKIE_MAX_AGE},
 };
 use askama::Template;
@@ -31,12 +31,7 @@ pub async fn get_login() -> Result<impl IntoResponse, (StatusCode, String)> {
 pub async fn post_login(
     Json(form): Json<LoginForm>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-    if DATABASE
-        .get_api_tokens()
-        .unwrap_or_default()
-        .iter()
-        .any(|token| token.to_string() == form.token)
-    {
+    if authenticate(form.token.trim()).is_some() {
         let mut response = Redirect::to("/").into_response();
         response.headers_mut().append(
             header::SET_COOKIE,
@@ -0,0 +1,66 @@
+use askama::Template;
+use axum::extract::{Extension, Path};
+use axum::response::{Html, IntoResponse};
+use reqwest::StatusCode;
+
+use crate::auth::Caller;
+use crate::datatype_endpoint::{FileChange, SaveDiff, TokenScope};
+use crate::save_diff::get_save_diff;
+
+#[derive(Template)]
+#[template(path = "save_diff.html")]
+struct SaveDiffTemplate<'a> {
+    title: &'a str,
+    is_admin: bool,
+    diff: SaveDiff,
+}
+
+fn format_hash(hash: &Option<String>) -> String {
+    hash.as_deref()
+        .map(|hash| hash.chars().take(12).collect())
+        .unwrap_or_default()
+}
+
+fn format_size(size: &Option<i64>) -> String {
+    size.map(|size| format!("{} B", size)).unwrap_or_default()
+}
+
+impl SaveDiffTemplate<'_> {
+    fn old_of(&self, change: &FileChange) -> String {
+        format!(
+            "{} {}",
+            format_hash(&change.old_hash),
+            format_size(&change.old_size)
+        )
+    }
+
+    fn new_of(&self, change: &FileChange) -> String {
+        format!(
+            "{} {}",
+            format_hash(&change.new_hash),
+            format_size(&change.new_size)
+        )
+    }
+}
+
+pub async fn save_diff_handler(
+    Path((uuid, other)): Path<(String, String)>,
+    Extension(caller): Extension<Caller>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let diff = match get_save_diff(&uuid, &other, caller.user_id) {
+        Ok(Some(diff)) => diff,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "save not found".to_string())),
+        Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    };
+
+    match (SaveDiffTemplate {
+        title: "Save changes",
+        is_admin: caller.allows(TokenScope::Admin),
+        diff,
+    }
+    .render())
+    {
+        Ok(html) => Ok(Html(html)),
+        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    }
+}
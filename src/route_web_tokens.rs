@@ -0,0 +1,91 @@
+use askama::Template;
+use axum::Extension;
+use axum::response::{Html, IntoResponse};
+use itertools::Itertools;
+use reqwest::StatusCode;
+use time::OffsetDateTime;
+
+use crate::DATABASE;
+use crate::auth::Caller;
+use crate::datatype_endpoint::TokenScope;
+use crate::route_web_users::{UserOptionTemplate, user_names, user_options};
+
+struct TokenTemplate {
+    id: i32,
+    prefix: String,
+    name: String,
+    user: String,
+    scopes: String,
+    created: String,
+    last_used: String,
+    expires: String,
+}
+
+struct ScopeTemplate {
+    value: &'static str,
+}
+
+#[derive(Template)]
+#[template(path = "tokens.html")]
+struct TokensTemplate<'a> {
+    title: &'a str,
+    is_admin: bool,
+    users: Vec<UserOptionTemplate>,
+    scopes: Vec<ScopeTemplate>,
+    tokens: Vec<TokenTemplate>,
+}
+
+fn format_timestamp(timestamp: Option<i64>, none: &str) -> String {
+    timestamp
+        .and_then(|timestamp| OffsetDateTime::from_unix_timestamp(timestamp).ok())
+        .map(|date| date.to_string())
+        .unwrap_or_else(|| none.to_string())
+}
+
+pub async fn tokens_handler(
+    Extension(caller): Extension<Caller>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let users = match DATABASE.get_users() {
+        Ok(users) => users,
+        Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    };
+    let options = user_options(&caller, &users);
+    let names = user_names(users);
+
+    let tokens = match DATABASE.get_api_token_details() {
+        Ok(tokens) => tokens,
+        Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    };
+
+    let tokens = tokens
+        .into_iter()
+        .map(|token| TokenTemplate {
+            id: token.id,
+            prefix: token.prefix,
+            name: token.name,
+            user: names.get(&token.user_id).cloned().unwrap_or_default(),
+            scopes: token.scopes.iter().map(|scope| scope.as_str()).join(", "),
+            created: format_timestamp(Some(token.created), ""),
+            last_used: format_timestamp(token.last_used, "never"),
+            expires: format_timestamp(token.expires, "never"),
+        })
+        .collect();
+
+    match (TokensTemplate {
+        title: "Tokens",
+        is_admin: caller.allows(TokenScope::Admin),
+        users: options,
+        scopes: TokenScope::ALL
+            .iter()
+            .map(|scope| ScopeTemplate {
+                value: scope.as_str(),
+            })
+            .collect(),
+        tokens,
+    }
+    .render())
+    {
+        Ok(html) => Ok(Html(html)),
+        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    }
+}
@@ -0,0 +1,79 @@
+use askama::Template;
+use axum::Extension;
+use axum::response::{Html, IntoResponse};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+use crate::DATABASE;
+use crate::auth::Caller;
+use crate::datatype_endpoint::{TokenScope, User};
+
+struct UserTemplate {
+    id: i32,
+    name: String,
+    admin: bool,
+    created: String,
+}
+
+/// Choice of the user a token or a device is issued for, the caller's user first
+pub struct UserOptionTemplate {
+    pub id: i32,
+    pub name: String,
+    pub selected: bool,
+}
+
+#[derive(Template)]
+#[template(path = "users.html")]
+struct UsersTemplate<'a> {
+    title: &'a str,
+    is_admin: bool,
+    users: Vec<UserTemplate>,
+}
+
+pub fn user_options(caller: &Caller, users: &[User]) -> Vec<UserOptionTemplate> {
+    users
+        .iter()
+        .map(|user| UserOptionTemplate {
+            id: user.id,
+            name: user.name.clone(),
+            selected: user.id == caller.user_id,
+        })
+        .collect()
+}
+
+pub fn user_names(users: Vec<User>) -> HashMap<i32, String> {
+    users.into_iter().map(|user| (user.id, user.name)).collect()
+}
+
+pub async fn users_handler(
+    Extension(caller): Extension<Caller>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let users = match DATABASE.get_users() {
+        Ok(users) => users,
+        Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    };
+
+    let users = users
+        .into_iter()
+        .map(|user| UserTemplate {
+            id: user.id,
+            name: user.name,
+            admin: user.admin,
+            created: OffsetDateTime::from_unix_timestamp(user.created)
+                .map(|date| date.to_string())
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    match (UsersTemplate {
+        title: "Users",
+        is_admin: caller.allows(TokenScope::Admin),
+        users,
+    }
+    .render())
+    {
+        Ok(html) => Ok(Html(html)),
+        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    }
+}
@@ -0,0 +1,673 @@
+use crate::blob_store::{UploadedFile, open_blob};
+use crate::const_var::TMP_DIR;
+use crate::datatype_endpoint::{ArchiveFormat, Codec, HashAlgorithm};
+use crate::save_compression::decoded_reader;
+use crate::save_download::skip_bytes;
+use crate::save_encryption::BlobKey;
+use crate::save_hash::FileHasher;
+use crate::save_store::SaveStore;
+use axum::body::Bytes;
+use futures_util::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use std::error::Error;
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tar::{Archive, EntryType, Header};
+use tokio::io::AsyncReadExt;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+const BLOCK_SIZE: u64 = 512;
+const GNU_NAME_SIZE: usize = 100;
+const GNU_LONG_NAME_PATH: &[u8] = b"././@LongLink";
+const ZIP_SIGNATURE: [u8; 4] = *b"PK\x03\x04";
+/// End of central directory, the first record of a zip without entries
+const ZIP_EMPTY_SIGNATURE: [u8; 4] = *b"PK\x05\x06";
+/// Written by POSIX and GNU tar in the header of every entry
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+
+pub struct ArchiveEntry {
+    pub path: String,
+    pub size: u64,
+    pub mtime: u64,
+    pub content: EntryContent,
+}
+
+pub enum EntryContent {
+    Blob {
+        /// Key of the blob in the save store
+        object: String,
+        codec: Codec,
+        stored_size: u64,
+        blob_key: Option<BlobKey>,
+    },
+    /// Generated by the server, like the manifest of a bundle
+    Bytes(Bytes),
+}
+
+enum ArchivePart {
+    Bytes(Bytes),
+    /// Streams `size` bytes of the file once decrypted with `blob_key` and decoded with `codec`
+    File {
+        object: String,
+        size: u64,
+        codec: Codec,
+        blob_key: Option<BlobKey>,
+    },
+}
+
+impl ArchivePart {
+    fn size(&self) -> u64 {
+        match self {
+            ArchivePart::Bytes(bytes) => bytes.len() as u64,
+            ArchivePart::File { size, .. } => *size,
+        }
+    }
+}
+
+pub struct TarArchive {
+    pub size: u64,
+    pub encoding: Codec,
+    parts: Vec<ArchivePart>,
+    store: &'static dyn SaveStore,
+}
+
+fn padding(size: u64) -> u64 {
+    (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE
+}
+
+fn zeros(size: u64) -> ArchivePart {
+    ArchivePart::Bytes(Bytes::from(vec![0; size as usize]))
+}
+
+fn file_header(size: u64, mtime: u64, entry_type: EntryType) -> Header {
+    let mut header = Header::new_gnu();
+    header.set_mode(0o644);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_size(size);
+    header.set_mtime(mtime);
+    header.set_entry_type(entry_type);
+    header
+}
+
+impl TarArchive {
+    /// With a zstd `encoding`, every entry must be stored as zstd: the tar headers are compressed
+    /// in frames of their own and the stored frames are passed through, their concatenation
+    /// being a valid zstd stream.
+    pub fn new(
+        entries: Vec<ArchiveEntry>,
+        encoding: Codec,
+        store: &'static dyn SaveStore,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if encoding == Codec::Zstd
+            && entries.iter().any(|entry| {
+                !matches!(
+                    entry.content,
+                    EntryContent::Blob {
+                        codec: Codec::Zstd,
+                        ..
+                    }
+                )
+            })
+        {
+            return Err("a zstd archive can only pass through zstd entries".into());
+        }
+
+        let mut parts = Vec::new();
+        let mut size = 0;
+
+        for entry in entries {
+            let path = entry.path.as_bytes();
+            let mut header = file_header(entry.size, entry.mtime, EntryType::Regular);
+
+            if path.len() > GNU_NAME_SIZE {
+                let mut long_name_header =
+                    file_header(path.len() as u64 + 1, 0, EntryType::GNULongName);
+                long_name_header
+                    .as_gnu_mut()
+                    .ok_or("Invalid GNU tar header")?
+                    .name[..GNU_LONG_NAME_PATH.len()]
+                    .copy_from_slice(GNU_LONG_NAME_PATH);
+                long_name_header.set_cksum();
+
+                let mut long_name = path.to_vec();
+                long_name.push(0);
+                long_name.resize(
+                    long_name.len() + padding(long_name.len() as u64) as usize,
+                    0,
+                );
+
+                size += BLOCK_SIZE + long_name.len() as u64;
+                parts.push(ArchivePart::Bytes(Bytes::copy_from_slice(
+                    long_name_header.as_bytes(),
+                )));
+                parts.push(ArchivePart::Bytes(Bytes::from(long_name)));
+
+                header
+                    .as_gnu_mut()
+                    .ok_or("Invalid GNU tar header")?
+                    .name
+                    .copy_from_slice(&path[..GNU_NAME_SIZE]);
+            } else {
+                header.set_path(&entry.path)?;
+            }
+            header.set_cksum();
+
+            size += BLOCK_SIZE + entry.size + padding(entry.size);
+            parts.push(ArchivePart::Bytes(Bytes::copy_from_slice(
+                header.as_bytes(),
+            )));
+            parts.push(match (entry.content, encoding) {
+                (EntryContent::Bytes(bytes), _) => ArchivePart::Bytes(bytes),
+                (
+                    EntryContent::Blob {
+                        object,
+                        codec,
+                        blob_key,
+                        ..
+                    },
+                    Codec::Identity,
+                ) => ArchivePart::File {
+                    object,
+                    size: entry.size,
+                    codec,
+                    blob_key,
+                },
+                (
+                    EntryContent::Blob {
+                        object,
+                        stored_size,
+                        blob_key,
+                        ..
+                    },
+                    Codec::Zstd,
+                ) => ArchivePart::File {
+                    object,
+                    size: stored_size,
+                    codec: Codec::Identity,
+                    blob_key,
+                },
+            });
+            parts.push(zeros(padding(entry.size)));
+        }
+
+        size += 2 * BLOCK_SIZE;
+        parts.push(zeros(2 * BLOCK_SIZE));
+
+        match encoding {
+            Codec::Identity => Ok(Self {
+                size,
+                encoding,
+                parts,
+                store,
+            }),
+            Codec::Zstd => {
+                let (size, parts) = compress_bytes_parts(parts)?;
+                Ok(Self {
+                    size,
+                    encoding,
+                    parts,
+                    store,
+                })
+            }
+        }
+    }
+
+    pub fn into_stream(self) -> BoxStream<'static, std::io::Result<Bytes>> {
+        let size = self.size;
+        self.into_range_stream(0..size)
+    }
+
+    /// Streams the bytes `range` of the archive, the blobs of the parts before it aren't opened
+    pub fn into_range_stream(
+        self,
+        range: Range<u64>,
+    ) -> BoxStream<'static, std::io::Result<Bytes>> {
+        let store = self.store;
+        let mut offset = 0;
+        let mut parts = Vec::new();
+        for part in self.parts {
+            let part_start = offset;
+            offset += part.size();
+            if offset <= range.start || part_start >= range.end {
+                continue;
+            }
+            let skip = range.start.saturating_sub(part_start);
+            let take = range.end.min(offset) - part_start - skip;
+            parts.push((part, skip, take));
+        }
+
+        stream::iter(parts)
+            .flat_map(move |(part, skip, take)| match part {
+                ArchivePart::Bytes(bytes) => {
+                    let bytes = bytes.slice(skip as usize..(skip + take) as usize);
+                    stream::once(async { Ok(bytes) }).boxed()
+                }
+                ArchivePart::File {
+                    object,
+                    size,
+                    codec,
+                    blob_key,
+                } => stream::once(async move {
+                    let blob = open_blob(store, &object, blob_key).await?;
+                    skip_bytes(decoded_reader(blob, codec).take(size), skip).await
+                })
+                .map_ok(move |reader| ReaderStream::new(reader.take(take)))
+                .try_flatten()
+                .boxed(),
+            })
+            .boxed()
+    }
+}
+
+/// Compresses each run of consecutive in-memory parts into a zstd frame, returns the new size
+fn compress_bytes_parts(
+    parts: Vec<ArchivePart>,
+) -> Result<(u64, Vec<ArchivePart>), Box<dyn Error + Send + Sync>> {
+    let mut compressed_parts = Vec::new();
+    let mut size = 0;
+    let mut pending: Vec<u8> = Vec::new();
+
+    for part in parts {
+        match part {
+            ArchivePart::Bytes(bytes) => pending.extend_from_slice(&bytes),
+            file_part @ ArchivePart::File {
+                size: file_size, ..
+            } => {
+                if !pending.is_empty() {
+                    let frame = zstd::encode_all(pending.as_slice(), 0)?;
+                    size += frame.len() as u64;
+                    compressed_parts.push(ArchivePart::Bytes(Bytes::from(frame)));
+                    pending.clear();
+                }
+                size += file_size;
+                compressed_parts.push(file_part);
+            }
+        }
+    }
+    if !pending.is_empty() {
+        let frame = zstd::encode_all(pending.as_slice(), 0)?;
+        size += frame.len() as u64;
+        compressed_parts.push(ArchivePart::Bytes(Bytes::from(frame)));
+    }
+
+    Ok((size, compressed_parts))
+}
+
+pub async fn extract_tar_archive(
+    archive_path: PathBuf,
+    hash_algorithm: HashAlgorithm,
+) -> Result<Vec<UploadedFile>, Box<dyn Error + Send + Sync>> {
+    extract_files(move |uploaded_files| {
+        tar_entries(
+            &archive_path,
+            Path::new(TMP_DIR),
+            hash_algorithm,
+            uploaded_files,
+        )
+    })
+    .await
+}
+
+/// Format of the archive at `path` told from its first bytes, `None` if it isn't a zip or a tar
+pub async fn archive_format(path: &Path) -> std::io::Result<Option<ArchiveFormat>> {
+    let mut header = Vec::with_capacity(BLOCK_SIZE as usize);
+    tokio::fs::File::open(path)
+        .await?
+        .take(BLOCK_SIZE)
+        .read_to_end(&mut header)
+        .await?;
+    if header.starts_with(&ZIP_SIGNATURE) || header.starts_with(&ZIP_EMPTY_SIGNATURE) {
+        Ok(Some(ArchiveFormat::Zip))
+    } else if header.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC) {
+        Ok(Some(ArchiveFormat::Tar))
+    } else {
+        Ok(None)
+    }
+}
+
+pub async fn extract_archive(
+    archive_path: PathBuf,
+    archive_format: ArchiveFormat,
+    hash_algorithm: HashAlgorithm,
+) -> Result<Vec<UploadedFile>, Box<dyn Error + Send + Sync>> {
+    match archive_format {
+        ArchiveFormat::Tar => extract_tar_archive(archive_path, hash_algorithm).await,
+        ArchiveFormat::Zip => {
+            extract_files(move |uploaded_files| {
+                zip_entries(
+                    &archive_path,
+                    Path::new(TMP_DIR),
+                    hash_algorithm,
+                    uploaded_files,
+                )
+            })
+            .await
+        }
+    }
+}
+
+/// Runs `extract` on a blocking thread, removing the files it extracted if it fails
+async fn extract_files(
+    extract: impl FnOnce(&mut Vec<UploadedFile>) -> Result<(), Box<dyn Error + Send + Sync>>
+    + Send
+    + 'static,
+) -> Result<Vec<UploadedFile>, Box<dyn Error + Send + Sync>> {
+    tokio::task::spawn_blocking(move || {
+        let mut uploaded_files = Vec::new();
+        match extract(&mut uploaded_files) {
+            Ok(()) => Ok(uploaded_files),
+            Err(err) => {
+                for uploaded_file in uploaded_files {
+                    let _ = std::fs::remove_file(uploaded_file.tmp_path);
+                }
+                Err(err)
+            }
+        }
+    })
+    .await?
+}
+
+fn tar_entries(
+    archive_path: &Path,
+    tmp_dir: &Path,
+    hash_algorithm: HashAlgorithm,
+    uploaded_files: &mut Vec<UploadedFile>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut archive = Archive::new(std::fs::File::open(archive_path)?);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let entry_path = entry.path()?;
+        let relative_path = entry_path
+            .to_str()
+            .ok_or("archive entry path is not valid UTF-8")?
+            .to_string();
+        extract_entry(
+            relative_path,
+            &mut entry,
+            tmp_dir,
+            hash_algorithm,
+            uploaded_files,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn zip_entries(
+    archive_path: &Path,
+    tmp_dir: &Path,
+    hash_algorithm: HashAlgorithm,
+    uploaded_files: &mut Vec<UploadedFile>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(archive_path)?)?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        if !entry.is_file() {
+            continue;
+        }
+
+        let relative_path = entry.name().to_string();
+        extract_entry(
+            relative_path,
+            &mut entry,
+            tmp_dir,
+            hash_algorithm,
+            uploaded_files,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Copies an archive entry to a temporary file in `tmp_dir`, hashing it on the way
+fn extract_entry(
+    relative_path: String,
+    entry: &mut impl Read,
+    tmp_dir: &Path,
+    hash_algorithm: HashAlgorithm,
+    uploaded_files: &mut Vec<UploadedFile>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let relative_path = relative_path
+        .strip_prefix("./")
+        .unwrap_or(&relative_path)
+        .to_string();
+    let tmp_path = tmp_dir.join(Uuid::new_v4().to_string());
+    let mut file = std::fs::File::create(&tmp_path)?;
+    let tmp_path = tmp_path
+        .into_os_string()
+        .into_string()
+        .map_err(|_| "temporary path is not valid UTF-8")?;
+    uploaded_files.push(UploadedFile {
+        relative_path,
+        tmp_path,
+        hash: String::new(),
+        blob_hash: String::new(),
+        size: 0,
+    });
+
+    let mut hasher = FileHasher::new(hash_algorithm);
+    let mut size = 0;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = entry.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as i64;
+        file.write_all(&buffer[..read])?;
+    }
+
+    if let Some(uploaded_file) = uploaded_files.last_mut() {
+        (uploaded_file.blob_hash, uploaded_file.hash) = hasher.finalize();
+        uploaded_file.size = size;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_store::LocalStore;
+    use futures_util::TryStreamExt;
+    use std::io::Read;
+
+    #[tokio::test]
+    async fn test_tar_archive_round_trip() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(&dir).await?;
+        let store: &'static LocalStore = Box::leak(Box::new(LocalStore::new(&dir)));
+        tokio::fs::write(dir.join("short"), b"short content").await?;
+        tokio::fs::write(dir.join("long"), vec![7u8; 1000]).await?;
+        let long_path = format!("{}/save.dat", "nested".repeat(20));
+
+        let entries = || {
+            vec![
+                ArchiveEntry {
+                    path: "config/settings.ini".to_string(),
+                    size: 13,
+                    mtime: 1_700_000_000,
+                    content: EntryContent::Blob {
+                        object: "short".to_string(),
+                        codec: Codec::Identity,
+                        stored_size: 13,
+                        blob_key: None,
+                    },
+                },
+                ArchiveEntry {
+                    path: long_path.clone(),
+                    size: 1000,
+                    mtime: 1_700_000_000,
+                    content: EntryContent::Blob {
+                        object: "long".to_string(),
+                        codec: Codec::Identity,
+                        stored_size: 1000,
+                        blob_key: None,
+                    },
+                },
+                ArchiveEntry {
+                    path: "manifest.json".to_string(),
+                    size: 2,
+                    mtime: 1_700_000_000,
+                    content: EntryContent::Bytes(Bytes::from_static(b"[]")),
+                },
+            ]
+        };
+        let archive = TarArchive::new(entries(), Codec::Identity, store)?;
+        let expected_size = archive.size;
+        let bytes: Vec<Bytes> = archive
+            .into_range_stream(0..expected_size)
+            .try_collect()
+            .await?;
+        let bytes = bytes.concat();
+        assert_eq!(bytes.len() as u64, expected_size);
+
+        // Ranges starting and ending inside headers, file contents and padding
+        for range in [
+            0..10,
+            500..530,
+            520..1600,
+            1030..expected_size,
+            0..expected_size,
+        ] {
+            let archive = TarArchive::new(entries(), Codec::Identity, store)?;
+            let part: Vec<Bytes> = archive
+                .into_range_stream(range.clone())
+                .try_collect()
+                .await?;
+            assert_eq!(
+                part.concat(),
+                bytes[range.start as usize..range.end as usize]
+            );
+        }
+
+        let mut reader = tar::Archive::new(bytes.as_slice());
+        let mut entries = Vec::new();
+        for entry in reader.entries()? {
+            let mut entry = entry?;
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            entries.push((entry.path()?.to_string_lossy().to_string(), content));
+        }
+
+        tokio::fs::remove_dir_all(&dir).await?;
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].0, "config/settings.ini");
+        assert_eq!(entries[0].1, b"short content");
+        assert_eq!(entries[1].0, long_path);
+        assert_eq!(entries[1].1, vec![7u8; 1000]);
+        assert_eq!(entries[2].0, "manifest.json");
+        assert_eq!(entries[2].1, b"[]");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zstd_entries() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(&dir).await?;
+        let store: &'static LocalStore = Box::leak(Box::new(LocalStore::new(&dir)));
+        let content = b"save data ".repeat(200);
+        let compressed = zstd::encode_all(content.as_slice(), 3)?;
+        tokio::fs::write(dir.join("compressed"), &compressed).await?;
+
+        let entries = || {
+            vec![ArchiveEntry {
+                path: "slot1.sav".to_string(),
+                size: content.len() as u64,
+                mtime: 0,
+                content: EntryContent::Blob {
+                    object: "compressed".to_string(),
+                    codec: Codec::Zstd,
+                    stored_size: compressed.len() as u64,
+                    blob_key: None,
+                },
+            }]
+        };
+
+        let decoded = TarArchive::new(entries(), Codec::Identity, store)?;
+        let decoded_size = decoded.size;
+        let decoded: Vec<Bytes> = decoded
+            .into_range_stream(0..decoded_size)
+            .try_collect()
+            .await?;
+        let decoded = decoded.concat();
+        assert_eq!(decoded.len() as u64, decoded_size);
+
+        let passthrough = TarArchive::new(entries(), Codec::Zstd, store)?;
+        let passthrough_size = passthrough.size;
+        let passthrough: Vec<Bytes> = passthrough
+            .into_range_stream(0..passthrough_size)
+            .try_collect()
+            .await?;
+        let passthrough = passthrough.concat();
+        assert_eq!(passthrough.len() as u64, passthrough_size);
+        assert!(passthrough_size < decoded_size);
+        assert_eq!(zstd::decode_all(passthrough.as_slice())?, decoded);
+
+        let mut reader = tar::Archive::new(decoded.as_slice());
+        let mut entry = reader.entries()?.next().ok_or("empty archive")??;
+        let mut entry_content = Vec::new();
+        entry.read_to_end(&mut entry_content)?;
+        assert_eq!(entry_content, content);
+
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_extract_uploaded_archives() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let zip_path = dir.join("save.zip");
+        let mut zip_writer = zip::ZipWriter::new(std::fs::File::create(&zip_path)?);
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        zip_writer.add_directory("slots/", options)?;
+        zip_writer.start_file("slots/1.sav", options)?;
+        zip_writer.write_all(&[1u8; 2000])?;
+        zip_writer.finish()?;
+
+        let tar_path = dir.join("save.tar");
+        let mut tar_builder = tar::Builder::new(std::fs::File::create(&tar_path)?);
+        let mut header = Header::new_gnu();
+        header.set_size(3);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar_builder.append_data(&mut header, "./config.ini", b"a=1".as_slice())?;
+        tar_builder.finish()?;
+        drop(tar_builder);
+
+        let other_path = dir.join("save.sav");
+        tokio::fs::write(&other_path, b"not an archive").await?;
+
+        assert_eq!(archive_format(&zip_path).await?, Some(ArchiveFormat::Zip));
+        assert_eq!(archive_format(&tar_path).await?, Some(ArchiveFormat::Tar));
+        assert_eq!(archive_format(&other_path).await?, None);
+
+        let mut uploaded_files = Vec::new();
+        zip_entries(&zip_path, &dir, HashAlgorithm::Sha256, &mut uploaded_files)?;
+        tar_entries(&tar_path, &dir, HashAlgorithm::Sha256, &mut uploaded_files)?;
+        let extracted: Vec<(&str, i64)> = uploaded_files
+            .iter()
+            .map(|file| (file.relative_path.as_str(), file.size))
+            .collect();
+        assert_eq!(extracted, vec![("slots/1.sav", 2000), ("config.ini", 3)]);
+        assert_eq!(
+            tokio::fs::read(&uploaded_files[1].tmp_path).await?,
+            b"a=1".to_vec()
+        );
+
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use crate::configuration::COMPRESSION_LEVEL_INFO;
+use crate::datatype_endpoint::Codec;
+use async_compression::tokio::bufread::ZstdDecoder;
+use axum::http::{HeaderMap, header};
+use std::error::Error;
+use std::io::Read;
+use tokio::io::{AsyncRead, BufReader};
+
+/// Codec and level new blobs are stored with, set by the `compression_level` configuration
+pub fn configured_codec() -> Result<(Codec, i32), Box<dyn Error + Send + Sync>> {
+    match COMPRESSION_LEVEL_INFO.get_number_in_db()? {
+        0 => Ok((Codec::Identity, 0)),
+        level => Ok((Codec::Zstd, i32::try_from(level)?)),
+    }
+}
+
+/// Blocking reader over the content of `file` encoded with `codec`
+pub fn encoded_reader(
+    file: std::fs::File,
+    codec: Codec,
+    level: i32,
+) -> std::io::Result<Box<dyn Read + Send>> {
+    match codec {
+        Codec::Identity => Ok(Box::new(file)),
+        Codec::Zstd => Ok(Box::new(zstd::stream::read::Encoder::new(file, level)?)),
+    }
+}
+
+/// Reader over the decompressed content of a stored blob
+pub fn decoded_reader<R>(reader: R, codec: Codec) -> Box<dyn AsyncRead + Send + Unpin>
+where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    match codec {
+        Codec::Identity => Box::new(reader),
+        Codec::Zstd => {
+            let mut decoder = ZstdDecoder::new(BufReader::new(reader));
+            // Blobs are a single frame, but frames are concatenated in passthrough archives
+            decoder.multiple_members(true);
+            Box::new(decoder)
+        }
+    }
+}
+
+/// Whether the `Accept-Encoding` header of the request allows a zstd encoded response
+pub fn accepts_zstd(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut parameters = coding.split(';').map(str::trim);
+            parameters
+                .next()
+                .is_some_and(|name| name.eq_ignore_ascii_case("zstd"))
+                && parameters
+                    .filter_map(|parameter| parameter.strip_prefix("q="))
+                    .all(|quality| quality.parse::<f32>().is_ok_and(|quality| quality > 0.0))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use tokio::io::AsyncReadExt;
+
+    fn accept_encoding(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_accepts_zstd() {
+        assert!(accepts_zstd(&accept_encoding("gzip, zstd")));
+        assert!(accepts_zstd(&accept_encoding("ZSTD;q=0.5")));
+        assert!(!accepts_zstd(&accept_encoding("zstd;q=0")));
+        assert!(!accepts_zstd(&accept_encoding("gzip, br")));
+        assert!(!accepts_zstd(&HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn test_compress_file_round_trip() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(&dir).await?;
+        let source = dir.join("source");
+        let content = b"[settings]\nvolume=10\n".repeat(100);
+        tokio::fs::write(&source, &content).await?;
+
+        let mut compressed = Vec::new();
+        encoded_reader(std::fs::File::open(&source)?, Codec::Zstd, 3)?
+            .read_to_end(&mut compressed)?;
+        assert!(compressed.len() < content.len());
+
+        let mut decoded = Vec::new();
+        decoded_reader(std::io::Cursor::new(compressed), Codec::Zstd)
+            .read_to_end(&mut decoded)
+            .await?;
+        tokio::fs::remove_dir_all(&dir).await?;
+        assert_eq!(decoded, content);
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use crate::DATABASE;
+use crate::database::interface::SaveFileHash;
+use crate::datatype_endpoint::{FileChange, SaveDiff};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+// Blob hashes are computed by the server, the client hash is only used for legacy saves
+fn same_content(old: &SaveFileHash, new: &SaveFileHash) -> bool {
+    match (&old.blob_hash, &new.blob_hash) {
+        (Some(old_blob_hash), Some(new_blob_hash)) => old_blob_hash == new_blob_hash,
+        _ => old.hash == new.hash,
+    }
+}
+
+fn file_change(old: Option<&SaveFileHash>, new: Option<&SaveFileHash>) -> FileChange {
+    FileChange {
+        relative_path: old
+            .or(new)
+            .map(|file_hash| file_hash.relative_path.clone())
+            .unwrap_or_default(),
+        old_hash: old.map(|file_hash| file_hash.hash.clone()),
+        new_hash: new.map(|file_hash| file_hash.hash.clone()),
+        old_size: old.and_then(|file_hash| file_hash.size),
+        new_size: new.and_then(|file_hash| file_hash.size),
+    }
+}
+
+pub fn diff_save_files(
+    from: &str,
+    from_files: &[SaveFileHash],
+    to: &str,
+    to_files: &[SaveFileHash],
+) -> SaveDiff {
+    let from_files: BTreeMap<&str, &SaveFileHash> = from_files
+        .iter()
+        .map(|file_hash| (file_hash.relative_path.as_str(), file_hash))
+        .collect();
+    let to_files: BTreeMap<&str, &SaveFileHash> = to_files
+        .iter()
+        .map(|file_hash| (file_hash.relative_path.as_str(), file_hash))
+        .collect();
+
+    let mut diff = SaveDiff {
+        from: from.to_string(),
+        to: to.to_string(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+        unchanged: 0,
+    };
+    for (relative_path, old) in &from_files {
+        match to_files.get(relative_path) {
+            Some(new) if same_content(old, new) => diff.unchanged += 1,
+            Some(new) => diff.modified.push(file_change(Some(old), Some(new))),
+            None => diff.removed.push(file_change(Some(old), None)),
+        }
+    }
+    for (relative_path, new) in &to_files {
+        if !from_files.contains_key(relative_path) {
+            diff.added.push(file_change(None, Some(new)));
+        }
+    }
+    diff
+}
+
+/// Compares two saves of `user_id`, `None` if either isn't one of theirs
+pub fn get_save_diff(
+    from: &str,
+    to: &str,
+    user_id: i32,
+) -> Result<Option<SaveDiff>, Box<dyn Error + Send + Sync>> {
+    let from_files = match DATABASE.get_save_file_hashes(from, user_id)? {
+        Some(files) => files,
+        None => return Ok(None),
+    };
+    let to_files = match DATABASE.get_save_file_hashes(to, user_id)? {
+        Some(files) => files,
+        None => return Ok(None),
+    };
+    Ok(Some(diff_save_files(from, &from_files, to, &to_files)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(relative_path: &str, hash: &str, size: i64) -> SaveFileHash {
+        SaveFileHash {
+            relative_path: relative_path.to_string(),
+            hash: hash.to_string(),
+            blob_hash: Some(hash.to_string()),
+            size: Some(size),
+        }
+    }
+
+    #[test]
+    fn test_diff_save_files() {
+        let from = vec![
+            file("kept.cfg", "same", 1),
+            file("slot1.sav", "old", 10),
+            file("removed.sav", "gone", 5),
+        ];
+        let to = vec![
+            file("kept.cfg", "same", 1),
+            file("slot1.sav", "new", 12),
+            file("added.sav", "fresh", 7),
+        ];
+
+        let diff = diff_save_files("a", &from, "b", &to);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.added, vec![file_change(None, Some(&to[2]))]);
+        assert_eq!(diff.removed, vec![file_change(Some(&from[2]), None)]);
+        assert_eq!(
+            diff.modified,
+            vec![FileChange {
+                relative_path: "slot1.sav".to_string(),
+                old_hash: Some("old".to_string()),
+                new_hash: Some("new".to_string()),
+                old_size: Some(10),
+                new_size: Some(12),
+            }]
+        );
+    }
+}
@@ -0,0 +1,391 @@
+use crate::datatype_endpoint::Codec;
+use axum::body::Body;
+use axum::http::response::Builder;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::Response;
+use futures_util::stream::{self, TryStreamExt};
+use std::io;
+use std::ops::Range;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::io::ReaderStream;
+
+/// Validators of a download, a stored save never changes so they are derived from its content
+pub struct Validators {
+    /// Strong entity tag, quoted
+    pub etag: Option<String>,
+    pub last_modified: SystemTime,
+}
+
+impl Validators {
+    /// The entity tag is the stored content `digest`, suffixed by the content encoding since the
+    /// bytes sent differ with it
+    pub fn new(digest: Option<&str>, encoding: Codec, time: i64) -> Self {
+        let etag = digest.map(|digest| match encoding {
+            Codec::Identity => format!("\"{}\"", digest),
+            Codec::Zstd => format!("\"{}-zstd\"", digest),
+        });
+        Self {
+            etag,
+            last_modified: UNIX_EPOCH
+                + Duration::from_secs(u64::try_from(time).unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadPart {
+    NotModified,
+    Full,
+    Partial(Range<u64>),
+    Unsatisfiable,
+}
+
+/// Which part of a download of `size` bytes to send, given the conditional and `Range` headers
+pub fn requested_part(headers: &HeaderMap, validators: &Validators, size: u64) -> DownloadPart {
+    let header_str = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+    // If-Modified-Since is ignored when If-None-Match is given
+    if let Some(if_none_match) = header_str(header::IF_NONE_MATCH) {
+        if etag_list_matches(if_none_match, validators.etag.as_deref()) {
+            return DownloadPart::NotModified;
+        }
+    } else if let Some(since) = header_str(header::IF_MODIFIED_SINCE)
+        .and_then(|since| httpdate::parse_http_date(since).ok())
+        && validators.last_modified <= since
+    {
+        return DownloadPart::NotModified;
+    }
+
+    let Some(range) = header_str(header::RANGE) else {
+        return DownloadPart::Full;
+    };
+    if let Some(if_range) = header_str(header::IF_RANGE)
+        && !if_range_matches(if_range, validators)
+    {
+        return DownloadPart::Full;
+    }
+    parse_range(range, size)
+}
+
+/// Weak comparison against a list of entity tags, as used by `If-None-Match`
+fn etag_list_matches(list: &str, etag: Option<&str>) -> bool {
+    let Some(etag) = etag else {
+        return false;
+    };
+    list.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+/// `If-Range` requires a strong match of the entity tag or an exact modification date
+fn if_range_matches(if_range: &str, validators: &Validators) -> bool {
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        validators.etag.as_deref() == Some(if_range)
+    } else {
+        httpdate::parse_http_date(if_range).ok() == Some(validators.last_modified)
+    }
+}
+
+/// Only a single byte range is served, any other `Range` header gets the full content
+fn parse_range(range: &str, size: u64) -> DownloadPart {
+    let Some(range) = range.trim().strip_prefix("bytes=") else {
+        return DownloadPart::Full;
+    };
+    let Some((start, end)) = range.trim().split_once('-') else {
+        return DownloadPart::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    if start.is_empty() {
+        // Suffix range: the last `end` bytes
+        return match end.parse::<u64>() {
+            Ok(0) => DownloadPart::Unsatisfiable,
+            Ok(_) if size == 0 => DownloadPart::Unsatisfiable,
+            Ok(length) => DownloadPart::Partial(size - length.min(size)..size),
+            Err(_) => DownloadPart::Full,
+        };
+    }
+    let Ok(start) = start.parse::<u64>() else {
+        return DownloadPart::Full;
+    };
+    let end = if end.is_empty() {
+        size
+    } else {
+        match end.parse::<u64>() {
+            Ok(end) if end >= start => end.saturating_add(1).min(size),
+            _ => return DownloadPart::Full,
+        }
+    };
+    if start >= size {
+        DownloadPart::Unsatisfiable
+    } else {
+        DownloadPart::Partial(start..end)
+    }
+}
+
+/// Builds the response of a download of `size` bytes, `body` streams the requested range of it.
+/// `builder` holds the headers describing the content, sent with every status.
+pub fn download_response(
+    builder: Builder,
+    headers: &HeaderMap,
+    validators: &Validators,
+    size: u64,
+    body: impl FnOnce(Range<u64>) -> Body,
+) -> Response {
+    let mut builder = builder.header(header::ACCEPT_RANGES, "bytes").header(
+        header::LAST_MODIFIED,
+        httpdate::fmt_http_date(validators.last_modified),
+    );
+    if let Some(etag) = &validators.etag {
+        builder = builder.header(header::ETAG, etag);
+    }
+
+    let response = match requested_part(headers, validators, size) {
+        DownloadPart::NotModified => builder.status(StatusCode::NOT_MODIFIED).body(Body::empty()),
+        DownloadPart::Unsatisfiable => builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+            .body(Body::empty()),
+        DownloadPart::Full => builder
+            .header(header::CONTENT_LENGTH, size)
+            .body(body(0..size)),
+        DownloadPart::Partial(range) => builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", range.start, range.end - 1, size),
+            )
+            .header(header::CONTENT_LENGTH, range.end - range.start)
+            .body(body(range)),
+    };
+    response.unwrap_or_else(|_| {
+        Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::empty())
+            .unwrap()
+    })
+}
+
+/// Body streaming the bytes `range` of the content `open` resolves to, lazily so that `HEAD`
+/// requests don't open it
+pub fn range_body<R>(
+    open: impl Future<Output = io::Result<R>> + Send + 'static,
+    range: Range<u64>,
+) -> Body
+where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    let length = range.end - range.start;
+    Body::from_stream(
+        stream::once(async move { skip_bytes(open.await?, range.start).await })
+            .map_ok(move |reader| ReaderStream::new(reader.take(length)))
+            .try_flatten(),
+    )
+}
+
+/// Reads and drops the first `count` bytes of `reader`, content streamed by the save store
+/// can't be seeked once decrypted and decoded
+pub async fn skip_bytes<R: AsyncRead + Unpin>(mut reader: R, count: u64) -> io::Result<R> {
+    let skipped = tokio::io::copy(&mut (&mut reader).take(count), &mut tokio::io::sink()).await?;
+    if skipped < count {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "content shorter than the requested range",
+        ));
+    }
+    Ok(reader)
+}
+
+/// `Content-Disposition` of a download named `file_name`, with an ASCII fallback for old clients
+/// and the exact name encoded as in RFC 6266
+pub fn attachment_disposition(file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| {
+            if c == ' ' || (c.is_ascii_graphic() && c != '"' && c != '\\') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let encoded: String = file_name
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect();
+    format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn validators() -> Validators {
+        Validators::new(Some("digest"), Codec::Identity, 1_700_000_000)
+    }
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_etag() {
+        assert_eq!(validators().etag.as_deref(), Some("\"digest\""));
+        assert_eq!(
+            Validators::new(Some("digest"), Codec::Zstd, 0)
+                .etag
+                .as_deref(),
+            Some("\"digest-zstd\"")
+        );
+        assert_eq!(Validators::new(None, Codec::Identity, 0).etag, None);
+    }
+
+    #[test]
+    fn test_attachment_disposition() {
+        assert_eq!(
+            attachment_disposition("slot 1.sav"),
+            "attachment; filename=\"slot 1.sav\"; filename*=UTF-8''slot%201.sav"
+        );
+        let disposition = attachment_disposition("sauvegarde\"é\n.sav");
+        assert_eq!(
+            disposition,
+            "attachment; filename=\"sauvegarde___.sav\"; filename*=UTF-8''sauvegarde%22%C3%A9%0A.sav"
+        );
+        assert!(HeaderValue::from_str(&disposition).is_ok());
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(
+            parse_range("bytes=0-99", 1000),
+            DownloadPart::Partial(0..100)
+        );
+        assert_eq!(
+            parse_range("bytes=900-", 1000),
+            DownloadPart::Partial(900..1000)
+        );
+        assert_eq!(
+            parse_range("bytes=900-5000", 1000),
+            DownloadPart::Partial(900..1000)
+        );
+        assert_eq!(
+            parse_range("bytes=-100", 1000),
+            DownloadPart::Partial(900..1000)
+        );
+        assert_eq!(
+            parse_range("bytes=-5000", 1000),
+            DownloadPart::Partial(0..1000)
+        );
+        assert_eq!(
+            parse_range("bytes=1000-", 1000),
+            DownloadPart::Unsatisfiable
+        );
+        assert_eq!(parse_range("bytes=-0", 1000), DownloadPart::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-10", 0), DownloadPart::Unsatisfiable);
+        assert_eq!(parse_range("bytes=10-5", 1000), DownloadPart::Full);
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), DownloadPart::Full);
+        assert_eq!(parse_range("items=0-1", 1000), DownloadPart::Full);
+    }
+
+    #[test]
+    fn test_requested_part() {
+        let validators = validators();
+        let last_modified = httpdate::fmt_http_date(validators.last_modified);
+        let earlier = httpdate::fmt_http_date(validators.last_modified - Duration::from_secs(60));
+
+        assert_eq!(
+            requested_part(&HeaderMap::new(), &validators, 1000),
+            DownloadPart::Full
+        );
+        assert_eq!(
+            requested_part(
+                &headers(&[(header::IF_NONE_MATCH, "\"other\", W/\"digest\"")]),
+                &validators,
+                1000
+            ),
+            DownloadPart::NotModified
+        );
+        assert_eq!(
+            requested_part(
+                &headers(&[(header::IF_MODIFIED_SINCE, &last_modified)]),
+                &validators,
+                1000
+            ),
+            DownloadPart::NotModified
+        );
+        assert_eq!(
+            requested_part(
+                &headers(&[(header::IF_MODIFIED_SINCE, &earlier)]),
+                &validators,
+                1000
+            ),
+            DownloadPart::Full
+        );
+        // If-None-Match takes precedence over If-Modified-Since
+        assert_eq!(
+            requested_part(
+                &headers(&[
+                    (header::IF_NONE_MATCH, "\"other\""),
+                    (header::IF_MODIFIED_SINCE, &last_modified)
+                ]),
+                &validators,
+                1000
+            ),
+            DownloadPart::Full
+        );
+        assert_eq!(
+            requested_part(
+                &headers(&[
+                    (header::RANGE, "bytes=10-"),
+                    (header::IF_RANGE, "\"digest\"")
+                ]),
+                &validators,
+                1000
+            ),
+            DownloadPart::Partial(10..1000)
+        );
+        assert_eq!(
+            requested_part(
+                &headers(&[
+                    (header::RANGE, "bytes=10-"),
+                    (header::IF_RANGE, "\"other\"")
+                ]),
+                &validators,
+                1000
+            ),
+            DownloadPart::Full
+        );
+        assert_eq!(
+            requested_part(
+                &headers(&[(header::RANGE, "bytes=10-"), (header::IF_RANGE, &earlier)]),
+                &validators,
+                1000
+            ),
+            DownloadPart::Full
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skip_bytes() -> io::Result<()> {
+        let mut rest = Vec::new();
+        skip_bytes(&b"0123456789"[..], 4)
+            .await?
+            .read_to_end(&mut rest)
+            .await?;
+        assert_eq!(rest, b"456789");
+        assert!(skip_bytes(&b"0123"[..], 5).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+use crate::DATABASE;
+use crate::database::interface::EncryptionKey;
+use aes_gcm::aead::stream::{DecryptorBE32, EncryptorBE32};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use axum::body::Bytes;
+use futures_util::stream;
+use hkdf::Hkdf;
+use once_cell::sync::Lazy;
+use sha2::Sha256;
+use std::error::Error;
+use std::io::{Read, Write};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::io::StreamReader;
+
+pub const MASTER_KEY_ENV: &str = "GAME_SAVE_MASTER_KEY";
+pub const MASTER_KEY_FILE_ENV: &str = "GAME_SAVE_MASTER_KEY_FILE";
+
+/// Plaintext bytes per encrypted chunk, each chunk carries its own authentication tag
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+/// Nonce prefix of the STREAM construction, the 5 remaining bytes are the chunk counter
+const NONCE_SIZE: usize = 7;
+const WRAP_NONCE_SIZE: usize = 12;
+
+/// Master key of the server, `None` when encryption at rest isn't configured
+pub static MASTER_KEY: Lazy<Option<MasterKey>> = Lazy::new(|| match MasterKey::from_env() {
+    Ok(master_key) => master_key,
+    Err(e) => panic!("Invalid master key: {}", e),
+});
+
+/// Key wrapping the data key of every save
+pub struct MasterKey(Key<Aes256Gcm>);
+
+/// Key and nonce one blob is encrypted with, derived from the data key of a save
+#[derive(Clone)]
+pub struct BlobKey {
+    key: Key<Aes256Gcm>,
+    nonce: [u8; NONCE_SIZE],
+}
+
+impl MasterKey {
+    /// Parses a key of 32 bytes written in hexadecimal
+    pub fn parse(hex_key: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let bytes = hex::decode(hex_key.trim())?;
+        if bytes.len() != 32 {
+            return Err("the master key must be 32 bytes written in hexadecimal".into());
+        }
+        Ok(Self(*Key::<Aes256Gcm>::from_slice(&bytes)))
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Reads the key from `GAME_SAVE_MASTER_KEY`, or from the file `GAME_SAVE_MASTER_KEY_FILE`
+    /// points to
+    pub fn from_env() -> Result<Option<Self>, Box<dyn Error + Send + Sync>> {
+        if let Ok(hex_key) = std::env::var(MASTER_KEY_ENV) {
+            return Self::parse(&hex_key).map(Some);
+        }
+        match std::env::var(MASTER_KEY_FILE_ENV) {
+            Ok(path) => Self::from_file(&path).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Generates the data key and nonce of a new save
+    pub fn generate_data_key(&self) -> Result<EncryptionKey, Box<dyn Error + Send + Sync>> {
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let mut nonce = vec![0u8; NONCE_SIZE];
+        OsRng.fill_bytes(&mut nonce);
+        Ok(EncryptionKey {
+            wrapped_key: self.wrap(&data_key)?,
+            nonce,
+        })
+    }
+
+    fn wrap(&self, data_key: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let wrap_nonce = Aes256Gcm::generate_nonce(OsRng);
+        let wrapped = Aes256Gcm::new(&self.0)
+            .encrypt(&wrap_nonce, data_key)
+            .map_err(|_| "failed to wrap a data key")?;
+        Ok([wrap_nonce.as_slice(), &wrapped].concat())
+    }
+
+    fn unwrap(&self, wrapped_key: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        if wrapped_key.len() < WRAP_NONCE_SIZE {
+            return Err("invalid wrapped data key".into());
+        }
+        let (wrap_nonce, wrapped) = wrapped_key.split_at(WRAP_NONCE_SIZE);
+        Aes256Gcm::new(&self.0)
+            .decrypt(Nonce::from_slice(wrap_nonce), wrapped)
+            .map_err(|_| "failed to unwrap a data key, the master key doesn't match".into())
+    }
+
+    /// Wraps a data key wrapped by this master key with `new_master_key` instead
+    pub fn rewrap(
+        &self,
+        new_master_key: &MasterKey,
+        wrapped_key: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        new_master_key.wrap(&self.unwrap(wrapped_key)?)
+    }
+
+    /// Every blob gets a key of its own derived from the data key, so blobs of a save never
+    /// share a key and nonce pair
+    pub fn blob_key(
+        &self,
+        encryption: &EncryptionKey,
+        blob_hash: &str,
+    ) -> Result<BlobKey, Box<dyn Error + Send + Sync>> {
+        let data_key = self.unwrap(&encryption.wrapped_key)?;
+        let mut key = Key::<Aes256Gcm>::default();
+        Hkdf::<Sha256>::new(None, &data_key)
+            .expand(blob_hash.as_bytes(), &mut key)
+            .map_err(|_| "failed to derive a blob key")?;
+        Ok(BlobKey {
+            key,
+            nonce: encryption
+                .nonce
+                .as_slice()
+                .try_into()
+                .map_err(|_| "invalid save nonce")?,
+        })
+    }
+}
+
+/// Key of a blob stored with `encryption`, using the master key of the server
+pub fn blob_key(
+    encryption: Option<&EncryptionKey>,
+    blob_hash: &str,
+) -> Result<Option<BlobKey>, Box<dyn Error + Send + Sync>> {
+    match (encryption, MASTER_KEY.as_ref()) {
+        (None, _) => Ok(None),
+        (Some(encryption), Some(master_key)) => {
+            master_key.blob_key(encryption, blob_hash).map(Some)
+        }
+        (Some(_), None) => Err(format!(
+            "blob {} is encrypted but no master key is configured",
+            blob_hash
+        )
+        .into()),
+    }
+}
+
+/// Wraps every data key with the master key read from `new_key_path` instead of the configured
+/// one, returns the number of keys rewrapped. The server must be stopped meanwhile, and started
+/// with the new master key afterwards.
+pub fn rotate_keys(new_key_path: &str) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let master_key = MASTER_KEY.as_ref().ok_or(format!(
+        "no master key is configured, set {} or {}",
+        MASTER_KEY_ENV, MASTER_KEY_FILE_ENV
+    ))?;
+    let new_master_key = MasterKey::from_file(new_key_path)?;
+    DATABASE.rewrap_data_keys(|wrapped_key| master_key.rewrap(&new_master_key, wrapped_key))
+}
+
+/// Size on disk of `size` bytes once encrypted
+pub fn encrypted_size(size: u64) -> u64 {
+    let chunks = size.div_ceil(CHUNK_SIZE as u64).max(1);
+    size + chunks * TAG_SIZE as u64
+}
+
+fn read_chunk(reader: &mut impl Read) -> std::io::Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    reader.take(CHUNK_SIZE as u64).read_to_end(&mut chunk)?;
+    Ok(chunk)
+}
+
+/// Encrypts everything `reader` returns into `writer`, returns the number of bytes read
+pub fn encrypt(
+    mut reader: impl Read,
+    mut writer: impl Write,
+    blob_key: &BlobKey,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let mut encryptor = EncryptorBE32::from_aead(
+        Aes256Gcm::new(&blob_key.key),
+        blob_key.nonce.as_slice().into(),
+    );
+    let mut size = 0;
+    let mut chunk = read_chunk(&mut reader)?;
+    loop {
+        size += chunk.len() as u64;
+        // A full chunk may be followed by an empty one, the last chunk must be known to seal it
+        let next = if chunk.len() == CHUNK_SIZE {
+            read_chunk(&mut reader)?
+        } else {
+            Vec::new()
+        };
+        if next.is_empty() {
+            let encrypted = encryptor
+                .encrypt_last(chunk.as_slice())
+                .map_err(|_| "failed to encrypt a blob")?;
+            writer.write_all(&encrypted)?;
+            break;
+        }
+        let encrypted = encryptor
+            .encrypt_next(chunk.as_slice())
+            .map_err(|_| "failed to encrypt a blob")?;
+        writer.write_all(&encrypted)?;
+        chunk = next;
+    }
+    writer.flush()?;
+    Ok(size)
+}
+
+/// Reader over the decrypted content of the `encrypted_size` bytes of `reader`, a damaged or
+/// truncated chunk is reported as `InvalidData`
+pub fn decrypted_reader<R>(
+    reader: R,
+    blob_key: BlobKey,
+    encrypted_size: u64,
+) -> impl AsyncRead + Send + Unpin
+where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    let decryptor = DecryptorBE32::from_aead(
+        Aes256Gcm::new(&blob_key.key),
+        blob_key.nonce.as_slice().into(),
+    );
+    let chunks = stream::try_unfold(
+        (reader, Some(decryptor), encrypted_size),
+        |(mut reader, decryptor, remaining)| async move {
+            let Some(mut decryptor) = decryptor else {
+                return Ok::<_, std::io::Error>(None);
+            };
+            let chunk_size = remaining.min((CHUNK_SIZE + TAG_SIZE) as u64);
+            let mut chunk = vec![0u8; usize::try_from(chunk_size).unwrap_or_default()];
+            reader.read_exact(&mut chunk).await?;
+            let remaining = remaining - chunk_size;
+
+            let invalid_data =
+                |_| std::io::Error::new(std::io::ErrorKind::InvalidData, "damaged encrypted blob");
+            if remaining == 0 {
+                let decrypted = decryptor
+                    .decrypt_last(chunk.as_slice())
+                    .map_err(invalid_data)?;
+                Ok(Some((Bytes::from(decrypted), (reader, None, 0))))
+            } else {
+                let decrypted = decryptor
+                    .decrypt_next(chunk.as_slice())
+                    .map_err(invalid_data)?;
+                Ok(Some((
+                    Bytes::from(decrypted),
+                    (reader, Some(decryptor), remaining),
+                )))
+            }
+        },
+    );
+    StreamReader::new(Box::pin(chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master_key(byte: u8) -> MasterKey {
+        MasterKey::parse(&hex::encode([byte; 32])).unwrap()
+    }
+
+    #[test]
+    fn test_parse_master_key() {
+        assert!(MasterKey::parse(&format!("{}\n", hex::encode([1u8; 32]))).is_ok());
+        assert!(MasterKey::parse(&hex::encode([1u8; 16])).is_err());
+        assert!(MasterKey::parse("not hexadecimal").is_err());
+    }
+
+    #[test]
+    fn test_rewrap_data_key() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let old_master_key = master_key(1);
+        let new_master_key = master_key(2);
+        let encryption = old_master_key.generate_data_key()?;
+        let blob_key = old_master_key.blob_key(&encryption, "blob")?;
+
+        let rewrapped = EncryptionKey {
+            wrapped_key: old_master_key.rewrap(&new_master_key, &encryption.wrapped_key)?,
+            nonce: encryption.nonce.clone(),
+        };
+        assert!(old_master_key.blob_key(&rewrapped, "blob").is_err());
+        assert_eq!(
+            new_master_key.blob_key(&rewrapped, "blob")?.key,
+            blob_key.key
+        );
+        assert_ne!(
+            new_master_key.blob_key(&rewrapped, "other")?.key,
+            blob_key.key
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_round_trip() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let master_key = master_key(3);
+        let encryption = master_key.generate_data_key()?;
+        let blob_key = master_key.blob_key(&encryption, "blob")?;
+
+        for size in [0, 10, CHUNK_SIZE, 2 * CHUNK_SIZE + 5] {
+            let content: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            let mut encrypted = Vec::new();
+            assert_eq!(
+                encrypt(content.as_slice(), &mut encrypted, &blob_key)?,
+                size as u64
+            );
+            assert_eq!(encrypted.len() as u64, encrypted_size(size as u64));
+
+            let mut decrypted = Vec::new();
+            decrypted_reader(
+                std::io::Cursor::new(encrypted.clone()),
+                blob_key.clone(),
+                encrypted.len() as u64,
+            )
+            .read_to_end(&mut decrypted)
+            .await?;
+            assert_eq!(decrypted, content);
+
+            let mut damaged = encrypted.clone();
+            damaged[0] ^= 1;
+            let error = decrypted_reader(
+                std::io::Cursor::new(damaged),
+                blob_key.clone(),
+                encrypted.len() as u64,
+            )
+            .read_to_end(&mut Vec::new())
+            .await
+            .unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        }
+        Ok(())
+    }
+}
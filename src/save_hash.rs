@@ -0,0 +1,136 @@
+use crate::blob_store::UploadedFile;
+use crate::datatype_endpoint::HashAlgorithm;
+use itertools::Itertools;
+use sha2::{Digest, Sha256, Sha512};
+
+enum AlgorithmHasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl AlgorithmHasher {
+    fn new(hash_algorithm: HashAlgorithm) -> Self {
+        match hash_algorithm {
+            HashAlgorithm::Sha256 => AlgorithmHasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha512 => AlgorithmHasher::Sha512(Sha512::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            AlgorithmHasher::Sha256(hasher) => hasher.update(data),
+            AlgorithmHasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            AlgorithmHasher::Sha256(hasher) => hex::encode(hasher.finalize()),
+            AlgorithmHasher::Sha512(hasher) => hex::encode(hasher.finalize()),
+        }
+    }
+}
+
+/// Hashes an uploaded file in a single pass, with SHA-256 for the blob store and with the
+/// algorithm the client declared its hashes in.
+pub struct FileHasher {
+    blob_hasher: Sha256,
+    // `None` when the declared algorithm is SHA-256, the blob hash is reused
+    declared_hasher: Option<AlgorithmHasher>,
+}
+
+impl FileHasher {
+    pub fn new(hash_algorithm: HashAlgorithm) -> Self {
+        Self {
+            blob_hasher: Sha256::new(),
+            declared_hasher: match hash_algorithm {
+                HashAlgorithm::Sha256 => None,
+                other => Some(AlgorithmHasher::new(other)),
+            },
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.blob_hasher.update(data);
+        if let Some(declared_hasher) = &mut self.declared_hasher {
+            declared_hasher.update(data);
+        }
+    }
+
+    /// Returns the blob hash and the hash in the declared algorithm
+    pub fn finalize(self) -> (String, String) {
+        let blob_hash = hex::encode(self.blob_hasher.finalize());
+        match self.declared_hasher {
+            Some(declared_hasher) => (blob_hash, declared_hasher.finalize()),
+            None => (blob_hash.clone(), blob_hash),
+        }
+    }
+}
+
+/// Digest of a whole save: the `relative_path\0hash\n` lines of its files sorted by relative path,
+/// so clients can compute it from their own file list whatever the upload format was.
+pub fn archive_digest(hash_algorithm: HashAlgorithm, files: &[UploadedFile]) -> String {
+    let mut hasher = AlgorithmHasher::new(hash_algorithm);
+    for file in files
+        .iter()
+        .sorted_by(|file1, file2| file1.relative_path.cmp(&file2.relative_path))
+    {
+        hasher.update(file.relative_path.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(file.hash.as_bytes());
+        hasher.update(b"\n");
+    }
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uploaded_file(relative_path: &str, hash: &str) -> UploadedFile {
+        UploadedFile {
+            relative_path: relative_path.to_string(),
+            tmp_path: String::new(),
+            hash: hash.to_string(),
+            blob_hash: String::new(),
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn test_file_hasher() {
+        let mut hasher = FileHasher::new(HashAlgorithm::Sha256);
+        hasher.update(b"ab");
+        hasher.update(b"c");
+        let (blob_hash, hash) = hasher.finalize();
+        assert_eq!(
+            blob_hash,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(hash, blob_hash);
+
+        let mut hasher = FileHasher::new(HashAlgorithm::Sha512);
+        hasher.update(b"abc");
+        let (blob_hash, hash) = hasher.finalize();
+        assert_eq!(
+            blob_hash,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(hash.starts_with("ddaf35a193617aba"));
+        assert_eq!(hash.len(), 128);
+    }
+
+    #[test]
+    fn test_archive_digest_ignores_upload_order() {
+        let files = vec![uploaded_file("b.sav", "2"), uploaded_file("a.sav", "1")];
+        let reversed = vec![uploaded_file("a.sav", "1"), uploaded_file("b.sav", "2")];
+        assert_eq!(
+            archive_digest(HashAlgorithm::Sha256, &files),
+            archive_digest(HashAlgorithm::Sha256, &reversed)
+        );
+        assert_ne!(
+            archive_digest(HashAlgorithm::Sha256, &files),
+            archive_digest(HashAlgorithm::Sha256, &[uploaded_file("a.sav", "1")])
+        );
+    }
+}
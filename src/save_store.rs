@@ -0,0 +1,570 @@
+use crate::const_var::SAVE_DIR;
+use crate::file_system::{is_safe_relative_path, move_file};
+use async_trait::async_trait;
+use futures_util::{StreamExt, TryStreamExt};
+use object_store::ObjectStore;
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::buffered::BufWriter;
+use once_cell::sync::Lazy;
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::fs::{self, File};
+use tokio::io::{AsyncRead, AsyncWriteExt};
+use tokio_util::io::StreamReader;
+use uuid::Uuid;
+
+pub const SAVE_STORE_ENV: &str = "GAME_SAVE_STORE";
+
+/// Store holding the blobs and legacy archives, selected by `GAME_SAVE_STORE`
+pub static SAVE_STORE: Lazy<Box<dyn SaveStore>> = Lazy::new(|| {
+    let location = match std::env::var(SAVE_STORE_ENV) {
+        Ok(location) => location.parse(),
+        Err(_) => Ok(StoreLocation::Local),
+    };
+    match location.and_then(|location| open_store(&location)) {
+        Ok(store) => store,
+        Err(e) => panic!("Invalid save store: {}", e),
+    }
+});
+
+pub struct StoreObject {
+    pub size: u64,
+    pub reader: Box<dyn AsyncRead + Send + Unpin>,
+}
+
+/// Where the saves are persisted, keys are relative paths separated by `/`
+#[async_trait]
+pub trait SaveStore: Send + Sync {
+    /// Moves the local file `source` into the store as `key`, replacing any object with that key
+    async fn put(&self, key: &str, source: &Path) -> io::Result<()>;
+
+    /// Streams the object `key`, fails with `NotFound` if it doesn't exist
+    async fn get(&self, key: &str) -> io::Result<StoreObject>;
+
+    /// Deleting a missing object isn't an error
+    async fn delete(&self, key: &str) -> io::Result<()>;
+
+    /// Keys of every object of the store
+    async fn list(&self) -> io::Result<Vec<String>>;
+
+    /// Size of the object `key`, `None` if it doesn't exist
+    async fn stat(&self, key: &str) -> io::Result<Option<u64>>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreLocation {
+    /// `./data/saves` on the local file system
+    Local,
+    /// A bucket of an S3 compatible service, configured by the `AWS_*` environment variables
+    S3 { bucket: String, prefix: String },
+}
+
+impl FromStr for StoreLocation {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    /// `local`, or `s3://bucket` with an optional `/prefix`
+    fn from_str(location: &str) -> Result<Self, Self::Err> {
+        if location == "local" {
+            return Ok(StoreLocation::Local);
+        }
+        match location.strip_prefix("s3://") {
+            Some(bucket_and_prefix) => {
+                let (bucket, prefix) = bucket_and_prefix
+                    .split_once('/')
+                    .unwrap_or((bucket_and_prefix, ""));
+                if bucket.is_empty() {
+                    return Err("the S3 store location is missing a bucket".into());
+                }
+                Ok(StoreLocation::S3 {
+                    bucket: bucket.to_string(),
+                    prefix: prefix.trim_matches('/').to_string(),
+                })
+            }
+            None => Err(format!(
+                "unknown save store {}, expected local or s3://bucket/prefix",
+                location
+            )
+            .into()),
+        }
+    }
+}
+
+pub fn open_store(
+    location: &StoreLocation,
+) -> Result<Box<dyn SaveStore>, Box<dyn Error + Send + Sync>> {
+    match location {
+        StoreLocation::Local => Ok(Box::new(LocalStore::new(SAVE_DIR))),
+        StoreLocation::S3 { bucket, prefix } => Ok(Box::new(S3Store::new(
+            AmazonS3Builder::from_env().with_bucket_name(bucket),
+            prefix,
+        )?)),
+    }
+}
+
+fn invalid_key(key: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("invalid store key {}", key),
+    )
+}
+
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        if !is_safe_relative_path(key) {
+            return Err(invalid_key(key));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl SaveStore for LocalStore {
+    async fn put(&self, key: &str, source: &Path) -> io::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        move_file(source, &path).await.map_err(io::Error::other)
+    }
+
+    async fn get(&self, key: &str) -> io::Result<StoreObject> {
+        let file = File::open(self.path(key)?).await?;
+        Ok(StoreObject {
+            size: file.metadata().await?.len(),
+            reader: Box::new(file),
+        })
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    async fn list(&self) -> io::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut directories = vec![(self.root.clone(), String::new())];
+        while let Some((directory, prefix)) = directories.pop() {
+            let mut entries = match fs::read_dir(&directory).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let key = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+                if entry.file_type().await?.is_dir() {
+                    directories.push((entry.path(), format!("{}/", key)));
+                } else {
+                    keys.push(key);
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    async fn stat(&self, key: &str) -> io::Result<Option<u64>> {
+        match fs::metadata(self.path(key)?).await {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Store of an S3 compatible service, keys are placed under `prefix` in the bucket
+pub struct S3Store {
+    store: Arc<AmazonS3>,
+    prefix: String,
+}
+
+fn object_store_error(e: object_store::Error) -> io::Error {
+    match e {
+        object_store::Error::NotFound { .. } => io::Error::new(io::ErrorKind::NotFound, e),
+        e => io::Error::other(e),
+    }
+}
+
+impl S3Store {
+    pub fn new(
+        builder: AmazonS3Builder,
+        prefix: &str,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self {
+            store: Arc::new(builder.build()?),
+            prefix: prefix.trim_matches('/').to_string(),
+        })
+    }
+
+    fn path(&self, key: &str) -> io::Result<object_store::path::Path> {
+        if !is_safe_relative_path(key) {
+            return Err(invalid_key(key));
+        }
+        let location = match self.prefix.as_str() {
+            "" => key.to_string(),
+            prefix => format!("{}/{}", prefix, key),
+        };
+        object_store::path::Path::parse(location).map_err(|_| invalid_key(key))
+    }
+}
+
+#[async_trait]
+impl SaveStore for S3Store {
+    async fn put(&self, key: &str, source: &Path) -> io::Result<()> {
+        // Large files are sent as a multipart upload
+        let mut writer = BufWriter::new(self.store.clone(), self.path(key)?);
+        let mut file = File::open(source).await?;
+        if let Err(e) = tokio::io::copy(&mut file, &mut writer).await {
+            let _ = writer.abort().await;
+            return Err(e);
+        }
+        writer.shutdown().await?;
+        fs::remove_file(source).await
+    }
+
+    async fn get(&self, key: &str) -> io::Result<StoreObject> {
+        let result = self
+            .store
+            .get(&self.path(key)?)
+            .await
+            .map_err(object_store_error)?;
+        let size = result.meta.size;
+        let stream = result.into_stream().map_err(object_store_error);
+        Ok(StoreObject {
+            size,
+            reader: Box::new(StreamReader::new(stream)),
+        })
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match self.store.delete(&self.path(key)?).await {
+            Err(object_store::Error::NotFound { .. }) => Ok(()),
+            result => result.map_err(object_store_error),
+        }
+    }
+
+    async fn list(&self) -> io::Result<Vec<String>> {
+        let prefix = match self.prefix.as_str() {
+            "" => None,
+            prefix => Some(object_store::path::Path::from(prefix)),
+        };
+        let mut objects = self.store.list(prefix.as_ref());
+        let mut keys = Vec::new();
+        while let Some(object) = objects.next().await {
+            let location = object.map_err(object_store_error)?.location.to_string();
+            let key = match self.prefix.as_str() {
+                "" => Some(location.as_str()),
+                prefix => location
+                    .strip_prefix(prefix)
+                    .and_then(|key| key.strip_prefix('/')),
+            };
+            if let Some(key) = key {
+                keys.push(key.to_string());
+            }
+        }
+        Ok(keys)
+    }
+
+    async fn stat(&self, key: &str) -> io::Result<Option<u64>> {
+        match self.store.head(&self.path(key)?).await {
+            Ok(meta) => Ok(Some(meta.size)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(object_store_error(e)),
+        }
+    }
+}
+
+/// Copies the object `from_key` of `from` to `to_key` of `to` through a file of `tmp_dir`
+pub async fn copy_object(
+    from: &dyn SaveStore,
+    from_key: &str,
+    to: &dyn SaveStore,
+    to_key: &str,
+    tmp_dir: &Path,
+) -> io::Result<()> {
+    let tmp_path = tmp_dir.join(format!("{}.copy", Uuid::new_v4()));
+    let result = async {
+        let mut object = from.get(from_key).await?;
+        let mut tmp_file = File::create(&tmp_path).await?;
+        tokio::io::copy(&mut object.reader, &mut tmp_file).await?;
+        tmp_file.flush().await?;
+        to.put(to_key, &tmp_path).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path).await;
+    }
+    result
+}
+
+/// Copies every object of `from` missing from `to`, or of another size there. Returns the
+/// number of objects copied and skipped. The objects of `from` are kept.
+pub async fn migrate_store(
+    from: &dyn SaveStore,
+    to: &dyn SaveStore,
+    tmp_dir: &Path,
+) -> Result<(usize, usize), Box<dyn Error + Send + Sync>> {
+    let mut copied = 0;
+    let mut skipped = 0;
+    for key in from.list().await? {
+        let size = from.stat(&key).await?;
+        if size.is_some() && to.stat(&key).await? == size {
+            skipped += 1;
+            continue;
+        }
+        copy_object(from, &key, to, &key, tmp_dir).await?;
+        copied += 1;
+        tracing::debug!("Copied {}", key);
+    }
+    Ok((copied, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, Bytes};
+    use axum::extract::{Query, State};
+    use axum::http::{HeaderMap, Method, StatusCode, Uri, header};
+    use axum::response::{IntoResponse, Response};
+    use std::collections::{BTreeMap, HashMap};
+    use tokio::io::AsyncReadExt;
+    use tokio::sync::Mutex;
+
+    type Objects = Arc<Mutex<BTreeMap<String, Bytes>>>;
+
+    /// Bare S3 stand-in serving the path style requests of a single bucket, without multipart
+    /// uploads nor signature checks
+    async fn fake_s3(
+        State(objects): State<Objects>,
+        method: Method,
+        uri: Uri,
+        Query(query): Query<HashMap<String, String>>,
+        body: Bytes,
+    ) -> Response {
+        let key = uri
+            .path()
+            .trim_start_matches('/')
+            .split_once('/')
+            .map(|(_, key)| key.to_string())
+            .unwrap_or_default();
+        let key = percent_decode(&key);
+        let mut objects = objects.lock().await;
+        let object_headers = |content: &Bytes| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_LENGTH, content.len().into());
+            headers.insert(
+                header::LAST_MODIFIED,
+                "Sun, 18 Oct 2026 00:00:00 GMT".parse().unwrap(),
+            );
+            headers.insert(
+                header::ETAG,
+                format!("\"{}\"", content.len()).parse().unwrap(),
+            );
+            headers
+        };
+
+        match method {
+            Method::PUT => {
+                let headers = object_headers(&body);
+                objects.insert(key, body);
+                (StatusCode::OK, headers).into_response()
+            }
+            Method::GET if key.is_empty() => {
+                let prefix = query.get("prefix").cloned().unwrap_or_default();
+                let contents: String = objects
+                    .iter()
+                    .filter(|(key, _)| key.starts_with(&prefix))
+                    .map(|(key, content)| {
+                        format!(
+                            "<Contents><Key>{}</Key><LastModified>2026-10-18T00:00:00.000Z</LastModified><Size>{}</Size></Contents>",
+                            key,
+                            content.len()
+                        )
+                    })
+                    .collect();
+                format!(
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListBucketResult><IsTruncated>false</IsTruncated>{}</ListBucketResult>",
+                    contents
+                )
+                .into_response()
+            }
+            Method::GET | Method::HEAD => match objects.get(&key) {
+                Some(content) => {
+                    let body = match method {
+                        Method::GET => Body::from(content.clone()),
+                        _ => Body::empty(),
+                    };
+                    (object_headers(content), body).into_response()
+                }
+                None => StatusCode::NOT_FOUND.into_response(),
+            },
+            Method::DELETE => {
+                objects.remove(&key);
+                StatusCode::NO_CONTENT.into_response()
+            }
+            _ => StatusCode::NOT_IMPLEMENTED.into_response(),
+        }
+    }
+
+    fn percent_decode(value: &str) -> String {
+        let bytes = value.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            match (bytes[i], value.get(i + 1..i + 3)) {
+                (b'%', Some(hex)) if u8::from_str_radix(hex, 16).is_ok() => {
+                    decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                    i += 3;
+                }
+                (byte, _) => {
+                    decoded.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&decoded).to_string()
+    }
+
+    async fn start_fake_s3() -> Result<(String, Objects), Box<dyn Error + Send + Sync>> {
+        let objects = Objects::default();
+        let app = axum::Router::new()
+            .fallback(fake_s3)
+            .with_state(objects.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let endpoint = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok((endpoint, objects))
+    }
+
+    fn s3_store(endpoint: &str, prefix: &str) -> Result<S3Store, Box<dyn Error + Send + Sync>> {
+        S3Store::new(
+            AmazonS3Builder::new()
+                .with_endpoint(endpoint)
+                .with_allow_http(true)
+                .with_bucket_name("saves")
+                .with_region("us-east-1")
+                .with_access_key_id("access")
+                .with_secret_access_key("secret"),
+            prefix,
+        )
+    }
+
+    async fn tmp_file(content: &[u8]) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir_all(&dir).await?;
+        let path = dir.join("content");
+        fs::write(&path, content).await?;
+        Ok(path)
+    }
+
+    async fn read(store: &dyn SaveStore, key: &str) -> io::Result<Vec<u8>> {
+        let mut object = store.get(key).await?;
+        let mut content = Vec::new();
+        object.reader.read_to_end(&mut content).await?;
+        assert_eq!(object.size, content.len() as u64);
+        Ok(content)
+    }
+
+    async fn check_store(store: &dyn SaveStore) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let source = tmp_file(b"blob content").await?;
+        store.put("blobs/ab/abcdef", &source).await?;
+        assert!(!fs::try_exists(&source).await?);
+        store
+            .put("legacy.sav", &tmp_file(b"archive").await?)
+            .await?;
+
+        assert_eq!(read(store, "blobs/ab/abcdef").await?, b"blob content");
+        assert_eq!(store.stat("blobs/ab/abcdef").await?, Some(12));
+        assert_eq!(store.stat("blobs/ab/missing").await?, None);
+        assert_eq!(
+            store.get("blobs/ab/missing").await.err().map(|e| e.kind()),
+            Some(io::ErrorKind::NotFound)
+        );
+        assert!(store.get("../escape").await.is_err());
+
+        let mut keys = store.list().await?;
+        keys.sort();
+        assert_eq!(keys, vec!["blobs/ab/abcdef", "legacy.sav"]);
+
+        store.delete("legacy.sav").await?;
+        store.delete("legacy.sav").await?;
+        assert_eq!(store.list().await?, vec!["blobs/ab/abcdef"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_store_location() {
+        assert_eq!(
+            "local".parse::<StoreLocation>().ok(),
+            Some(StoreLocation::Local)
+        );
+        assert_eq!(
+            "s3://saves/server/".parse::<StoreLocation>().ok(),
+            Some(StoreLocation::S3 {
+                bucket: "saves".to_string(),
+                prefix: "server".to_string(),
+            })
+        );
+        assert_eq!(
+            "s3://saves".parse::<StoreLocation>().ok(),
+            Some(StoreLocation::S3 {
+                bucket: "saves".to_string(),
+                prefix: String::new(),
+            })
+        );
+        assert!("s3://".parse::<StoreLocation>().is_err());
+        assert!("ftp://saves".parse::<StoreLocation>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_store() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let root = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        check_store(&LocalStore::new(&root)).await?;
+        fs::remove_dir_all(&root).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_s3_store() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (endpoint, objects) = start_fake_s3().await?;
+        check_store(&s3_store(&endpoint, "server")?).await?;
+        assert_eq!(
+            objects.lock().await.keys().collect::<Vec<_>>(),
+            vec!["server/blobs/ab/abcdef"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migrate_store() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let root = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let tmp_dir = root.join("tmp");
+        fs::create_dir_all(&tmp_dir).await?;
+        let local = LocalStore::new(&root);
+        local.put("blobs/aa/aa", &tmp_file(b"first").await?).await?;
+        local
+            .put("blobs/bb/bb", &tmp_file(b"second").await?)
+            .await?;
+        let (endpoint, _) = start_fake_s3().await?;
+        let s3 = s3_store(&endpoint, "")?;
+        s3.put("blobs/aa/aa", &tmp_file(b"first").await?).await?;
+
+        assert_eq!(migrate_store(&local, &s3, &tmp_dir).await?, (1, 1));
+        assert_eq!(read(&s3, "blobs/bb/bb").await?, b"second");
+        assert_eq!(read(&local, "blobs/bb/bb").await?, b"second");
+        assert_eq!(migrate_store(&local, &s3, &tmp_dir).await?, (0, 2));
+        fs::remove_dir_all(&root).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+use crate::blob_store::open_blob;
+use crate::save_archive::{ArchiveEntry, EntryContent};
+use crate::save_compression::decoded_reader;
+use crate::save_store::SaveStore;
+use axum::body::Bytes;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::mpsc;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const ZIP64_END_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const END_SIGNATURE: u32 = 0x06054b50;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+/// CRC and sizes follow the data in a descriptor, names are UTF-8
+const FLAGS: u16 = 0x0008 | 0x0800;
+const VERSION: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+/// Upper byte: made on Unix, so the external attributes hold the file mode
+const VERSION_MADE_BY: u16 = 0x0300 | VERSION_ZIP64;
+const FILE_MODE: u32 = 0o100644;
+const STORED: u16 = 0;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Streams a zip of `entries` stored without compression, built on the fly: the CRC of each file
+/// is sent in a data descriptor after its content, and Zip64 records are added past 4 GiB.
+pub fn zip_stream(
+    entries: Vec<ArchiveEntry>,
+    store: &'static dyn SaveStore,
+) -> BoxStream<'static, io::Result<Bytes>> {
+    let (sender, receiver) = mpsc::channel(4);
+    tokio::spawn(async move {
+        let mut writer = ZipWriter {
+            sender,
+            offset: 0,
+            central_directory: Vec::new(),
+            entry_count: 0,
+        };
+        if let Err(e) = writer.write_archive(entries, store).await {
+            let _ = writer.sender.send(Err(e)).await;
+        }
+    });
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    })
+    .boxed()
+}
+
+struct ZipWriter {
+    sender: mpsc::Sender<io::Result<Bytes>>,
+    offset: u64,
+    central_directory: Vec<u8>,
+    entry_count: u64,
+}
+
+impl ZipWriter {
+    async fn write(&mut self, bytes: Bytes) -> io::Result<()> {
+        self.offset += bytes.len() as u64;
+        self.sender
+            .send(Ok(bytes))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "zip download closed"))
+    }
+
+    async fn write_archive(
+        &mut self,
+        entries: Vec<ArchiveEntry>,
+        store: &'static dyn SaveStore,
+    ) -> io::Result<()> {
+        for entry in entries {
+            self.write_entry(entry, store).await?;
+        }
+        self.write_end().await
+    }
+
+    async fn write_entry(
+        &mut self,
+        entry: ArchiveEntry,
+        store: &'static dyn SaveStore,
+    ) -> io::Result<()> {
+        let header_offset = self.offset;
+        let name = entry.path.into_bytes();
+        let name_size = u16::try_from(name.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "zip entry name too long"))?;
+        let (time, date) = dos_date_time(entry.mtime);
+        let zip64 = entry.size >= u64::from(u32::MAX);
+        let size32 = u32::try_from(entry.size).unwrap_or(u32::MAX);
+
+        let mut header = Vec::with_capacity(30 + name.len() + 20);
+        put_u32(&mut header, LOCAL_HEADER_SIGNATURE);
+        put_u16(&mut header, if zip64 { VERSION_ZIP64 } else { VERSION });
+        put_u16(&mut header, FLAGS);
+        put_u16(&mut header, STORED);
+        put_u16(&mut header, time);
+        put_u16(&mut header, date);
+        put_u32(&mut header, 0);
+        put_u32(&mut header, size32);
+        put_u32(&mut header, size32);
+        put_u16(&mut header, name_size);
+        put_u16(&mut header, if zip64 { 20 } else { 0 });
+        header.extend_from_slice(&name);
+        if zip64 {
+            put_u16(&mut header, ZIP64_EXTRA_ID);
+            put_u16(&mut header, 16);
+            put_u64(&mut header, entry.size);
+            put_u64(&mut header, entry.size);
+        }
+        self.write(Bytes::from(header)).await?;
+
+        let reader: Box<dyn AsyncRead + Send + Unpin> = match entry.content {
+            EntryContent::Bytes(bytes) => Box::new(io::Cursor::new(bytes)),
+            EntryContent::Blob {
+                object,
+                codec,
+                blob_key,
+                ..
+            } => decoded_reader(open_blob(store, &object, blob_key).await?, codec),
+        };
+        let mut reader = reader.take(entry.size);
+        let mut hasher = crc32fast::Hasher::new();
+        let mut written = 0;
+        loop {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            let read = reader.read(&mut chunk).await?;
+            if read == 0 {
+                break;
+            }
+            chunk.truncate(read);
+            hasher.update(&chunk);
+            written += read as u64;
+            self.write(Bytes::from(chunk)).await?;
+        }
+        if written != entry.size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "zip entry shorter than its recorded size",
+            ));
+        }
+        let crc = hasher.finalize();
+
+        let mut descriptor = Vec::with_capacity(24);
+        put_u32(&mut descriptor, DATA_DESCRIPTOR_SIGNATURE);
+        put_u32(&mut descriptor, crc);
+        if zip64 {
+            put_u64(&mut descriptor, entry.size);
+            put_u64(&mut descriptor, entry.size);
+        } else {
+            put_u32(&mut descriptor, size32);
+            put_u32(&mut descriptor, size32);
+        }
+        self.write(Bytes::from(descriptor)).await?;
+
+        // The Zip64 extra field of the central directory only holds the values that overflow
+        let offset32 = u32::try_from(header_offset).unwrap_or(u32::MAX);
+        let mut extra = Vec::new();
+        if zip64 {
+            put_u64(&mut extra, entry.size);
+            put_u64(&mut extra, entry.size);
+        }
+        if offset32 == u32::MAX {
+            put_u64(&mut extra, header_offset);
+        }
+        let needs_zip64 = !extra.is_empty();
+
+        let record = &mut self.central_directory;
+        put_u32(record, CENTRAL_HEADER_SIGNATURE);
+        put_u16(record, VERSION_MADE_BY);
+        put_u16(record, if needs_zip64 { VERSION_ZIP64 } else { VERSION });
+        put_u16(record, FLAGS);
+        put_u16(record, STORED);
+        put_u16(record, time);
+        put_u16(record, date);
+        put_u32(record, crc);
+        put_u32(record, size32);
+        put_u32(record, size32);
+        put_u16(record, name_size);
+        put_u16(
+            record,
+            if needs_zip64 {
+                extra.len() as u16 + 4
+            } else {
+                0
+            },
+        );
+        put_u16(record, 0);
+        put_u16(record, 0);
+        put_u16(record, 0);
+        put_u32(record, FILE_MODE << 16);
+        put_u32(record, offset32);
+        record.extend_from_slice(&name);
+        if needs_zip64 {
+            put_u16(record, ZIP64_EXTRA_ID);
+            put_u16(record, extra.len() as u16);
+            record.extend_from_slice(&extra);
+        }
+        self.entry_count += 1;
+        Ok(())
+    }
+
+    async fn write_end(&mut self) -> io::Result<()> {
+        let central_directory_offset = self.offset;
+        let central_directory = std::mem::take(&mut self.central_directory);
+        let central_directory_size = central_directory.len() as u64;
+        self.write(Bytes::from(central_directory)).await?;
+
+        let mut end = Vec::with_capacity(98);
+        if self.entry_count >= u64::from(u16::MAX)
+            || central_directory_offset >= u64::from(u32::MAX)
+            || central_directory_size >= u64::from(u32::MAX)
+        {
+            let zip64_end_offset = self.offset;
+            put_u32(&mut end, ZIP64_END_SIGNATURE);
+            put_u64(&mut end, 44);
+            put_u16(&mut end, VERSION_MADE_BY);
+            put_u16(&mut end, VERSION_ZIP64);
+            put_u32(&mut end, 0);
+            put_u32(&mut end, 0);
+            put_u64(&mut end, self.entry_count);
+            put_u64(&mut end, self.entry_count);
+            put_u64(&mut end, central_directory_size);
+            put_u64(&mut end, central_directory_offset);
+
+            put_u32(&mut end, ZIP64_LOCATOR_SIGNATURE);
+            put_u32(&mut end, 0);
+            put_u64(&mut end, zip64_end_offset);
+            put_u32(&mut end, 1);
+        }
+        let entry_count = u16::try_from(self.entry_count).unwrap_or(u16::MAX);
+        put_u32(&mut end, END_SIGNATURE);
+        put_u16(&mut end, 0);
+        put_u16(&mut end, 0);
+        put_u16(&mut end, entry_count);
+        put_u16(&mut end, entry_count);
+        put_u32(
+            &mut end,
+            u32::try_from(central_directory_size).unwrap_or(u32::MAX),
+        );
+        put_u32(
+            &mut end,
+            u32::try_from(central_directory_offset).unwrap_or(u32::MAX),
+        );
+        put_u16(&mut end, 0);
+        self.write(Bytes::from(end)).await
+    }
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buffer: &mut Vec<u8>, value: u64) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+/// MS-DOS time and date of a Unix timestamp, clamped to the 1980-2107 range they can hold
+fn dos_date_time(mtime: u64) -> (u16, u16) {
+    let datetime = time::OffsetDateTime::from_unix_timestamp(i64::try_from(mtime).unwrap_or(0))
+        .unwrap_or(time::OffsetDateTime::UNIX_EPOCH);
+    if datetime.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let year = datetime.year().min(2107) as u16;
+    let time = ((datetime.hour() as u16) << 11)
+        | ((datetime.minute() as u16) << 5)
+        | (datetime.second() as u16 / 2);
+    let date = ((year - 1980) << 9) | ((datetime.month() as u16) << 5) | datetime.day() as u16;
+    (time, date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatype_endpoint::Codec;
+    use crate::save_store::LocalStore;
+    use futures_util::TryStreamExt;
+    use std::error::Error;
+    use std::io::Read;
+
+    #[test]
+    fn test_dos_date_time() {
+        // 2023-11-14 22:13:20 UTC
+        assert_eq!(
+            dos_date_time(1_700_000_000),
+            ((22 << 11) | (13 << 5) | 10, (43 << 9) | (11 << 5) | 14)
+        );
+        assert_eq!(dos_date_time(0), (0, (1 << 5) | 1));
+    }
+
+    #[tokio::test]
+    async fn test_zip_stream() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(&dir).await?;
+        let store: &'static LocalStore = Box::leak(Box::new(LocalStore::new(&dir)));
+        let content = b"save data ".repeat(10_000);
+        let compressed = zstd::encode_all(content.as_slice(), 3)?;
+        tokio::fs::write(dir.join("compressed"), &compressed).await?;
+
+        let entries = vec![
+            ArchiveEntry {
+                path: "Game/1/slot1.sav".to_string(),
+                size: content.len() as u64,
+                mtime: 1_700_000_000,
+                content: EntryContent::Blob {
+                    object: "compressed".to_string(),
+                    codec: Codec::Zstd,
+                    stored_size: compressed.len() as u64,
+                    blob_key: None,
+                },
+            },
+            ArchiveEntry {
+                path: "manifest.json".to_string(),
+                size: 2,
+                mtime: 1_700_000_000,
+                content: EntryContent::Bytes(Bytes::from_static(b"[]")),
+            },
+        ];
+        let bytes: Vec<Bytes> = zip_stream(entries, store).try_collect().await?;
+        let bytes = bytes.concat();
+        tokio::fs::remove_dir_all(&dir).await?;
+
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(bytes))?;
+        assert_eq!(archive.len(), 2);
+        let mut slot = Vec::new();
+        archive
+            .by_name("Game/1/slot1.sav")?
+            .read_to_end(&mut slot)?;
+        assert_eq!(slot, content);
+        let mut manifest = Vec::new();
+        archive
+            .by_name("manifest.json")?
+            .read_to_end(&mut manifest)?;
+        assert_eq!(manifest, b"[]");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zip_stream_missing_blob() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let store: &'static LocalStore = Box::leak(Box::new(LocalStore::new(&dir)));
+        let entries = vec![ArchiveEntry {
+            path: "missing.sav".to_string(),
+            size: 3,
+            mtime: 0,
+            content: EntryContent::Blob {
+                object: "missing".to_string(),
+                codec: Codec::Identity,
+                stored_size: 3,
+                blob_key: None,
+            },
+        }];
+        let result: io::Result<Vec<Bytes>> = zip_stream(entries, store).try_collect().await;
+        assert!(result.is_err());
+        Ok(())
+    }
+}
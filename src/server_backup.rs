@@ -0,0 +1,442 @@
+use crate::DATABASE;
+use crate::blob_store::{lock_store, referenced_objects};
+use crate::configuration::BACKUP_RETENTION_COUNT_INFO;
+use crate::const_var::{BACKUP_DIR, DATA_DIR, DATABASE_PATH, TMP_DIR};
+use crate::database::interface::GameDatabase;
+use crate::database::sqlite_backup::{backup_database, checkpoint_database};
+use crate::datatype_endpoint::{BackupInfo, Codec};
+use crate::file_system::is_safe_relative_path;
+use crate::save_archive::{ArchiveEntry, EntryContent, TarArchive};
+use crate::save_store::{LocalStore, SAVE_STORE, SaveStore, migrate_store};
+use axum::body::Bytes;
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tar::Archive;
+use time::OffsetDateTime;
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Version of the layout of the backup archives, restoring refuses any other
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+const HEADER_ENTRY: &str = "backup.json";
+const DATABASE_ENTRY: &str = "database.sqlite";
+const OBJECT_ENTRY_PREFIX: &str = "saves/";
+const BACKUP_FILE_PREFIX: &str = "backup-";
+const BACKUP_FILE_EXTENSION: &str = ".tar";
+
+// The job, the API and the command line can all start a backup, only one runs at a time
+static BACKUP_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// First entry of a backup archive
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct BackupHeader {
+    pub format_version: u32,
+    /// Version of the server that created the backup
+    pub server_version: String,
+    pub created: i64,
+    pub database_uuid: Option<String>,
+    /// Number of objects of the save store in the archive
+    pub objects: usize,
+}
+
+fn backup_file_name(time: OffsetDateTime) -> String {
+    format!(
+        "{}{:04}{:02}{:02}-{:02}{:02}{:02}{}",
+        BACKUP_FILE_PREFIX,
+        time.year(),
+        u8::from(time.month()),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+        BACKUP_FILE_EXTENSION
+    )
+}
+
+/// Whether `file_name` is the name of a backup archive, the only files served from the backup
+/// directory
+pub fn is_backup_file_name(file_name: &str) -> bool {
+    file_name
+        .strip_prefix(BACKUP_FILE_PREFIX)
+        .and_then(|rest| rest.strip_suffix(BACKUP_FILE_EXTENSION))
+        .is_some_and(|stamp| {
+            !stamp.is_empty() && stamp.chars().all(|c| c.is_ascii_digit() || c == '-')
+        })
+}
+
+pub fn backup_path(file_name: &str) -> PathBuf {
+    Path::new(BACKUP_DIR).join(file_name)
+}
+
+/// Backup archives of the backup directory, newest first
+pub async fn list_backups() -> Result<Vec<BackupInfo>, Box<dyn Error + Send + Sync>> {
+    let mut backups = Vec::new();
+    let mut dir = fs::read_dir(BACKUP_DIR).await?;
+    while let Some(entry) = dir.next_entry().await? {
+        let Some(file_name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if !is_backup_file_name(&file_name) {
+            continue;
+        }
+        let metadata = entry.metadata().await?;
+        let created = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_secs();
+        backups.push(BackupInfo {
+            file_name,
+            size: metadata.len(),
+            created: i64::try_from(created)?,
+        });
+    }
+    // The file names embed the creation date
+    backups.sort_by(|a, b| b.file_name.cmp(&a.file_name));
+    Ok(backups)
+}
+
+/// Removes the oldest backup archives beyond the `backup_retention_count` newest
+async fn rotate_backups() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let keep = BACKUP_RETENTION_COUNT_INFO.get_number_in_db()? as usize;
+    for backup in list_backups().await?.into_iter().skip(keep) {
+        fs::remove_file(backup_path(&backup.file_name)).await?;
+        tracing::info!("Removed old backup {}", backup.file_name);
+    }
+    Ok(())
+}
+
+/// Writes a backup archive of the database and of every object of the save store it references
+/// to the backup directory, then rotates the backups. Returns `None` if a backup is already
+/// running.
+///
+/// The database is copied with SQLite's online backup API, and blob placement and removal are
+/// held off until the objects are copied, so the archive is consistent while the server runs.
+pub async fn create_backup() -> Result<Option<BackupInfo>, Box<dyn Error + Send + Sync>> {
+    let Ok(_backup_guard) = BACKUP_LOCK.try_lock() else {
+        return Ok(None);
+    };
+
+    let now = OffsetDateTime::now_utc();
+    let file_name = backup_file_name(now);
+    let path = backup_path(&file_name);
+    if fs::try_exists(&path).await? {
+        return Err(format!("the backup {} already exists", file_name).into());
+    }
+    let partial_path = path.with_extension("tar.partial");
+    let snapshot_path = PathBuf::from(format!("{}/{}.sqlite", TMP_DIR, Uuid::new_v4()));
+
+    let result = async {
+        let _store_guard = lock_store().await;
+
+        let snapshot = snapshot_path.clone();
+        tokio::task::spawn_blocking(move || backup_database(Path::new(DATABASE_PATH), &snapshot))
+            .await??;
+        let database = fs::read(&snapshot_path).await?;
+
+        // Objects are archived as stored, the database holds their codec and key
+        let mtime = u64::try_from(now.unix_timestamp())?;
+        let mut object_entries = Vec::new();
+        for object in referenced_objects(&DATABASE)? {
+            let size = SAVE_STORE
+                .stat(&object)
+                .await?
+                .ok_or_else(|| format!("{} is missing from the save store", object))?;
+            object_entries.push(ArchiveEntry {
+                path: format!("{}{}", OBJECT_ENTRY_PREFIX, object),
+                size,
+                mtime,
+                content: EntryContent::Blob {
+                    object,
+                    codec: Codec::Identity,
+                    stored_size: size,
+                    blob_key: None,
+                },
+            });
+        }
+
+        let header = serde_json::to_vec_pretty(&BackupHeader {
+            format_version: BACKUP_FORMAT_VERSION,
+            server_version: env!("CARGO_PKG_VERSION").to_string(),
+            created: now.unix_timestamp(),
+            database_uuid: DATABASE.get_database_uuid()?.map(|uuid| uuid.to_string()),
+            objects: object_entries.len(),
+        })?;
+        let mut entries = vec![
+            ArchiveEntry {
+                path: HEADER_ENTRY.to_string(),
+                size: header.len() as u64,
+                mtime,
+                content: EntryContent::Bytes(Bytes::from(header)),
+            },
+            ArchiveEntry {
+                path: DATABASE_ENTRY.to_string(),
+                size: database.len() as u64,
+                mtime,
+                content: EntryContent::Bytes(Bytes::from(database)),
+            },
+        ];
+        entries.extend(object_entries);
+
+        let archive = TarArchive::new(entries, Codec::Identity, SAVE_STORE.as_ref())?;
+        let mut stream = archive.into_stream();
+        let mut file = File::create(&partial_path).await?;
+        while let Some(chunk) = stream.try_next().await? {
+            file.write_all(&chunk).await?;
+        }
+        file.sync_all().await?;
+        fs::rename(&partial_path, &path).await?;
+        Ok::<_, Box<dyn Error + Send + Sync>>(())
+    }
+    .await;
+
+    let _ = fs::remove_file(&snapshot_path).await;
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial_path).await;
+        return Err(e);
+    }
+
+    rotate_backups().await?;
+    let metadata = fs::metadata(&path).await?;
+    Ok(Some(BackupInfo {
+        file_name,
+        size: metadata.len(),
+        created: now.unix_timestamp(),
+    }))
+}
+
+/// Extracts the backup `archive` to `staging`, checking its header and entries
+fn extract_backup(
+    archive: &Path,
+    staging: &Path,
+) -> Result<BackupHeader, Box<dyn Error + Send + Sync>> {
+    let mut header = None;
+    let mut has_database = false;
+    let mut archive = Archive::new(std::fs::File::open(archive)?);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_dir() {
+            continue;
+        }
+        if !entry.header().entry_type().is_file() {
+            return Err("the backup holds an entry that isn't a file".into());
+        }
+        let entry_path = entry.path()?;
+        let entry_path = entry_path
+            .to_str()
+            .ok_or("backup entry path is not valid UTF-8")?
+            .to_string();
+
+        if entry_path == HEADER_ENTRY {
+            let mut json = String::new();
+            entry.read_to_string(&mut json)?;
+            let backup_header: BackupHeader = serde_json::from_str(&json)?;
+            if backup_header.format_version != BACKUP_FORMAT_VERSION {
+                return Err(format!(
+                    "unsupported backup format version {}, expected {}",
+                    backup_header.format_version, BACKUP_FORMAT_VERSION
+                )
+                .into());
+            }
+            header = Some(backup_header);
+        } else if header.is_none() {
+            return Err(format!("the backup must start with {}", HEADER_ENTRY).into());
+        } else if entry_path == DATABASE_ENTRY {
+            entry.unpack(staging.join(DATABASE_ENTRY))?;
+            has_database = true;
+        } else if let Some(object) = entry_path.strip_prefix(OBJECT_ENTRY_PREFIX)
+            && is_safe_relative_path(object)
+        {
+            let destination = staging.join(OBJECT_ENTRY_PREFIX).join(object);
+            if let Some(parent) = destination.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            entry.unpack(destination)?;
+        } else {
+            return Err(format!("unexpected backup entry {}", entry_path).into());
+        }
+    }
+
+    if !has_database {
+        return Err(format!("the backup has no {}", DATABASE_ENTRY).into());
+    }
+    header.ok_or_else(|| format!("the backup has no {}", HEADER_ENTRY).into())
+}
+
+/// Restores the backup `archive`: the database is migrated to the current schema, the objects
+/// it references are copied to the save store, then it replaces the live database in a single
+/// rename. The live database is kept next to it as `database.sqlite.pre-restore-<date>`.
+/// The server must not be running.
+pub async fn restore_backup(archive: &Path) -> Result<BackupHeader, Box<dyn Error + Send + Sync>> {
+    let staging = PathBuf::from(format!("{}/restore-{}", DATA_DIR, Uuid::new_v4()));
+    fs::create_dir_all(&staging).await?;
+    let result = restore_staged_backup(archive, &staging).await;
+    let _ = fs::remove_dir_all(&staging).await;
+    result
+}
+
+async fn restore_staged_backup(
+    archive: &Path,
+    staging: &Path,
+) -> Result<BackupHeader, Box<dyn Error + Send + Sync>> {
+    let (archive_path, staging_path) = (archive.to_path_buf(), staging.to_path_buf());
+    let header =
+        tokio::task::spawn_blocking(move || extract_backup(&archive_path, &staging_path)).await??;
+    tracing::info!(
+        "Restoring the backup of {} created by version {}",
+        header.created,
+        header.server_version
+    );
+
+    // Opening the database runs the migrations, it panics if they fail
+    let staged_database = staging.join(DATABASE_ENTRY);
+    let database_path = staged_database
+        .to_str()
+        .ok_or("staging path is not valid UTF-8")?
+        .to_string();
+    let referenced =
+        tokio::task::spawn_blocking(move || referenced_objects(&GameDatabase::new(&database_path)))
+            .await
+            .map_err(|_| "the database of the backup can't be migrated")??;
+
+    let staged_objects = LocalStore::new(staging.join(OBJECT_ENTRY_PREFIX));
+    for object in &referenced {
+        if staged_objects.stat(object).await?.is_none() {
+            return Err(format!("{} is missing from the backup", object).into());
+        }
+    }
+    let (copied, skipped) =
+        migrate_store(&staged_objects, SAVE_STORE.as_ref(), Path::new(TMP_DIR)).await?;
+    tracing::info!(
+        "Copied {} object(s) to the save store, skipped {} already present",
+        copied,
+        skipped
+    );
+
+    let live_database = PathBuf::from(DATABASE_PATH);
+    tokio::task::spawn_blocking(move || -> Result<(), Box<dyn Error + Send + Sync>> {
+        checkpoint_database(&staged_database)?;
+        if live_database.exists() {
+            checkpoint_database(&live_database)?;
+            let kept = format!(
+                "{}.pre-restore-{}",
+                DATABASE_PATH,
+                OffsetDateTime::now_utc().unix_timestamp()
+            );
+            backup_database(&live_database, Path::new(&kept))?;
+            tracing::info!("Kept the replaced database as {}", kept);
+            for suffix in ["-wal", "-shm"] {
+                match std::fs::remove_file(format!("{}{}", DATABASE_PATH, suffix)) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => (),
+                }
+            }
+        }
+        std::fs::rename(&staged_database, &live_database)?;
+        Ok(())
+    })
+    .await??;
+
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tar::{Builder, Header};
+
+    fn add_file(builder: &mut Builder<Vec<u8>>, path: &str, content: &[u8]) {
+        let mut header = Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, path, content).unwrap();
+    }
+
+    fn header_json(format_version: u32) -> Vec<u8> {
+        serde_json::to_vec(&BackupHeader {
+            format_version,
+            server_version: "0.0.0".to_string(),
+            created: 0,
+            database_uuid: None,
+            objects: 1,
+        })
+        .unwrap()
+    }
+
+    fn extract(entries: &[(&str, Vec<u8>)]) -> Result<BackupHeader, Box<dyn Error + Send + Sync>> {
+        let mut builder = Builder::new(Vec::new());
+        for (path, content) in entries {
+            add_file(&mut builder, path, content);
+        }
+        let dir = std::env::temp_dir().join(format!("backup-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("backup.tar");
+        std::fs::write(&archive, builder.into_inner().unwrap()).unwrap();
+        let staging = dir.join("staging");
+        std::fs::create_dir_all(&staging).unwrap();
+
+        let result = extract_backup(&archive, &staging);
+        if result.is_ok() {
+            assert!(staging.join(DATABASE_ENTRY).is_file());
+            assert!(staging.join("saves/blobs/ab/abcd").is_file());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    #[test]
+    fn test_backup_file_name() {
+        let time = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let file_name = backup_file_name(time);
+        assert_eq!(file_name, "backup-20231114-221320.tar");
+        assert!(is_backup_file_name(&file_name));
+        assert!(!is_backup_file_name("backup-.tar"));
+        assert!(!is_backup_file_name("backup-20231114-221320.tar.partial"));
+        assert!(!is_backup_file_name("backup-../database.sqlite.tar"));
+        assert!(!is_backup_file_name("database.sqlite"));
+    }
+
+    #[test]
+    fn test_extract_backup() {
+        let header = extract(&[
+            (HEADER_ENTRY, header_json(BACKUP_FORMAT_VERSION)),
+            (DATABASE_ENTRY, b"db".to_vec()),
+            ("saves/blobs/ab/abcd", b"blob".to_vec()),
+        ])
+        .unwrap();
+        assert_eq!(header.format_version, BACKUP_FORMAT_VERSION);
+        assert_eq!(header.objects, 1);
+
+        // Unknown format version
+        assert!(
+            extract(&[
+                (HEADER_ENTRY, header_json(BACKUP_FORMAT_VERSION + 1)),
+                (DATABASE_ENTRY, b"db".to_vec()),
+            ])
+            .is_err()
+        );
+        // Header after the content
+        assert!(
+            extract(&[
+                (DATABASE_ENTRY, b"db".to_vec()),
+                (HEADER_ENTRY, header_json(BACKUP_FORMAT_VERSION)),
+            ])
+            .is_err()
+        );
+        // No database
+        assert!(extract(&[(HEADER_ENTRY, header_json(BACKUP_FORMAT_VERSION))]).is_err());
+        // Unexpected entry
+        assert!(
+            extract(&[
+                (HEADER_ENTRY, header_json(BACKUP_FORMAT_VERSION)),
+                (DATABASE_ENTRY, b"db".to_vec()),
+                ("config.toml", b"".to_vec()),
+            ])
+            .is_err()
+        );
+    }
+}
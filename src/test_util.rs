@@ -0,0 +1,76 @@
+//! Fixtures shared by the tests of several modules
+
+use crate::database::interface::{GameDatabase, SaveOwner};
+use crate::datatype_endpoint::{
+    Codec, GameMetadataCreate, OS, SaveMetadata, SaveParent, SavePathCreate, User, UserCreate,
+};
+use std::error::Error;
+use uuid::Uuid;
+
+/// Adds the game `name` with one save path, both get the id 1 in a fresh database
+pub fn test_game_with_path(
+    database: &GameDatabase,
+    name: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    database.add_games_metadata(vec![&GameMetadataCreate {
+        known_name: None,
+        steam_appid: None,
+        default_name: name.to_string(),
+        install_dir: None,
+        gog: None,
+        flatpak_id: None,
+        lutris_id: None,
+        epic_cloud: None,
+        gog_cloud: None,
+        origin_cloud: None,
+        steam_cloud: None,
+        uplay_cloud: None,
+        ludusavi_managed: None,
+        gog_extra: None,
+        steam_extra: None,
+    }])?;
+    let game_id = database
+        .get_game_id_by_name(name)?
+        .ok_or("game not added")?;
+    database.add_game_path(
+        game_id,
+        &SavePathCreate {
+            path: format!("{}_dir", name.to_lowercase()),
+            operating_system: OS::Undefined,
+        },
+    )
+}
+
+/// Adds the user `player`, who isn't an administrator
+pub fn test_player(database: &GameDatabase) -> Result<User, Box<dyn Error + Send + Sync>> {
+    let user = database.add_user(
+        &UserCreate {
+            name: "player".to_string(),
+            admin: false,
+        },
+        Uuid::new_v4(),
+    )?;
+    Ok(user.ok_or("player already added")?)
+}
+
+/// Adds a save without files of `owner` to `path_id`, returns its UUID
+pub fn test_save(
+    database: &GameDatabase,
+    path_id: i32,
+    owner: SaveOwner,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let uuid = Uuid::new_v4();
+    database.add_reference_to_save(
+        uuid,
+        path_id,
+        &SaveParent::default(),
+        &SaveMetadata::default(),
+        owner,
+        None,
+        Codec::Identity,
+        None,
+        None,
+        vec![],
+    )?;
+    Ok(uuid.to_string())
+}